@@ -0,0 +1,117 @@
+use crate::game::{CardType, Deck};
+
+/// The number of ways to choose `k` things out of `n`, as a float so that big decks do not overflow.
+fn choose(n: usize, k: usize) -> f64 {
+    if k > n {
+        return 0.0;
+    }
+    // use the smaller half of the symmetry to keep the product short
+    let k = k.min(n - k);
+    (0..k).fold(1.0, |acc, i| acc * (n - i) as f64 / (i + 1) as f64)
+}
+
+/// The probability of drawing exactly `k` successes in `draws` cards, from a deck of `deck_size` cards
+/// containing `successes` successes (the hypergeometric distribution).
+/// ```
+/// use deck_optim::analytics;
+///
+/// // 17 lands in a 40 card deck, the chance of exactly 3 lands in a 7 card hand
+/// let p = analytics::prob_exactly_k(40, 17, 7, 3);
+/// assert!((p - 0.32297).abs() < 1e-5);
+/// ```
+pub fn prob_exactly_k(deck_size: usize, successes: usize, draws: usize, k: usize) -> f64 {
+    if successes > deck_size || draws > deck_size || k > draws || k > successes {
+        return 0.0;
+    }
+    let failures = deck_size - successes;
+    choose(successes, k) * choose(failures, draws - k) / choose(deck_size, draws)
+}
+
+/// The probability of drawing at least `k` successes in `draws` cards, from a deck of `deck_size` cards
+/// containing `successes` successes.
+/// ```
+/// use deck_optim::analytics;
+///
+/// assert_eq!(analytics::prob_at_least_k(40, 17, 7, 0), 1.0);
+/// ```
+pub fn prob_at_least_k(deck_size: usize, successes: usize, draws: usize, k: usize) -> f64 {
+    if k == 0 {
+        return 1.0;
+    }
+    (k..=draws)
+        .map(|i| prob_exactly_k(deck_size, successes, draws, i))
+        .sum()
+}
+
+/// The probability of having each number of lands, from 0 to `hand_size`, in an opening hand drawn from `deck`.
+/// This does not account for mulligans, so it is a baseline to compare the simulated opening hands against.
+pub fn opening_hand_land_distribution(deck: &Deck, hand_size: usize) -> Vec<f64> {
+    let deck_size = deck.deck.size();
+    let lands = deck.deck
+        .iter()
+        .filter(|card| card.data().card_type == CardType::Land)
+        .count();
+    (0..=hand_size)
+        .map(|k| prob_exactly_k(deck_size, lands, hand_size, k))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::collection::fixtures;
+    use crate::game::{CommandZone, UnorderedPile};
+
+    use super::*;
+
+    const EPSILON: f64 = 1e-9;
+
+    #[test]
+    fn test_prob_exactly_k_limited_deck() {
+        // 17 lands in 40 cards, drawing 7
+        let expected = [
+            0.01314968814968815,
+            0.09204781704781705,
+            0.24546084546084546,
+            0.3229747966590072,
+            0.22608235766130502,
+            0.08397344713134187,
+            0.015267899478425793,
+            0.0010431484115694643,
+        ];
+        for (k, expected) in expected.into_iter().enumerate() {
+            let actual = prob_exactly_k(40, 17, 7, k);
+            assert!((actual - expected).abs() < EPSILON, "P(X = {k}) was {actual}, expected {expected}");
+        }
+    }
+
+    #[test]
+    fn test_prob_at_least_k() {
+        let actual = prob_at_least_k(40, 17, 7, 2);
+
+        assert!((actual - 0.8948024948024949).abs() < EPSILON);
+    }
+
+    #[test]
+    fn test_impossible_draws() {
+        assert_eq!(prob_exactly_k(40, 17, 7, 8), 0.0);
+        assert_eq!(prob_exactly_k(10, 2, 7, 3), 0.0);
+        assert_eq!(prob_at_least_k(40, 0, 7, 1), 0.0);
+    }
+
+    #[test]
+    fn test_opening_hand_land_distribution() {
+        let mut deck = UnorderedPile::empty();
+        deck.add_copies(fixtures::card_named("Forest"), 17);
+        deck.add_copies(fixtures::card_named("Hill Giant"), 23);
+        let deck = Deck {
+            command_zone: CommandZone::empty(),
+            deck,
+        };
+
+        let distribution = opening_hand_land_distribution(&deck, 7);
+
+        assert_eq!(distribution.len(), 8);
+        assert!((distribution[3] - prob_exactly_k(40, 17, 7, 3)).abs() < EPSILON);
+        assert!((distribution.iter().sum::<f64>() - 1.0).abs() < EPSILON);
+    }
+}
@@ -1,14 +1,17 @@
-use std::path::PathBuf;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
 
 use clap::Parser;
 
 use deck_optim::collection::CardSource;
 use deck_optim::game::annotations::CardAnnotations;
-use deck_optim::game::Deck;
-use deck_optim::scryfall::ScryfallClient;
-use deck_optim::deck::DeckList;
-use deck_optim::strategies::StrategyImpl;
+use deck_optim::game::{CardData, Deck, PlayDraw};
+use deck_optim::scryfall::{BulkDataSource, ScryfallClient};
+use deck_optim::deck::{BanLists, DeckAllocation, DeckFetcher, DeckList, DeckSummary, Format};
+use deck_optim::strategies::{LandDropTieBreak, MulliganStrategy, StrategyImpl, UtilityFunction};
 use deck_optim::trial;
+use deck_optim::experiment::{self, Experiment};
 use directories::ProjectDirs;
 use itertools::Itertools;
 use prettytable::{row, Table};
@@ -21,7 +24,7 @@ use tracing_subscriber::filter::LevelFilter;
 
 use deck_optim::collection::CardCollection;
 use deck_optim::metrics::MetricsData;
-use deck_optim::watcher::WatcherImpl;
+use deck_optim::watcher::{self, CastabilityWatcher, CombinedWatcher, ConsistencyWatcher, EfficiencyWatcher, ThreatWatcher, WatcherImpl};
 
 use deck_optim::card_cache::LocalCardCache;
 use deck_optim::file_utils;
@@ -35,18 +38,229 @@ pub struct Cli {
     /// Supply this to force the cache to be refreshed
     pub refresh: bool,
 
+    #[arg(long)]
+    /// Refresh cached cards that are older than this many days
+    pub cache_ttl: Option<u64>,
+
     #[arg(short='t', long)]
     pub num_trials: Option<u32>,
 
     #[arg(long)]
     pub max_turns: Option<u32>,
 
+    #[arg(long)]
+    /// How many cards are in the opening hand (defaults to 7)
+    pub hand_size: Option<u32>,
+
+    #[arg(long)]
+    /// The most mulligans to take before keeping (defaults to 7)
+    pub max_mulligans: Option<u32>,
+
+    #[arg(long)]
+    /// The chance, from 0 to 1, that the opponent destroys all of our creatures on each of their turns
+    pub wipe_chance: Option<f64>,
+
+    #[arg(long)]
+    /// Spells of at least this mana value count as threats, for `first-threat-turn` (defaults to 4)
+    pub threat_mana_value: Option<u8>,
+
+    #[arg(long, requires = "target_turn")]
+    /// Report the chance of being able to cast this card by each turn, such as a commander
+    pub target_card: Option<String>,
+
+    #[arg(long, requires = "target_card")]
+    /// The turn to report the chance of being able to cast `--target-card` by
+    pub target_turn: Option<u32>,
+
+    #[arg(long, default_value = "land-count")]
+    /// How to decide whether to mulligan: one of land-count, curve, functional, or never
+    pub mulligan_strategy: MulliganStrategy,
+
+    #[arg(long, default_value = "priority")]
+    /// How to choose what to play each turn: one of mana-value, mana-value-or-fixed-land, or priority
+    pub utility_fn: UtilityFunction,
+
+    #[arg(long, default_value = "castable-next-turn")]
+    /// How to choose between land drops that are as good this turn: one of castable-next-turn or none
+    pub land_tie_break: LandDropTieBreak,
+
+    #[arg(long, value_enum, default_value_t = PlayDrawArg::Random)]
+    /// Whether to go first, go second, or flip a coin each game
+    pub play_draw: PlayDrawArg,
+
+    #[arg(long)]
+    /// How many threads to run trials on (defaults to one per core)
+    pub threads: Option<usize>,
+
+    #[arg(long)]
+    /// Seed the random number generator, so that runs are reproducible
+    pub seed: Option<u64>,
+
+    #[arg(long)]
+    /// Stop starting new trials after this many seconds, and report the ones that finished
+    pub time_budget: Option<u64>,
+
     #[arg(long)]
     /// Supply this parameter to change the default level filters
     pub level_filter: Option<LevelFilter>,
 
-    #[arg(short='d', long)]
-    pub deck_list: PathBuf,
+    #[arg(short='d', long, global = true)]
+    /// The deck list to simulate, or to warm the cache with. Pass this more than once, or pass a directory, to compare several decks
+    pub deck_list: Vec<PathBuf>,
+
+    #[arg(long, global = true)]
+    /// Fetch a deck list from this url, such as a raw export link from an online deck builder.
+    /// The list may be json or text, and this may be passed more than once
+    pub deck_url: Vec<String>,
+
+    #[arg(long, value_enum, default_value_t = DeckListFormat::Json)]
+    /// The format the deck list is written in
+    pub format: DeckListFormat,
+
+    #[arg(long, value_enum, default_value_t = OutputFormat::Table)]
+    /// How to report the metrics
+    pub output_format: OutputFormat,
+
+    #[arg(long)]
+    /// Write the metrics report to this file instead of stdout
+    pub output: Option<PathBuf>,
+
+    #[arg(long)]
+    /// An annotations file to apply to the cards (defaults to the one in the project directory).
+    /// Pass this more than once to layer files, with later files adding to the annotations of earlier ones
+    pub annotations: Vec<PathBuf>,
+
+    #[arg(long)]
+    /// Look up cards in this scryfall bulk data file (such as "default-cards") before asking the scryfall api
+    pub bulk_data: Option<PathBuf>,
+
+    #[arg(long)]
+    /// Print what happened in one trial of each deck, turn by turn
+    pub replay: bool,
+
+    #[arg(long)]
+    /// Load, validate, and summarize the decks, then exit without simulating. Implies `--validate`
+    pub dry_run: bool,
+
+    #[arg(long)]
+    /// Check that the deck fits its commander's color identity before simulating
+    pub validate: bool,
+
+    #[arg(long, requires = "game_format")]
+    /// Check that the deck is legal against the banned and restricted lists in this json file before simulating
+    pub banlist: Option<PathBuf>,
+
+    #[arg(long, value_enum, requires = "banlist")]
+    /// The format to check `--banlist` against
+    pub game_format: Option<FormatArg>,
+
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(clap::Subcommand, Debug)]
+pub enum Command {
+    /// Vary the number of copies of one card, and find the number that does the best on a metric
+    Sweep {
+        #[arg(long)]
+        /// The card to vary the number of copies of
+        card: String,
+
+        #[arg(long, default_value_t = 0)]
+        /// The fewest copies to try
+        min: usize,
+
+        #[arg(long)]
+        /// The most copies to try
+        max: usize,
+
+        #[arg(long)]
+        /// The metric to measure, such as `land-drops`
+        metric: String,
+
+        #[arg(long)]
+        /// Look for the smallest average of the metric, instead of the largest
+        minimize: bool,
+    },
+    /// Write every card in the deck lists, with the annotations applied to it, to a json file, without running any trials.
+    /// Useful for checking what the simulator thinks each card does
+    DumpCollection {
+        /// Where to write the cards
+        path: PathBuf,
+    },
+    /// Print the cards added, removed, and changed in quantity between two deck lists, without running any trials
+    Diff {
+        /// The deck list to compare from
+        a: PathBuf,
+        /// The deck list to compare to
+        b: PathBuf,
+    },
+    /// Manage the local card cache, without running any trials
+    Cache {
+        #[command(subcommand)]
+        action: CacheCommand,
+    },
+}
+
+#[derive(clap::Subcommand, Debug)]
+pub enum CacheCommand {
+    /// Fetch every card in the deck lists, and in the names file, that is not already cached
+    Warm {
+        #[arg(long)]
+        /// A file with one card name on each line
+        names_file: Option<PathBuf>,
+    },
+    /// Delete the cache file
+    Clear,
+}
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug)]
+pub enum OutputFormat {
+    Table,
+    Json,
+    Csv,
+}
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug)]
+pub enum FormatArg {
+    Commander,
+    Standard,
+}
+
+impl From<FormatArg> for Format {
+    fn from(arg: FormatArg) -> Self {
+        match arg {
+            FormatArg::Commander => Format::Commander,
+            FormatArg::Standard => Format::Standard,
+        }
+    }
+}
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug)]
+pub enum PlayDrawArg {
+    Random,
+    /// Always go first, and skip the first draw
+    Play,
+    /// Always go second, and draw on the first turn
+    Draw,
+}
+
+impl From<PlayDrawArg> for PlayDraw {
+    fn from(arg: PlayDrawArg) -> Self {
+        match arg {
+            PlayDrawArg::Random => PlayDraw::Random,
+            PlayDrawArg::Play => PlayDraw::AlwaysPlay,
+            PlayDrawArg::Draw => PlayDraw::AlwaysDraw,
+        }
+    }
+}
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug)]
+pub enum DeckListFormat {
+    /// A json file with `command_zone` and `decklist` entries
+    Json,
+    /// A plain text list of `<quantity> <name>` lines, as exported by MTGA
+    Text,
 }
 
 pub fn configure_logging(cli: &Cli) {
@@ -78,32 +292,218 @@ fn make_table() -> Table {
     table
 }
 
-fn report_metrics_data(_cli: &Cli, metrics: &MetricsData) -> Result<()> {
+/// Renders the metrics that are keyed by turn with one row per metric and one column per turn
+fn turn_table(metrics: &MetricsData) -> Table {
+    let by_turn = metrics.by_turn();
+    // the opening hand is recorded as turn 0, so the columns start wherever the data does
+    let (first_turn, last_turn) = by_turn
+        .values()
+        .flat_map(|turns| turns.keys().copied())
+        .minmax()
+        .into_option()
+        .unwrap_or((1, 0));
+
     let mut table = make_table();
+    let mut titles = row!["Metrics Name"];
+    for turn in first_turn..=last_turn {
+        titles.add_cell(prettytable::Cell::new(&format!("Turn {turn}")));
+    }
+    table.set_titles(titles);
+    for (key, turns) in by_turn.iter() {
+        let mut row = row![key];
+        for turn in first_turn..=last_turn {
+            let average = turns.get(&turn).map(|average| format!("{average:.2}")).unwrap_or_default();
+            row.add_cell(prettytable::Cell::new(&average));
+        }
+        table.add_row(row);
+    }
+    table
+}
+
+fn report_metrics_data(cli: &Cli, metrics: &MetricsData) -> Result<()> {
+    let report = match cli.output_format {
+        OutputFormat::Table => {
+            let mut table = make_table();
+
+            table.set_titles(row!["Metrics Name", "Average", "Min", "Max"]);
+            // turn keyed metrics get their own table below, with a column per turn
+            for key in metrics.keys().filter(|key| key.turn().is_none()).sorted() {
 
-    table.set_titles(row!["Metrics Name", "Average", "Min", "Max"]);
-    for key in metrics.keys().sorted() {
+                table.add_row(row![key, metrics.average(key), metrics.min(key), metrics.max(key)]);
 
-        table.add_row(row![key, metrics.average(key), metrics.min(key), metrics.max(key)]);
+            }
+            if metrics.get(ConsistencyWatcher::KEY).trials_seen() > 0 {
+                let consistency = 100.0 * metrics.average(ConsistencyWatcher::KEY);
+                table.add_row(row!["Consistency", format!("{consistency:.1}%"), "", ""]);
+            }
+
+            format!("{table}{}", turn_table(metrics))
+        }
+        OutputFormat::Json => serde_json::to_string_pretty(&metrics.to_json())?,
+        OutputFormat::Csv => metrics.to_csv(),
+    };
 
+    match &cli.output {
+        Some(path) => {
+            log::info!("writing metrics report to {}", path.display());
+            file_utils::write_text_to_path(path, &report)?;
+        }
+        None => print!("{report}"),
     }
 
-    table.printstd();
+    Ok(())
+}
+
+fn print_deck_summary(name: &str, summary: &DeckSummary) {
+    let mut table = make_table();
+
+    table.set_titles(row![name, ""]);
+    table.add_row(row!["Lands", summary.land_count]);
+    table.add_row(row!["Average Mana Value", format!("{:.2}", summary.average_mana_value)]);
+    for (mana_value, count) in summary.curve.iter() {
+        table.add_row(row![format!("Mana Value {mana_value}"), count]);
+    }
+    for (mana_type, count) in summary.pips.iter() {
+        table.add_row(row![format!("{mana_type:?} Pips"), count]);
+    }
+
+    print!("{table}");
+}
+
+fn report_comparison(cli: &Cli, results: &[(String, MetricsData)]) -> Result<()> {
+    let keys = results
+        .iter()
+        .flat_map(|(_, metrics)| metrics.keys())
+        .unique()
+        .sorted()
+        .collect_vec();
+
+    let report = match cli.output_format {
+        OutputFormat::Table => {
+            let mut table = make_table();
+
+            let mut titles = row!["Metrics Name"];
+            for (name, _) in results {
+                titles.add_cell(prettytable::Cell::new(name));
+            }
+            table.set_titles(titles);
+            for key in keys.iter() {
+                let mut row = row![key];
+                for (_, metrics) in results {
+                    row.add_cell(prettytable::Cell::new(&metrics.average(*key).to_string()));
+                }
+                table.add_row(row);
+            }
+
+            table.to_string()
+        }
+        OutputFormat::Json => {
+            let report: serde_json::Map<String, serde_json::Value> = results
+                .iter()
+                .map(|(name, metrics)| (name.clone(), metrics.to_json()))
+                .collect();
+            serde_json::to_string_pretty(&report)?
+        }
+        OutputFormat::Csv => {
+            let mut report = std::iter::once("metric".to_string())
+                .chain(results.iter().map(|(name, _)| name.clone()))
+                .join(",");
+            report.push('\n');
+            for key in keys.iter() {
+                let line = std::iter::once(key.to_string())
+                    .chain(results.iter().map(|(_, metrics)| metrics.average(*key).to_string()))
+                    .join(",");
+                report.push_str(&line);
+                report.push('\n');
+            }
+            report
+        }
+    };
+
+    match &cli.output {
+        Some(path) => {
+            log::info!("writing comparison report to {}", path.display());
+            file_utils::write_text_to_path(path, &report)?;
+        }
+        None => print!("{report}"),
+    }
 
     Ok(())
 }
 
-fn evaluate_deck(cli: &Cli, deck: Deck) -> MetricsData {
-    let watcher = WatcherImpl;
-    let strategies = StrategyImpl {
-        rng: rand::rngs::StdRng::from_entropy()
+fn make_strategies(cli: &Cli) -> StrategyImpl {
+    let rng = match cli.seed {
+        Some(seed) => rand::rngs::StdRng::seed_from_u64(seed),
+        None => rand::rngs::StdRng::from_entropy(),
     };
+    StrategyImpl {
+        rng,
+        mulligan: cli.mulligan_strategy,
+        utility: cli.utility_fn,
+        land_tie_break: cli.land_tie_break,
+    }
+}
 
-    let props = trial::Props {
+type MainWatcher = CombinedWatcher<
+    CombinedWatcher<CombinedWatcher<CombinedWatcher<WatcherImpl, ThreatWatcher>, ConsistencyWatcher>, EfficiencyWatcher>,
+    Option<CastabilityWatcher>,
+>;
+
+/// Builds the watchers, looking up the `--target-card` in `cards`
+fn make_watcher(cli: &Cli, cards: &CardCollection) -> Result<MainWatcher> {
+    let threats = ThreatWatcher {
+        min_mana_value: cli.threat_mana_value.unwrap_or(watcher::DEFAULT_THREAT_MANA_VALUE),
+    };
+    let castability = match (&cli.target_card, cli.target_turn) {
+        (Some(name), Some(target_turn)) => {
+            let target = cards.card_named_ci(name)
+                .ok_or_else(|| format!("the target card '{name}' could not be found"))?;
+            Some(CastabilityWatcher { target, target_turn })
+        }
+        _ => None,
+    };
+    let watcher = watcher::combine(watcher::combine(WatcherImpl, threats), ConsistencyWatcher::default());
+    let watcher = watcher::combine(watcher, EfficiencyWatcher);
+    Ok(watcher::combine(watcher, castability))
+}
+
+fn make_props(cli: &Cli) -> trial::Props {
+    let defaults = trial::Props::default();
+    trial::Props {
         num_trials: cli.num_trials.unwrap_or(10_000),
         max_turn: cli.max_turns.unwrap_or(12),
+        seed: cli.seed,
+        hand_size: cli.hand_size.unwrap_or(defaults.hand_size),
+        max_mulligans: cli.max_mulligans.unwrap_or(defaults.max_mulligans),
+        play_draw: cli.play_draw.into(),
+        wipe_chance: cli.wipe_chance.unwrap_or(defaults.wipe_chance),
+        num_threads: cli.threads,
+        time_budget: cli.time_budget.map(Duration::from_secs),
+        ..defaults
+    }
+}
+
+/// Runs a single trial with a replay, and prints it. With a seed, this is the first trial of the full run.
+fn print_replay(cli: &Cli, watcher: &MainWatcher, name: &str, deck: Deck) {
+    let props = trial::Props {
+        num_trials: 1,
+        record_replay: true,
+        ..make_props(cli)
+    };
+    let rng = match cli.seed {
+        Some(seed) => trial::Rand::seed_from_u64(seed),
+        None => trial::Rand::from_entropy(),
     };
-    let metrics = trial::run_trials(deck, strategies, watcher, props);
+    let (_, replay) = trial::Trial::from_props(deck, rng, props)
+        .run_with_replay(&mut make_strategies(cli), watcher);
+    if let Some(replay) = replay {
+        println!("Replay of {name}:");
+        print!("{replay}");
+    }
+}
+
+fn evaluate_deck(cli: &Cli, watcher: &MainWatcher, deck: Deck) -> MetricsData {
+    let metrics = trial::run_trials(deck, make_strategies(cli), watcher.clone(), make_props(cli));
     
     report_metrics_data(&cli, &metrics)
         .handle_err(|e| log::error!("failed to report metrics data: {e}"));
@@ -111,6 +511,17 @@ fn evaluate_deck(cli: &Cli, deck: Deck) -> MetricsData {
     metrics
 }
 
+fn compare_decks(cli: &Cli, watcher: &MainWatcher, decks: Vec<(String, Deck)>) -> Vec<(String, MetricsData)> {
+    let results = experiment::compare_decks(decks, make_strategies(cli), watcher.clone(), make_props(cli));
+
+    report_comparison(cli, &results)
+        .handle_err(|e| log::error!("failed to report comparison: {e}"));
+
+    results
+}
+
+const SECONDS_PER_DAY: u64 = 24 * 60 * 60;
+
 const CARD_CACHE_FILENAME: &'static str = "cards.json";
 fn card_cache_path() -> Result<PathBuf> {
     let project_dirs = project_dirs()?;
@@ -125,36 +536,86 @@ fn annotations_path() -> Result<PathBuf> {
     Ok(path)
 }
 
-fn load_annotations(_cli: &Cli) -> Result<CardAnnotations> {
-    let path = annotations_path()?;
+/// The annotation files to apply, in order. Defaults to the one in the project directory
+fn annotations_paths(cli: &Cli) -> Result<Vec<PathBuf>> {
+    if cli.annotations.is_empty() {
+        return Ok(vec![annotations_path()?]);
+    }
+    Ok(cli.annotations.clone())
+}
 
-    log::info!("Loading annotations from project directory {}", path.display());
-    let annotations: CardAnnotations = file_utils::read_json_from_path(&path)?;
+fn load_annotations(path: &PathBuf) -> Result<CardAnnotations> {
+    log::info!("Loading annotations from {}", path.display());
+    let annotations: CardAnnotations = file_utils::read_json_from_path(path)?;
 
     Ok(annotations)
 }
 
-fn load_card_data(scenario: Vec<&str>, cli: &Cli, card_cache: &mut LocalCardCache, scryfall_client: &mut ScryfallClient) -> Result<CardCollection> {
-    let mut cards;
-    if cli.refresh {
-        log::info!("Refresh was requested, loading all card data from: {scryfall_client:?}");
-        cards = CardCollection::from_source(&scenario, scryfall_client)?;
-    } else {
-        cards = CardCollection::from_source(&scenario, &mut card_cache.chain(scryfall_client))?;
+/// Retrieves the cards, and logs how many came from each source
+fn collect_cards<S: CardSource>(scenario: &[&DeckAllocation], source: &mut S) -> Result<CardCollection> {
+    let card_data = source.retrieve_printings_with_provenance(scenario)?;
+    let counts = card_data
+        .iter()
+        .counts_by(|(_, source_id)| source_id.clone());
+    for (source_id, count) in counts.into_iter().sorted() {
+        log::info!("{count} cards from {source_id}");
     }
+    let card_data = card_data
+        .into_iter()
+        .map(|(card, _)| card)
+        .collect();
+    Ok(CardCollection::from_card_data(card_data))
+}
+
+fn load_card_data(scenario: Vec<&DeckAllocation>, inline_cards: Vec<CardData>, cli: &Cli, card_cache: &mut LocalCardCache, scryfall_client: &mut ScryfallClient) -> Result<CardCollection> {
+    let mut bulk_data = cli.bulk_data
+        .as_ref()
+        .map(BulkDataSource::from_path)
+        .transpose()?;
+
+    let mut cards = match (cli.refresh, bulk_data.as_mut()) {
+        (true, Some(bulk_data)) => {
+            log::info!("Refresh was requested, loading all card data from: {bulk_data:?} and {scryfall_client:?}");
+            collect_cards(&scenario, &mut bulk_data.chain(scryfall_client))?
+        }
+        (true, None) => {
+            log::info!("Refresh was requested, loading all card data from: {scryfall_client:?}");
+            collect_cards(&scenario, scryfall_client)?
+        }
+        (false, Some(bulk_data)) => {
+            collect_cards(&scenario, &mut card_cache.chain(bulk_data).extend(scryfall_client))?
+        }
+        (false, None) => {
+            collect_cards(&scenario, &mut card_cache.chain(scryfall_client))?
+        }
+    };
 
     log::info!("found {} total cards", cards.num_cards());
 
     log::info!("writing back to cache...");
     card_cache.save(cards.all_card_data());
 
-    match load_annotations(cli) {
-        Ok(annotations) => {
-            log::info!("found {} annotations, applying them now", annotations.len());
-            cards.apply_annotations(annotations);
-        }
-        Err(e) => {
-            log::error!("could not load annotations due to {e}");
+    // cards defined in the deck lists are only used when no source knew about them, and are never cached
+    let num_inline = cards.add_missing_cards(inline_cards);
+    if num_inline > 0 {
+        log::info!("using {num_inline} cards defined in the deck lists");
+    }
+
+    // later files extend the annotations from earlier ones
+    for path in annotations_paths(cli)? {
+        let annotations = match load_annotations(&path) {
+            Ok(annotations) => annotations,
+            // files passed on the command line must load, only the default file may be missing
+            Err(e) if cli.annotations.is_empty() => {
+                log::error!("could not load annotations due to {e}");
+                continue;
+            }
+            Err(e) => return Err(e),
+        };
+        log::info!("found {} annotations, applying them now", annotations.len());
+        let unmatched = cards.apply_annotations(annotations);
+        if !unmatched.is_empty() {
+            log::warn!("{} annotation targets in {} did not match any loaded card, check them for typos if they are in the deck: {}", unmatched.len(), path.display(), unmatched.join(", "));
         }
     }
 
@@ -165,29 +626,284 @@ fn load_card_data(scenario: Vec<&str>, cli: &Cli, card_cache: &mut LocalCardCach
 
 fn run(cli: Cli) -> Result<()> {
     let card_cache = card_cache_path()?;
-    let mut card_cache = LocalCardCache::from(card_cache);
+    let mut card_cache = match cli.cache_ttl {
+        Some(days) => LocalCardCache::with_ttl(card_cache, Duration::from_secs(days * SECONDS_PER_DAY)),
+        None => LocalCardCache::from(card_cache),
+    };
     let mut scryfall_client = ScryfallClient::new();
 
-    log::info!("loading deck from file");
-    let decklist: DeckList = file_utils::read_json_from_path(&cli.deck_list)?;
-    log::info!("openned deck, has {} cards", decklist.count());
+    if let Some(Command::Cache { action }) = &cli.command {
+        return run_cache(&cli, action, &mut card_cache, &mut scryfall_client);
+    }
+    if let Some(Command::Diff { a, b }) = &cli.command {
+        return run_diff(&cli, a, b);
+    }
+    if cli.deck_list.is_empty() && cli.deck_url.is_empty() {
+        return Err("at least one --deck-list or --deck-url is required".into());
+    }
 
+    let decklists = load_deck_lists(&cli)?;
 
-    let scenario = decklist.card_names();
+    let sweep_card = match &cli.command {
+        Some(Command::Sweep { card, .. }) => Some(card.as_str()),
+        _ => None,
+    };
+    let extra_cards = sweep_card
+        .into_iter()
+        .chain(cli.target_card.as_deref())
+        .map(|name| DeckAllocation::new(name, 1))
+        .collect_vec();
+    // the first allocation of each card decides which printing is requested
+    let scenario = decklists
+        .iter()
+        .flat_map(|(_, decklist)| decklist.allocations())
+        .chain(extra_cards.iter())
+        .unique_by(|da| da.name())
+        .collect_vec();
+
+    let inline_cards = decklists
+        .iter()
+        .flat_map(|(_, decklist)| decklist.inline_cards())
+        .collect_vec();
+
+    let cards = load_card_data(scenario, inline_cards, &cli, &mut card_cache, &mut scryfall_client)?;
+
+    if let Some(Command::DumpCollection { path }) = &cli.command {
+        file_utils::write_text_to_path(path, &cards.to_json()?)?;
+        println!("wrote {} cards to {}", cards.num_cards(), path.display());
+        return Ok(());
+    }
 
-    let cards = load_card_data(scenario, &cli, &mut card_cache, &mut scryfall_client)?;
-    let deck = decklist.into_deck(&cards)
+    if cli.validate || cli.dry_run {
+        for (name, decklist) in decklists.iter() {
+            if let Err(offenders) = decklist.validate_color_identity(&cards) {
+                println!("{name}: {} cards are outside the commander's color identity:", offenders.len());
+                for card in offenders {
+                    println!("  {}", cards.card_data(card).name);
+                }
+                return Err("deck failed validation".into());
+            }
+        }
+        log::info!("deck passed color identity validation");
+    }
+    if let (Some(path), Some(format)) = (&cli.banlist, cli.game_format) {
+        log::info!("loading banned and restricted lists from {}", path.display());
+        let ban_lists: BanLists = file_utils::read_json_from_path(path)?;
+        let format = Format::from(format);
+        for (name, decklist) in decklists.iter() {
+            if let Err(e) = decklist.validate_format_with_ban_lists(format, &cards, &ban_lists) {
+                println!("{name}: {e}");
+                return Err("deck failed validation".into());
+            }
+        }
+        log::info!("deck passed format validation");
+    }
+    for (name, decklist) in decklists.iter() {
+        let unreachable = decklist.unreachable_cards(&cards);
+        if !unreachable.is_empty() {
+            let names = unreachable.iter().map(|card| cards.card_data(*card).name.as_str()).join(", ");
+            log::warn!("{name}: no mana source in the deck can make the colors for {} cards, they will never be cast: {names}", unreachable.len());
+        }
+    }
+    // keep stdout clean when it is being used for a machine readable report
+    if cli.dry_run || cli.output.is_some() || matches!(cli.output_format, OutputFormat::Table) {
+        for (name, decklist) in decklists.iter() {
+            print_deck_summary(name, &decklist.summary(&cards));
+        }
+    }
+
+    let watcher = make_watcher(&cli, &cards)?;
+
+    let hand_size = make_props(&cli).hand_size;
+    let mut decks = decklists
+        .iter()
+        .map(|(name, decklist)| decklist.into_deck_with_hand_size(&cards, hand_size).map(|deck| (name.clone(), deck)))
+        .collect::<Result<Vec<_>, _>>()
         .inspect_err(|e| log::error!("error while loading deck list: {e}"))?;
 
+    if cli.dry_run {
+        println!("dry run: loaded {} deck(s), skipping the simulation", decks.len());
+        return Ok(());
+    }
+
+    if let Some(Command::Sweep { card, min, max, metric, minimize }) = &cli.command {
+        let [(_, decklist)] = decklists.as_slice() else {
+            return Err("sweeping needs exactly one deck list".into());
+        };
+        return run_sweep(&cli, &cards, decklist.clone(), card, *min..*max + 1, metric, *minimize);
+    }
+
     deck_optim::init(cards);
 
+    if cli.replay {
+        for (name, deck) in decks.iter() {
+            print_replay(&cli, &watcher, name, deck.clone());
+        }
+    }
+
     // do the trial
 
-    let _metrics = evaluate_deck(&cli, deck);
+    if decks.len() == 1 {
+        let (_, deck) = decks.remove(0);
+        let _metrics = evaluate_deck(&cli, &watcher, deck);
+    } else {
+        let _results = compare_decks(&cli, &watcher, decks);
+    }
 
     Ok(())
 }
 
+fn run_sweep(cli: &Cli, cards: &CardCollection, decklist: DeckList, card: &str, range: Range<usize>, metric: &str, minimize: bool) -> Result<()> {
+    let experiment = Experiment {
+        collection: cards,
+        strategies: make_strategies(cli),
+        watcher: make_watcher(cli, cards)?,
+        props: make_props(cli),
+    };
+    let sweep = experiment.sweep(decklist, card, range, metric, minimize)
+        .inspect_err(|e| log::error!("sweep over {card} failed: {e}"))?;
+
+    let mut table = make_table();
+    table.set_titles(row![format!("# of {card}"), metric, ""]);
+    for exp in sweep.results.iter() {
+        let is_best = if sweep.best == Some(exp.param) { "BEST" } else { "" };
+        table.add_row(row![exp.param, exp.measure, is_best]);
+    }
+    table.printstd();
+
+    Ok(())
+}
+
+fn run_cache(cli: &Cli, action: &CacheCommand, card_cache: &mut LocalCardCache, scryfall_client: &mut ScryfallClient) -> Result<()> {
+    let names_file = match action {
+        CacheCommand::Clear => {
+            if card_cache.clear()? {
+                println!("cleared card cache at {card_cache}");
+            } else {
+                println!("there is no card cache at {card_cache}");
+            }
+            return Ok(());
+        }
+        CacheCommand::Warm { names_file } => names_file,
+    };
+
+    let decklists = load_deck_lists(cli)?
+        .into_iter()
+        .map(|(_, decklist)| decklist)
+        .collect_vec();
+    let names_text = names_file
+        .as_ref()
+        .map(file_utils::read_text_from_path)
+        .transpose()?
+        .unwrap_or_default();
+    let card_names = decklists
+        .iter()
+        .flat_map(DeckList::card_names)
+        .chain(names_text.lines().map(str::trim).filter(|line| !line.is_empty()))
+        .unique()
+        .collect_vec();
+    if card_names.is_empty() {
+        return Err("nothing to warm the cache with, pass --deck-list, --deck-url, or --names-file".into());
+    }
+
+    let mut bulk_data = cli.bulk_data
+        .as_ref()
+        .map(BulkDataSource::from_path)
+        .transpose()?;
+    let fetched = match bulk_data.as_mut() {
+        Some(bulk_data) => card_cache.warm(&card_names, &mut bulk_data.chain(scryfall_client))?,
+        None => card_cache.warm(&card_names, scryfall_client)?,
+    };
+    println!("fetched {fetched} of {} cards into the card cache at {card_cache}", card_names.len());
+
+    Ok(())
+}
+
+/// Expand the `--deck-list` arguments, replacing each directory with the deck lists inside of it.
+fn deck_list_paths(cli: &Cli) -> Result<Vec<PathBuf>> {
+    let extension = match cli.format {
+        DeckListFormat::Json => "json",
+        DeckListFormat::Text => "txt",
+    };
+    let mut paths = Vec::new();
+    for path in cli.deck_list.iter() {
+        if !path.is_dir() {
+            paths.push(path.clone());
+            continue;
+        }
+        let entries = std::fs::read_dir(path)?
+            .map(|entry| entry.map(|entry| entry.path()))
+            .collect::<std::io::Result<Vec<_>>>()?;
+        let before = paths.len();
+        paths.extend(entries
+            .into_iter()
+            .filter(|path| path.is_file() && path.extension().is_some_and(|ext| ext == extension))
+            .sorted());
+        if paths.len() == before {
+            return Err(format!("no .{extension} deck lists found in {}", path.display()).into());
+        }
+    }
+    Ok(paths)
+}
+
+fn deck_name(path: &Path) -> String {
+    path.file_stem()
+        .unwrap_or(path.as_os_str())
+        .to_string_lossy()
+        .into_owned()
+}
+
+/// The last part of the url's path, so that `https://example.com/decks/burn.txt` is named `burn`
+fn deck_name_from_url(url: &str) -> String {
+    let path = url.split(['?', '#']).next().unwrap_or(url).trim_end_matches('/');
+    let last = path.rsplit('/').next().unwrap_or(path);
+    deck_name(Path::new(last))
+}
+
+/// Every deck list from the `--deck-list` paths, then every one from the `--deck-url` urls, along with their names
+fn load_deck_lists(cli: &Cli) -> Result<Vec<(String, DeckList)>> {
+    let mut decklists = deck_list_paths(cli)?
+        .iter()
+        .map(|path| load_deck_list(cli, path).map(|decklist| (deck_name(path), decklist)))
+        .collect::<Result<Vec<_>>>()?;
+    if !cli.deck_url.is_empty() {
+        let fetcher = DeckFetcher::new();
+        for url in cli.deck_url.iter() {
+            let decklist = fetcher.fetch(url)?;
+            log::info!("fetched deck, has {} cards", decklist.count());
+            decklists.push((deck_name_from_url(url), decklist));
+        }
+    }
+    // a card listed on two lines should be validated and simulated as one entry
+    for (_, decklist) in decklists.iter_mut() {
+        decklist.normalize();
+    }
+    Ok(decklists)
+}
+
+fn run_diff(cli: &Cli, a: &PathBuf, b: &PathBuf) -> Result<()> {
+    let before = load_deck_list(cli, a)?;
+    let after = load_deck_list(cli, b)?;
+
+    let diff = before.diff(&after);
+    if diff.is_empty() {
+        println!("{} and {} have the same cards", deck_name(a), deck_name(b));
+    } else {
+        print!("{diff}");
+    }
+    Ok(())
+}
+
+fn load_deck_list(cli: &Cli, path: &PathBuf) -> Result<DeckList> {
+    log::info!("loading deck from file {}", path.display());
+    let decklist: DeckList = match cli.format {
+        DeckListFormat::Json => file_utils::read_json_from_path(path)?,
+        DeckListFormat::Text => DeckList::parse_text(&file_utils::read_text_from_path(path)?)?,
+    };
+    log::info!("openned deck, has {} cards", decklist.count());
+    Ok(decklist)
+}
+
 fn main() {
     let cli = Cli::parse();
     configure_logging(&cli);
@@ -209,3 +925,24 @@ impl <E> ResultExt<E> for Result<(), E> {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use deck_optim::metrics::MetricsKey;
+
+    use super::*;
+
+    #[test]
+    fn test_turn_table_includes_the_opening_hand() {
+        let mut metrics = MetricsData::empty();
+        metrics.add_count(MetricsKey::from("cards-drawn").turn_num(0), 7);
+        metrics.add(MetricsKey::from("cards-drawn").turn_num(1));
+        metrics.end_trial();
+
+        let table = turn_table(&metrics).to_string();
+
+        assert!(table.contains("Turn 0"), "no column for the opening hand in:\n{table}");
+        assert!(table.contains("7.00"), "the opening hand draws are missing from:\n{table}");
+        assert!(table.contains("Turn 1"));
+    }
+}
@@ -1,12 +1,18 @@
 mod card_source;
-pub use card_source::CardSource;
+pub use card_source::{CardSource, SourceId};
+
+mod error;
+pub use error::CollectionError;
 
 pub mod card_cache;
 
 use std::collections::HashMap;
 
+use serde::{Deserialize, Serialize};
+
 use crate::game::CardData;
 use crate::game::annotations::{
+    AnnotationSchema,
     AnnotationSet,
     AnnotationTarget,
     CardAnnotations,
@@ -16,6 +22,8 @@ use crate::game::annotations::{
 pub struct CardCollection {
     cards: Vec<CardData>,
     name_lookup: HashMap<String, Card>,
+    /// Same as `name_lookup`, but keyed by normalized names
+    normalized_name_lookup: HashMap<String, Card>,
     annotations: HashMap<Card, AnnotationSet>,
 }
 
@@ -25,25 +33,29 @@ impl CardCollection {
         Self {
             cards: Vec::new(),
             name_lookup: HashMap::new(),
+            normalized_name_lookup: HashMap::new(),
             annotations: HashMap::new(),
         }
     }
     /// Initialize a collection from a vector of card data
     pub fn from_card_data(cards: Vec<CardData>) -> Self {
         let mut name_lookup = HashMap::with_capacity(cards.len());
+        let mut normalized_name_lookup = HashMap::with_capacity(cards.len());
         for (card_idx, card_data) in cards.iter().enumerate() {
             let name = card_data.name.to_string();
             let card = Card { idx: card_idx };
+            normalized_name_lookup.insert(normalize_name(&name), card);
             name_lookup.insert(name, card);
         }
         Self {
             cards,
             name_lookup,
+            normalized_name_lookup,
             annotations: HashMap::new(),
         }
     }
     /// Initialize from a list of sources
-    pub fn from_source<'a, S: CardSource>(card_names: &[&'a str], source: &mut S) -> Result<Self, Box<dyn std::error::Error>> {
+    pub fn from_source<'a, S: CardSource>(card_names: &[&'a str], source: &mut S) -> Result<Self, CollectionError> {
         let card_data = source.retrieve_cards(card_names)?;
         let col = CardCollection::from_card_data(card_data);
         Ok(col)
@@ -54,6 +66,19 @@ impl CardCollection {
     pub fn card_named(&self, name: &str) -> Option<Card> {
         self.name_lookup.get(name).copied()
     }
+    /// Looks up a card by name, ignoring differences in case and whitespace
+    pub fn card_named_ci(&self, name: &str) -> Option<Card> {
+        self.normalized_name_lookup.get(&normalize_name(name)).copied()
+    }
+    /// Finds the card whose name is closest to the given one, along with how many edits away it is.
+    /// Useful for suggesting fixes to typos.
+    pub fn closest_match(&self, name: &str) -> Option<(Card, usize)> {
+        let name = normalize_name(name);
+        self.normalized_name_lookup
+            .iter()
+            .map(|(candidate, card)| (*card, levenshtein_distance(&name, candidate)))
+            .min_by_key(|(card, distance)| (*distance, *card))
+    }
     pub fn contains(&self, name: &str) -> bool {
         self.card_named(name).is_some()
     }
@@ -70,28 +95,117 @@ impl CardCollection {
     pub fn all_card_data(&self) -> &[CardData] {
         self.cards.as_slice()
     }
-    pub fn apply_annotations(&mut self, annotations: CardAnnotations) {
-        annotations
-            .into_iter()
-            .for_each(|an| {
-                let AnnotationTarget { targets, annotation } = an;
-                for card_name in targets {
-                    let Some(card) = self.card_named(card_name.as_str()) else {
-                        continue;
-                    };
-                    self.annotations.entry(card)
-                        .or_default()
-                        .insert(annotation.clone());
-                }
-            });
+    /// Adds each card that the collection does not already have one by the same name of.
+    /// Returns how many were added.
+    pub fn add_missing_cards<I: IntoIterator<Item = CardData>>(&mut self, cards: I) -> usize {
+        let mut num_added = 0;
+        for card_data in cards {
+            if self.name_lookup.contains_key(&card_data.name) {
+                continue;
+            }
+            let card = Card { idx: self.cards.len() };
+            self.normalized_name_lookup.insert(normalize_name(&card_data.name), card);
+            self.name_lookup.insert(card_data.name.clone(), card);
+            self.cards.push(card_data);
+            num_added += 1;
+        }
+        num_added
+    }
+    /// Applies each annotation to the cards it targets.
+    /// Unknown "core:" annotations, or ones with the wrong kind of values, are still applied but logged as warnings.
+    /// Use [`CardAnnotations::validate`] to reject them instead.
+    ///
+    /// Returns the target names that did not match any card in the collection, without duplicates.
+    pub fn apply_annotations(&mut self, annotations: CardAnnotations) -> Vec<String> {
+        let mut unmatched = Vec::new();
+        for an in annotations.into_iter() {
+            let AnnotationTarget { targets, annotation } = an;
+            if let Err(violation) = AnnotationSchema::CORE.validate(&annotation) {
+                log::warn!("{violation}, on cards {targets:?}");
+            }
+            for card_name in targets {
+                let Some(card) = self.card_named(card_name.as_str()) else {
+                    if !unmatched.contains(&card_name) {
+                        unmatched.push(card_name);
+                    }
+                    continue;
+                };
+                self.annotations.entry(card)
+                    .or_default()
+                    .insert(annotation.clone());
+            }
+        }
+        unmatched
     }
     pub fn get_annotations(&self, card: Card) -> &AnnotationSet {
         const EMPTY: &'static AnnotationSet = &AnnotationSet::empty();
         self.annotations.get(&card)
             .unwrap_or(EMPTY)
     }
+    /// Every card, with the annotations that were applied to it, as pretty printed json.
+    /// This is what the engine actually thinks each card does, after merging every source of card data and annotations.
+    /// Cards are written out by name, since [`Card`] only makes sense within this collection.
+    pub fn to_json(&self) -> Result<String, serde_json::Error> {
+        let resolved: Vec<_> = self.iter()
+            .map(|card| ResolvedCard {
+                data: self.card_data(card).clone(),
+                annotations: self.get_annotations(card).clone(),
+            })
+            .collect();
+        serde_json::to_string_pretty(&resolved)
+    }
+    /// Reads back a collection written by [`CardCollection::to_json`]
+    pub fn from_json(json: &str) -> Result<Self, serde_json::Error> {
+        let resolved: Vec<ResolvedCard> = serde_json::from_str(json)?;
+        let mut annotations = HashMap::new();
+        let mut cards = Vec::with_capacity(resolved.len());
+        for (idx, ResolvedCard { data, annotations: card_annotations }) in resolved.into_iter().enumerate() {
+            if card_annotations != AnnotationSet::empty() {
+                annotations.insert(Card { idx }, card_annotations);
+            }
+            cards.push(data);
+        }
+        Ok(Self {
+            annotations,
+            ..Self::from_card_data(cards)
+        })
+    }
 }
 
+/// A card's data together with its annotations, as written by [`CardCollection::to_json`]
+#[derive(Serialize,Deserialize)]
+struct ResolvedCard {
+    #[serde(flatten)]
+    data: CardData,
+    #[serde(default)]
+    annotations: AnnotationSet,
+}
+
+/// Lowercases a card name and collapses any runs of whitespace
+pub(crate) fn normalize_name(name: &str) -> String {
+    name.split_whitespace()
+        .map(str::to_lowercase)
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// The number of single character insertions, deletions, or substitutions to turn one string into another
+fn levenshtein_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut prev_row: Vec<usize> = (0..=b.len()).collect();
+    for (i, a_ch) in a.chars().enumerate() {
+        let mut row = Vec::with_capacity(b.len() + 1);
+        row.push(i + 1);
+        for (j, b_ch) in b.iter().enumerate() {
+            let substitution = prev_row[j] + usize::from(a_ch != *b_ch);
+            let deletion = prev_row[j + 1] + 1;
+            let insertion = row[j] + 1;
+            row.push(substitution.min(deletion).min(insertion));
+        }
+        prev_row = row;
+    }
+    prev_row[b.len()]
+}
 
 /// An opaque type that indexes into a CardCollection
 #[derive(Clone,Copy,Eq,PartialEq,Hash,PartialOrd,Ord)]
@@ -128,13 +242,18 @@ mod global_collection {
     use once_cell::sync::OnceCell;
     use thiserror::Error;
 
-    static CARD_COLLECTION: OnceCell<CardCollection> = OnceCell::new();
+    pub(super) static CARD_COLLECTION: OnceCell<CardCollection> = OnceCell::new();
 
     pub fn init(card_collection: CardCollection) {
         CARD_COLLECTION.set(card_collection)
             .expect("initialization");
     }
 
+    /// Initializes the global card collection with a copy of `card_collection`, if it has not been initialized yet.
+    pub fn init_once(card_collection: &CardCollection) {
+        CARD_COLLECTION.get_or_init(|| card_collection.clone());
+    }
+
     /// Retrieves the card data from a globally initialized card collection
     pub fn get_card_data(card: Card) -> Option<&'static CardData> {
         let col = CARD_COLLECTION.get()?;
@@ -155,7 +274,7 @@ mod global_collection {
         NotInitialized { card: Card },
     }
 }
-pub use global_collection::init;
+pub use global_collection::{init, init_once};
 
 #[allow(unused)]
 /// Create sample cards for testing purposes.
@@ -171,3 +290,263 @@ pub fn get_sample_cards(num: usize) -> Vec<Card> {
 pub fn get_sample_cards_static<const N: usize>() -> [Card; N] {
     std::array::from_fn(|idx| Card { idx })
 }
+
+#[cfg(test)]
+/// A small, fixed card collection shared by all unit tests.
+/// The global collection can only be initialized once per process, so every test must use this one.
+pub(crate) mod fixtures {
+    use super::*;
+
+    use crate::game::annotations::CardAnnotations;
+    use crate::game::{CardType, ManaCost, SuperType};
+
+    const ANNOTATIONS: &str = r#"
+    { "annotations": [
+        { "targets": ["Forest", "Llanowar Elves"], "key": "core:Produces", "values": [{ "Mana": "{G}" }] },
+        { "targets": ["Mountain"], "key": "core:Produces", "values": [{ "Mana": "{R}" }] },
+        { "targets": ["Island"], "key": "core:Produces", "values": [{ "Mana": "{U}" }] },
+        { "targets": ["Sol Ring"], "key": "core:Produces", "values": [{ "Mana": "{C}{C}" }] },
+        { "targets": ["Rugged Highlands"], "key": "core:Produces", "values": [{ "Mana": "{R}" }, { "Mana": "{G}" }] },
+        { "targets": ["Rugged Highlands"], "key": "core:EntersTapped" },
+        { "targets": ["Selesnya Guildgate"], "key": "core:Produces", "values": [{ "Mana": "{G}" }, { "Mana": "{W}" }] },
+        { "targets": ["Selesnya Guildgate"], "key": "core:EntersTapped" },
+        { "targets": ["Evolving Wilds"], "key": "core:GameEffect", "values": [{ "String": "fetches" }] },
+        { "targets": ["Evolving Wilds"], "key": "core:FetchTarget", "values": [{ "String": "Forest" }, { "String": "Mountain" }] },
+        { "targets": ["Tempt with Discovery"], "key": "core:GameEffect", "values": [{ "String": "fetches" }] },
+        { "targets": ["Tempt with Discovery"], "key": "core:FetchTarget", "values": [{ "String": "Rugged Highlands" }] },
+        { "targets": ["Grizzly Bears"], "key": "core:Priority", "values": [{ "Int": 10 }] },
+        { "targets": ["Howling Mine"], "key": "core:ExtraDraw", "values": [{ "Int": 1 }] },
+        { "targets": ["Exploration"], "key": "core:ExtraLandDrop", "values": [{ "Int": 1 }] },
+        { "targets": ["Demonic Tutor"], "key": "core:GameEffect", "values": [{ "String": "tutor" }] },
+        { "targets": ["Demonic Tutor"], "key": "core:TutorTarget", "values": [{ "String": "Colossal Dreadmaw" }] },
+        { "targets": ["Regrowth", "Gravedigger"], "key": "core:GameEffect", "values": [{ "String": "regrowth" }] },
+        { "targets": ["Gravedigger"], "key": "core:RecurTarget", "values": [{ "String": "Grizzly Bears" }, { "String": "Hill Giant" }] },
+        { "targets": ["Leyline of Abundance"], "key": "core:StartInPlay" },
+        { "targets": ["Faithless Looting"], "key": "core:CastFrom", "values": [{ "String": "Graveyard" }] },
+        { "targets": ["Kolaghan's Command"], "key": "core:Modes", "values": [{ "String": "tutor" }, { "String": "regrowth" }] },
+        { "targets": ["Kolaghan's Command"], "key": "core:TutorTarget", "values": [{ "String": "Colossal Dreadmaw" }] },
+        { "targets": ["Kolaghan's Command"], "key": "core:RecurTarget", "values": [{ "String": "Grizzly Bears" }, { "String": "Hill Giant" }] }
+    ] }
+    "#;
+
+    fn card(name: &str, card_type: CardType, cost: Option<&str>) -> CardData {
+        CardData {
+            name: name.to_string(),
+            card_type,
+            super_types: vec![],
+            cost: cost.map(|c| ManaCost::try_parse(c).expect("fixture mana cost should parse")),
+            power: None,
+            toughness: None,
+            back: None,
+        }
+    }
+
+    fn creature(name: &str, cost: &str, power: i32, toughness: i32) -> CardData {
+        CardData {
+            power: Some(power),
+            toughness: Some(toughness),
+            ..card(name, CardType::Creature, Some(cost))
+        }
+    }
+
+    fn basic_land(name: &str) -> CardData {
+        CardData {
+            super_types: vec![SuperType::Basic],
+            ..card(name, CardType::Land, None)
+        }
+    }
+
+    fn build() -> CardCollection {
+        let mut collection = CardCollection::from_card_data(vec![
+            basic_land("Forest"),
+            basic_land("Mountain"),
+            basic_land("Island"),
+            // left without an annotation, so it makes mana only because it is a basic land
+            basic_land("Wastes"),
+            CardData {
+                super_types: vec![SuperType::Basic, SuperType::Snow],
+                ..card("Snow-Covered Forest", CardType::Land, None)
+            },
+            card("Evolving Wilds", CardType::Land, None),
+            card("Rugged Highlands", CardType::Land, None),
+            card("Selesnya Guildgate", CardType::Land, None),
+            card("Lightning Bolt", CardType::Instant, Some("{R}")),
+            creature("Hill Giant", "{3}{R}", 3, 3),
+            creature("Colossal Dreadmaw", "{4}{G}{G}", 6, 6),
+            creature("Llanowar Elves", "{G}", 1, 1),
+            creature("Grizzly Bears", "{1}{G}", 2, 2),
+            creature("Watchwolf", "{G}{W}", 3, 3),
+            card("Sol Ring", CardType::Artifact, Some("{1}")),
+            card("Fireball", CardType::Sorcery, Some("{X}{R}")),
+            card("Howling Mine", CardType::Artifact, Some("{2}")),
+            creature("Centaur Courser", "{2}{G}", 3, 3),
+            card("Regrowth", CardType::Sorcery, Some("{1}{G}")),
+            creature("Gravedigger", "{3}{B}", 2, 2),
+            card("Exploration", CardType::Enchantment, Some("{G}")),
+            card("Demonic Tutor", CardType::Sorcery, Some("{1}{B}")),
+            creature("Thought-Knot Seer", "{3}{C}", 4, 4),
+            card("Leyline of Abundance", CardType::Enchantment, Some("{2}{G}{G}")),
+            card("Faithless Looting", CardType::Sorcery, Some("{R}")),
+            card("Tempt with Discovery", CardType::Sorcery, Some("{3}{G}")),
+            card("Kolaghan's Command", CardType::Instant, Some("{1}{B}{R}")),
+        ]);
+        let annotations: CardAnnotations = serde_json::from_str(ANNOTATIONS).expect("fixture annotations should parse");
+        let unmatched = collection.apply_annotations(annotations);
+        assert!(unmatched.is_empty(), "fixture annotations target missing cards: {unmatched:?}");
+        collection
+    }
+
+    /// The fixture collection, initializing the global collection if needed.
+    pub fn collection() -> &'static CardCollection {
+        global_collection::CARD_COLLECTION
+            .get_or_init(build)
+    }
+
+    /// Looks up a card from the fixture collection, initializing the global collection if needed.
+    pub fn card_named(name: &str) -> Card {
+        collection()
+            .card_named(name)
+            .unwrap_or_else(|| panic!("no card named `{name}` in the test fixtures"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::CardType;
+
+    fn collection() -> CardCollection {
+        let card = |name: &str| CardData {
+            name: name.to_string(),
+            card_type: CardType::Instant,
+            super_types: vec![],
+            cost: None,
+            power: None,
+            toughness: None,
+            back: None,
+        };
+        CardCollection::from_card_data(vec![card("Lightning Bolt"), card("Lightning Helix"), card("Counterspell")])
+    }
+
+    #[test]
+    fn test_json_round_trip_keeps_annotations() {
+        let original = fixtures::collection();
+
+        let json = original.to_json().expect("to serialize");
+        let read_back = CardCollection::from_json(&json).expect("to deserialize");
+
+        assert_eq!(read_back.num_cards(), original.num_cards());
+        let mut num_annotated = 0;
+        for card in original.iter() {
+            let data = original.card_data(card);
+            let copy = read_back.card_named(&data.name).expect("every card to be read back");
+            assert_eq!(read_back.card_data(copy).card_type, data.card_type);
+            assert_eq!(read_back.card_data(copy).cost, data.cost);
+            assert_eq!(read_back.get_annotations(copy), original.get_annotations(card), "annotations of {}", data.name);
+            if original.get_annotations(card) != &AnnotationSet::empty() {
+                num_annotated += 1;
+            }
+        }
+        assert!(num_annotated > 0, "the fixtures should have some annotations to round trip");
+    }
+
+    #[test]
+    fn test_apply_annotations_reports_unmatched_targets() {
+        let mut collection = CardCollection::from_card_data(vec![CardData {
+            name: "Forest".to_string(),
+            card_type: CardType::Land,
+            super_types: vec![],
+            cost: None,
+            power: None,
+            toughness: None,
+            back: None,
+        }]);
+        let annotations: CardAnnotations = serde_json::from_str(r#"
+        { "annotations": [
+            { "targets": ["Forrest", "Forest"], "key": "core:Produces", "values": [{ "Mana": "{G}" }] },
+            { "targets": ["Forrest"], "key": "core:EntersTapped" }
+        ] }
+        "#).expect("annotations should parse");
+
+        let unmatched = collection.apply_annotations(annotations);
+
+        assert_eq!(unmatched, vec!["Forrest".to_string()]);
+        let forest = collection.card_named("Forest").expect("forest is in the collection");
+        assert!(collection.get_annotations(forest).get("core:Produces").is_some());
+        assert!(collection.get_annotations(forest).get("core:EntersTapped").is_none());
+    }
+
+    #[test]
+    fn test_apply_layered_annotations() {
+        let mut collection = CardCollection::from_card_data(vec![CardData {
+            name: "Rugged Highlands".to_string(),
+            card_type: CardType::Land,
+            super_types: vec![],
+            cost: None,
+            power: None,
+            toughness: None,
+            back: None,
+        }]);
+        let base: CardAnnotations = serde_json::from_str(r#"
+        { "annotations": [
+            { "targets": ["Rugged Highlands"], "key": "core:Produces", "values": [{ "Mana": "{R}" }] }
+        ] }
+        "#).expect("annotations should parse");
+        let overlay: CardAnnotations = serde_json::from_str(r#"
+        { "annotations": [
+            { "targets": ["Rugged Highlands"], "key": "core:Produces", "values": [{ "Mana": "{G}" }] },
+            { "targets": ["Rugged Highlands"], "key": "core:EntersTapped" }
+        ] }
+        "#).expect("annotations should parse");
+
+        collection.apply_annotations(base);
+        collection.apply_annotations(overlay);
+
+        let highlands = collection.card_named("Rugged Highlands").expect("highlands is in the collection");
+        let annotations = collection.get_annotations(highlands);
+        assert_eq!(annotations.keys().collect::<Vec<_>>(), vec!["core:Produces", "core:EntersTapped"]);
+        assert_eq!(annotations.get("core:Produces").map(|an| an.values().len()), Some(2));
+    }
+
+    #[test]
+    fn test_card_named_ci() {
+        let collection = collection();
+        let bolt = collection.card_named("Lightning Bolt");
+
+        assert!(bolt.is_some());
+        assert_eq!(collection.card_named("lightning bolt"), None);
+        assert_eq!(collection.card_named_ci("lightning bolt"), bolt);
+        assert_eq!(collection.card_named_ci(" LIGHTNING   bolt "), bolt);
+        assert_eq!(collection.card_named_ci("lightning"), None);
+    }
+
+    #[test]
+    fn test_closest_match_one_typo() {
+        let collection = collection();
+
+        let (card, distance) = collection.closest_match("Lightning Blot").expect("has cards");
+
+        assert_eq!(collection.card_data(card).name, "Lightning Bolt");
+        assert_eq!(distance, 2);
+
+        let (card, distance) = collection.closest_match("Counterspel").expect("has cards");
+
+        assert_eq!(collection.card_data(card).name, "Counterspell");
+        assert_eq!(distance, 1);
+    }
+
+    #[test]
+    fn test_closest_match_empty_collection() {
+        assert_eq!(CardCollection::empty().closest_match("Lightning Bolt"), None);
+    }
+
+    #[test]
+    fn test_levenshtein_distance() {
+        assert_eq!(levenshtein_distance("", ""), 0);
+        assert_eq!(levenshtein_distance("bolt", "bolt"), 0);
+        assert_eq!(levenshtein_distance("bolt", "blot"), 2);
+        assert_eq!(levenshtein_distance("bolt", "bolts"), 1);
+        assert_eq!(levenshtein_distance("kitten", "sitting"), 3);
+        assert_eq!(levenshtein_distance("", "abc"), 3);
+    }
+}
@@ -1,45 +1,170 @@
+use std::collections::HashMap;
 use std::fmt;
 use std::path::PathBuf;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
-use crate::collection::CardSource;
+use serde::{Deserialize, Serialize};
+
+use crate::collection::{CardSource, CollectionError, SourceId};
+use crate::deck::DeckAllocation;
 use crate::game::CardData;
 use crate::file_utils;
 
+/// A card in the cache, along with when it was fetched.
+#[derive(Clone,Debug,Serialize,Deserialize)]
+struct CachedCard {
+    #[serde(flatten)]
+    data: CardData,
+    /// Seconds since the unix epoch. Caches written before this was tracked count as infinitely old.
+    #[serde(default)]
+    fetched_at: u64,
+}
+
+impl CachedCard {
+    fn is_stale(&self, max_age: Option<Duration>, now: u64) -> bool {
+        match max_age {
+            Some(max_age) => now.saturating_sub(self.fetched_at) > max_age.as_secs(),
+            None => false,
+        }
+    }
+}
+
+/// The current time, in seconds since the unix epoch
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|since_epoch| since_epoch.as_secs())
+        .unwrap_or(0)
+}
+
 /// Provides a source of cards that has been cached locally.
 pub struct LocalCardCache {
-    file_path: PathBuf
+    file_path: PathBuf,
+    /// Cards older than this are treated as missing, so that they are fetched again
+    max_age: Option<Duration>,
+    /// When each card we handed out was fetched, so that saving does not make them look new
+    fetched_at: HashMap<String, u64>,
 }
 impl LocalCardCache {
     /// Initialize a card cache from a specific path
     pub fn from(file_path: PathBuf) -> Self {
-        Self { file_path }
+        Self {
+            file_path,
+            max_age: None,
+            fetched_at: HashMap::new(),
+        }
+    }
+    /// Initialize a card cache from a specific path, where cards older than `max_age` are refreshed
+    pub fn with_ttl(file_path: PathBuf, max_age: Duration) -> Self {
+        Self {
+            max_age: Some(max_age),
+            ..Self::from(file_path)
+        }
     }
     pub fn save(&mut self, card_data: &[CardData]) {
-        match file_utils::write_json_to_path(&self.file_path, &card_data) {
+        let now = now();
+        let cached_cards: Vec<CachedCard> = card_data
+            .iter()
+            .map(|data| CachedCard {
+                fetched_at: self.fetched_at.get(&data.name).copied().unwrap_or(now),
+                data: data.clone(),
+            })
+            .collect();
+        match file_utils::write_json_to_path(&self.file_path, &cached_cards) {
             Ok(_) => {}
             Err(e) => {
                 log::error!("unable to save back to card cache at {self} due to: {e}");
             }
         }
     }
-}
+    /// Fetches the cards that are missing from the cache, or stale, from `source` and writes them to the cache.
+    /// Cards that were already cached are kept, even if they were not asked for.
+    /// Returns how many cards were fetched.
+    pub fn warm<S: CardSource>(&mut self, card_names: &[&str], source: &mut S) -> Result<usize, CollectionError> {
+        let now = now();
+        let mut cached_cards = self.read_cached_cards();
 
-impl CardSource for LocalCardCache {
-    fn retrieve_cards(&mut self, card_names: &[&str]) -> Result<Vec<CardData>, Box<dyn std::error::Error>> {
+        let fresh = self.fresh_cards(cached_cards.clone(), card_names, now);
+        let missing = card_names
+            .iter()
+            .copied()
+            .filter(|name| fresh.iter().all(|card| card.name != *name))
+            .collect::<Vec<_>>();
+        log::info!("{} of {} cards are already cached, fetching the rest from {source:?}", fresh.len(), card_names.len());
+
+        let fetched = source.retrieve_cards(&missing)?;
+        cached_cards.retain(|cached| fetched.iter().all(|card| card.name != cached.data.name));
+        cached_cards.extend(fetched.iter().map(|data| CachedCard {
+            data: data.clone(),
+            fetched_at: now,
+        }));
+
+        file_utils::write_json_to_path(&self.file_path, &cached_cards)
+            .map_err(|source| CollectionError::Cache { path: self.file_path.clone(), source })?;
+        Ok(fetched.len())
+    }
+    /// Deletes the cache file. Returns whether there was one to delete.
+    pub fn clear(&self) -> std::io::Result<bool> {
+        match std::fs::remove_file(&self.file_path) {
+            Ok(()) => Ok(true),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(false),
+            Err(e) => Err(e),
+        }
+    }
+    fn read_cached_cards(&self) -> Vec<CachedCard> {
         log::debug!("opening card cache at {}", self.file_path.display());
 
-        let mut cards: Vec<CardData> = file_utils::read_json_from_path(&self.file_path)
+        let cached_cards: Vec<CachedCard> = file_utils::read_json_from_path(&self.file_path)
             .unwrap_or_else(|e| {
                 log::warn!("Card cache will be refreshed. could not read from {self}, due to: {e}.");
                 Vec::new()
             });
 
-        log::debug!("read {} cards from cache", cards.len());
-        cards.retain(|card| card_names.contains(&card.name.as_str()));
+        log::debug!("read {} cards from cache", cached_cards.len());
+        cached_cards
+    }
+    /// Keeps the cards that were asked for and are still fresh
+    fn fresh_cards(&mut self, cached_cards: Vec<CachedCard>, card_names: &[&str], now: u64) -> Vec<CardData> {
+        cached_cards
+            .into_iter()
+            .filter(|card| card_names.contains(&card.data.name.as_str()))
+            .filter(|card| {
+                let stale = card.is_stale(self.max_age, now);
+                if stale {
+                    log::info!("cached data for {} is stale, it will be refreshed", card.data.name);
+                }
+                !stale
+            })
+            .map(|card| {
+                self.fetched_at.insert(card.data.name.clone(), card.fetched_at);
+                card.data
+            })
+            .collect()
+    }
+}
+
+impl CardSource for LocalCardCache {
+    fn retrieve_cards(&mut self, card_names: &[&str]) -> Result<Vec<CardData>, CollectionError> {
+        let cached_cards = self.read_cached_cards();
+        let cards = self.fresh_cards(cached_cards, card_names, now());
 
         Ok(cards)
 
     }
+
+    /// The cache only keeps one printing of each card, so cards pinned to a set are left to the other sources
+    fn retrieve_printings_with_provenance(&mut self, allocations: &[&DeckAllocation]) -> Result<Vec<(CardData, SourceId)>, CollectionError> {
+        let card_names: Vec<_> = allocations
+            .iter()
+            .filter(|da| da.set().is_none())
+            .map(|da| da.name())
+            .collect();
+        self.retrieve_cards_with_provenance(&card_names)
+    }
+
+    fn source_id(&self) -> SourceId {
+        "cache".to_string()
+    }
 }
 
 impl fmt::Debug for LocalCardCache {
@@ -52,3 +177,151 @@ impl fmt::Display for LocalCardCache {
         write!(f, "{}", self.file_path.display())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DAY: u64 = 24 * 60 * 60;
+
+    fn cached_cards(source: &str) -> Vec<CachedCard> {
+        serde_json::from_str(source).expect("cache should parse")
+    }
+
+    const CACHE: &str = r#"[
+        { "name": "Lightning Bolt", "card_type": "Instant", "cost": "{R}", "power": null, "toughness": null, "fetched_at": 1000000 },
+        { "name": "Hill Giant", "card_type": "Creature", "cost": "{3}{R}", "power": 3, "toughness": 3, "fetched_at": 1900000 }
+    ]"#;
+
+    #[test]
+    fn test_old_entries_are_stale() {
+        let mut cache = LocalCardCache::with_ttl(PathBuf::from("unused.json"), Duration::from_secs(7 * DAY));
+
+        let cards = cache.fresh_cards(cached_cards(CACHE), &["Lightning Bolt", "Hill Giant"], 2000000);
+
+        assert_eq!(cards.iter().map(|card| card.name.as_str()).collect::<Vec<_>>(), vec!["Hill Giant"]);
+    }
+
+    #[test]
+    fn test_entries_never_stale_without_ttl() {
+        let mut cache = LocalCardCache::from(PathBuf::from("unused.json"));
+
+        let cards = cache.fresh_cards(cached_cards(CACHE), &["Lightning Bolt", "Hill Giant"], 2000000);
+
+        assert_eq!(cards.len(), 2);
+    }
+
+    #[test]
+    fn test_cache_without_timestamps_is_stale() {
+        let source = r#"[{ "name": "Lightning Bolt", "card_type": "Instant", "cost": "{R}", "power": null, "toughness": null }]"#;
+        let mut cache = LocalCardCache::with_ttl(PathBuf::from("unused.json"), Duration::from_secs(DAY));
+
+        let cards = cache.fresh_cards(cached_cards(source), &["Lightning Bolt"], now());
+
+        assert!(cards.is_empty());
+    }
+
+    #[test]
+    fn test_save_keeps_original_timestamps() {
+        let file_path = std::env::temp_dir().join(format!("deck-optim-test-cache-{}.json", std::process::id()));
+        let mut cache = LocalCardCache::with_ttl(file_path.clone(), Duration::from_secs(7 * DAY));
+
+        let cards = cache.fresh_cards(cached_cards(CACHE), &["Hill Giant"], 2000000);
+        cache.save(&cards);
+        let saved: Vec<CachedCard> = file_utils::read_json_from_path(&file_path).expect("cache was saved");
+        std::fs::remove_file(&file_path).expect("cleaned up");
+
+        assert_eq!(saved.len(), 1);
+        assert_eq!(saved[0].fetched_at, 1900000);
+    }
+
+    /// Hands out the cards it was built with, and remembers what it was asked for
+    #[derive(Debug)]
+    struct MockSource {
+        cards: Vec<CardData>,
+        requested: Vec<String>,
+    }
+    impl CardSource for MockSource {
+        fn retrieve_cards(&mut self, card_names: &[&str]) -> Result<Vec<CardData>, CollectionError> {
+            self.requested.extend(card_names.iter().map(|name| name.to_string()));
+            let cards = self.cards
+                .iter()
+                .filter(|card| card_names.contains(&card.name.as_str()))
+                .cloned()
+                .collect();
+            Ok(cards)
+        }
+    }
+
+    fn mock_source() -> MockSource {
+        MockSource {
+            cards: cached_cards(CACHE).into_iter().map(|card| card.data).collect(),
+            requested: Vec::new(),
+        }
+    }
+
+    fn temp_cache_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("deck-optim-test-{name}-{}.json", std::process::id()))
+    }
+
+    #[test]
+    fn test_warm_persists_cards() {
+        let file_path = temp_cache_path("warm");
+        let mut source = mock_source();
+
+        let fetched = LocalCardCache::from(file_path.clone())
+            .warm(&["Lightning Bolt", "Hill Giant"], &mut source)
+            .expect("should warm");
+        let reloaded = LocalCardCache::from(file_path.clone())
+            .retrieve_cards(&["Lightning Bolt", "Hill Giant"])
+            .expect("should reload");
+        std::fs::remove_file(&file_path).expect("cleaned up");
+
+        assert_eq!(fetched, 2);
+        let mut names = reloaded.iter().map(|card| card.name.as_str()).collect::<Vec<_>>();
+        names.sort();
+        assert_eq!(names, vec!["Hill Giant", "Lightning Bolt"]);
+    }
+
+    #[test]
+    fn test_warm_only_fetches_missing_cards() {
+        let file_path = temp_cache_path("warm-missing");
+        let mut cache = LocalCardCache::from(file_path.clone());
+        cache.warm(&["Lightning Bolt"], &mut mock_source()).expect("should warm");
+
+        let mut source = mock_source();
+        let fetched = cache.warm(&["Lightning Bolt", "Hill Giant"], &mut source).expect("should warm");
+        let saved: Vec<CachedCard> = file_utils::read_json_from_path(&file_path).expect("cache was saved");
+        std::fs::remove_file(&file_path).expect("cleaned up");
+
+        assert_eq!(fetched, 1);
+        assert_eq!(source.requested, vec!["Hill Giant"]);
+        assert_eq!(saved.len(), 2);
+    }
+
+    #[test]
+    fn test_pinned_printings_skip_the_cache() {
+        let file_path = temp_cache_path("pinned");
+        let mut cache = LocalCardCache::from(file_path.clone());
+        cache.warm(&["Lightning Bolt", "Hill Giant"], &mut mock_source()).expect("should warm");
+        let decklist = crate::deck::DeckList::parse_text("1 Lightning Bolt (M10) 146\n1 Hill Giant\n").expect("should parse");
+        let allocations: Vec<_> = decklist.allocations().collect();
+
+        let cards = cache.retrieve_printings_with_provenance(&allocations).expect("should read the cache");
+        std::fs::remove_file(&file_path).expect("cleaned up");
+
+        let names: Vec<_> = cards.iter().map(|(card, _)| card.name.as_str()).collect();
+        assert_eq!(names, vec!["Hill Giant"]);
+    }
+
+    #[test]
+    fn test_clear_deletes_cache() {
+        let file_path = temp_cache_path("clear");
+        let mut cache = LocalCardCache::from(file_path.clone());
+        cache.warm(&["Lightning Bolt"], &mut mock_source()).expect("should warm");
+
+        assert!(cache.clear().expect("should clear"));
+        assert!(!file_path.exists());
+        assert!(!cache.clear().expect("nothing to clear"));
+    }
+}
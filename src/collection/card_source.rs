@@ -1,10 +1,37 @@
+use itertools::Itertools;
+
+use crate::collection::CollectionError;
+use crate::deck::DeckAllocation;
 use crate::game::CardData;
 
-type DynError = Box<dyn std::error::Error>;
+/// Names the card source that some card data came from
+pub type SourceId = String;
 
 /// A way to supply card data to the card collection
 pub trait CardSource : std::fmt::Debug {
-    fn retrieve_cards(&mut self, _card_names: &[&str]) -> Result<Vec<CardData>, DynError>;
+    fn retrieve_cards(&mut self, _card_names: &[&str]) -> Result<Vec<CardData>, CollectionError>;
+
+    /// A short name for this source, for reporting where cards came from
+    fn source_id(&self) -> SourceId {
+        format!("{self:?}")
+    }
+
+    /// Like `retrieve_cards`, but also says which source each card came from
+    fn retrieve_cards_with_provenance(&mut self, card_names: &[&str]) -> Result<Vec<(CardData, SourceId)>, CollectionError> {
+        let source_id = self.source_id();
+        let card_data = self.retrieve_cards(card_names)?
+            .into_iter()
+            .map(|card| (card, source_id.clone()))
+            .collect();
+        Ok(card_data)
+    }
+
+    /// Like `retrieve_cards_with_provenance`, but asks for the printings the deck lists name.
+    /// Sources that do not keep track of printings look the cards up by name.
+    fn retrieve_printings_with_provenance(&mut self, allocations: &[&DeckAllocation]) -> Result<Vec<(CardData, SourceId)>, CollectionError> {
+        let card_names = allocations.iter().map(|da| da.name()).collect_vec();
+        self.retrieve_cards_with_provenance(&card_names)
+    }
 
     /// Creates a new card source that attempts to pull from this, then uses another card source as
     /// a backup
@@ -31,14 +58,23 @@ impl <'a> ChainCardSource<'a> {
     }
 }
 impl <'a> CardSource for ChainCardSource<'a> {
-    fn retrieve_cards(&mut self, card_names: &[&str]) -> Result<Vec<CardData>, DynError> {
+    fn retrieve_cards(&mut self, card_names: &[&str]) -> Result<Vec<CardData>, CollectionError> {
+        let card_data = self.retrieve_cards_with_provenance(card_names)?
+            .into_iter()
+            .map(|(card, _)| card)
+            .collect();
+        Ok(card_data)
+    }
+
+    /// Each card is reported as coming from the first source that had it
+    fn retrieve_cards_with_provenance(&mut self, card_names: &[&str]) -> Result<Vec<(CardData, SourceId)>, CollectionError> {
         let mut card_data = Vec::with_capacity(card_names.len());
         let mut still_required = card_names.to_vec();
 
         for s in self.sources.iter_mut() {
-            let new_cards = s.retrieve_cards(still_required.as_mut())?;
-            still_required.retain(|card_name| new_cards.iter().all(|card| &card.name != card_name));
-            log::info!("adding {} cards to card data from {s:?}", new_cards.len());
+            let new_cards = s.retrieve_cards_with_provenance(still_required.as_mut())?;
+            still_required.retain(|card_name| new_cards.iter().all(|(card, _)| &card.name != card_name));
+            log::debug!("adding {} cards to card data from {s:?}", new_cards.len());
             card_data.extend(new_cards);
         }
 
@@ -48,6 +84,25 @@ impl <'a> CardSource for ChainCardSource<'a> {
 
         Ok(card_data)
     }
+
+    /// Like `retrieve_cards_with_provenance`, passing the printings on to each source
+    fn retrieve_printings_with_provenance(&mut self, allocations: &[&DeckAllocation]) -> Result<Vec<(CardData, SourceId)>, CollectionError> {
+        let mut card_data = Vec::with_capacity(allocations.len());
+        let mut still_required = allocations.to_vec();
+
+        for s in self.sources.iter_mut() {
+            let new_cards = s.retrieve_printings_with_provenance(&still_required)?;
+            still_required.retain(|da| new_cards.iter().all(|(card, _)| card.name != da.name()));
+            log::debug!("adding {} cards to card data from {s:?}", new_cards.len());
+            card_data.extend(new_cards);
+        }
+
+        still_required
+            .into_iter()
+            .for_each(|da| log::error!("unable to locate a card named '{}'", da.name()));
+
+        Ok(card_data)
+    }
 }
 
 impl <'a> std::fmt::Debug for ChainCardSource<'a> {
@@ -55,3 +110,114 @@ impl <'a> std::fmt::Debug for ChainCardSource<'a> {
         write!(f, "(Chain Source: {:?})", self.sources)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use crate::collection::CardCollection;
+    use crate::scryfall::ScryfallError;
+
+    use super::*;
+
+    /// Hands out the cards it was built with, under a fixed name
+    #[derive(Debug)]
+    struct MockSource {
+        id: &'static str,
+        cards: Vec<CardData>,
+    }
+    impl MockSource {
+        fn new(id: &'static str, names: &[&str]) -> Self {
+            let cards = names
+                .iter()
+                .map(|name| serde_json::from_value(serde_json::json!({
+                    "name": name, "card_type": "Instant", "cost": "{R}", "power": null, "toughness": null
+                })).expect("card data should parse"))
+                .collect();
+            Self { id, cards }
+        }
+    }
+    impl CardSource for MockSource {
+        fn retrieve_cards(&mut self, card_names: &[&str]) -> Result<Vec<CardData>, CollectionError> {
+            let cards = self.cards
+                .iter()
+                .filter(|card| card_names.contains(&card.name.as_str()))
+                .cloned()
+                .collect();
+            Ok(cards)
+        }
+        fn source_id(&self) -> SourceId {
+            self.id.to_string()
+        }
+    }
+
+    /// Fails every request, as if scryfall kept turning us away
+    #[derive(Debug)]
+    struct FailingSource;
+    impl CardSource for FailingSource {
+        fn retrieve_cards(&mut self, _card_names: &[&str]) -> Result<Vec<CardData>, CollectionError> {
+            Err(ScryfallError::MaxRetries { times_tried: 3, time_elapsed: Duration::from_secs(1) }.into())
+        }
+    }
+
+    #[test]
+    fn test_chain_surfaces_scryfall_failure() {
+        let mut cache = MockSource::new("cache", &["Lightning Bolt"]);
+        let mut scryfall = FailingSource;
+
+        let err = CardCollection::from_source(&["Lightning Bolt", "Shock"], &mut cache.chain(&mut scryfall))
+            .expect_err("the failing source should fail the chain");
+
+        assert!(matches!(err, CollectionError::Scryfall(ScryfallError::MaxRetries { times_tried: 3, .. })));
+    }
+
+    #[test]
+    fn test_chain_reports_provenance() {
+        let mut cache = MockSource::new("cache", &["Lightning Bolt", "Shock"]);
+        let mut scryfall = MockSource::new("scryfall", &["Shock", "Fireblast"]);
+
+        let mut cards = cache.chain(&mut scryfall)
+            .retrieve_cards_with_provenance(&["Lightning Bolt", "Shock", "Fireblast", "Nonexistent Card"])
+            .expect("should retrieve")
+            .into_iter()
+            .map(|(card, source_id)| (card.name, source_id))
+            .collect::<Vec<_>>();
+        cards.sort();
+
+        assert_eq!(cards, vec![
+            ("Fireblast".to_string(), "scryfall".to_string()),
+            ("Lightning Bolt".to_string(), "cache".to_string()),
+            ("Shock".to_string(), "cache".to_string()),
+        ]);
+    }
+
+    /// Finds nothing, but remembers the printings it was asked for
+    #[derive(Debug, Default)]
+    struct PrintingRecorder {
+        requested: Vec<(String, Option<String>)>,
+    }
+    impl CardSource for PrintingRecorder {
+        fn retrieve_cards(&mut self, _card_names: &[&str]) -> Result<Vec<CardData>, CollectionError> {
+            Ok(vec![])
+        }
+        fn retrieve_printings_with_provenance(&mut self, allocations: &[&DeckAllocation]) -> Result<Vec<(CardData, SourceId)>, CollectionError> {
+            self.requested.extend(allocations.iter().map(|da| (da.name().to_string(), da.set().map(str::to_string))));
+            Ok(vec![])
+        }
+    }
+
+    #[test]
+    fn test_chain_passes_printings_on() {
+        let mut cache = MockSource::new("cache", &["Lightning Bolt"]);
+        let mut scryfall = PrintingRecorder::default();
+        let decklist = crate::deck::DeckList::parse_text("4 Lightning Bolt (LEA)\n1 Hill Giant (M10) 146\n").expect("should parse");
+        let allocations = decklist.allocations().collect_vec();
+
+        let cards = cache.chain(&mut scryfall)
+            .retrieve_printings_with_provenance(&allocations)
+            .expect("should retrieve");
+
+        assert_eq!(cards.len(), 1);
+        assert_eq!(scryfall.requested, vec![("Hill Giant".to_string(), Some("m10".to_string()))]);
+    }
+}
@@ -0,0 +1,16 @@
+use std::path::PathBuf;
+
+use thiserror::Error;
+
+use crate::scryfall::ScryfallError;
+
+/// Error in retrieving card data for the card collection
+#[derive(Debug,Error)]
+pub enum CollectionError {
+    #[error("error while getting card data from scryfall")]
+    Scryfall(#[from] ScryfallError),
+    #[error("failed to write the card cache at {}: {source}", path.display())]
+    Cache { path: PathBuf, source: std::io::Error },
+    #[error("io error while getting card data")]
+    Io(#[from] std::io::Error),
+}
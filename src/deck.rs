@@ -1,20 +1,116 @@
-use serde::Deserialize;
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+use itertools::Itertools;
+
+use serde::{Deserialize, Serialize};
 use thiserror::Error;
 
-use crate::collection::{Card, CardCollection};
-use crate::game::{CommandZone, Deck, UnorderedPile};
+use crate::collection::{normalize_name, Card, CardCollection};
+use crate::game::{CardData, CardType, CommandZone, Deck, ManaCost, ManaType, SuperType, UnorderedPile};
+use crate::game::mana::{colors_produced_by, ManaSource};
 
-#[derive(Clone,Debug,Deserialize)]
+mod fetch;
+pub use fetch::{DeckFetchError, DeckFetcher};
+
+#[derive(Clone,Debug,PartialEq,Eq,Serialize,Deserialize)]
 pub struct DeckList {
     #[serde(default = "Vec::new")]
     command_zone: Vec<DeckAllocation>,
     decklist: Vec<DeckAllocation>
 }
 
-#[derive(Clone,Debug,Deserialize)]
+const COMMANDER_DECK_SIZE: usize = 100;
+const STANDARD_MIN_DECK_SIZE: usize = 60;
+
+/// A constructed format, with its own deck building rules
+#[derive(Clone,Copy,Debug,PartialEq,Eq,Hash,Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Format {
+    /// Exactly 100 cards including the commander, and one copy of each non-basic card
+    Commander,
+    /// At least 60 cards, and up to four copies of each non-basic card
+    Standard,
+}
+
+impl Format {
+    /// The most copies of a single non-basic card that a deck may contain
+    pub fn max_copies(self) -> usize {
+        match self {
+            Format::Commander => 1,
+            Format::Standard => 4,
+        }
+    }
+}
+
+/// The cards banned or restricted in a single format
+#[derive(Clone,Debug,Default,PartialEq,Eq,Deserialize)]
+pub struct BanList {
+    /// Cards that may not appear at all
+    #[serde(default)]
+    pub banned: Vec<String>,
+    /// Cards that may appear at most once
+    #[serde(default)]
+    pub restricted: Vec<String>,
+}
+
+/// Banned and restricted lists for each format, read from a json file like
+/// `{ "standard": { "banned": ["Oko, Thief of Crowns"] } }`.
+/// Formats without a list have nothing banned.
+#[derive(Clone,Debug,Default,PartialEq,Eq,Deserialize)]
+#[serde(transparent)]
+pub struct BanLists {
+    formats: HashMap<Format, BanList>,
+}
+
+impl BanLists {
+    pub fn for_format(&self, format: Format) -> Option<&BanList> {
+        self.formats.get(&format)
+    }
+}
+
+#[derive(Clone,Debug,PartialEq,Eq,Serialize,Deserialize)]
 pub struct DeckAllocation {
     name: String,
     quantity: usize,
+    /// The set code of a specific printing, like `m10`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    set: Option<String>,
+    /// The collector number of a specific printing within its set, like `146`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    collector_number: Option<String>,
+    /// Defines the card right here, for cards that are not on scryfall
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    definition: Option<CardDefinition>,
+}
+
+/// A card defined right in the deck list, for custom or unreleased cards that scryfall does not know about.
+/// It is only used when the collection does not already have a card by that name.
+#[derive(Clone,Debug,PartialEq,Eq,Serialize,Deserialize)]
+pub struct CardDefinition {
+    pub card_type: CardType,
+    #[serde(default)]
+    pub super_types: Vec<SuperType>,
+    #[serde(default)]
+    pub cost: Option<ManaCost>,
+    #[serde(default)]
+    pub power: Option<i32>,
+    #[serde(default)]
+    pub toughness: Option<i32>,
+}
+
+impl CardDefinition {
+    fn to_card_data(&self, name: &str) -> CardData {
+        let Self { card_type, super_types, cost, power, toughness } = self.clone();
+        CardData {
+            name: name.to_string(),
+            card_type,
+            super_types,
+            cost,
+            power,
+            toughness,
+            back: None,
+        }
+    }
 }
 
 impl DeckList {
@@ -32,6 +128,173 @@ impl DeckList {
 
         card_names
     }
+    /// Whether there are any cards in the command zone
+    pub fn has_commander(&self) -> bool {
+        !self.command_zone.is_empty()
+    }
+    /// Every allocation in the deck, followed by those in the command zone
+    pub fn allocations(&self) -> impl Iterator<Item = &DeckAllocation> {
+        self.decklist.iter().chain(self.command_zone.iter())
+    }
+    /// The cards that are defined in the deck list itself, to add to the collection with
+    /// [`CardCollection::add_missing_cards`] before building the deck.
+    pub fn inline_cards(&self) -> Vec<CardData> {
+        self.allocations()
+            .filter_map(|da| da.definition.as_ref().map(|definition| definition.to_card_data(&da.name)))
+            .unique_by(|card| card.name.clone())
+            .collect()
+    }
+    /// Merges allocations of the same card into one, summing their quantities, so that a card listed on
+    /// more than one line is seen as a single entry. The deck and the command zone are merged separately.
+    /// The first allocation of each card keeps its place in the list, and its printing.
+    /// ```
+    /// use deck_optim::deck::DeckList;
+    ///
+    /// let mut decklist = DeckList::parse_text("1 Sol Ring\n20 Mountain\n1 Sol Ring\n").expect("should parse");
+    /// decklist.normalize();
+    ///
+    /// assert_eq!(decklist.card_names(), vec!["Sol Ring", "Mountain"]);
+    /// assert_eq!(decklist.count(), 22);
+    /// ```
+    pub fn normalize(&mut self) {
+        merge_allocations(&mut self.decklist);
+        merge_allocations(&mut self.command_zone);
+    }
+    /// Returns a copy of this decklist with exactly `quantity` copies of the card named `name` in the deck.
+    /// The card is added if it was not already in the deck.
+    /// ```
+    /// use deck_optim::deck::DeckList;
+    ///
+    /// let decklist = DeckList::parse_text("4 Lightning Bolt\n20 Mountain\n").expect("should parse");
+    ///
+    /// assert_eq!(decklist.with_quantity("Lightning Bolt", 8).count(), 28);
+    /// assert_eq!(decklist.with_quantity("Fireball", 1).count(), 25);
+    /// ```
+    pub fn with_quantity(&self, name: &str, quantity: usize) -> DeckList {
+        let mut decklist = self.clone();
+        match decklist.decklist.iter_mut().find(|da| da.name == name) {
+            Some(da) => da.quantity = quantity,
+            None => decklist.decklist.push(DeckAllocation::new(name, quantity)),
+        }
+        decklist
+    }
+    /// The cards added, removed, or changed in quantity going from this deck list to `other`.
+    /// Cards in the command zone are counted together with those in the deck.
+    /// ```
+    /// use deck_optim::deck::DeckList;
+    ///
+    /// let before = DeckList::parse_text("4 Lightning Bolt\n20 Mountain\n").expect("should parse");
+    /// let after = DeckList::parse_text("4 Lightning Bolt\n18 Mountain\n2 Shock\n").expect("should parse");
+    ///
+    /// let diff = before.diff(&after);
+    /// assert_eq!(diff.added, vec![("Shock".to_string(), 2)]);
+    /// assert_eq!(diff.changed, vec![("Mountain".to_string(), 20, 18)]);
+    /// assert!(diff.removed.is_empty());
+    /// ```
+    pub fn diff(&self, other: &DeckList) -> DeckDiff {
+        let before = self.quantities();
+        let after = other.quantities();
+
+        let mut diff = DeckDiff::default();
+        for (name, &old) in before.iter() {
+            match after.get(name) {
+                None => diff.removed.push((name.to_string(), old)),
+                Some(&new) if new != old => diff.changed.push((name.to_string(), old, new)),
+                Some(_) => {}
+            }
+        }
+        for (name, &new) in after.iter() {
+            if !before.contains_key(name) {
+                diff.added.push((name.to_string(), new));
+            }
+        }
+        diff
+    }
+    /// The total quantity of each card, in the deck and the command zone
+    fn quantities(&self) -> BTreeMap<&str, usize> {
+        let mut quantities = BTreeMap::new();
+        for da in self.allocations() {
+            *quantities.entry(da.name.as_str()).or_insert(0) += da.quantity;
+        }
+        quantities
+    }
+    /// Parses a plain text decklist, as exported by MTGA and most deck building sites.
+    ///
+    /// Each line is `<quantity> <name>`. A `Commander` header puts the following cards into the
+    /// command zone, and a `Deck` header (or a blank line after the commanders) goes back to the deck.
+    /// Everything after a `Sideboard` header, or a blank line after the deck, is ignored.
+    /// ```
+    /// use deck_optim::deck::DeckList;
+    ///
+    /// let source = "Commander\n1 Ruby, Daring Tracker\n\nDeck\n4 Lightning Bolt\n30 Mountain\n";
+    /// let decklist = DeckList::parse_text(source).expect("should parse");
+    ///
+    /// assert_eq!(decklist.count(), 34);
+    /// ```
+    pub fn parse_text(input: &str) -> Result<DeckList, DeckParseError> {
+        #[derive(PartialEq)]
+        enum Section { Commander, Deck, Sideboard }
+
+        let mut section = Section::Deck;
+        let mut command_zone = Vec::new();
+        let mut decklist = Vec::new();
+
+        for (idx, line) in input.lines().enumerate() {
+            let line = line.trim();
+            match line.to_lowercase().as_str() {
+                "" => {
+                    // a blank line ends the commanders, or separates the deck from the sideboard
+                    section = match section {
+                        Section::Commander => Section::Deck,
+                        Section::Deck if !decklist.is_empty() => Section::Sideboard,
+                        other => other,
+                    };
+                    continue;
+                }
+                "commander" => { section = Section::Commander; continue; }
+                "deck" => { section = Section::Deck; continue; }
+                "sideboard" | "companion" | "maybeboard" => { section = Section::Sideboard; continue; }
+                _ => {}
+            }
+
+            let allocation = parse_text_line(line)
+                .map_err(|kind| DeckParseError { line_num: idx + 1, line: line.to_string(), kind })?;
+
+            match section {
+                Section::Commander => command_zone.push(allocation),
+                Section::Deck => decklist.push(allocation),
+                Section::Sideboard => log::debug!("ignoring sideboard card: {}", allocation.name),
+            }
+        }
+
+        Ok(DeckList {
+            command_zone,
+            decklist
+        })
+    }
+    /// Writes this decklist in the plain text format read by [`DeckList::parse_text`].
+    /// The command zone, if any, goes under a `Commander` header, followed by the rest under a `Deck` header.
+    /// ```
+    /// use deck_optim::deck::DeckList;
+    ///
+    /// let decklist = DeckList::parse_text("4 Lightning Bolt\n20 Mountain\n").expect("should parse");
+    ///
+    /// assert_eq!(decklist.to_text(), "4 Lightning Bolt\n20 Mountain\n");
+    /// ```
+    pub fn to_text(&self) -> String {
+        let mut text = String::new();
+        if !self.command_zone.is_empty() {
+            text.push_str("Commander\n");
+            for da in self.command_zone.iter() {
+                text.push_str(&da.to_text_line());
+            }
+            text.push_str("\nDeck\n");
+        }
+        for da in self.decklist.iter() {
+            text.push_str(&da.to_text_line());
+        }
+        text
+    }
     pub fn into_deck(&self, collection: &CardCollection) -> Result<Deck, DeckConstructionError> {
         let mut command_zone = CommandZone::empty();
         for_each_card(&self.command_zone, collection, |card| command_zone.add(card))?;
@@ -44,38 +307,463 @@ impl DeckList {
             deck
         })
     }
+    /// Like [`DeckList::into_deck`], but also checks that the library can fill an opening hand of `hand_size`,
+    /// rather than leaving the simulation to play out a degenerate game.
+    pub fn into_deck_with_hand_size(&self, collection: &CardCollection, hand_size: u32) -> Result<Deck, DeckConstructionError> {
+        let deck = self.into_deck(collection)?;
+        let num_cards = deck.deck.size();
+        if num_cards < hand_size as usize {
+            log::error!("could not construct deck - {num_cards} cards is not enough for an opening hand of {hand_size}");
+            return Err(DeckConstructionError::TooFewCards { num_cards, hand_size });
+        }
+        Ok(deck)
+    }
+    /// Checks the deck against the deck building rules of a format: the number of cards in the deck,
+    /// and the number of copies allowed of each non-basic card.
+    /// Every violation is reported, not just the first.
+    ///
+    /// Cards missing from the collection are treated as non-basic.
+    pub fn validate_format(&self, format: Format, collection: &CardCollection) -> Result<(), FormatError> {
+        self.validate_format_with_ban_lists(format, collection, &BanLists::default())
+    }
+    /// Like [`DeckList::validate_format`], but also checks the format's banned and restricted lists.
+    /// Banned cards may not appear at all, and restricted cards at most once.
+    pub fn validate_format_with_ban_lists(&self, format: Format, collection: &CardCollection, ban_lists: &BanLists) -> Result<(), FormatError> {
+        let mut violations = Vec::new();
+        let ban_list = ban_lists.for_format(format);
+        // names are matched the same way as when building the deck, ignoring case and spacing
+        let listed = |names: &[String], name: &str| names.iter().any(|listed| normalize_name(listed) == normalize_name(name));
+        let is_banned = |name: &str| ban_list.is_some_and(|list| listed(&list.banned, name));
+        let is_restricted = |name: &str| ban_list.is_some_and(|list| listed(&list.restricted, name));
+
+        let total = self.count() + self.command_zone.iter().map(DeckAllocation::quantity).sum::<usize>();
+        match format {
+            Format::Commander if total != COMMANDER_DECK_SIZE => {
+                violations.push(FormatViolation::WrongDeckSize { expected: COMMANDER_DECK_SIZE, actual: total });
+            }
+            Format::Standard if total < STANDARD_MIN_DECK_SIZE => {
+                violations.push(FormatViolation::TooFewCards { minimum: STANDARD_MIN_DECK_SIZE, actual: total });
+            }
+            _ => {}
+        }
+
+        let max_copies = format.max_copies();
+        let mut copies: HashMap<&str, usize> = HashMap::new();
+        for da in self.command_zone.iter().chain(self.decklist.iter()) {
+            *copies.entry(da.name.as_str()).or_default() += da.quantity();
+        }
+        for (name, quantity) in copies.into_iter().sorted() {
+            if is_banned(name) {
+                violations.push(FormatViolation::Banned { name: name.to_string() });
+                continue;
+            }
+            if is_restricted(name) && quantity > 1 {
+                violations.push(FormatViolation::Restricted { name: name.to_string(), quantity });
+                continue;
+            }
+            let is_basic = collection.card_named(name).is_some_and(|card| collection.card_data(card).is_basic());
+            if !is_basic && quantity > max_copies {
+                violations.push(FormatViolation::TooManyCopies { name: name.to_string(), quantity, max_copies });
+            }
+        }
+
+        if violations.is_empty() {
+            Ok(())
+        } else {
+            Err(FormatError { format, violations })
+        }
+    }
+    /// Checks that every card in the deck fits within the color identity of the commanders.
+    /// Returns the cards whose costs contain a color outside that identity.
+    ///
+    /// Decks without a commander have no color identity to check, and always pass.
+    /// Cards missing from the collection are skipped; `into_deck` reports those.
+    pub fn validate_color_identity(&self, collection: &CardCollection) -> Result<(), Vec<Card>> {
+        if self.command_zone.is_empty() {
+            return Ok(());
+        }
+
+        let mut identity = HashSet::new();
+        for da in self.command_zone.iter() {
+            if let Some(card) = collection.card_named(&da.name) {
+                identity.extend(card_colors(collection, card));
+            }
+        }
+
+        let offenders: Vec<Card> = self.decklist
+            .iter()
+            .filter_map(|da| collection.card_named(&da.name))
+            .filter(|card| !card_colors(collection, *card).is_subset(&identity))
+            .collect();
+
+        if offenders.is_empty() {
+            Ok(())
+        } else {
+            Err(offenders)
+        }
+    }
+    /// Finds the nonland cards, commanders included, whose pips no combination of the deck's mana sources can ever pay,
+    /// such as a blue card in a deck without any blue sources. Catches a forgotten color before simulating.
+    ///
+    /// Mana rocks and dorks only count once the colors to cast them are available. Phyrexian pips can be paid with
+    /// life, so they never make a card unreachable. Cards missing from the collection are skipped; `into_deck` reports those.
+    pub fn unreachable_cards(&self, collection: &CardCollection) -> Vec<Card> {
+        let cards = self.command_zone
+            .iter()
+            .chain(self.decklist.iter())
+            .filter_map(|da| collection.card_named(&da.name))
+            .unique()
+            .collect_vec();
+        let is_land = |card: Card| collection.card_data(card).card_type == CardType::Land;
+
+        let (land_sources, mut spell_sources): (Vec<_>, Vec<_>) = cards
+            .iter()
+            .filter_map(|card| ManaSource::try_from_collection(*card, collection))
+            .partition(|source| is_land(source.card));
+        let mut makes_snow = land_sources.iter().any(|source| source.produces_snow);
+        let mut producible = colors_produced_by(land_sources);
+
+        // each spell that becomes castable may make the colors for another
+        loop {
+            let (castable, uncastable): (Vec<_>, Vec<_>) = spell_sources
+                .into_iter()
+                .partition(|source| {
+                    collection.card_data(source.card).cost
+                        .is_some_and(|cost| can_pay_pips(&cost, &producible, makes_snow))
+                });
+            if castable.is_empty() {
+                break;
+            }
+            makes_snow |= castable.iter().any(|source| source.produces_snow);
+            producible.extend(colors_produced_by(castable));
+            spell_sources = uncastable;
+        }
+
+        cards
+            .into_iter()
+            .filter(|card| !is_land(*card))
+            .filter(|card| {
+                collection.card_data(*card).cost
+                    .is_some_and(|cost| !can_pay_pips(&cost, &producible, makes_snow))
+            })
+            .collect()
+    }
+    /// Profiles the mana curve, color requirements, and land count of this deck, commanders included.
+    /// Cards missing from the collection are skipped; `into_deck` reports those.
+    pub fn summary(&self, collection: &CardCollection) -> DeckSummary {
+        let mut curve = BTreeMap::new();
+        let mut pips = BTreeMap::new();
+        let mut land_count = 0;
+        let mut total_mana_value = 0;
+
+        for da in self.command_zone.iter().chain(self.decklist.iter()) {
+            let Some(card) = collection.card_named(&da.name) else {
+                log::debug!("skipping `{}' in the deck summary, it is not in the collection", da.name);
+                continue;
+            };
+            let data = collection.card_data(card);
+            if data.card_type == CardType::Land {
+                land_count += da.quantity();
+                continue;
+            }
+
+            let cost = data.cost.unwrap_or_default();
+            *curve.entry(cost.mana_value()).or_default() += da.quantity();
+            total_mana_value += cost.mana_value() as usize * da.quantity();
+            for &mana_type in ManaType::all() {
+                let count = (cost.colors[mana_type] + cost.phyrexian[mana_type]) as usize;
+                if count > 0 {
+                    *pips.entry(mana_type).or_default() += count * da.quantity();
+                }
+            }
+        }
+
+        let nonland_count: usize = curve.values().sum();
+        let average_mana_value = match nonland_count {
+            0 => 0.0,
+            n => total_mana_value as f32 / n as f32,
+        };
+
+        DeckSummary {
+            curve,
+            pips,
+            land_count,
+            average_mana_value,
+        }
+    }
+}
+
+/// A static profile of a deck list, which does not need any trials to compute.
+#[derive(Clone,Debug,PartialEq)]
+pub struct DeckSummary {
+    /// The number of nonland cards at each mana value
+    pub curve: BTreeMap<u8, usize>,
+    /// The number of mana symbols of each type across every cost in the deck, phyrexian pips included
+    pub pips: BTreeMap<ManaType, usize>,
+    pub land_count: usize,
+    /// The average mana value of the nonland cards, or 0 if there are none
+    pub average_mana_value: f32,
+}
+
+/// Merges allocations with the same name into the first of them, see [`DeckList::normalize`].
+/// Names are matched the way the deck is built, ignoring case and spacing.
+fn merge_allocations(allocations: &mut Vec<DeckAllocation>) {
+    let mut merged: Vec<DeckAllocation> = Vec::with_capacity(allocations.len());
+    let mut index_of: HashMap<String, usize> = HashMap::new();
+    for da in allocations.drain(..) {
+        let key = normalize_name(&da.name);
+        match index_of.get(&key) {
+            Some(&idx) => {
+                let first = &mut merged[idx];
+                first.quantity += da.quantity;
+                if first.definition.is_none() {
+                    first.definition = da.definition;
+                }
+            }
+            None => {
+                index_of.insert(key, merged.len());
+                merged.push(da);
+            }
+        }
+    }
+    *allocations = merged;
+}
+
+/// Whether mana of these types, with snow mana if `makes_snow`, could pay for every pip of `cost` that needs a
+/// particular type. Phyrexian pips are left out, since they can be paid with life.
+fn can_pay_pips(cost: &ManaCost, producible: &HashSet<ManaType>, makes_snow: bool) -> bool {
+    cost.colors.mana_types().all(|mana_type| producible.contains(&mana_type))
+        && (cost.s_count == 0 || makes_snow)
+}
+
+/// The changes between two deck lists, see [`DeckList::diff`]. Each list is sorted by card name.
+#[derive(Clone,Debug,Default,PartialEq,Eq)]
+pub struct DeckDiff {
+    /// The cards only in the second list, with their quantity
+    pub added: Vec<(String, usize)>,
+    /// The cards only in the first list, with their quantity
+    pub removed: Vec<(String, usize)>,
+    /// The cards in both lists with a different quantity, as the old and the new quantity
+    pub changed: Vec<(String, usize, usize)>,
+}
+
+impl DeckDiff {
+    /// Whether the two lists have exactly the same cards
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty()
+    }
 }
 
+impl std::fmt::Display for DeckDiff {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (name, quantity) in self.added.iter() {
+            writeln!(f, "+ {quantity} {name}")?;
+        }
+        for (name, quantity) in self.removed.iter() {
+            writeln!(f, "- {quantity} {name}")?;
+        }
+        for (name, old, new) in self.changed.iter() {
+            writeln!(f, "~ {name}: {old} -> {new}")?;
+        }
+        Ok(())
+    }
+}
+
+/// The colors in a card's mana cost, including those of its phyrexian pips.
+fn card_colors(collection: &CardCollection, card: Card) -> HashSet<ManaType> {
+    let Some(cost) = collection.card_data(card).cost else {
+        return HashSet::new();
+    };
+    let mut colors = cost.colors.colors_present();
+    colors.extend(cost.phyrexian.colors_present());
+    colors
+}
+
+/// Names further than this many edits away from any card are not worth suggesting
+const MAX_SUGGESTION_DISTANCE: usize = 3;
+
 fn for_each_card<'a, Iter: IntoIterator<Item = &'a DeckAllocation>, F: FnMut(Card)>(deck_allocations: Iter, collection: &CardCollection, mut consumer: F) -> Result<(), DeckConstructionError> {
-    let mut num_missing = 0;
+    let mut missing = Vec::new();
     for da in deck_allocations {
         let name = da.name.as_str();
-        let Some(card) = collection.card_named(name) else {
-            log::error!("could not construct deck - no card with name `{name}'");
-            num_missing += 1;
+        let card = collection.card_named(name)
+            .or_else(|| {
+                let card = collection.card_named_ci(name)?;
+                log::warn!("no card with name `{name}', using `{}' instead", collection.card_data(card).name);
+                Some(card)
+            });
+        let Some(card) = card else {
+            let suggestion = collection.closest_match(name)
+                .filter(|(_, distance)| *distance <= MAX_SUGGESTION_DISTANCE)
+                .map(|(card, _)| collection.card_data(card).name.clone());
+            match &suggestion {
+                Some(suggestion) => log::error!("could not construct deck - no card with name `{name}', did you mean `{suggestion}'?"),
+                None => log::error!("could not construct deck - no card with name `{name}'"),
+            }
+            missing.push(MissingCard { name: name.to_string(), suggestion });
             continue;
         };
         for _ in 0..da.quantity() {
             consumer(card);
         }
     }
-    if num_missing > 0 {
-        return Err(DeckConstructionError::MissingCards { num_missing });
+    if !missing.is_empty() {
+        return Err(DeckConstructionError::MissingCards { num_missing: missing.len(), missing });
     }
     Ok(())
 }
 
 
+/// Parses a single `<quantity> <name>` line.
+/// Quantities may be written as `4` or `4x`, and a trailing set code like `(M10) 146` selects that printing.
+fn parse_text_line(line: &str) -> Result<DeckAllocation, DeckParseErrorKind> {
+    let Some((quantity, name)) = line.split_once(char::is_whitespace) else {
+        return Err(DeckParseErrorKind::MissingName);
+    };
+    let quantity = quantity
+        .strip_suffix(['x', 'X'])
+        .unwrap_or(quantity)
+        .parse::<usize>()
+        .map_err(|source| DeckParseErrorKind::InvalidQuantity { source })?;
+
+    let (name, printing) = match name.find(" (") {
+        Some(idx) => (&name[..idx], parse_printing(&name[idx + 1..])),
+        None => (name, None),
+    };
+    let name = name.trim();
+    if name.is_empty() {
+        return Err(DeckParseErrorKind::MissingName);
+    }
+
+    let (set, collector_number) = match printing {
+        Some((set, collector_number)) => (Some(set), collector_number),
+        None => (None, None),
+    };
+    Ok(DeckAllocation {
+        name: name.to_string(),
+        quantity,
+        set,
+        collector_number,
+        definition: None,
+    })
+}
+
+/// Parses a printing like `(M10) 146` into a lowercase set code and an optional collector number.
+/// Anything that does not look like a set code is ignored.
+fn parse_printing(printing: &str) -> Option<(String, Option<String>)> {
+    let (set, rest) = printing.strip_prefix('(')?.split_once(')')?;
+    let set = set.trim();
+    if set.is_empty() {
+        return None;
+    }
+    let collector_number = rest
+        .split_whitespace()
+        .next()
+        .map(str::to_string);
+    Some((set.to_lowercase(), collector_number))
+}
+
 impl DeckAllocation {
+    /// Some copies of a card by name, with no particular printing
+    pub fn new(name: &str, quantity: usize) -> Self {
+        Self {
+            name: name.to_string(),
+            quantity,
+            set: None,
+            collector_number: None,
+            definition: None,
+        }
+    }
+    pub fn name(&self) -> &str {
+        self.name.as_str()
+    }
     pub fn quantity(&self) ->  usize {
         self.quantity
     }
+    /// The set code of the printing to use, if one was requested
+    pub fn set(&self) -> Option<&str> {
+        self.set.as_deref()
+    }
+    /// The collector number of the printing to use, if one was requested
+    pub fn collector_number(&self) -> Option<&str> {
+        self.collector_number.as_deref()
+    }
+    /// The card's own definition, if it was defined in the deck list
+    pub fn definition(&self) -> Option<&CardDefinition> {
+        self.definition.as_ref()
+    }
+    /// The inverse of [`parse_text_line`], including the trailing newline.
+    /// Text deck lists can not define cards, so any definition is left out.
+    fn to_text_line(&self) -> String {
+        let Self { name, quantity, set, collector_number, definition: _ } = self;
+        match (set, collector_number) {
+            (Some(set), Some(collector_number)) => format!("{quantity} {name} ({}) {collector_number}\n", set.to_uppercase()),
+            (Some(set), None) => format!("{quantity} {name} ({})\n", set.to_uppercase()),
+            (None, _) => format!("{quantity} {name}\n"),
+        }
+    }
+}
+
+#[derive(Debug,Error)]
+#[error("could not parse line {line_num} of decklist `{line}`: {kind}")]
+pub struct DeckParseError {
+    pub line_num: usize,
+    pub line: String,
+    pub kind: DeckParseErrorKind,
+}
+
+#[derive(Debug,Error)]
+pub enum DeckParseErrorKind {
+    #[error("expected a line of the form `<quantity> <name>`")]
+    MissingName,
+    #[error("invalid quantity: {source}")]
+    InvalidQuantity { source: std::num::ParseIntError },
+}
+
+#[derive(Debug,Error)]
+#[error("deck is not legal in {format:?}: {}", violations.iter().join("; "))]
+pub struct FormatError {
+    pub format: Format,
+    pub violations: Vec<FormatViolation>,
+}
+
+#[derive(Debug,Error,PartialEq,Eq)]
+pub enum FormatViolation {
+    #[error("deck must have exactly {expected} cards, found {actual}")]
+    WrongDeckSize { expected: usize, actual: usize },
+    #[error("deck must have at least {minimum} cards, found {actual}")]
+    TooFewCards { minimum: usize, actual: usize },
+    #[error("found {quantity} copies of `{name}`, at most {max_copies} are allowed")]
+    TooManyCopies { name: String, quantity: usize, max_copies: usize },
+    #[error("`{name}` is banned")]
+    Banned { name: String },
+    #[error("found {quantity} copies of `{name}`, which is restricted to one")]
+    Restricted { name: String, quantity: usize },
 }
 
 #[derive(Debug,Error)]
 pub enum DeckConstructionError {
-    #[error("unable to construct deck - unable to find {num_missing} cards")]
-    MissingCards { num_missing: usize }
+    #[error("unable to construct deck - unable to find {num_missing} cards: {}", missing.iter().join(", "))]
+    MissingCards { num_missing: usize, missing: Vec<MissingCard> },
+    #[error("unable to construct deck - it has {num_cards} cards, fewer than an opening hand of {hand_size}")]
+    TooFewCards { num_cards: usize, hand_size: u32 },
+}
+
+/// A card in the deck list that is not in the collection
+#[derive(Debug,Clone,PartialEq,Eq)]
+pub struct MissingCard {
+    pub name: String,
+    /// The name of a similar card, if there is one
+    pub suggestion: Option<String>,
+}
+
+impl std::fmt::Display for MissingCard {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.suggestion {
+            Some(suggestion) => write!(f, "`{}` (did you mean `{suggestion}`?)", self.name),
+            None => write!(f, "`{}`", self.name),
+        }
+    }
 }
 
 #[cfg(test)]
@@ -85,6 +773,7 @@ mod tests {
     use crate::game::CardData;
     use crate::game::CardType;
     use crate::game::ManaCost;
+    use crate::game::SuperType;
 
 
     fn mock_collection() -> CardCollection {
@@ -92,17 +781,56 @@ mod tests {
             CardData {
                 name: "Hill Giant".to_string(),
                 card_type: CardType::Creature,
-                cost: Some(ManaCost::try_parse("{3}{R}").expect("mana cost"))
+                super_types: vec![],
+                cost: Some(ManaCost::try_parse("{3}{R}").expect("mana cost")),
+                power: Some(3),
+                toughness: Some(3),
+                back: None,
             },
             CardData {
                 name: "Lightning Bolt".to_string(),
                 card_type: CardType::Instant,
-                cost: Some(ManaCost::try_parse("{R}").expect("mana cost"))
+                super_types: vec![],
+                cost: Some(ManaCost::try_parse("{R}").expect("mana cost")),
+                power: None,
+                toughness: None,
+                back: None,
             },
             CardData {
                 name: "Island".to_string(),
                 card_type: CardType::Land,
+                super_types: vec![SuperType::Basic],
                 cost: None,
+                power: None,
+                toughness: None,
+                back: None,
+            },
+            CardData {
+                name: "Thalia, Guardian of Thraben".to_string(),
+                card_type: CardType::Creature,
+                super_types: vec![],
+                cost: Some(ManaCost::try_parse("{1}{W}").expect("mana cost")),
+                power: Some(2),
+                toughness: Some(1),
+                back: None,
+            },
+            CardData {
+                name: "Swords to Plowshares".to_string(),
+                card_type: CardType::Instant,
+                super_types: vec![],
+                cost: Some(ManaCost::try_parse("{W}").expect("mana cost")),
+                power: None,
+                toughness: None,
+                back: None,
+            },
+            CardData {
+                name: "Counterspell".to_string(),
+                card_type: CardType::Instant,
+                super_types: vec![],
+                cost: Some(ManaCost::try_parse("{U}{U}").expect("mana cost")),
+                power: None,
+                toughness: None,
+                back: None,
             },
         ];
         CardCollection::from_card_data(cards)
@@ -113,8 +841,8 @@ mod tests {
         let decklist = DeckList {
             command_zone: vec![],
             decklist: vec![
-                DeckAllocation { name: "Fireball".to_string(), quantity: 3 },
-                DeckAllocation { name: "Lightning Bolt".to_string(), quantity: 2 },
+                DeckAllocation::new("Fireball", 3),
+                DeckAllocation::new("Lightning Bolt", 2),
             ],
         };
         assert_eq!(decklist.count(), 5);
@@ -125,8 +853,8 @@ mod tests {
         let decklist = DeckList {
             command_zone: vec![],
             decklist: vec![
-                DeckAllocation { name: "Fireball".to_string(), quantity: 3 },
-                DeckAllocation { name: "Lightning Bolt".to_string(), quantity: 2 },
+                DeckAllocation::new("Fireball", 3),
+                DeckAllocation::new("Lightning Bolt", 2),
             ],
         };
         let mut names = decklist.card_names();
@@ -134,37 +862,558 @@ mod tests {
         assert_eq!(names, vec!["Fireball", "Lightning Bolt"]);
     }
 
+    #[test]
+    fn test_with_quantity_replaces_existing_allocation() {
+        let decklist = DeckList::parse_text("4 Lightning Bolt\n20 Mountain\n").expect("should parse");
+
+        let changed = decklist.with_quantity("Mountain", 16);
+
+        assert_eq!(changed.card_names(), vec!["Lightning Bolt", "Mountain"]);
+        assert_eq!(changed.count(), 20);
+        assert_eq!(decklist.count(), 24);
+    }
+
+    #[test]
+    fn test_normalize_merges_duplicate_entries() {
+        let mut decklist = DeckList {
+            command_zone: vec![],
+            decklist: vec![
+                DeckAllocation::new("Sol Ring", 1),
+                DeckAllocation::new("Forest", 30),
+                DeckAllocation::new("Sol Ring", 1),
+            ],
+        };
+
+        decklist.normalize();
+
+        assert_eq!(decklist.decklist, vec![
+            DeckAllocation::new("Sol Ring", 2),
+            DeckAllocation::new("Forest", 30),
+        ]);
+        assert_eq!(decklist.count(), 32);
+    }
+
+    #[test]
+    fn test_normalize_merges_names_in_different_case() {
+        let mut decklist = DeckList::parse_text("1 Sol Ring\n30 Forest\n1 sol ring\n").expect("should parse");
+
+        decklist.normalize();
+
+        assert_eq!(decklist.decklist, vec![
+            DeckAllocation::new("Sol Ring", 2),
+            DeckAllocation::new("Forest", 30),
+        ]);
+    }
+
+    #[test]
+    fn test_normalize_keeps_zones_apart_and_first_printing() {
+        let source = "Commander\n1 Colossal Dreadmaw\n\nDeck\n1 Hill Giant (M10) 146\n1 Colossal Dreadmaw\n3 Hill Giant\n";
+        let mut decklist = DeckList::parse_text(source).expect("should parse");
+
+        decklist.normalize();
+
+        assert_eq!(decklist.command_zone.len(), 1);
+        assert_eq!(decklist.decklist.len(), 2);
+        let giant = &decklist.decklist[0];
+        assert_eq!(giant.name(), "Hill Giant");
+        assert_eq!(giant.quantity(), 4);
+        assert_eq!(giant.set.as_deref(), Some("m10"));
+        assert_eq!(decklist.decklist[1].quantity(), 1);
+    }
+
+    #[test]
+    fn test_parse_text_quantities() {
+        let source = "4 Lightning Bolt\n2x Island\n1 Hill Giant (M10) 146\n";
+
+        let decklist = DeckList::parse_text(source).expect("should parse");
+
+        assert_eq!(decklist.command_zone.len(), 0);
+        assert_eq!(decklist.count(), 7);
+        assert_eq!(decklist.card_names(), vec!["Lightning Bolt", "Island", "Hill Giant"]);
+    }
+
+    #[test]
+    fn test_parse_text_printings() {
+        let source = "1 Hill Giant (M10) 146
+1 Island (DMU)
+1 Lightning Bolt
+";
+
+        let decklist = DeckList::parse_text(source).expect("should parse");
+        let printings = decklist.allocations()
+            .map(|da| (da.name(), da.set(), da.collector_number()))
+            .collect_vec();
+
+        assert_eq!(printings, vec![
+            ("Hill Giant", Some("m10"), Some("146")),
+            ("Island", Some("dmu"), None),
+            ("Lightning Bolt", None, None),
+        ]);
+    }
+
+    #[test]
+    fn test_to_text_round_trip() {
+        let source = "
+Commander
+1 Ruby, Daring Tracker
+
+Deck
+4 Lightning Bolt
+1 Hill Giant (M10) 146
+2 Island (DMU)
+";
+        let decklist = DeckList::parse_text(source).expect("should parse");
+
+        let text = decklist.to_text();
+        assert_eq!(text, "Commander\n1 Ruby, Daring Tracker\n\nDeck\n4 Lightning Bolt\n1 Hill Giant (M10) 146\n2 Island (DMU)\n");
+
+        let reparsed = DeckList::parse_text(&text).expect("should parse");
+        assert_eq!(reparsed, decklist);
+    }
+
+    #[test]
+    fn test_json_round_trip() {
+        let decklist = DeckList::parse_text("Commander\n1 Ruby, Daring Tracker\n\nDeck\n1 Hill Giant (M10) 146\n30 Mountain\n").expect("should parse");
+
+        let json = serde_json::to_string(&decklist).expect("should serialize");
+        let reparsed: DeckList = serde_json::from_str(&json).expect("should deserialize");
+
+        assert_eq!(reparsed, decklist);
+    }
+
+    #[test]
+    fn test_parse_text_commander_section() {
+        let source = "
+Commander
+1 Ruby, Daring Tracker
+
+Deck
+4 Lightning Bolt
+2 Island
+
+Sideboard
+3 Hill Giant
+";
+
+        let decklist = DeckList::parse_text(source).expect("should parse");
+
+        assert_eq!(decklist.command_zone.len(), 1);
+        assert_eq!(decklist.command_zone[0].name, "Ruby, Daring Tracker");
+        assert_eq!(decklist.count(), 6);
+        assert!(!decklist.card_names().contains(&"Hill Giant"));
+    }
+
+    #[test]
+    fn test_parse_text_blank_line_starts_sideboard() {
+        let source = "4 Lightning Bolt\n\n3 Hill Giant\n";
+
+        let decklist = DeckList::parse_text(source).expect("should parse");
+
+        assert_eq!(decklist.count(), 4);
+    }
+
+    #[test]
+    fn test_parse_text_malformed_lines() {
+        let err = DeckList::parse_text("4 Lightning Bolt\nIsland\n").expect_err("should fail");
+        assert_eq!(err.line_num, 2);
+        assert!(matches!(err.kind, DeckParseErrorKind::MissingName));
+
+        let err = DeckList::parse_text("four Lightning Bolt\n").expect_err("should fail");
+        assert_eq!(err.line_num, 1);
+        assert!(matches!(err.kind, DeckParseErrorKind::InvalidQuantity { .. }));
+    }
+
     #[test]
     fn test_into_deck_success() {
         let collection = mock_collection();
         let decklist = DeckList {
             command_zone: vec![],
             decklist: vec![
-                DeckAllocation { name: "Hill Giant".to_string(), quantity: 2 },
-                DeckAllocation { name: "Lightning Bolt".to_string(), quantity: 1 },
+                DeckAllocation::new("Hill Giant", 2),
+                DeckAllocation::new("Lightning Bolt", 1),
             ],
         };
         let deck = decklist.into_deck(&collection).unwrap();
         assert_eq!(deck.deck.size(), 3);
     }
 
+    fn mono_white_commander_deck(cards: &[&str]) -> DeckList {
+        DeckList {
+            command_zone: vec![
+                DeckAllocation::new("Thalia, Guardian of Thraben", 1),
+            ],
+            decklist: cards.iter()
+                .map(|name| DeckAllocation::new(name, 1))
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn test_validate_color_identity_legal() {
+        let collection = mock_collection();
+        let decklist = mono_white_commander_deck(&["Swords to Plowshares", "Island"]);
+
+        assert_eq!(decklist.validate_color_identity(&collection), Ok(()));
+    }
+
+    #[test]
+    fn test_validate_color_identity_illegal_card() {
+        let collection = mock_collection();
+        let decklist = mono_white_commander_deck(&["Swords to Plowshares", "Counterspell"]);
+
+        let offenders = decklist.validate_color_identity(&collection).expect_err("should fail");
+
+        assert_eq!(offenders, vec![collection.card_named("Counterspell").unwrap()]);
+    }
+
+    fn unreachable(collection: &CardCollection, cards: &[&str]) -> Vec<String> {
+        let decklist = DeckList {
+            command_zone: vec![],
+            decklist: cards.iter().map(|name| DeckAllocation::new(name, 1)).collect(),
+        };
+        decklist.unreachable_cards(collection)
+            .into_iter()
+            .map(|card| collection.card_data(card).name.clone())
+            .collect()
+    }
+
+    #[test]
+    fn test_unreachable_cards_missing_a_color() {
+        let collection = mock_collection();
+
+        let offenders = unreachable(&collection, &["Island", "Counterspell", "Lightning Bolt", "Hill Giant"]);
+
+        assert_eq!(offenders, vec!["Lightning Bolt", "Hill Giant"]);
+    }
+
+    #[test]
+    fn test_unreachable_cards_counts_mana_dorks_once_castable() {
+        let collection = crate::collection::fixtures::collection();
+
+        // the elves make green, and the forest casts them
+        assert!(unreachable(collection, &["Forest", "Llanowar Elves", "Grizzly Bears"]).is_empty());
+        // without a forest, the elves can not be cast to make green for anything
+        assert_eq!(unreachable(collection, &["Island", "Llanowar Elves", "Grizzly Bears"]), vec!["Llanowar Elves", "Grizzly Bears"]);
+        // sol ring costs only generic mana, and makes colorless
+        assert!(unreachable(collection, &["Island", "Sol Ring", "Thought-Knot Seer"]).is_empty());
+    }
+
+    #[test]
+    fn test_unreachable_cards_needs_colorless_sources_for_colorless_pips() {
+        let collection = crate::collection::fixtures::collection();
+
+        assert_eq!(unreachable(collection, &["Forest", "Thought-Knot Seer"]), vec!["Thought-Knot Seer"]);
+        assert!(unreachable(collection, &["Wastes", "Thought-Knot Seer"]).is_empty());
+    }
+
+    #[test]
+    fn test_validate_color_identity_without_commander() {
+        let collection = mock_collection();
+        let decklist = DeckList {
+            command_zone: vec![],
+            decklist: vec![
+                DeckAllocation::new("Counterspell", 1),
+                DeckAllocation::new("Lightning Bolt", 1),
+            ],
+        };
+
+        assert_eq!(decklist.validate_color_identity(&collection), Ok(()));
+    }
+
+    #[test]
+    fn test_validate_format_too_many_copies() {
+        let collection = mock_collection();
+        let decklist = DeckList {
+            command_zone: vec![],
+            decklist: vec![
+                DeckAllocation::new("Lightning Bolt", 5),
+                DeckAllocation::new("Island", 55),
+            ],
+        };
+
+        let err = decklist.validate_format(Format::Standard, &collection).expect_err("should fail");
+        assert_eq!(err.violations, vec![
+            FormatViolation::TooManyCopies { name: "Lightning Bolt".to_string(), quantity: 5, max_copies: 4 },
+        ]);
+
+        let err = decklist.validate_format(Format::Commander, &collection).expect_err("should fail");
+        assert_eq!(err.violations, vec![
+            FormatViolation::WrongDeckSize { expected: 100, actual: 60 },
+            FormatViolation::TooManyCopies { name: "Lightning Bolt".to_string(), quantity: 5, max_copies: 1 },
+        ]);
+    }
+
+    #[test]
+    fn test_validate_format_legal_commander_deck() {
+        let collection = mock_collection();
+        let decklist = mono_white_commander_deck(&["Swords to Plowshares", "Lightning Bolt"]);
+        let decklist = DeckList {
+            decklist: decklist.decklist
+                .into_iter()
+                .chain([DeckAllocation::new("Island", 97)])
+                .collect(),
+            ..decklist
+        };
+
+        decklist.validate_format(Format::Commander, &collection).expect("should be legal");
+    }
+
+    const BAN_LISTS: &str = r#"{
+        "standard": { "banned": ["Lightning Bolt"], "restricted": ["Counterspell"] },
+        "commander": { "banned": ["Counterspell"] }
+    }"#;
+
+    #[test]
+    fn test_validate_format_banned_card() {
+        let collection = mock_collection();
+        let ban_lists: BanLists = serde_json::from_str(BAN_LISTS).expect("ban lists should parse");
+        let decklist = DeckList {
+            command_zone: vec![],
+            decklist: vec![
+                DeckAllocation::new("Lightning Bolt", 1),
+                DeckAllocation::new("Counterspell", 1),
+                DeckAllocation::new("Island", 58),
+            ],
+        };
+
+        let err = decklist.validate_format_with_ban_lists(Format::Standard, &collection, &ban_lists).expect_err("should fail");
+        assert_eq!(err.violations, vec![
+            FormatViolation::Banned { name: "Lightning Bolt".to_string() },
+        ]);
+
+        decklist.validate_format(Format::Standard, &collection).expect("nothing is banned without a list");
+    }
+
+    #[test]
+    fn test_validate_format_banned_card_in_different_case() {
+        let collection = mock_collection();
+        let ban_lists: BanLists = serde_json::from_str(BAN_LISTS).expect("ban lists should parse");
+        let decklist = DeckList {
+            command_zone: vec![],
+            decklist: vec![
+                DeckAllocation::new("lightning  bolt", 1),
+                DeckAllocation::new("COUNTERSPELL", 2),
+                DeckAllocation::new("Island", 57),
+            ],
+        };
+
+        let err = decklist.validate_format_with_ban_lists(Format::Standard, &collection, &ban_lists).expect_err("should fail");
+        assert_eq!(err.violations, vec![
+            FormatViolation::Restricted { name: "COUNTERSPELL".to_string(), quantity: 2 },
+            FormatViolation::Banned { name: "lightning  bolt".to_string() },
+        ]);
+    }
+
+    #[test]
+    fn test_validate_format_restricted_card() {
+        let collection = mock_collection();
+        let ban_lists: BanLists = serde_json::from_str(BAN_LISTS).expect("ban lists should parse");
+        let decklist = DeckList {
+            command_zone: vec![],
+            decklist: vec![
+                DeckAllocation::new("Counterspell", 2),
+                DeckAllocation::new("Island", 58),
+            ],
+        };
+
+        let err = decklist.validate_format_with_ban_lists(Format::Standard, &collection, &ban_lists).expect_err("should fail");
+        assert_eq!(err.violations, vec![
+            FormatViolation::Restricted { name: "Counterspell".to_string(), quantity: 2 },
+        ]);
+
+        let legal = decklist.with_quantity("Counterspell", 1).with_quantity("Island", 59);
+        legal.validate_format_with_ban_lists(Format::Standard, &collection, &ban_lists).expect("one copy is allowed");
+    }
+
+    #[test]
+    fn test_validate_format_small_standard_deck() {
+        let collection = mock_collection();
+        let decklist = DeckList {
+            command_zone: vec![],
+            decklist: vec![
+                DeckAllocation::new("Island", 40),
+            ],
+        };
+
+        let err = decklist.validate_format(Format::Standard, &collection).expect_err("should fail");
+        assert_eq!(err.violations, vec![
+            FormatViolation::TooFewCards { minimum: 60, actual: 40 },
+        ]);
+    }
+
+    #[test]
+    fn test_summary() {
+        let collection = mock_collection();
+        let decklist = DeckList::parse_text("
+Commander
+1 Thalia, Guardian of Thraben
+
+Deck
+2 Hill Giant
+4 Lightning Bolt
+3 Counterspell
+1 Fireball
+10 Island
+").expect("should parse");
+
+        let summary = decklist.summary(&collection);
+
+        assert_eq!(summary.curve, BTreeMap::from([(1, 4), (2, 4), (4, 2)]));
+        assert_eq!(summary.pips, BTreeMap::from([
+            (ManaType::White, 1),
+            (ManaType::Blue, 6),
+            (ManaType::Red, 6),
+        ]));
+        assert_eq!(summary.land_count, 10);
+        assert_eq!(summary.average_mana_value, 20.0 / 10.0);
+    }
+
+    #[test]
+    fn test_summary_of_only_lands() {
+        let collection = mock_collection();
+        let decklist = DeckList::parse_text("40 Island\n").expect("should parse");
+
+        let summary = decklist.summary(&collection);
+
+        assert!(summary.curve.is_empty());
+        assert!(summary.pips.is_empty());
+        assert_eq!(summary.land_count, 40);
+        assert_eq!(summary.average_mana_value, 0.0);
+    }
+
     #[test]
     fn test_into_deck_missing_cards() {
         let collection = mock_collection();
         let decklist = DeckList {
             command_zone: vec![],
             decklist: vec![
-                DeckAllocation { name: "Lightning Bolt".to_string(), quantity: 2 },
-                DeckAllocation { name: "Nonexistent Card".to_string(), quantity: 1 },
+                DeckAllocation::new("Lightning Bolt", 2),
+                DeckAllocation::new("Nonexistent Card", 1),
             ],
         };
         let result = decklist.into_deck(&collection);
         let err = result.expect_err("should fail");
         match err {
-            DeckConstructionError::MissingCards { num_missing } => {
+            DeckConstructionError::MissingCards { num_missing, missing } => {
                 assert_eq!(num_missing, 1);
+                assert_eq!(missing, vec![MissingCard { name: "Nonexistent Card".to_string(), suggestion: None }]);
             }
+            other => panic!("expected missing cards, found: {other}"),
         }
     }
+
+    #[test]
+    fn test_into_deck_smaller_than_opening_hand() {
+        let collection = mock_collection();
+        let decklist = DeckList::parse_text("2 Lightning Bolt\n1 Island\n").expect("should parse");
+
+        let err = decklist.into_deck_with_hand_size(&collection, 7).expect_err("should fail");
+
+        assert!(matches!(err, DeckConstructionError::TooFewCards { num_cards: 3, hand_size: 7 }), "{err}");
+        assert_eq!(decklist.into_deck_with_hand_size(&collection, 3).expect("enough cards").deck.size(), 3);
+    }
+
+    #[test]
+    fn test_into_deck_ignores_case() {
+        let collection = mock_collection();
+        let decklist = DeckList {
+            command_zone: vec![],
+            decklist: vec![
+                DeckAllocation::new("lightning bolt", 2),
+            ],
+        };
+        let deck = decklist.into_deck(&collection).expect("should find the card");
+        assert_eq!(deck.deck.size(), 2);
+    }
+
+    #[test]
+    fn test_into_deck_suggests_typo_fix() {
+        let collection = mock_collection();
+        let decklist = DeckList {
+            command_zone: vec![],
+            decklist: vec![
+                DeckAllocation::new("Lightning Blot", 2),
+            ],
+        };
+        let err = decklist.into_deck(&collection).expect_err("should fail");
+        assert!(err.to_string().contains("did you mean `Lightning Bolt`?"), "{err}");
+    }
+
+    #[test]
+    fn test_inline_card_definitions() {
+        let mut collection = mock_collection();
+        let decklist: DeckList = serde_json::from_str(r#"{
+            "decklist": [
+                { "name": "Island", "quantity": 20 },
+                { "name": "Spoiled Sphinx", "quantity": 4, "definition": { "card_type": "Creature", "cost": "{2}{U}", "power": 2, "toughness": 3 } },
+                { "name": "Lightning Bolt", "quantity": 4, "definition": { "card_type": "Sorcery", "cost": "{5}" } }
+            ]
+        }"#).expect("should parse");
+
+        assert!(decklist.into_deck(&collection).is_err());
+        assert_eq!(collection.add_missing_cards(decklist.inline_cards()), 1);
+
+        let deck = decklist.into_deck(&collection).expect("inline cards should be found");
+        assert_eq!(deck.deck.size(), 28);
+        let sphinx = collection.card_data(collection.card_named("Spoiled Sphinx").expect("sphinx was added"));
+        assert_eq!(sphinx.cost, Some(ManaCost::try_parse("{2}{U}").expect("mana cost")));
+        assert_eq!(sphinx.power, Some(2));
+        // cards the collection already knows about keep their real data
+        let bolt = collection.card_data(collection.card_named("Lightning Bolt").expect("bolt is in the collection"));
+        assert_eq!(bolt.card_type, CardType::Instant);
+    }
+
+    #[test]
+    fn test_inline_card_json_round_trip() {
+        let decklist: DeckList = serde_json::from_str(r#"{
+            "decklist": [
+                { "name": "Spoiled Sphinx", "quantity": 4, "definition": { "card_type": "Creature", "cost": "{2}{U}" } }
+            ]
+        }"#).expect("should parse");
+
+        let json = serde_json::to_string(&decklist).expect("should serialize");
+        let reparsed: DeckList = serde_json::from_str(&json).expect("should deserialize");
+
+        assert_eq!(reparsed, decklist);
+        assert_eq!(reparsed.allocations().next().and_then(DeckAllocation::definition).map(|d| &d.card_type), Some(&CardType::Creature));
+    }
+
+    #[test]
+    fn test_diff_added_cards() {
+        let before = DeckList::parse_text("4 Lightning Bolt\n20 Mountain\n").expect("should parse");
+        let after = DeckList::parse_text("4 Lightning Bolt\n20 Mountain\n2 Shock\n1 Fireball\n").expect("should parse");
+
+        let diff = before.diff(&after);
+        assert_eq!(diff.added, vec![("Fireball".to_string(), 1), ("Shock".to_string(), 2)]);
+        assert!(diff.removed.is_empty());
+        assert!(diff.changed.is_empty());
+    }
+
+    #[test]
+    fn test_diff_removed_cards() {
+        let before = DeckList::parse_text("Commander\n1 Ruby, Daring Tracker\n\nDeck\n4 Lightning Bolt\n20 Mountain\n").expect("should parse");
+        let after = DeckList::parse_text("20 Mountain\n").expect("should parse");
+
+        let diff = before.diff(&after);
+        assert_eq!(diff.removed, vec![("Lightning Bolt".to_string(), 4), ("Ruby, Daring Tracker".to_string(), 1)]);
+        assert!(diff.added.is_empty());
+        assert!(diff.changed.is_empty());
+        assert!(after.diff(&after).is_empty());
+    }
+
+    #[test]
+    fn test_diff_changed_counts() {
+        let before = DeckList::parse_text("4 Lightning Bolt\n20 Mountain\n2 Shock\n").expect("should parse");
+        // a card split over several lines is compared by its total
+        let after = DeckList::parse_text("2 Lightning Bolt\n22 Mountain\n1 Shock\n1 Shock\n").expect("should parse");
+
+        let diff = before.diff(&after);
+        assert_eq!(diff.changed, vec![
+            ("Lightning Bolt".to_string(), 4, 2),
+            ("Mountain".to_string(), 20, 22),
+        ]);
+        assert!(diff.added.is_empty());
+        assert!(diff.removed.is_empty());
+        assert_eq!(diff.to_string(), "~ Lightning Bolt: 4 -> 2\n~ Mountain: 20 -> 22\n");
+    }
 }
-    
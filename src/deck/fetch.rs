@@ -0,0 +1,112 @@
+use reqwest::header::USER_AGENT;
+use thiserror::Error;
+
+use crate::PROJECT_NAME;
+use crate::scryfall::build_http_client;
+
+use super::{DeckList, DeckParseError};
+
+type HttpClient = reqwest::blocking::Client;
+
+#[derive(Debug,Error)]
+pub enum DeckFetchError {
+    #[error("could not fetch deck list: {0}")]
+    Http(#[from] reqwest::Error),
+    #[error("fetched deck list is not valid json: {0}")]
+    Json(#[from] serde_json::Error),
+    #[error(transparent)]
+    Parse(#[from] DeckParseError),
+}
+
+/// Downloads deck lists from a url, like the raw export links that online deck builders offer.
+/// The same http client is used for every deck list fetched.
+pub struct DeckFetcher {
+    http_client: HttpClient,
+}
+
+impl DeckFetcher {
+    pub fn new() -> Self {
+        Self {
+            http_client: build_http_client(),
+        }
+    }
+
+    /// Fetch the deck list at `url`, reading it as json if it looks like json and as text otherwise.
+    pub fn fetch(&self, url: &str) -> Result<DeckList, DeckFetchError> {
+        log::info!("fetching deck list from {url}");
+        let body = self.http_client
+            .get(url)
+            .header(USER_AGENT, PROJECT_NAME)
+            .send()?
+            .error_for_status()?
+            .text()?;
+        parse_fetched(&body)
+    }
+}
+
+impl Default for DeckFetcher {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Deck builders do not agree on a content type for exports, so look at the body itself instead
+fn parse_fetched(body: &str) -> Result<DeckList, DeckFetchError> {
+    if body.trim_start().starts_with('{') {
+        return Ok(serde_json::from_str(body)?);
+    }
+    Ok(DeckList::parse_text(body)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_server::{self, Response};
+
+    use super::*;
+
+    /// Serve a single http response with this status and body, returning the url to request it from
+    fn serve_once(status: &'static str, body: &'static str) -> String {
+        let (endpoint, _) = test_server::serve(vec![Response::text(status, body)]);
+        format!("{endpoint}/decks/export.txt")
+    }
+
+    #[test]
+    fn test_fetch_text_deck_list() {
+        let url = serve_once("200 OK", "Commander\n1 Colossal Dreadmaw\n\nDeck\n4 Forest\n2 Hill Giant\n");
+
+        let decklist = DeckFetcher::new().fetch(&url).expect("should fetch and parse");
+
+        let expected = DeckList::parse_text("Commander\n1 Colossal Dreadmaw\n\n4 Forest\n2 Hill Giant").expect("should parse");
+        assert_eq!(decklist, expected);
+        assert_eq!(decklist.count(), 6);
+        assert!(decklist.has_commander());
+    }
+
+    #[test]
+    fn test_fetch_json_deck_list() {
+        let url = serve_once("200 OK", r#"{ "decklist": [ { "name": "Forest", "quantity": 3 } ] }"#);
+
+        let decklist = DeckFetcher::new().fetch(&url).expect("should fetch and parse");
+
+        assert_eq!(decklist.count(), 3);
+        assert_eq!(decklist.card_names(), vec!["Forest"]);
+    }
+
+    #[test]
+    fn test_fetch_missing_deck_list() {
+        let url = serve_once("404 Not Found", "not found");
+
+        let result = DeckFetcher::new().fetch(&url);
+
+        assert!(matches!(result, Err(DeckFetchError::Http(_))), "expected an http error, got {result:?}");
+    }
+
+    #[test]
+    fn test_fetch_bad_text_deck_list() {
+        let url = serve_once("200 OK", "4 Forest\nHill Giant\n");
+
+        let result = DeckFetcher::new().fetch(&url);
+
+        assert!(matches!(result, Err(DeckFetchError::Parse(_))), "expected a parse error, got {result:?}");
+    }
+}
@@ -1,45 +1,257 @@
-/// The outcome of a single experiment
-#[allow(dead_code)]
-struct ExperimentResult {
-    param: usize,
-    measure: f32
-}
+use std::ops::Range;
+
+use thiserror::Error;
+
+use crate::collection::CardCollection;
+use crate::deck::{DeckConstructionError, DeckList};
+use crate::game::Deck;
+use crate::metrics::MetricsData;
+use crate::strategies::Strategy;
+use crate::trial::{self, Props};
+use crate::watcher::Watcher;
 
-/*
- *let results = (0..TOTAL_DECK_SIZE)
+/// Run the same trials against each of `decks`, so that their metrics can be compared side by side.
+/// Each deck keeps its name, and the results are in the same order as `decks`.
+pub fn compare_decks<S, W>(decks: Vec<(String, Deck)>, strategies: S, watcher: W, props: Props) -> Vec<(String, MetricsData)>
+where S: Strategy + Clone + Sync,
+      W: Watcher + Clone + Sync
+{
+    decks
         .into_iter()
-        .map(|param| {
-            let metrics = evaluate_deck(&cli, num_trials, param);
-            let mut measure = metrics.average("turn-to-reach-7-plays");
-            if measure == 0.0 {
-                measure = 9999.0;
-            }
-            ExperimentResult {
-                param,
-                measure
-            }
+        .map(|(name, deck)| {
+            log::info!("simulating deck {name}");
+            let metrics = trial::run_trials(deck, strategies.clone(), watcher.clone(), props);
+            (name, metrics)
         })
-        .collect_vec();
-
-    // select the best parameter
-    let best = results
-        .iter()
-        .min_by(|exp1, exp2| exp1.measure.partial_cmp(&exp2.measure).expect("no nan"));
-
-    let mut table = make_table();
-    table.set_titles(row!["# of lightning bolts", "turns until 21 damage", ""]);
-    for exp in results.iter() {
-        let is_best = match best {
-            Some(best_exp) if best_exp.param == exp.param => "BEST",
-            _ => ""
+        .collect()
+}
+
+/// The outcome of a single experiment
+#[derive(Debug,Clone,Copy,PartialEq)]
+pub struct ExperimentResult {
+    /// How many copies of the varied card were in the deck
+    pub param: usize,
+    /// The average of the chosen metric over every trial
+    pub measure: f32,
+}
+
+/// Every result of a sweep, along with the parameter that did the best.
+#[derive(Debug,Clone,PartialEq)]
+pub struct Sweep {
+    pub results: Vec<ExperimentResult>,
+    /// `None` if the sweep was over an empty range
+    pub best: Option<usize>,
+}
+
+#[derive(Debug,Error)]
+pub enum SweepError {
+    #[error(transparent)]
+    DeckConstruction(#[from] DeckConstructionError),
+    #[error("no trial recorded the metric {metric}")]
+    UnknownMetric { metric: String },
+}
+
+/// Runs trials over variations of a single deck list.
+pub struct Experiment<'a, S, W> {
+    /// Used to rebuild the deck for each variation
+    pub collection: &'a CardCollection,
+    pub strategies: S,
+    pub watcher: W,
+    pub props: Props,
+}
+
+impl<S, W> Experiment<'_, S, W>
+where S: Strategy + Clone + Sync,
+      W: Watcher + Clone + Sync
+{
+    /// Runs trials with every number of copies of `vary_card` in `range`, measuring the average of `metric`.
+    /// The best parameter has the smallest measure when `minimize` is set, and the largest otherwise.
+    ///
+    /// A metric that is not recorded for some numbers of copies averages to zero there,
+    /// but one that is not recorded for any of them is an error, since it is most likely misspelled.
+    /// Each number of copies is simulated in parallel, and the results are ordered by the number of copies.
+    pub fn sweep(&self, base: DeckList, vary_card: &str, range: Range<usize>, metric: &str, minimize: bool) -> Result<Sweep, SweepError> {
+        use rayon::iter::IntoParallelIterator;
+        use rayon::iter::ParallelIterator;
+
+        // every trial looks up cards in the global collection, so it needs to exist before splitting up the work
+        crate::collection::init_once(self.collection);
+
+        let measures = range
+            .into_par_iter()
+            .map(|param| {
+                let deck = base.with_quantity(vary_card, param).into_deck_with_hand_size(self.collection, self.props.hand_size)?;
+                let metrics = trial::run_trials(deck, self.strategies.clone(), self.watcher.clone(), self.props);
+                let measure = metrics.find_key(metric).map(|key| metrics.average(key));
+                log::info!("with {param} copies of {vary_card}, {metric} averaged {measure:?}");
+                Ok((param, measure))
+            })
+            .collect::<Result<Vec<_>, DeckConstructionError>>()?;
+
+        if !measures.is_empty() && measures.iter().all(|(_, measure)| measure.is_none()) {
+            return Err(SweepError::UnknownMetric { metric: metric.to_string() });
+        }
+        let results: Vec<_> = measures
+            .into_iter()
+            .map(|(param, measure)| ExperimentResult { param, measure: measure.unwrap_or(0.0) })
+            .collect();
+
+        let best = best_param(&results, minimize);
+
+        Ok(Sweep { results, best })
+    }
+}
+
+/// Picks the parameter with the smallest or largest measure, ignoring NaN measures.
+fn best_param(results: &[ExperimentResult], minimize: bool) -> Option<usize> {
+    let candidates = results.iter().filter(|exp| !exp.measure.is_nan());
+    let compare = |exp1: &&ExperimentResult, exp2: &&ExperimentResult| exp1.measure.total_cmp(&exp2.measure);
+    let best = if minimize {
+        candidates.min_by(compare)
+    } else {
+        candidates.max_by(compare)
+    };
+    best.map(|exp| exp.param)
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::SeedableRng;
+
+    use crate::collection::fixtures;
+    use crate::game::{CommandZone, UnorderedPile};
+    use crate::strategies::{LandDropTieBreak, MulliganStrategy, StrategyImpl, UtilityFunction};
+    use crate::trial::Rand;
+    use crate::watcher::WatcherImpl;
+
+    use super::*;
+
+    fn deck_of(cards: &[(&str, usize)]) -> Deck {
+        let mut deck = UnorderedPile::empty();
+        for (name, count) in cards {
+            deck.add_copies(fixtures::card_named(name), *count);
+        }
+        Deck {
+            command_zone: CommandZone::empty(),
+            deck,
+        }
+    }
+
+    #[test]
+    fn test_compare_decks_has_a_row_per_deck() {
+        let decks = vec![
+            ("all lands".to_string(), deck_of(&[("Forest", 40)])),
+            ("half lands".to_string(), deck_of(&[("Forest", 20), ("Hill Giant", 20)])),
+        ];
+        let props = Props {
+            max_turn: 6,
+            num_trials: 20,
+            seed: Some(7),
+            ..Props::default()
+        };
+        let strategies = StrategyImpl {
+            rng: Rand::seed_from_u64(7),
+            mulligan: MulliganStrategy::default(),
+            utility: UtilityFunction::default(),
+            land_tie_break: LandDropTieBreak::default(),
+        };
+
+        let rows = compare_decks(decks, strategies, WatcherImpl, props);
+
+        assert_eq!(rows.len(), 2);
+        assert_eq!(rows[0].0, "all lands");
+        assert_eq!(rows[1].0, "half lands");
+        assert_ne!(rows[0].1, rows[1].1);
+        assert!(rows[0].1.average("land-drops") > rows[1].1.average("land-drops"));
+    }
+
+    fn sweep_strategies() -> StrategyImpl {
+        StrategyImpl {
+            rng: Rand::seed_from_u64(3),
+            mulligan: MulliganStrategy::default(),
+            utility: UtilityFunction::default(),
+            land_tie_break: LandDropTieBreak::default(),
+        }
+    }
+
+    fn sweep_props() -> Props {
+        Props {
+            max_turn: 6,
+            num_trials: 100,
+            seed: Some(3),
+            ..Props::default()
+        }
+    }
+
+    fn sweep_forests(minimize: bool) -> Sweep {
+        let experiment = Experiment {
+            collection: fixtures::collection(),
+            strategies: sweep_strategies(),
+            watcher: WatcherImpl,
+            props: sweep_props(),
         };
-        table.add_row(row![exp.param, exp.measure, is_best]);
+        let base = DeckList::parse_text("20 Hill Giant\n").expect("should parse");
+
+        experiment.sweep(base, "Forest", 0..4, "land-drops", minimize).expect("all cards are in the fixtures")
+    }
+
+    #[test]
+    fn test_sweep_maximizes_metric() {
+        let sweep = sweep_forests(false);
+
+        assert_eq!(sweep.results.iter().map(|exp| exp.param).collect::<Vec<_>>(), vec![0, 1, 2, 3]);
+        assert_eq!(sweep.results[0].measure, 0.0);
+        assert_eq!(sweep.best, Some(3));
+    }
+
+    #[test]
+    fn test_sweep_minimizes_metric() {
+        let sweep = sweep_forests(true);
+
+        assert_eq!(sweep.best, Some(0));
+    }
+
+    #[test]
+    fn test_parallel_sweep_matches_serial_runs() {
+        let sweep = sweep_forests(false);
+
+        let base = DeckList::parse_text("20 Hill Giant\n").expect("should parse");
+        let serial = (0..4)
+            .map(|param| {
+                let deck = base.with_quantity("Forest", param).into_deck(fixtures::collection()).expect("deck");
+                let metrics = trial::run_trials(deck, sweep_strategies(), WatcherImpl, sweep_props());
+                ExperimentResult { param, measure: metrics.average("land-drops") }
+            })
+            .collect::<Vec<_>>();
+
+        assert_eq!(sweep.results, serial);
     }
-    println!();
-    println!();
-    println!("===================================================");
-    table.printstd();
 
-*/
+    #[test]
+    fn test_sweep_over_unknown_metric_is_an_error() {
+        let experiment = Experiment {
+            collection: fixtures::collection(),
+            strategies: sweep_strategies(),
+            watcher: WatcherImpl,
+            props: Props { num_trials: 10, ..sweep_props() },
+        };
+        let base = DeckList::parse_text("20 Hill Giant\n").expect("should parse");
+
+        let result = experiment.sweep(base, "Forest", 0..4, "land-dorps", false);
 
+        assert!(matches!(result, Err(SweepError::UnknownMetric { .. })), "{result:?}");
+    }
 
+    #[test]
+    fn test_best_param_ignores_nan() {
+        let results = vec![
+            ExperimentResult { param: 0, measure: f32::NAN },
+            ExperimentResult { param: 1, measure: 2.5 },
+            ExperimentResult { param: 2, measure: 1.5 },
+        ];
+
+        assert_eq!(best_param(&results, true), Some(2));
+        assert_eq!(best_param(&results, false), Some(1));
+        assert_eq!(best_param(&[], true), None);
+    }
+}
@@ -23,9 +23,7 @@ pub enum ArgumentReadError {
     }
 }
 
-pub fn read_json_from_path<T>(path: &PathBuf) -> Result<T, ArgumentReadError>
-where T: serde::de::DeserializeOwned
-{
+pub fn read_text_from_path(path: &PathBuf) -> Result<String, ArgumentReadError> {
     let mut file = File::open(path)
         .map_err(|source| ArgumentReadError::FailedToOpenFile { 
             file_name: path.clone(), 
@@ -37,6 +35,13 @@ where T: serde::de::DeserializeOwned
             file_name: path.clone(),
             source
         })?;
+    Ok(buf)
+}
+
+pub fn read_json_from_path<T>(path: &PathBuf) -> Result<T, ArgumentReadError>
+where T: serde::de::DeserializeOwned
+{
+    let buf = read_text_from_path(path)?;
     let out = serde_json::from_str(&buf)
         .map_err(|source| {
             let report = build_report_for_json_error(path, &source, &buf);
@@ -61,6 +66,16 @@ pub fn write_json_to_path<T: serde::ser::Serialize>(path: &PathBuf,  data: &T) -
     Ok(())
 }
 
+pub fn write_text_to_path(path: &PathBuf, text: &str) -> std::io::Result<()> {
+    if let Some(parent) = path.parent() {
+        log::info!("creating parent directory at {}", parent.display());
+        std::fs::create_dir_all(parent)?;
+    }
+    let mut file = File::create(path)?;
+    file.write_all(text.as_bytes())?;
+    Ok(())
+}
+
 fn build_report_for_json_error(file_name: &PathBuf, cause: &serde_json::Error, source: &str) -> String {
     use ariadne::Report;
     use ariadne::ReportKind;
@@ -24,6 +24,7 @@ pub type Library = OrderedPile;
 pub type Hand = UnorderedPile;
 pub type Graveyard = UnorderedPile;
 pub type Battlefield = UnorderedPile;
+pub type Exile = UnorderedPile;
 
 #[derive(Copy,Clone,Debug,PartialEq,Eq)]
 pub enum Zone {
@@ -31,5 +32,29 @@ pub enum Zone {
     CommandZone,
     Hand,
     Graveyard,
-    Battlefield
+    Battlefield,
+    Exile,
+}
+
+impl Zone {
+    /// Looks a zone up by its name, ignoring case, as written in a `core:CastFrom` annotation
+    /// ```
+    /// use deck_optim::game::Zone;
+    ///
+    /// assert_eq!(Zone::from_name("graveyard"), Some(Zone::Graveyard));
+    /// assert_eq!(Zone::from_name("Exile"), Some(Zone::Exile));
+    /// assert_eq!(Zone::from_name("sideboard"), None);
+    /// ```
+    pub fn from_name(name: &str) -> Option<Self> {
+        let zone = match name.to_lowercase().as_str() {
+            "library" => Zone::Library,
+            "commandzone" | "command zone" => Zone::CommandZone,
+            "hand" => Zone::Hand,
+            "graveyard" => Zone::Graveyard,
+            "battlefield" => Zone::Battlefield,
+            "exile" => Zone::Exile,
+            _ => return None,
+        };
+        Some(zone)
+    }
 }
@@ -1,7 +1,15 @@
+use std::cmp::Ordering;
+use std::fmt;
+
 use serde::Deserialize;
 use serde::Serialize;
+use thiserror::Error;
 
 use crate::game::ManaPool;
+use crate::game::card::{
+    CAST_FROM_TAG, ENTERS_TAPPED_TAG, EXTRA_DRAW_TAG, EXTRA_LAND_DROP_TAG, FETCH_TARGET_TAG, FLASH_TAG, GAME_EFFECT_TAG,
+    MANA_ABILITY_TAG, MODES_TAG, PRIORITY_TAG, PRODUCES_MANA_TAG, RECUR_TARGET_TAG, START_IN_PLAY_TAG, TUTOR_TARGET_TAG,
+};
 
 /// A list of annotations to apply to particular targets
 #[derive(Clone,Debug,Serialize,Deserialize,PartialEq,Eq)]
@@ -15,6 +23,19 @@ impl CardAnnotations {
     pub fn len(&self) -> usize {
         self.annotations.len()
     }
+    /// Checks every annotation against the [`AnnotationSchema`] of the engine's `core:` annotations.
+    /// Returns every violation, not just the first.
+    pub fn validate(&self) -> Result<(), Vec<SchemaViolation>> {
+        let violations: Vec<SchemaViolation> = self.annotations
+            .iter()
+            .filter_map(|an| AnnotationSchema::CORE.validate(&an.annotation).err())
+            .collect();
+        if violations.is_empty() {
+            Ok(())
+        } else {
+            Err(violations)
+        }
+    }
 }
 
 /// An annotation being applied to a list of targets, which are Card Names
@@ -56,6 +77,21 @@ impl Annotation {
     pub fn values(&self) -> &[AnnotationValue] {
         self.values.as_slice()
     }
+    /// The first numeric value of this annotation, if there is one. Integers are converted.
+    /// ```
+    /// use deck_optim::game::annotations::{Annotation, AnnotationValue};
+    ///
+    /// let annotation = Annotation {
+    ///     key: "user:Threshold".to_string(),
+    ///     values: vec![AnnotationValue::String("ignored".to_string()), AnnotationValue::Number(2.5)],
+    /// };
+    /// assert_eq!(annotation.first_number(), Some(2.5));
+    /// ```
+    pub fn first_number(&self) -> Option<f64> {
+        self.values
+            .iter()
+            .find_map(AnnotationValue::as_number)
+    }
     pub fn extend(&mut self, values: Vec<AnnotationValue>) {
         self.values.extend(values);
         // remove duplicates
@@ -64,10 +100,168 @@ impl Annotation {
     }
 }
 
-#[derive(Clone,Debug,Serialize,Deserialize,PartialEq,Eq,PartialOrd,Ord)]
+#[derive(Clone,Debug,Serialize,Deserialize)]
 pub enum AnnotationValue {
     String(String),
-    Mana(ManaPool)
+    Mana(ManaPool),
+    Int(i64),
+    Number(f64),
+}
+
+impl AnnotationValue {
+    /// Which kind of value this is
+    pub fn kind(&self) -> ValueKind {
+        match self {
+            AnnotationValue::String(_) => ValueKind::String,
+            AnnotationValue::Mana(_) => ValueKind::Mana,
+            AnnotationValue::Int(_) => ValueKind::Int,
+            AnnotationValue::Number(_) => ValueKind::Number,
+        }
+    }
+    /// The value as a number, if it is numeric
+    pub fn as_number(&self) -> Option<f64> {
+        match self {
+            AnnotationValue::Int(value) => Some(*value as f64),
+            AnnotationValue::Number(value) => Some(*value),
+            _ => None,
+        }
+    }
+    /// Position of the variant, so that values of different kinds sort in declaration order
+    fn variant_order(&self) -> u8 {
+        match self {
+            AnnotationValue::String(_) => 0,
+            AnnotationValue::Mana(_) => 1,
+            AnnotationValue::Int(_) => 2,
+            AnnotationValue::Number(_) => 3,
+        }
+    }
+}
+
+// Floats are not `Ord`, so these are written by hand, comparing numbers by `f64::total_cmp`.
+// This keeps `Annotation::extend` able to sort and dedup its values.
+impl PartialEq for AnnotationValue {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+impl Eq for AnnotationValue {}
+impl PartialOrd for AnnotationValue {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+impl Ord for AnnotationValue {
+    fn cmp(&self, other: &Self) -> Ordering {
+        match (self, other) {
+            (AnnotationValue::String(a), AnnotationValue::String(b)) => a.cmp(b),
+            (AnnotationValue::Mana(a), AnnotationValue::Mana(b)) => a.cmp(b),
+            (AnnotationValue::Int(a), AnnotationValue::Int(b)) => a.cmp(b),
+            (AnnotationValue::Number(a), AnnotationValue::Number(b)) => a.total_cmp(b),
+            _ => self.variant_order().cmp(&other.variant_order()),
+        }
+    }
+}
+
+/// The kind of values an annotation expects
+#[derive(Clone,Copy,Debug,PartialEq,Eq)]
+pub enum ValueKind {
+    /// The annotation is a flag, and takes no values
+    Flag,
+    String,
+    Mana,
+    Int,
+    Number,
+}
+impl fmt::Display for ValueKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ValueKind::Flag => write!(f, "no"),
+            ValueKind::String => write!(f, "String"),
+            ValueKind::Mana => write!(f, "Mana"),
+            ValueKind::Int => write!(f, "Int"),
+            ValueKind::Number => write!(f, "Number"),
+        }
+    }
+}
+
+/// The annotation keys that the engine understands, and the kind of values each one takes.
+/// Keys outside of the reserved "core:" namespace are free for anyone to use, and are not checked.
+pub struct AnnotationSchema {
+    keys: &'static [(&'static str, ValueKind)],
+}
+impl AnnotationSchema {
+    /// Every "core:" annotation the engine understands
+    pub const CORE: AnnotationSchema = AnnotationSchema {
+        keys: &[
+            (PRODUCES_MANA_TAG, ValueKind::Mana),
+            (GAME_EFFECT_TAG, ValueKind::String),
+            (FETCH_TARGET_TAG, ValueKind::String),
+            (ENTERS_TAPPED_TAG, ValueKind::Flag),
+            (FLASH_TAG, ValueKind::Flag),
+            (PRIORITY_TAG, ValueKind::Int),
+            (MANA_ABILITY_TAG, ValueKind::String),
+            (EXTRA_DRAW_TAG, ValueKind::Int),
+            (RECUR_TARGET_TAG, ValueKind::String),
+            (EXTRA_LAND_DROP_TAG, ValueKind::Int),
+            (TUTOR_TARGET_TAG, ValueKind::String),
+            (START_IN_PLAY_TAG, ValueKind::Flag),
+            (CAST_FROM_TAG, ValueKind::String),
+            (MODES_TAG, ValueKind::String),
+        ],
+    };
+
+    /// The kind of values that `key` takes, or `None` if the schema does not know about it.
+    pub fn expected_kind(&self, key: &str) -> Option<ValueKind> {
+        self.keys
+            .iter()
+            .find(|(known, _)| *known == key)
+            .map(|(_, kind)| *kind)
+    }
+
+    /// Checks that a "core:" annotation is one we know about, and that all of its values have the right kind.
+    /// ```
+    /// use deck_optim::game::annotations::{Annotation, AnnotationSchema, AnnotationValue, SchemaViolation};
+    ///
+    /// let typo = Annotation {
+    ///     key: "core:Produes".to_string(),
+    ///     values: vec![],
+    /// };
+    /// assert_eq!(AnnotationSchema::CORE.validate(&typo), Err(SchemaViolation::UnknownKey { key: "core:Produes".to_string() }));
+    /// ```
+    pub fn validate(&self, annotation: &Annotation) -> Result<(), SchemaViolation> {
+        if !annotation.key.starts_with(CORE_NAMESPACE) {
+            return Ok(());
+        }
+        let Some(expected) = self.expected_kind(&annotation.key) else {
+            return Err(SchemaViolation::UnknownKey { key: annotation.key.clone() });
+        };
+        let wrong_value = annotation.values
+            .iter()
+            .find(|value| value.kind() != expected);
+        match wrong_value {
+            Some(found) => Err(SchemaViolation::WrongValueKind {
+                key: annotation.key.clone(),
+                expected,
+                found: found.clone(),
+            }),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Annotation keys starting with this are reserved for the engine
+const CORE_NAMESPACE: &str = "core:";
+
+#[derive(Debug,Error,PartialEq,Eq)]
+pub enum SchemaViolation {
+    #[error("unknown annotation `{key}', keys starting with \"core:\" are reserved for the engine")]
+    UnknownKey { key: String },
+    #[error("annotation `{key}' should have {expected} values, instead found: {found:?}")]
+    WrongValueKind {
+        key: String,
+        expected: ValueKind,
+        found: AnnotationValue,
+    },
 }
 
 
@@ -154,6 +348,41 @@ impl AnnotationSet {
         let idx = self.annotations.iter().position(|an| an.key == key.as_ref())?;
         Some(&mut self.annotations[idx])
     }
+    /// Remove a key from the annotation set, returning its annotation if it was there
+    pub fn remove<K: AsRef<str>>(&mut self, key: K) -> Option<Annotation> {
+        let idx = self.annotations.iter().position(|an| an.key == key.as_ref())?;
+        Some(self.annotations.remove(idx))
+    }
+    /// Insert every annotation in `other` into this annotation set.
+    /// Keys that are in both sets end up with the values from both.
+    ///
+    /// # Ex
+    ///
+    /// ```
+    /// use deck_optim::game::annotations::{
+    ///     Annotation, AnnotationValue, AnnotationSet
+    /// };
+    ///
+    /// let mut base = AnnotationSet::from([
+    ///     Annotation { key: "foo".to_string(), values: vec![AnnotationValue::Int(1)] }
+    /// ]);
+    /// base.merge(AnnotationSet::from([
+    ///     Annotation { key: "foo".to_string(), values: vec![AnnotationValue::Int(2)] },
+    ///     Annotation { key: "bar".to_string(), values: vec![] }
+    /// ]));
+    ///
+    /// assert_eq!(base.keys().collect::<Vec<_>>(), vec!["foo", "bar"]);
+    /// assert_eq!(base.get("foo").unwrap().values(), &[AnnotationValue::Int(1), AnnotationValue::Int(2)]);
+    /// ```
+    pub fn merge(&mut self, other: AnnotationSet) {
+        for annotation in other.annotations {
+            self.insert(annotation);
+        }
+    }
+    /// Iterate over the keys in the annotation set, in the order they were first inserted
+    pub fn keys(&self) -> impl Iterator<Item = &str> + '_ {
+        self.annotations.iter().map(|an| an.key.as_str())
+    }
 }
 impl Default for AnnotationSet {
     /// An empty set of annotations
@@ -270,5 +499,150 @@ mod tests {
         assert_eq!(actual, expected);
     }
 
+    #[test]
+    fn test_deser_with_int_value() {
+        let source = r#"
+        { "annotations": [{
+            "targets": ["Sol Ring"],
+            "key": "core:Priority",
+            "values": [{ "Int": 100 }]
+        }]}
+        "#;
+
+        let actual: CardAnnotations = serde_json::from_str(source).expect("no errors");
+
+        assert_eq!(actual.annotations[0].annotation.values, vec![AnnotationValue::Int(100)]);
+        assert_eq!(actual.validate(), Ok(()));
+    }
+
+    #[test]
+    fn test_deser_with_number_value() {
+        let source = r#"
+        { "annotations": [{
+            "targets": ["Tarmogoyf"],
+            "key": "user:ToughnessThreshold",
+            "values": [{ "Number": 3 }]
+        }]}
+        "#;
+
+        let actual: CardAnnotations = serde_json::from_str(source).expect("no errors");
+        let annotation = &actual.annotations[0].annotation;
+
+        assert_eq!(annotation.values, vec![AnnotationValue::Number(3.0)]);
+        assert_eq!(annotation.first_number(), Some(3.0));
+    }
+
+    #[test]
+    fn test_extend_dedups_numbers() {
+        let mut annotation = annotation("user:Counts", vec![AnnotationValue::Number(2.0)]);
+
+        annotation.extend(vec![AnnotationValue::Number(1.5), AnnotationValue::Number(2.0), AnnotationValue::Int(1)]);
+
+        assert_eq!(annotation.values, vec![AnnotationValue::Int(1), AnnotationValue::Number(1.5), AnnotationValue::Number(2.0)]);
+        assert_eq!(annotation.first_number(), Some(1.0));
+    }
+
+    fn annotation(key: &str, values: Vec<AnnotationValue>) -> Annotation {
+        Annotation {
+            key: key.to_string(),
+            values,
+        }
+    }
+
+    #[test]
+    fn test_remove_key() {
+        let mut set = AnnotationSet::from([
+            annotation("user:Foo", vec![AnnotationValue::Int(1)]),
+            annotation("user:Bar", vec![]),
+        ]);
+
+        let removed = set.remove("user:Foo");
+
+        assert_eq!(removed, Some(annotation("user:Foo", vec![AnnotationValue::Int(1)])));
+        assert!(set.get("user:Foo").is_none());
+        assert_eq!(set.remove("user:Foo"), None);
+        assert_eq!(set, AnnotationSet::from([annotation("user:Bar", vec![])]));
+    }
 
+    #[test]
+    fn test_merge_overlapping_keys() {
+        let mut base = AnnotationSet::from([
+            annotation("user:Foo", vec![AnnotationValue::Int(1), AnnotationValue::Int(2)]),
+            annotation("user:Bar", vec![]),
+        ]);
+        let overrides = AnnotationSet::from([
+            annotation("user:Foo", vec![AnnotationValue::Int(2), AnnotationValue::Int(3)]),
+            annotation("user:Baz", vec![AnnotationValue::String("quux".to_string())]),
+        ]);
+
+        base.merge(overrides);
+
+        assert_eq!(base, AnnotationSet::from([
+            annotation("user:Foo", vec![AnnotationValue::Int(1), AnnotationValue::Int(2), AnnotationValue::Int(3)]),
+            annotation("user:Bar", vec![]),
+            annotation("user:Baz", vec![AnnotationValue::String("quux".to_string())]),
+        ]));
+    }
+
+    #[test]
+    fn test_keys() {
+        let set = AnnotationSet::from([
+            annotation("user:Foo", vec![]),
+            annotation("user:Bar", vec![]),
+            annotation("user:Foo", vec![AnnotationValue::Int(1)]),
+        ]);
+
+        assert_eq!(set.keys().collect::<Vec<_>>(), vec!["user:Foo", "user:Bar"]);
+        assert_eq!(AnnotationSet::empty().keys().count(), 0);
+    }
+
+    #[test]
+    fn test_schema_accepts_produces_mana() {
+        let produces = annotation("core:Produces", vec![AnnotationValue::Mana(ManaPool::green(1))]);
+
+        assert_eq!(AnnotationSchema::CORE.validate(&produces), Ok(()));
+    }
+
+    #[test]
+    fn test_schema_rejects_produces_string() {
+        let produces = annotation("core:Produces", vec![AnnotationValue::String("{G}".to_string())]);
+
+        assert_eq!(AnnotationSchema::CORE.validate(&produces), Err(SchemaViolation::WrongValueKind {
+            key: "core:Produces".to_string(),
+            expected: ValueKind::Mana,
+            found: AnnotationValue::String("{G}".to_string()),
+        }));
+    }
+
+    #[test]
+    fn test_schema_rejects_unknown_core_key() {
+        let typo = annotation("core:Produes", vec![AnnotationValue::Mana(ManaPool::green(1))]);
+
+        assert_eq!(AnnotationSchema::CORE.validate(&typo), Err(SchemaViolation::UnknownKey {
+            key: "core:Produes".to_string(),
+        }));
+    }
+
+    #[test]
+    fn test_schema_ignores_user_keys() {
+        let user = annotation("payoff", vec![AnnotationValue::String("go-wide".to_string())]);
+
+        assert_eq!(AnnotationSchema::CORE.validate(&user), Ok(()));
+    }
+
+    #[test]
+    fn test_validate_card_annotations() {
+        let source = r#"
+        { "annotations": [
+            { "targets": ["Forest"], "key": "core:Produces", "values": [{ "Mana": "{G}" }] },
+            { "targets": ["Sol Ring"], "key": "core:Produes", "values": [{ "Mana": "{C}{C}" }] },
+            { "targets": ["Evolving Wilds"], "key": "core:FetchTarget", "values": [{ "String": "Forest" }] }
+        ] }
+        "#;
+        let annotations: CardAnnotations = serde_json::from_str(source).expect("no errors");
+
+        let violations = annotations.validate().expect_err("has a typo");
+
+        assert_eq!(violations, vec![SchemaViolation::UnknownKey { key: "core:Produes".to_string() }]);
+    }
 }
@@ -2,7 +2,8 @@ use serde::{Deserialize, Serialize};
 
 use crate::collection::Card;
 use crate::game::annotations::Annotation;
-use crate::game::mana::ManaCost;
+use crate::game::mana::{basic_land_mana, ManaCost};
+use crate::game::Zone;
 
 use super::annotations::AnnotationValue;
 
@@ -11,10 +12,19 @@ use super::annotations::AnnotationValue;
 pub struct CardData {
     pub name: String,
     pub card_type: CardType,
+    #[serde(default)]
+    pub super_types: Vec<SuperType>,
     pub cost: Option<ManaCost>,
+    /// Combat stats, only present for creatures. Variable stats such as `*` are treated as 0.
+    pub power: Option<i32>,
+    pub toughness: Option<i32>,
+    /// The other face of a split or double-faced card. The fields above describe the front face,
+    /// which is what the simulator plays.
+    #[serde(default)]
+    pub back: Option<Box<CardData>>,
 }
 
-#[derive(Clone,Debug,Serialize,Deserialize,Eq,PartialEq)]
+#[derive(Clone,Copy,Debug,Serialize,Deserialize,Eq,PartialEq,Hash)]
 pub enum SuperType {
     Legendary,
     Basic,
@@ -22,6 +32,23 @@ pub enum SuperType {
     World
 }
 
+impl CardData {
+    /// Basic lands are exempt from limits on the number of copies in a deck
+    pub fn is_basic(&self) -> bool {
+        self.super_types.contains(&SuperType::Basic)
+    }
+
+    /// Mana from snow permanents can pay for {S} pips
+    pub fn is_snow(&self) -> bool {
+        self.super_types.contains(&SuperType::Snow)
+    }
+
+    /// Only one copy of a legendary permanent may be on the battlefield at a time
+    pub fn is_legendary(&self) -> bool {
+        self.super_types.contains(&SuperType::Legendary)
+    }
+}
+
 #[derive(Clone,Debug,Serialize,Deserialize,Eq,PartialEq)]
 pub enum CardType {
     Land,
@@ -35,6 +62,18 @@ pub enum CardType {
 
 pub const PRODUCES_MANA_TAG: &'static str = "core:Produces";
 pub const GAME_EFFECT_TAG: &'static str = "core:GameEffect";
+pub const FETCH_TARGET_TAG: &'static str = "core:FetchTarget";
+pub const ENTERS_TAPPED_TAG: &'static str = "core:EntersTapped";
+pub const FLASH_TAG: &'static str = "core:Flash";
+pub const PRIORITY_TAG: &'static str = "core:Priority";
+pub const MANA_ABILITY_TAG: &'static str = "core:ManaAbility";
+pub const EXTRA_DRAW_TAG: &'static str = "core:ExtraDraw";
+pub const RECUR_TARGET_TAG: &'static str = "core:RecurTarget";
+pub const EXTRA_LAND_DROP_TAG: &'static str = "core:ExtraLandDrop";
+pub const TUTOR_TARGET_TAG: &'static str = "core:TutorTarget";
+pub const START_IN_PLAY_TAG: &'static str = "core:StartInPlay";
+pub const CAST_FROM_TAG: &'static str = "core:CastFrom";
+pub const MODES_TAG: &'static str = "core:Modes";
 
 impl Card {
     /// Get the name of the card
@@ -49,4 +88,141 @@ impl Card {
             .unwrap_or(EMPTY) 
     }
 
+    /// The effects this card may choose between when it is played, as with charms and other modal spells.
+    /// The strategy picks one of them with [`crate::strategies::Strategy::choose_mode`].
+    pub fn modes(self) -> &'static [AnnotationValue] {
+        self.annotations().get(MODES_TAG)
+            .map(Annotation::values)
+            .unwrap_or_default()
+    }
+
+    /// Can this card be played at instant speed?
+    pub fn is_instant_speed(self) -> bool {
+        self.data().card_type == CardType::Instant || self.has_annotation(FLASH_TAG)
+    }
+
+    /// Does this permanent enter the battlefield tapped?
+    pub fn enters_tapped(self) -> bool {
+        self.has_annotation(ENTERS_TAPPED_TAG)
+    }
+
+    /// The zones other than the hand that this card may be cast from, as with flashback from the graveyard,
+    /// or foretell and adventures from exile. Unknown zone names are logged and ignored.
+    pub fn cast_from_zones(self) -> Vec<Zone> {
+        self.card_names_tagged(CAST_FROM_TAG)
+            .into_iter()
+            .filter_map(|name| {
+                let zone = Zone::from_name(name);
+                if zone.is_none() {
+                    log::error!("tag with {CAST_FROM_TAG} on {} names an unknown zone `{name}`. This will be ignored", self.name());
+                }
+                zone
+            })
+            .collect()
+    }
+
+    /// Can this card be cast from `zone`? Every card with a cost can be cast from the hand.
+    pub fn can_cast_from(self, zone: Zone) -> bool {
+        zone == Zone::Hand || self.cast_from_zones().contains(&zone)
+    }
+
+    /// Does this card begin the game on the battlefield, instead of in the library?
+    pub fn starts_in_play(self) -> bool {
+        self.has_annotation(START_IN_PLAY_TAG)
+    }
+
+    /// How eager the strategies should be to play this card, if the annotations say.
+    /// Takes the largest value, if there are several.
+    pub fn priority(self) -> Option<i64> {
+        self.annotations().get(PRIORITY_TAG)?
+            .values()
+            .iter()
+            .filter_map(|value| match value {
+                AnnotationValue::Int(priority) => Some(*priority),
+                _ => {
+                    log::error!("tag with {PRIORITY_TAG} should have type Int, instead found: {value:?}. This will be ignored");
+                    None
+                }
+            })
+            .max()
+    }
+
+    /// How many extra cards we draw each turn while this card is on the battlefield.
+    /// Tagged cards without a value draw one extra card.
+    pub fn extra_draws(self) -> u32 {
+        self.count_tagged(EXTRA_DRAW_TAG)
+    }
+
+    /// How many extra lands we may play each turn while this card is on the battlefield, as with Exploration.
+    /// Tagged cards without a value allow one extra land drop.
+    pub fn extra_land_drops(self) -> u32 {
+        self.count_tagged(EXTRA_LAND_DROP_TAG)
+    }
+
+    /// The largest non-negative `Int` value under `tag`, 1 if the tag has no values, or 0 if the card is not tagged.
+    fn count_tagged(self, tag: &str) -> u32 {
+        let Some(annotation) = self.annotations().get(tag) else {
+            return 0;
+        };
+        annotation.values()
+            .iter()
+            .filter_map(|value| match value {
+                AnnotationValue::Int(count) => Some((*count).max(0) as u32),
+                _ => {
+                    log::error!("tag with {tag} should have type Int, instead found: {value:?}. This will be ignored");
+                    None
+                }
+            })
+            .max()
+            .unwrap_or(1)
+    }
+
+    /// The names of the cards this card can fetch out of the library
+    pub fn fetch_targets(self) -> Vec<&'static str> {
+        self.card_names_tagged(FETCH_TARGET_TAG)
+    }
+
+    /// Can this card fetch `target` out of the library? Each fetch target is either a card name,
+    /// or a basic land type such as `Forest`, which any basic of that type matches.
+    pub fn can_fetch(self, target: Card) -> bool {
+        self.fetch_targets()
+            .into_iter()
+            .any(|name| target.name() == name || target.is_basic_land_type(name))
+    }
+
+    /// Is this a basic land of the given type, such as `Forest`? Snow-covered basics count as their usual type.
+    pub fn is_basic_land_type(self, land_type: &str) -> bool {
+        let data = self.data();
+        data.card_type == CardType::Land
+            && data.is_basic()
+            && basic_land_mana(land_type).is_some()
+            && data.name.strip_prefix("Snow-Covered ").unwrap_or(&data.name) == land_type
+    }
+
+    /// The names of the cards this card can return from the graveyard.
+    /// When empty, it returns whichever card went to the graveyard last.
+    pub fn recur_targets(self) -> Vec<&'static str> {
+        self.card_names_tagged(RECUR_TARGET_TAG)
+    }
+
+    /// The names of the cards this card can search the library for and put into hand.
+    pub fn tutor_targets(self) -> Vec<&'static str> {
+        self.card_names_tagged(TUTOR_TARGET_TAG)
+    }
+
+    fn card_names_tagged(self, tag: &'static str) -> Vec<&'static str> {
+        self.annotations().get(tag)
+            .map(Annotation::values)
+            .unwrap_or_default()
+            .iter()
+            .filter_map(|value| match value {
+                AnnotationValue::String(name) => Some(name.as_str()),
+                _ => {
+                    log::error!("tag with {tag} should have type String, instead found: {value:?}. This will be ignored");
+                    None
+                }
+            })
+            .collect()
+    }
+
 }
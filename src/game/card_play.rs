@@ -1,5 +1,6 @@
 use crate::collection::Card;
 use crate::game::Zone;
+use crate::game::mana::ManaCost;
 use crate::game::mana::ManaPool;
 
 /// Contains all information necessary to make a card play
@@ -8,8 +9,22 @@ pub struct CardPlay {
     /// What card is being played?
     pub card: Card,
     /// The origin of the card. Usually this will be from the hand, but could also be from the
-    /// command zone, library, or graveyard.
+    /// command zone, library, graveyard, or exile. Spells cast from the graveyard are exiled afterwards, as with flashback.
     pub zone: Zone,
     // The mana we are using to pay for this card.
-    pub payment: ManaPool
+    pub payment: ManaPool,
+    /// The value chosen for X, if the card has {X} in its cost.
+    pub x_value: u8,
+    /// Generic mana added on top of the card's cost, like the commander tax.
+    pub tax: u8,
+    /// The mana sources tapped to make the payment.
+    pub tapped: Vec<Card>,
+}
+
+impl CardPlay {
+    /// What it costs to make this play: the card's own cost, plus any tax.
+    /// Cards without a cost, like lands, can not be cast and have no cost to pay.
+    pub fn cost(&self) -> Option<ManaCost> {
+        self.card.data().cost.map(|cost| cost + ManaCost::generic(self.tax))
+    }
 }
@@ -25,4 +25,12 @@ pub enum ManaParseError {
     DidNotMatchRegex { re: Regex, bad_string: String },
     #[error("can not use generic mana in a mana pool - only costs have generic portions. Did you mean colorless instead?")]
     GenericCostInManaPool,
+    #[error("can not use phyrexian mana in a mana pool - only costs can be paid with life")]
+    PhyrexianCostInManaPool,
+    #[error("can not use {{X}} in a mana pool - only costs have a variable portion")]
+    XCostInManaPool,
+    #[error("can not use {{S}} in a mana pool - snow is a property of the source, not a type of mana")]
+    SnowCostInManaPool,
+    #[error("a mana ability must be written as `<cost>: <mana>`, found `{bad_string}`")]
+    MissingAbilitySeparator { bad_string: String },
 }
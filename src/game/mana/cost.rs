@@ -4,14 +4,25 @@ use regex::Regex;
 
 use super::ManaParseError;
 use super::ManaPool;
+use super::ManaSymbol;
+use super::ManaType;
 
 /// Represents an amount of mana required by a cost
 #[derive(Debug,Clone,Copy,PartialEq,Eq,PartialOrd,Ord)]
 pub struct ManaCost {
     pub colors: ManaPool, 
     pub generic: u8,
+    /// Phyrexian pips, each of which may be paid with its color or with life
+    pub phyrexian: ManaPool,
+    /// The number of {X} pips in this cost
+    pub x_count: u8,
+    /// The number of {S} pips in this cost, each of which must be paid with mana from a snow source
+    pub s_count: u8,
 }
 
+/// The amount of life it takes to pay for a single phyrexian pip instead of mana
+pub const PHYREXIAN_LIFE_COST: u8 = 2;
+
 
 impl std::default::Default for ManaCost {
     fn default() -> Self {
@@ -31,6 +42,9 @@ impl ManaCost {
         Self {
             colors: ManaPool::empty(),
             generic: 0,
+            phyrexian: ManaPool::empty(),
+            x_count: 0,
+            s_count: 0,
         }
     }
 
@@ -38,42 +52,42 @@ impl ManaCost {
     pub fn white(white: u8) -> Self {
         Self {
             colors: ManaPool::white(white),
-            generic: 0
+            ..Self::empty()
         }
     }
     // Creates a mana cost of entirely blue mana
     pub fn blue(blue: u8) -> Self {
         Self {
             colors: ManaPool::blue(blue),
-            generic: 0
+            ..Self::empty()
         }
     }
     // Creates a mana cost of entirely black mana
     pub fn black(black: u8) -> Self {
         Self {
             colors: ManaPool::black(black),
-            generic: 0
+            ..Self::empty()
         }
     }
     // Creates a mana cost of entirely red mana
     pub fn red(red: u8) -> Self {
         Self {
             colors: ManaPool::red(red),
-            generic: 0
+            ..Self::empty()
         }
     }
     // Creates a mana cost of entirely green mana
     pub fn green(green: u8) -> Self {
         Self {
             colors: ManaPool::green(green),
-            generic: 0
+            ..Self::empty()
         }
     }
     // Creates a mana cost of entirely generic
     pub fn generic(generic: u8) -> Self {
         Self {
-            colors: ManaPool::empty(),
-            generic
+            generic,
+            ..Self::empty()
         }
     }
     
@@ -89,21 +103,22 @@ impl ManaCost {
     /// let actual_mana = ManaCost::try_parse(source).expect("should parse");
     /// let expected_mana = ManaCost {
     ///     colors: ManaPool::green(1),
-    ///     generic: 2
+    ///     generic: 2,
+    ///     ..Default::default()
     /// };
     ///
     /// assert_eq!(expected_mana, actual_mana);
     /// ```
     pub fn try_parse(source: &str) -> Result<ManaCost, ManaParseError> {
 
-        let overall = Regex::new(r"^(\{[WUBRGC0-9]+\})*$").expect("regex to compile");
+        let overall = Regex::new(r"^(\{([WUBRGC0-9]+|[WUBRG]/P|X|S)\})*$").expect("regex to compile");
         if !overall.is_match(source) {
             return Err(ManaParseError::DidNotMatchRegex {
                 re: overall,
                 bad_string: source.to_string()
             })
         }
-        let re = Regex::new(r"\{([WUBRGC0-9]+)\}").expect("regex to compile");
+        let re = Regex::new(r"\{([WUBRGC0-9]+|[WUBRG]/P|X|S)\}").expect("regex to compile");
 
         let mut mana = ManaCost::empty();
 
@@ -116,6 +131,13 @@ impl ManaCost {
                 "R" => mana.colors.red       += 1,
                 "G" => mana.colors.green     += 1,
                 "C" => mana.colors.colorless += 1,
+                "W/P" => mana.phyrexian.white += 1,
+                "U/P" => mana.phyrexian.blue  += 1,
+                "B/P" => mana.phyrexian.black += 1,
+                "R/P" => mana.phyrexian.red   += 1,
+                "G/P" => mana.phyrexian.green += 1,
+                "X" => mana.x_count += 1,
+                "S" => mana.s_count += 1,
                 digits if digits.chars().all(|ch| ch.is_ascii_digit()) => {
                     mana.generic += match digits.parse::<u8>() {
                         Ok(num) => num,
@@ -136,8 +158,51 @@ impl ManaCost {
         Ok(mana)
     }
 
+    /// The mana value of this cost. By convention, X is counted as zero.
+    /// Each {S} counts as one.
     pub fn mana_value(&self) -> u8 {
-        self.colors.mana_value() + self.generic
+        self.mana_value_with_x(0)
+    }
+
+    /// The mana value of this cost, if X were chosen to be `x`.
+    /// ```
+    /// use deck_optim::game::mana::ManaCost;
+    ///
+    /// let fireball = ManaCost::try_parse("{X}{R}").expect("should parse");
+    /// assert_eq!(fireball.mana_value(), 1);
+    /// assert_eq!(fireball.mana_value_with_x(4), 5);
+    /// ```
+    pub fn mana_value_with_x(&self, x: u8) -> u8 {
+        self.colors.mana_value() + self.generic + self.phyrexian.mana_value() + self.s_count + self.x_count * x
+    }
+
+    /// Every symbol of this cost in the order they are written: {X} first, then generic and snow,
+    /// then each colored pip in WUBRG order, then colorless, then phyrexian pips in WUBRG order.
+    /// A cost of nothing has no symbols.
+    /// ```
+    /// use deck_optim::game::mana::{ManaCost, ManaSymbol, ManaType};
+    ///
+    /// let cost = ManaCost::try_parse("{G}{W}{2}").expect("should parse");
+    /// assert_eq!(cost.pips_in_canonical_order(), vec![
+    ///     ManaSymbol::Generic(2),
+    ///     ManaSymbol::Mana(ManaType::White),
+    ///     ManaSymbol::Mana(ManaType::Green),
+    /// ]);
+    /// ```
+    pub fn pips_in_canonical_order(&self) -> Vec<ManaSymbol> {
+        let mut pips = Vec::with_capacity(self.mana_value() as usize + self.x_count as usize);
+        pips.extend((0..self.x_count).map(|_| ManaSymbol::X));
+        if self.generic > 0 {
+            pips.push(ManaSymbol::Generic(self.generic));
+        }
+        pips.extend((0..self.s_count).map(|_| ManaSymbol::Snow));
+        for &mana_type in ManaType::all() {
+            pips.extend((0..self.colors[mana_type]).map(|_| ManaSymbol::Mana(mana_type)));
+        }
+        for &mana_type in ManaType::all() {
+            pips.extend((0..self.phyrexian[mana_type]).map(|_| ManaSymbol::Phyrexian(mana_type)));
+        }
+        pips
     }
 
 }
@@ -147,8 +212,11 @@ impl std::ops::Add for ManaCost {
 
     fn add(self, rhs: Self) -> Self::Output {
         Self {
-            colors:  self.colors    + rhs.colors,
-            generic: self.generic   + rhs.generic,
+            colors:    self.colors    + rhs.colors,
+            generic:   self.generic   + rhs.generic,
+            phyrexian: self.phyrexian + rhs.phyrexian,
+            x_count:   self.x_count   + rhs.x_count,
+            s_count:   self.s_count   + rhs.s_count,
         }
     }
 }
@@ -166,20 +234,13 @@ impl std::iter::Sum for ManaCost {
 
 impl fmt::Display for ManaCost {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        
-        let generic = self.generic;
-        if generic > 0 { write!(f, "{{{generic}}}")?; }
-        for _ in 0..self.colors.white     { write!(f, "{{W}}")?; }
-        for _ in 0..self.colors.blue      { write!(f, "{{U}}")?; }
-        for _ in 0..self.colors.black     { write!(f, "{{B}}")?; }
-        for _ in 0..self.colors.red       { write!(f, "{{R}}")?; }
-        for _ in 0..self.colors.green     { write!(f, "{{G}}")?; }
-        for _ in 0..self.colors.colorless { write!(f, "{{C}}")?; }
-
-        if self.mana_value() ==  0 {
-            write!(f, "{{0}}")?;
+        let pips = self.pips_in_canonical_order();
+        if pips.is_empty() {
+            return write!(f, "{{0}}");
+        }
+        for pip in pips {
+            write!(f, "{pip}")?;
         }
-
         Ok(())
     }
 }
@@ -264,6 +325,7 @@ mod tests {
                 ..default()
             },
             generic: 10,
+            ..default()
         };
 
         assert_eq!(expected_mana, actual_mana);
@@ -304,6 +366,7 @@ mod tests {
                 ..default()
             },
             generic: 10,
+            ..default()
         };
 
         let actual = format!("{}", mana);
@@ -311,4 +374,126 @@ mod tests {
 
         assert_eq!(actual, expected);
     }
+
+    #[test]
+    fn test_parse_phyrexian() {
+        let source = "{1}{R/P}{R/P}";
+
+        let actual_mana = ManaCost::try_parse(source).expect("should parse");
+        let expected_mana = ManaCost {
+            generic: 1,
+            phyrexian: ManaPool::red(2),
+            ..default()
+        };
+
+        assert_eq!(expected_mana, actual_mana);
+        assert_eq!(actual_mana.mana_value(), 3);
+    }
+
+    #[test]
+    fn test_parse_fail_colorless_phyrexian() {
+        let source = "{C/P}";
+
+        let result = ManaCost::try_parse(source);
+        assert!(result.is_err())
+    }
+
+    #[test]
+    fn test_parse_x() {
+        let source = "{X}{R}";
+
+        let actual_mana = ManaCost::try_parse(source).expect("should parse");
+        let expected_mana = ManaCost {
+            colors: ManaPool::red(1),
+            x_count: 1,
+            ..default()
+        };
+
+        assert_eq!(expected_mana, actual_mana);
+        assert_eq!(actual_mana.mana_value(), 1);
+    }
+
+    #[test]
+    fn test_parse_double_x() {
+        let source = "{X}{X}";
+
+        let actual_mana = ManaCost::try_parse(source).expect("should parse");
+        let expected_mana = ManaCost {
+            x_count: 2,
+            ..default()
+        };
+
+        assert_eq!(expected_mana, actual_mana);
+        assert_eq!(actual_mana.mana_value(), 0);
+        assert_eq!(actual_mana.mana_value_with_x(3), 6);
+    }
+
+    #[test]
+    fn test_serialize_x() {
+        for source in ["{X}{R}", "{X}{X}", "{X}{2}{U}{G}"] {
+            let mana = ManaCost::try_parse(source).expect("should parse");
+            assert_eq!(mana.to_string(), source);
+        }
+    }
+
+    #[test]
+    fn test_serialize_phyrexian() {
+        let source = "{2}{U}{G/P}";
+
+        let mana = ManaCost::try_parse(source).expect("should parse");
+
+        assert_eq!(mana.to_string(), source);
+    }
+
+    #[test]
+    fn test_parse_snow() {
+        let source = "{2}{S}{S}{G}";
+
+        let actual_mana = ManaCost::try_parse(source).expect("should parse");
+        let expected_mana = ManaCost {
+            colors: ManaPool::green(1),
+            generic: 2,
+            s_count: 2,
+            ..default()
+        };
+
+        assert_eq!(expected_mana, actual_mana);
+        assert_eq!(actual_mana.mana_value(), 5);
+        assert_eq!(actual_mana.to_string(), source);
+    }
+
+    #[test]
+    fn test_pips_in_canonical_order_mixed_cost() {
+        let cost = ManaCost::try_parse("{C}{G/P}{G}{S}{U}{X}{W}{3}{R/P}{G}").expect("should parse");
+
+        let expected = vec![
+            ManaSymbol::X,
+            ManaSymbol::Generic(3),
+            ManaSymbol::Snow,
+            ManaSymbol::Mana(ManaType::White),
+            ManaSymbol::Mana(ManaType::Blue),
+            ManaSymbol::Mana(ManaType::Green),
+            ManaSymbol::Mana(ManaType::Green),
+            ManaSymbol::Mana(ManaType::Colorless),
+            ManaSymbol::Phyrexian(ManaType::Red),
+            ManaSymbol::Phyrexian(ManaType::Green),
+        ];
+
+        assert_eq!(cost.pips_in_canonical_order(), expected);
+        assert_eq!(cost.to_string(), "{X}{3}{S}{W}{U}{G}{G}{C}{R/P}{G/P}");
+    }
+
+    #[test]
+    fn test_pips_in_canonical_order_empty_cost() {
+        assert_eq!(ManaCost::empty().pips_in_canonical_order(), vec![]);
+        assert_eq!(ManaCost::empty().to_string(), "{0}");
+        assert_eq!(ManaPool::empty().to_string(), "{0}");
+    }
+
+    #[test]
+    fn test_pool_displays_in_canonical_order() {
+        let pool = ManaPool::try_parse("{C}{G}{R}{W}").expect("should parse");
+
+        assert_eq!(pool.to_string(), "{W}{R}{G}{C}");
+    }
 }
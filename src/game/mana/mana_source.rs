@@ -1,64 +1,200 @@
+use std::collections::HashSet;
 use std::fmt;
 
 use itertools::Itertools;
 
-use crate::game::annotations::AnnotationValue;
-use crate::game::mana::ManaPool;
-use crate::collection::Card;
-use crate::game::PRODUCES_MANA_TAG;
+use crate::game::annotations::{AnnotationSet, AnnotationValue};
+use crate::game::mana::{ManaCost, ManaParseError, ManaPool, ManaType};
+use crate::collection::{Card, CardCollection};
+use crate::game::{CardData, CardType, MANA_ABILITY_TAG, PRODUCES_MANA_TAG};
+
+/// The mana a basic land makes, recognized by name so that basics work without a `core:Produces` annotation.
+/// Wastes makes colorless, which is what `{C}` pips need. Snow-covered basics make the same mana as the usual ones.
+/// ```
+/// use deck_optim::game::mana::{basic_land_mana, ManaPool};
+///
+/// assert_eq!(basic_land_mana("Wastes"), Some(ManaPool::colorless(1)));
+/// assert_eq!(basic_land_mana("Snow-Covered Forest"), Some(ManaPool::green(1)));
+/// assert_eq!(basic_land_mana("Evolving Wilds"), None);
+/// ```
+pub fn basic_land_mana(name: &str) -> Option<ManaPool> {
+    let name = name.strip_prefix("Snow-Covered ").unwrap_or(name);
+    match name {
+        "Plains" => Some(ManaPool::white(1)),
+        "Island" => Some(ManaPool::blue(1)),
+        "Swamp" => Some(ManaPool::black(1)),
+        "Mountain" => Some(ManaPool::red(1)),
+        "Forest" => Some(ManaPool::green(1)),
+        "Wastes" => Some(ManaPool::colorless(1)),
+        _ => None,
+    }
+}
+
+/// One way to tap a mana source: pay `input`, and get `output`.
+/// Most sources have no input, but filter lands need to be fed some mana first.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct ManaAbility {
+    pub input: ManaCost,
+    pub output: ManaPool,
+}
+
+impl ManaAbility {
+    /// An ability that produces `output` just by tapping
+    pub fn free(output: ManaPool) -> Self {
+        Self {
+            input: ManaCost::empty(),
+            output,
+        }
+    }
+    /// Parses an ability written as `<cost>: <mana>`.
+    /// ```
+    /// use deck_optim::game::mana::{ManaAbility, ManaCost, ManaPool};
+    ///
+    /// let ability = ManaAbility::try_parse("{1}: {W}{W}").expect("should parse");
+    ///
+    /// assert_eq!(ability.input, ManaCost::generic(1));
+    /// assert_eq!(ability.output, ManaPool::white(2));
+    /// ```
+    pub fn try_parse(source: &str) -> Result<ManaAbility, ManaParseError> {
+        let Some((input, output)) = source.split_once(':') else {
+            return Err(ManaParseError::MissingAbilitySeparator { bad_string: source.to_string() });
+        };
+        Ok(Self {
+            input: ManaCost::try_parse(input.trim())?,
+            output: ManaPool::try_parse(output.trim())?,
+        })
+    }
+    /// Whether this ability can be activated without paying any mana
+    pub fn is_free(&self) -> bool {
+        self.input == ManaCost::empty()
+    }
+    /// How much more mana this ability makes than it costs
+    pub fn net_mana_value(&self) -> u8 {
+        self.output.mana_value().saturating_sub(self.input.mana_value())
+    }
+}
+
+impl From<ManaPool> for ManaAbility {
+    fn from(output: ManaPool) -> Self {
+        Self::free(output)
+    }
+}
+
+impl fmt::Debug for ManaAbility {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_free() {
+            write!(f, "{}", self.output)
+        } else {
+            write!(f, "{}: {}", self.input, self.output)
+        }
+    }
+}
 
 /// A mana source is a way to produce mana.
 /// Typically, this is by tapping a land, a mana rock, or a mana dork.
 #[derive(Clone, PartialEq, Eq)]
 pub struct ManaSource {
     pub card: Card,
-    pub produces: Vec<ManaPool>
+    pub produces: Vec<ManaAbility>,
+    /// Mana from snow sources can pay for {S} pips
+    pub produces_snow: bool,
 }
 
 impl fmt::Debug for ManaSource {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         let mut dbg = f.debug_struct("ManaSource");
         dbg.field("card", &self.card.name())
-            .field("produces", &format_args!("{:?}", self.produces.iter().map(|m| format!("{}", m)).collect::<Vec<_>>()));
+            .field("produces", &self.produces)
+            .field("produces_snow", &self.produces_snow);
         dbg.finish()
     }
 }
 
 impl ManaSource {
+    /// Looks the card up in the global collection
     pub fn try_from(card: Card) -> Option<Self> {
-        let produces = card
-            .annotations()
-            .get(PRODUCES_MANA_TAG)?
-            .values()
+        Self::from_parts(card, card.data(), card.annotations())
+    }
+
+    /// Like [`ManaSource::try_from`], but looks the card up in `collection`, which need not be the global one
+    pub fn try_from_collection(card: Card, collection: &CardCollection) -> Option<Self> {
+        Self::from_parts(card, collection.card_data(card), collection.get_annotations(card))
+    }
+
+    fn from_parts(card: Card, data: &CardData, annotations: &AnnotationSet) -> Option<Self> {
+        let free_abilities = annotations.get(PRODUCES_MANA_TAG);
+        let costed_abilities = annotations.get(MANA_ABILITY_TAG);
+        if free_abilities.is_none() && costed_abilities.is_none() {
+            // basic lands need no annotation, unless it is to say they make something unusual
+            let mana = basic_land_mana(&data.name).filter(|_| data.card_type == CardType::Land && data.is_basic())?;
+            return Some(Self {
+                card,
+                produces: vec![ManaAbility::free(mana)],
+                produces_snow: data.is_snow(),
+            });
+        }
+
+        let free_abilities = free_abilities
             .iter()
+            .flat_map(|annotation| annotation.values())
             .filter_map(|value| match value {
-                AnnotationValue::Mana(mana) => Some(mana),
+                AnnotationValue::Mana(mana) => Some(ManaAbility::free(*mana)),
                 _ => {
                     log::error!("tag with {PRODUCES_MANA_TAG} should have type Mana, instead found: {value:?}. This will be ignored");
                     None
                 }
-            })
-            .copied()
-            .collect_vec();
+            });
+        let costed_abilities = costed_abilities
+            .iter()
+            .flat_map(|annotation| annotation.values())
+            .filter_map(|value| match value {
+                AnnotationValue::String(ability) => ManaAbility::try_parse(ability)
+                    .inspect_err(|e| log::error!("could not parse mana ability of {}: {e}. This will be ignored", data.name))
+                    .ok(),
+                _ => {
+                    log::error!("tag with {MANA_ABILITY_TAG} should have type String, instead found: {value:?}. This will be ignored");
+                    None
+                }
+            });
+        let produces = free_abilities.chain(costed_abilities).collect_vec();
 
         if produces.is_empty() {
-            log::warn!("tag with {PRODUCES_MANA_TAG} has no values. Did you intend to supply a Mana tag value?");
+            log::warn!("tags with {PRODUCES_MANA_TAG} or {MANA_ABILITY_TAG} have no values. Did you intend to supply a Mana tag value?");
             return None;
         }
 
         Some(Self {
             card,
-            produces
+            produces,
+            produces_snow: data.is_snow(),
         })
 
     }
 
+    /// The most mana this source can add on its own, after paying for its ability
     pub fn highest_mana_value(&self) -> u8 {
         self.produces
             .iter()
-            .map(|mana| mana.mana_value())
+            .map(ManaAbility::net_mana_value)
             .max()
             .unwrap_or(0)
     }
+    /// The mana this source can make just by tapping, without paying anything
+    pub fn free_outputs(&self) -> impl Iterator<Item = ManaPool> + '_ {
+        self.produces
+            .iter()
+            .filter(|ability| ability.is_free())
+            .map(|ability| ability.output)
+    }
+}
+
+/// Every type of mana that any of these sources can make, counting colorless as its own type.
+/// This does not mean they can all be made at once, but a cost needing any other type can not be paid.
+pub fn colors_produced_by<I: IntoIterator<Item = ManaSource>>(mana_sources: I) -> HashSet<ManaType> {
+    mana_sources
+        .into_iter()
+        .flat_map(|mana_source| mana_source.produces)
+        .flat_map(|ability| ability.output.mana_types().collect_vec())
+        .collect()
 }
 
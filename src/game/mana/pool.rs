@@ -1,3 +1,4 @@
+use std::collections::HashSet;
 use std::fmt;
 
 use super::ManaParseError;
@@ -45,6 +46,15 @@ impl ManaPool {
         if cost.generic > 0 {
             return Err(ManaParseError::GenericCostInManaPool);
         }
+        if cost.phyrexian.mana_value() > 0 {
+            return Err(ManaParseError::PhyrexianCostInManaPool);
+        }
+        if cost.x_count > 0 {
+            return Err(ManaParseError::XCostInManaPool);
+        }
+        if cost.s_count > 0 {
+            return Err(ManaParseError::SnowCostInManaPool);
+        }
         Ok(cost.colors)
     }
 
@@ -84,9 +94,9 @@ impl ManaPool {
         }
     }
     /// Creates a mana cost of colorless mana.
-    pub fn colorless(green: u8) -> Self {
+    pub fn colorless(colorless: u8) -> Self {
         Self {
-            green,
+            colorless,
             ..Default::default()
         }
     }
@@ -135,6 +145,30 @@ impl ManaPool {
         [white, blue, black, red, green, colorless].into_iter().sum()
     }
 
+    /// Whether this pool can pay for `cost`, without working out all of the ways to do it.
+    /// Agrees with [`payment_methods_for`](crate::strategies::payment_solver::payment_methods_for):
+    /// phyrexian pips can always be paid with life, and {S} pips can never be paid, since this pool
+    /// does not say which of its mana came from snow sources.
+    /// # Example
+    /// ```
+    /// use deck_optim::game::mana::{ManaCost, ManaPool};
+    ///
+    /// let available = ManaPool::try_parse("{R}{G}{G}").expect("should parse");
+    ///
+    /// assert!(available.can_pay(&ManaCost::try_parse("{1}{G}{G}").expect("should parse")));
+    /// assert!(!available.can_pay(&ManaCost::try_parse("{R}{R}").expect("should parse")));
+    /// assert!(!available.can_pay(&ManaCost::try_parse("{2}{G}{G}").expect("should parse")));
+    /// ```
+    pub fn can_pay(&self, cost: &ManaCost) -> bool {
+        if cost.s_count > 0 {
+            return false;
+        }
+        match *self - cost.colors {
+            Some(remaining) => remaining.mana_value() >= cost.generic,
+            None => false,
+        }
+    }
+
     /// Remove a single pip from this mana pool.
     pub fn remove_pip(&self, mana_type: ManaType) -> ManaPool {
         let mut new = self.clone();
@@ -148,6 +182,31 @@ impl ManaPool {
         new
     }
 
+    /// Subtract as much of `rhs` as possible from this pool.
+    /// Returns `(remaining, unmet)`, where `remaining` is the mana left over in this pool and
+    /// `unmet` is the portion of `rhs` that could not be covered.
+    /// # Example
+    /// ```
+    /// use deck_optim::game::mana::ManaPool;
+    ///
+    /// let available = ManaPool::try_parse("{R}{R}{G}").expect("should parse");
+    /// let cost = ManaPool::try_parse("{R}{U}").expect("should parse");
+    ///
+    /// let (remaining, unmet) = available.saturating_diff(&cost);
+    ///
+    /// assert_eq!(remaining, ManaPool::try_parse("{R}{G}").expect("should parse"));
+    /// assert_eq!(unmet, ManaPool::blue(1));
+    /// ```
+    pub fn saturating_diff(&self, rhs: &ManaPool) -> (ManaPool, ManaPool) {
+        let mut remaining = ManaPool::empty();
+        let mut unmet = ManaPool::empty();
+        for &mt in ManaType::all() {
+            remaining[mt] = self[mt].saturating_sub(rhs[mt]);
+            unmet[mt] = rhs[mt].saturating_sub(self[mt]);
+        }
+        (remaining, unmet)
+    }
+
     /// Iterate over all types of mana present in this mana pool.
     /// # Example
     /// ```
@@ -167,8 +226,61 @@ impl ManaPool {
             .copied()
             .filter(|mt| self[*mt] > 0)
     }
+
+    /// The colors present in this pool. Colorless mana is not a color, so it is never included.
+    /// ```
+    /// use deck_optim::game::mana::ManaPool;
+    /// use deck_optim::game::mana::ManaType;
+    ///
+    /// let pool = ManaPool::try_parse("{W}{W}{U}{C}").expect("should parse");
+    /// let colors = pool.colors_present();
+    ///
+    /// assert_eq!(colors.len(), 2);
+    /// assert!(colors.contains(&ManaType::White));
+    /// assert!(colors.contains(&ManaType::Blue));
+    /// ```
+    pub fn colors_present(&self) -> HashSet<ManaType> {
+        self.mana_types()
+            .filter(|mt| *mt != ManaType::Colorless)
+            .collect()
+    }
+    /// Renders this pool the way a card would print it: colorless first, then colors in WUBRG order.
+    /// Runs of more than [`COMPACT_RUN_LENGTH`] of the same symbol are grouped with a count, so that
+    /// big pools stay readable in tables.
+    /// ```
+    /// use deck_optim::game::ManaPool;
+    ///
+    /// let pool = ManaPool::try_parse("{R}{C}{C}{C}{C}").expect("should parse");
+    ///
+    /// assert_eq!(pool.to_string(), "{R}{C}{C}{C}{C}");
+    /// assert_eq!(pool.to_compact_string(), "{C}x4{R}");
+    /// ```
+    pub fn to_compact_string(&self) -> String {
+        if self.mana_value() == 0 {
+            return "{0}".to_string();
+        }
+        let ManaPool { white, blue, black, red, green, colorless } = *self;
+        [
+            ("C", colorless),
+            ("W", white),
+            ("U", blue),
+            ("B", black),
+            ("R", red),
+            ("G", green),
+        ]
+            .into_iter()
+            .map(|(symbol, count)| match count {
+                0 => String::new(),
+                count if count > COMPACT_RUN_LENGTH => format!("{{{symbol}}}x{count}"),
+                count => format!("{{{symbol}}}").repeat(count as usize),
+            })
+            .collect()
+    }
 }
 
+/// The most copies of a symbol that [`ManaPool::to_compact_string`] writes out one by one
+pub const COMPACT_RUN_LENGTH: u8 = 3;
+
 impl std::ops::Index<ManaType> for ManaPool {
     type Output = u8;
 
@@ -248,19 +360,12 @@ impl std::iter::Sum for ManaPool {
 
 impl fmt::Display for ManaPool {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        let ManaPool { white, blue, black, red, green, colorless } = *self;
-        
-        for _ in 0..white     { write!(f, "{{W}}")?; }
-        for _ in 0..blue      { write!(f, "{{U}}")?; }
-        for _ in 0..black     { write!(f, "{{B}}")?; }
-        for _ in 0..red       { write!(f, "{{R}}")?; }
-        for _ in 0..green     { write!(f, "{{G}}")?; }
-        for _ in 0..colorless { write!(f, "{{C}}")?; }
-
-        if self.mana_value() == 0 {
-            write!(f, "{{0}}")?;
-        }
-        Ok(())
+        // written the same as a cost of exactly this mana, so the symbols come out in the same order
+        let cost = ManaCost {
+            colors: *self,
+            ..ManaCost::empty()
+        };
+        write!(f, "{cost}")
     }
 }
 
@@ -274,6 +379,28 @@ mod tests {
         Default::default()
     }
 
+    #[test]
+    fn test_colorless_constructor() {
+        assert_eq!(ManaPool::colorless(2), ManaPool::try_parse("{C}{C}").expect("should parse"));
+        assert_eq!(ManaPool::of(ManaType::Colorless, 2), ManaPool::colorless(2));
+    }
+
+    #[test]
+    fn test_can_pay_agrees_with_payment_methods() {
+        use crate::strategies::payment_solver::payment_methods_for;
+
+        let pools = ["", "{G}", "{R}{G}{G}", "{W}{U}{B}", "{C}{C}{C}", "{R}{R}{R}{R}"];
+        let costs = ["", "{0}", "{G}", "{1}{G}", "{G}{G}", "{2}{R}", "{R}{R}{R}{R}{R}", "{U}{B}", "{W}{W}", "{C}{C}", "{4}", "{G/P}", "{1}{R/P}", "{X}{R}", "{S}"];
+        for pool in pools {
+            let pool = ManaPool::try_parse(pool).expect("should parse");
+            for cost in costs {
+                let cost = ManaCost::try_parse(cost).expect("should parse");
+                let expected = payment_methods_for(&pool, &cost).next().is_some();
+                assert_eq!(pool.can_pay(&cost), expected, "paying for {cost:?} with {pool}");
+            }
+        }
+    }
+
     #[test]
     fn test_parse_generic() {
         let source = "{3}";
@@ -283,6 +410,24 @@ mod tests {
         assert!(matches!(err, ManaParseError::GenericCostInManaPool));
     }
 
+    #[test]
+    fn test_parse_phyrexian() {
+        let source = "{G/P}";
+
+        let err = ManaPool::try_parse(source).expect_err("should not parse");
+
+        assert!(matches!(err, ManaParseError::PhyrexianCostInManaPool));
+    }
+
+    #[test]
+    fn test_parse_snow() {
+        let source = "{S}";
+
+        let err = ManaPool::try_parse(source).expect_err("should not parse");
+
+        assert!(matches!(err, ManaParseError::SnowCostInManaPool));
+    }
+
     #[test]
     fn test_parse_empty() {
         let source = "";
@@ -386,5 +531,58 @@ mod tests {
         assert_eq!(remaining, expected_remaining);
     }
 
+    #[test]
+    fn test_saturating_diff_exact() {
+        let available = ManaPool::try_parse("{R}{R}{G}").expect("should parse");
+        let payment = ManaPool::try_parse("{R}{R}{G}").expect("should parse");
+
+        let (remaining, unmet) = available.saturating_diff(&payment);
+
+        assert_eq!(remaining, ManaPool::empty());
+        assert_eq!(unmet, ManaPool::empty());
+    }
+
+    #[test]
+    fn test_saturating_diff_overpayment() {
+        let available = ManaPool::try_parse("{R}{R}{G}{C}").expect("should parse");
+        let payment = ManaPool::try_parse("{R}{G}").expect("should parse");
+
+        let (remaining, unmet) = available.saturating_diff(&payment);
+
+        assert_eq!(remaining, ManaPool::try_parse("{R}{C}").expect("should parse"));
+        assert_eq!(unmet, ManaPool::empty());
+    }
+
+    #[test]
+    fn test_saturating_diff_partial() {
+        let available = ManaPool::try_parse("{W}{W}{U}").expect("should parse");
+        let payment = ManaPool::try_parse("{W}{B}{B}").expect("should parse");
+
+        let (remaining, unmet) = available.saturating_diff(&payment);
+
+        assert_eq!(remaining, ManaPool::try_parse("{W}{U}").expect("should parse"));
+        assert_eq!(unmet, ManaPool::black(2));
+    }
+
+    #[test]
+    fn test_compact_string_short_runs_match_cards() {
+        let pool = ManaPool::try_parse("{G}{G}{C}{W}").expect("should parse");
+
+        assert_eq!(pool.to_string(), "{W}{G}{G}{C}");
+        assert_eq!(pool.to_compact_string(), "{C}{W}{G}{G}");
+    }
 
+    #[test]
+    fn test_compact_string_groups_long_runs() {
+        let pool = ManaPool { colorless: 6, green: 4, red: 3, ..default() };
+
+        assert_eq!(pool.to_string(), "{R}{R}{R}{G}{G}{G}{G}{C}{C}{C}{C}{C}{C}");
+        assert_eq!(pool.to_compact_string(), "{C}x6{R}{R}{R}{G}x4");
+    }
+
+    #[test]
+    fn test_compact_string_empty() {
+        assert_eq!(ManaPool::empty().to_string(), "{0}");
+        assert_eq!(ManaPool::empty().to_compact_string(), "{0}");
+    }
 }
@@ -1,3 +1,5 @@
+use std::fmt;
+
 #[derive(Debug,Copy,Clone,PartialEq,Eq,PartialOrd,Ord,Hash)]
 pub enum ManaType {
     White,
@@ -20,4 +22,43 @@ impl ManaType {
         ];
         ALL
     }
+
+    /// The letter used for this type of mana between braces, as in `{W}`
+    pub const fn symbol(&self) -> &'static str {
+        match self {
+            ManaType::White => "W",
+            ManaType::Blue => "U",
+            ManaType::Black => "B",
+            ManaType::Red => "R",
+            ManaType::Green => "G",
+            ManaType::Colorless => "C",
+        }
+    }
+}
+
+/// A single symbol of a mana cost, written between braces
+#[derive(Debug,Copy,Clone,PartialEq,Eq)]
+pub enum ManaSymbol {
+    /// A variable amount, `{X}`
+    X,
+    /// Some amount of generic mana, like `{3}`
+    Generic(u8),
+    /// Generic mana that must come from a snow source, `{S}`
+    Snow,
+    /// A pip of exactly this type of mana, like `{W}` or `{C}`
+    Mana(ManaType),
+    /// A pip that may be paid with this color or with life, like `{W/P}`
+    Phyrexian(ManaType),
+}
+
+impl fmt::Display for ManaSymbol {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ManaSymbol::X => write!(f, "{{X}}"),
+            ManaSymbol::Generic(amount) => write!(f, "{{{amount}}}"),
+            ManaSymbol::Snow => write!(f, "{{S}}"),
+            ManaSymbol::Mana(mana_type) => write!(f, "{{{}}}", mana_type.symbol()),
+            ManaSymbol::Phyrexian(mana_type) => write!(f, "{{{}/P}}", mana_type.symbol()),
+        }
+    }
 }
@@ -45,6 +45,30 @@ impl OrderedPile {
         }
         hand
     }
+    /// Remove the top-most card matching `predicate` from this pile, if there is one.
+    pub fn take_first<F>(&mut self, predicate: F) -> Option<Card>
+    where F: Fn(&Card) -> bool
+    {
+        let idx = self.cards.iter().rposition(predicate)?;
+        Some(self.cards.remove(idx))
+    }
+    /// Remove the top-most copy of `card` from this pile, returning false if there is none.
+    ///
+    /// # Example
+    /// ```
+    /// use deck_optim::game::OrderedPile;
+    /// use deck_optim::collection::get_sample_cards;
+    ///
+    /// let cards = get_sample_cards(3);
+    /// let mut pile = OrderedPile::from(cards[..2].to_vec());
+    ///
+    /// assert!(pile.remove(cards[0]));
+    /// assert!(!pile.remove(cards[2]));
+    /// assert_eq!(pile.size(), 1);
+    /// ```
+    pub fn remove(&mut self, card: Card) -> bool {
+        self.take_first(|c| *c == card).is_some()
+    }
     /// Iterate over all cards in the pile
     pub fn iter(&self) -> impl Iterator<Item = Card> + '_ {
         self.cards
@@ -57,11 +81,65 @@ impl OrderedPile {
             self.cards.push(c);
         }
     }
+    /// Put all of `cards` on the bottom of this pile.
+    /// The first card in `cards` will end up as the bottom-most card.
+    pub fn add_to_bottom(&mut self, cards: &[Card]) {
+        self.cards.splice(0..0, cards.iter().copied());
+    }
+    /// Look at the top `n` cards of this pile, without removing them.
+    /// The cards are in the same order as the pile, so the last card is the top card.
+    /// If there are fewer than `n` cards, all of them are returned.
+    ///
+    /// # Example
+    /// ```
+    /// use deck_optim::game::OrderedPile;
+    /// use deck_optim::collection::get_sample_cards;
+    ///
+    /// let cards = get_sample_cards(3);
+    /// let mut pile = OrderedPile::from(cards.clone());
+    ///
+    /// assert_eq!(pile.peek_top(2), &[cards[1], cards[2]]);
+    /// assert_eq!(pile.draw(), Some(cards[2]));
+    /// ```
+    pub fn peek_top(&self, n: usize) -> &[Card] {
+        let start = self.cards.len().saturating_sub(n);
+        &self.cards[start..]
+    }
+    /// Move the top-most copy of `card` to the bottom of this pile, as when scrying.
+    /// If the card is not in the pile, it is just added to the bottom.
+    pub fn put_on_bottom(&mut self, card: Card) {
+        if self.take_first(|c| *c == card).is_none() {
+            log::warn!("putting {card:?} on the bottom, but it was not in the pile");
+        }
+        self.cards.insert(0, card);
+    }
+    /// Replace the top cards of this pile with `new_order`, which must be a rearrangement of them.
+    /// Like [`OrderedPile::peek_top`], the last card of `new_order` becomes the top card.
+    /// If `new_order` is not a rearrangement of the top cards, the pile is left alone.
+    pub fn reorder_top(&mut self, new_order: Vec<Card>) {
+        let mut current = self.peek_top(new_order.len()).to_vec();
+        let mut proposed = new_order.clone();
+        current.sort();
+        proposed.sort();
+        if current != proposed {
+            log::error!("can not reorder the top of the pile to {new_order:?}, those are not the top {} cards", new_order.len());
+            return;
+        }
+        let start = self.cards.len() - new_order.len();
+        self.cards.splice(start.., new_order);
+    }
     /// Shuffle this pile.
     pub fn shuffle<R: Rng>(&mut self, rng: &mut R) {
         use rand::seq::SliceRandom;
         self.cards.shuffle(rng)
     }
+    /// Replace the cards in this pile with a shuffled copy of `pile`, reusing this pile's allocation.
+    /// The shuffle is the same as [`UnorderedPile::to_ordered`] would make with the same rng.
+    pub fn reset_from<R: Rng>(&mut self, pile: &UnorderedPile, rng: &mut R) {
+        self.cards.clear();
+        self.cards.extend(pile.iter());
+        self.shuffle(rng);
+    }
 }
 
 #[cfg(test)]
@@ -132,4 +210,106 @@ mod tests {
         assert_eq!(hand, Vec::<Card>::new());
         assert_eq!(pile.cards, Vec::<Card>::new());
     }
+
+    #[test]
+    fn test_take_first() {
+        let cards = get_sample_cards(3);
+        let mut pile = OrderedPile { cards: vec![cards[0], cards[1], cards[0], cards[2]] };
+
+        let taken = pile.take_first(|c| *c == cards[0]);
+
+        assert_eq!(taken, Some(cards[0]));
+        assert_eq!(pile.cards, vec![cards[0], cards[1], cards[2]]);
+    }
+
+    #[test]
+    fn test_take_first_no_match() {
+        let cards = get_sample_cards(3);
+        let mut pile = OrderedPile { cards: vec![cards[0], cards[1]] };
+
+        let taken = pile.take_first(|c| *c == cards[2]);
+
+        assert_eq!(taken, None);
+        assert_eq!(pile.cards, vec![cards[0], cards[1]]);
+    }
+
+    #[test]
+    fn test_add_to_bottom() {
+        let cards = get_sample_cards(4);
+        let mut pile = OrderedPile { cards: vec![cards[0], cards[1]] };
+
+        pile.add_to_bottom(&[cards[2], cards[3]]);
+
+        assert_eq!(pile.cards, vec![cards[2], cards[3], cards[0], cards[1]]);
+        assert_eq!(pile.draw(), Some(cards[1]));
+    }
+
+    #[test]
+    fn test_peek_top_fewer_than_present() {
+        let cards = get_sample_cards(4);
+        let pile = OrderedPile { cards: cards.clone() };
+
+        assert_eq!(pile.peek_top(2), &[cards[2], cards[3]]);
+        assert_eq!(pile.size(), 4);
+    }
+
+    #[test]
+    fn test_peek_top_more_than_present() {
+        let cards = get_sample_cards(2);
+        let pile = OrderedPile { cards: cards.clone() };
+
+        assert_eq!(pile.peek_top(5), &[cards[0], cards[1]]);
+    }
+
+    #[test]
+    fn test_put_on_bottom() {
+        let cards = get_sample_cards(3);
+        let mut pile = OrderedPile { cards: cards.clone() };
+
+        // scry 1, and bottom the top card
+        let top = pile.peek_top(1)[0];
+        pile.put_on_bottom(top);
+
+        assert_eq!(pile.cards, vec![cards[2], cards[0], cards[1]]);
+        assert_eq!(pile.draw(), Some(cards[1]));
+    }
+
+    #[test]
+    fn test_reorder_top_three() {
+        let cards = get_sample_cards(5);
+        let mut pile = OrderedPile { cards: cards.clone() };
+
+        pile.reorder_top(vec![cards[4], cards[2], cards[3]]);
+
+        assert_eq!(pile.cards, vec![cards[0], cards[1], cards[4], cards[2], cards[3]]);
+        assert_eq!(pile.draw(), Some(cards[3]));
+    }
+
+    #[test]
+    fn test_reorder_top_rejects_other_cards() {
+        let cards = get_sample_cards(4);
+        let mut pile = OrderedPile { cards: vec![cards[0], cards[1], cards[2]] };
+
+        pile.reorder_top(vec![cards[3], cards[2]]);
+
+        assert_eq!(pile.cards, vec![cards[0], cards[1], cards[2]]);
+    }
+
+    #[test]
+    fn test_remove_present_card() {
+        let cards = get_sample_cards(3);
+        let mut pile = OrderedPile { cards: vec![cards[0], cards[1], cards[0], cards[2]] };
+
+        assert!(pile.remove(cards[0]));
+        assert_eq!(pile.cards, vec![cards[0], cards[1], cards[2]]);
+    }
+
+    #[test]
+    fn test_remove_absent_card() {
+        let cards = get_sample_cards(3);
+        let mut pile = OrderedPile { cards: vec![cards[0], cards[1]] };
+
+        assert!(!pile.remove(cards[2]));
+        assert_eq!(pile.cards, vec![cards[0], cards[1]]);
+    }
 }
@@ -1,3 +1,5 @@
+use std::collections::{HashMap, HashSet};
+
 use itertools::Itertools;
 use rand::Rng;
 
@@ -5,17 +7,40 @@ use crate::collection::Card;
 use crate::game::card::CardType;
 use crate::trial::Rand;
 use crate::game::{
-    Battlefield, CommandZone, Graveyard, Hand, Library 
+    Battlefield, CommandZone, Exile, Graveyard, Hand, Library 
 };
 use crate::game::unordered_pile::UnorderedPile;
 use crate::game::Deck;
 use crate::game::Zone;
 use crate::game::card_play::CardPlay;
 use crate::game::mana::ManaPool;
-use crate::game::mana::ManaSource;
+use crate::game::mana::{colors_produced_by, ManaSource};
+use crate::game::mana::ManaType;
 
 const PROB_OF_GOING_FIRST: f64 = 0.5;
-const HAND_SIZE: u32 = 7;
+pub const DEFAULT_HAND_SIZE: u32 = 7;
+
+/// Who goes first, which decides whether a card is drawn on the first turn
+#[derive(Debug,Clone,Copy,Default,PartialEq,Eq)]
+pub enum PlayDraw {
+    /// Flip a coin
+    #[default]
+    Random,
+    /// Always go first, skipping the first draw
+    AlwaysPlay,
+    /// Always go second, drawing on the first turn
+    AlwaysDraw,
+}
+
+impl PlayDraw {
+    fn draw_on_first_turn(self, rng: &mut Rand) -> bool {
+        match self {
+            PlayDraw::Random => rng.gen_bool(PROB_OF_GOING_FIRST),
+            PlayDraw::AlwaysPlay => false,
+            PlayDraw::AlwaysDraw => true,
+        }
+    }
+}
 
 /// Represents the state of the game simulation at a given instant.
 #[derive(Debug, Clone)]
@@ -26,7 +51,14 @@ pub struct State {
     pub game_loss: bool,
     pub turn_state: TurnState,
 
-    pub max_land_drops_per_turn: u32,
+    /// How many cards are drawn for the opening hand
+    pub hand_size: u32,
+    /// How many turns in a row, before this one, the player has not made a land drop
+    pub missed_land_drops: u32,
+    /// How many times each commander has been cast from the command zone, for the commander tax
+    pub commander_cast_count: HashMap<Card, u32>,
+    /// The cards from the deck that began the game on the battlefield, because they are tagged `core:StartInPlay`
+    pub opening_battlefield: Vec<Card>,
 
     // 
     // ZONES
@@ -35,41 +67,106 @@ pub struct State {
     pub hand: Hand,
     pub permanents: Battlefield,
     pub graveyard: Graveyard, 
+    pub exile: Exile,
     pub command_zone: CommandZone,
 }
 
 impl State {
     /// Create a new initial state from the deck. 
-    pub fn new(deck: Deck, rng: &mut Rand) -> State {
-        State {
-            library: deck.deck.to_ordered(rng),
+    pub fn new(deck: Deck, play_draw: PlayDraw, rng: &mut Rand) -> State {
+        let library = deck.deck.to_ordered(rng);
+        let draw_on_first_turn = play_draw.draw_on_first_turn(rng);
+        let mut state = State {
+            library,
             command_zone: deck.command_zone,
 
             hand: Hand::empty(),
             permanents: Battlefield::empty(),
             graveyard: Graveyard::empty(),
+            exile: Exile::empty(),
 
             turn: 0,
-            draw_on_first_turn: rng.gen_bool(PROB_OF_GOING_FIRST),
+            draw_on_first_turn,
             num_mulligans_taken: 0,
             game_loss: false,
-            max_land_drops_per_turn: 1,
+            hand_size: DEFAULT_HAND_SIZE,
+            missed_land_drops: 0,
+            commander_cast_count: HashMap::new(),
+            opening_battlefield: Vec::new(),
             turn_state: TurnState::new(),
+        };
+        state.put_starting_permanents_into_play();
+        state
+    }
+
+    /// Put this state back to how [`State::new`] would make it from the deck, reusing the allocations
+    /// of the zones. Given the same rng, the library is shuffled into the same order.
+    pub fn reset_from(&mut self, deck: &Deck, play_draw: PlayDraw, rng: &mut Rand) {
+        self.library.reset_from(&deck.deck, rng);
+        self.command_zone.clone_from(&deck.command_zone);
+
+        self.hand.clear();
+        self.permanents.clear();
+        self.graveyard.clear();
+        self.exile.clear();
+
+        self.turn = 0;
+        self.draw_on_first_turn = play_draw.draw_on_first_turn(rng);
+        self.num_mulligans_taken = 0;
+        self.game_loss = false;
+        self.hand_size = DEFAULT_HAND_SIZE;
+        self.missed_land_drops = 0;
+        self.commander_cast_count.clear();
+        self.turn_state.reset();
+        self.put_starting_permanents_into_play();
+    }
+
+    /// Moves the cards tagged to start in play from the library onto the battlefield, before the game begins.
+    /// They are neither tapped nor summoning sick on the first turn.
+    fn put_starting_permanents_into_play(&mut self) {
+        let mut starting = std::mem::take(&mut self.opening_battlefield);
+        starting.clear();
+        starting.extend(self.library.iter().filter(|card| card.starts_in_play()));
+        for &card in starting.iter() {
+            self.library.remove(card);
+            self.add_permanent(card);
         }
+        self.opening_battlefield = starting;
+        self.turn_state.reset();
     }
 
     // ===================================================================
     //  Game actions and methods to mutate the state
     // ===================================================================
 
-    /// Draw a hand, decreases as the number of mulligans taken.
+    /// Draw a full hand, usually seven cards. With the London mulligan, cards are bottomed after keeping
+    /// instead of drawing fewer cards.
     pub fn draw_hand(&mut self) {
-        if self.num_mulligans_taken >= HAND_SIZE {
-            log::warn!("taking more mulligans than hand size allowed, ignoring extra mulligans");
-            return;
+        self.hand = self.library.draw_n(self.hand_size as usize).into();
+    }
+
+    /// Move the cards from the hand to the bottom of the library, as in the London mulligan.
+    /// Cards that are not in the hand are ignored.
+    pub fn bottom_cards(&mut self, cards: &[Card]) {
+        for &card in cards {
+            if !self.hand.remove(card) {
+                log::warn!("can not bottom {card:?}, it is not in hand");
+                continue;
+            }
+            self.library.add_to_bottom(&[card]);
+        }
+    }
+
+    /// Move the cards from the hand to the graveyard, as in the cleanup step.
+    /// Cards that are not in the hand are ignored.
+    pub fn discard(&mut self, cards: &[Card]) {
+        for &card in cards {
+            if !self.hand.remove(card) {
+                log::warn!("can not discard {card:?}, it is not in hand");
+                continue;
+            }
+            self.graveyard.add(card);
         }
-        let hand_size = HAND_SIZE - self.num_mulligans_taken;
-        self.hand = self.library.draw_n(hand_size as usize).into();
     }
 
     /// Put the hand into library and shuffle. Hand is now empty.
@@ -80,10 +177,11 @@ impl State {
     }
 
     
-    /// Remove a card from the top of the library and put it into hand.
+    /// Remove a card from the top of the library and put it into hand, returning the card drawn.
     /// Will mark the game loss flag if this draw is impossible.
-    pub fn draw_to_hand(&mut self) {
-        match self.library.draw() {
+    pub fn draw_to_hand(&mut self) -> Option<Card> {
+        let drawn = self.library.draw();
+        match drawn {
             Some(card) => {
                 self.hand.add(card);
             }
@@ -91,6 +189,34 @@ impl State {
                 self.game_loss = true;
             }
         }
+        drawn
+    }
+
+    /// Search the library for a card matching `predicate` and take it out.
+    /// Returns `None` if there is no such card, leaving the library untouched.
+    pub fn fetch_card<F>(&mut self, predicate: F) -> Option<Card>
+    where F: Fn(&Card) -> bool
+    {
+        self.library.take_first(predicate)
+    }
+
+    /// Search the library for a card matching `predicate` and put it into hand.
+    /// Returns `None` if there is no such card, leaving the library untouched.
+    pub fn tutor_to_hand<F>(&mut self, predicate: F) -> Option<Card>
+    where F: Fn(&Card) -> bool
+    {
+        let card = self.fetch_card(predicate)?;
+        self.hand.add(card);
+        Some(card)
+    }
+
+    /// Returns the most recent card to hit the graveyard that matches the predicate to hand.
+    pub fn return_to_hand<F>(&mut self, predicate: F) -> Option<Card>
+    where F: Fn(&Card) -> bool
+    {
+        let card = self.graveyard.take_last(predicate)?;
+        self.hand.add(card);
+        Some(card)
     }
 
     /// Removes a card from wherever.
@@ -100,7 +226,8 @@ impl State {
             Zone::Hand => self.hand.remove(card),
             Zone::Graveyard => self.graveyard.remove(card),
             Zone::Battlefield => self.permanents.remove(card),
-            Zone::Library => todo!("not sure how to remove from library. i.e. which copy are we removing? is it from the top? etc."),
+            Zone::Library => self.library.remove(card),
+            Zone::Exile => self.exile.remove(card),
         };
     }
 
@@ -113,28 +240,67 @@ impl State {
 
     /// Move the card from wherever it came from to wherever it is going.
     pub fn play_card(&mut self, card_play: CardPlay) {
-         let CardPlay { card, zone, payment: _ } = card_play;
+         let CardPlay { card, zone, payment: _, x_value: _, tax: _, tapped } = card_play;
+
+         for source in tapped {
+             self.turn_state.mark_as_tapped(source);
+         }
+
+         if zone == Zone::CommandZone {
+             *self.commander_cast_count.entry(card).or_default() += 1;
+         }
 
          self.remove_from_zone(card, zone);
 
          match card.data().card_type {
+            // spells cast from the graveyard, as with flashback, are exiled instead of going back
+            CardType::Instant  | CardType::Sorcery if zone == Zone::Graveyard => {
+                self.exile.add(card);
+            }
             CardType::Instant  | CardType::Sorcery => {
                 self.graveyard.add(card);
             }
             CardType::Land => {
                 self.turn_state.land_drops_made += 1;
-                if self.turn_state.land_drops_made > self.max_land_drops_per_turn {
-                    log::warn!("ILLEGAL PLAY: played {card:?} as {}th land drop, only {}", self.turn_state.land_drops_made, self.max_land_drops_per_turn);
+                let max_land_drops = self.max_land_drops_per_turn();
+                if self.turn_state.land_drops_made > max_land_drops {
+                    log::warn!("ILLEGAL PLAY: played {card:?} as {}th land drop, only {max_land_drops}", self.turn_state.land_drops_made);
                 }
-                self.permanents.add(card);
+                self.add_permanent(card);
             }
             _ => {
-                self.permanents.add(card);
+                self.add_permanent(card);
             }
         }
     }
 
+    /// Destroy every permanent of the given type, as with a board wipe, putting them into the graveyard.
+    /// Returns how many permanents were destroyed.
+    pub fn destroy_all(&mut self, card_type: CardType) -> usize {
+        let destroyed = self.permanents
+            .iter()
+            .filter(|card| card.data().card_type == card_type)
+            .collect_vec();
+        for &card in destroyed.iter() {
+            self.permanents.remove(card);
+            self.turn_state.tapped.remove(card);
+            self.turn_state.entered.remove(card);
+            self.graveyard.add(card);
+        }
+        destroyed.len()
+    }
+
+    /// Put a card onto the battlefield, tapping it if it enters tapped.
+    pub(crate) fn add_permanent(&mut self, card: Card) {
+        self.permanents.add(card);
+        self.turn_state.entered.add(card);
+        if card.enters_tapped() {
+            self.turn_state.mark_as_tapped(card);
+        }
+    }
+
     pub fn end_turn(&mut self) {
+        self.missed_land_drops = self.missed_land_drops_in_a_row();
         self.turn_state.reset();
         self.turn += 1;
     }
@@ -150,36 +316,110 @@ impl State {
     }
 
 
+    /// The cards that can be played in the main phase.
+    /// Instants and cards with flash are held until the end of the turn instead.
     pub fn legal_card_plays(&self) -> impl Iterator<Item = CardPlay> + '_ {
         let hand = self.hand.iter()
             .filter(|c| c.data().cost.is_some())
+            .filter(|c| !c.is_instant_speed())
             .map(|card| CardPlay {
-                card, zone: Zone::Hand, payment: ManaPool::empty()
+                card, zone: Zone::Hand, payment: ManaPool::empty(), x_value: 0, tax: 0, tapped: vec![]
             });
         let commanders = self.command_zone.iter()
             .map(|card| CardPlay {
-                card, zone: Zone::CommandZone, payment: ManaPool::empty()
+                card, zone: Zone::CommandZone, payment: ManaPool::empty(), x_value: 0, tax: self.commander_tax(card), tapped: vec![]
             });
         
         // TODO: some enforcement here, before we go into the strategies
         hand.chain(commanders)
+            .chain(self.plays_outside_hand(false))
     }
 
+    /// The cards in the graveyard or exile that are tagged to be cast from there, at the given speed
+    fn plays_outside_hand(&self, instant_speed: bool) -> impl Iterator<Item = CardPlay> + '_ {
+        let graveyard = self.graveyard.iter().map(|card| (card, Zone::Graveyard));
+        let exile = self.exile.iter().map(|card| (card, Zone::Exile));
+        graveyard.chain(exile)
+            .filter(|(card, _)| card.data().cost.is_some())
+            .filter(move |(card, _)| card.is_instant_speed() == instant_speed)
+            .filter(|(card, zone)| card.can_cast_from(*zone))
+            .map(|(card, zone)| CardPlay {
+                card, zone, payment: ManaPool::empty(), x_value: 0, tax: 0, tapped: vec![]
+            })
+    }
+
+    /// The extra generic mana it costs to cast this commander from the command zone,
+    /// two for each time it has already been cast from there.
+    pub fn commander_tax(&self, card: Card) -> u8 {
+        let count = self.commander_cast_count.get(&card).copied().unwrap_or(0);
+        (2 * count).try_into().unwrap_or(u8::MAX)
+    }
+
+    /// The cards that can be played at instant speed, at the end of the turn.
+    pub fn legal_instant_plays(&self) -> impl Iterator<Item = CardPlay> + '_ {
+        self.hand.iter()
+            .filter(|c| c.data().cost.is_some())
+            .filter(|c| c.is_instant_speed())
+            .map(|card| CardPlay {
+                card, zone: Zone::Hand, payment: ManaPool::empty(), x_value: 0, tax: 0, tapped: vec![]
+            })
+            .chain(self.plays_outside_hand(true))
+    }
+
+    /// The lands in hand that can be played, or none if we have used up our land drops this turn.
     pub fn legal_land_drops(&self) -> impl Iterator<Item = CardPlay> + use<'_> {
+        let has_land_drop = self.turn_state.land_drops_made < self.max_land_drops_per_turn();
         let hand = self.hand
             .iter()
+            .filter(move |_| has_land_drop)
             .filter(|c| c.data().card_type == CardType::Land)
             .unique_by(|c| c.name())
             .map(|card| CardPlay {
-                card, zone: Zone::Hand, payment: ManaPool::empty()
+                card, zone: Zone::Hand, payment: ManaPool::empty(), x_value: 0, tax: 0, tapped: vec![]
             });
         hand
     }
 
+    /// The permanents that can be tapped for mana right now.
+    /// Creatures that entered this turn are summoning sick, and can not tap for mana yet.
     pub fn mana_sources(&self) -> impl Iterator<Item = ManaSource> + use<'_> {
+        self.permanents_excluding(|card| self.turn_state.num_tapped(card) + self.num_summoning_sick(card))
+            .filter_map(ManaSource::try_from)
+    }
+
+    /// Like [`State::mana_sources`], but as if nothing had been tapped yet this turn.
+    /// Useful for asking what could have been cast this turn, after the turn is over.
+    pub fn mana_sources_before_tapping(&self) -> impl Iterator<Item = ManaSource> + use<'_> {
+        self.permanents_excluding(|card| self.num_summoning_sick(card))
+            .filter_map(ManaSource::try_from)
+    }
+
+    /// How many copies of this card can not tap for mana because they are creatures that entered this turn.
+    fn num_summoning_sick(&self, card: Card) -> usize {
+        match card.data().card_type {
+            CardType::Creature => self.turn_state.num_entered(card),
+            _ => 0,
+        }
+    }
+
+    /// The permanents which are not tapped.
+    /// Copies of a card are interchangeable, so if some copies are tapped, the rest are still untapped.
+    pub fn untapped_permanents(&self) -> impl Iterator<Item = Card> + use<'_> {
+        self.permanents_excluding(|card| self.turn_state.num_tapped(card))
+    }
+
+    /// The permanents, leaving out `num_excluded(card)` copies of each card.
+    fn permanents_excluding<F>(&self, num_excluded: F) -> impl Iterator<Item = Card> + use<'_, F>
+    where F: Fn(Card) -> usize
+    {
+        let mut copies_seen: HashMap<Card, usize> = HashMap::new();
         self.permanents
             .iter()
-            .filter_map(ManaSource::try_from)
+            .filter(move |card| {
+                let seen = copies_seen.entry(*card).or_default();
+                *seen += 1;
+                *seen > num_excluded(*card)
+            })
     }
     
     // ===========================================
@@ -190,27 +430,59 @@ impl State {
     /// How much mana does the player theoretically have access to?
     /// Note: this should **not** be used for making game decisions, it's merely a heuristic.
     pub fn available_mana(&self) -> u8 {
+        self.mana_sources()
+            .map(|mana_source| mana_source.highest_mana_value())
+            .sum()
+    }
+
+    /// Which types of mana the untapped mana sources could make, counting colorless as its own type.
+    /// This does not mean they can all be made at once, but a spell needing any other color can not be cast.
+    pub fn producible_colors(&self) -> HashSet<ManaType> {
+        colors_produced_by(self.mana_sources())
+    }
+
+    /// Like [`State::producible_colors`], but counting every permanent, even if it is tapped or summoning sick.
+    pub fn colors_on_battlefield(&self) -> HashSet<ManaType> {
+        colors_produced_by(self.permanents.iter().filter_map(ManaSource::try_from))
+    }
+
+    /// How many lands may be played each turn: one, and more from permanents like Exploration
+    pub fn max_land_drops_per_turn(&self) -> u32 {
+        1 + self.permanents
+            .iter()
+            .map(|card| card.extra_land_drops())
+            .sum::<u32>()
+    }
+
+    /// Cards drawn each turn on top of the usual one, from permanents like Howling Mine
+    pub fn extra_draws_per_turn(&self) -> u32 {
         self.permanents
             .iter()
-            .filter_map(ManaSource::try_from)
-            .map(|mana_source| mana_source.highest_mana_value())
+            .map(|card| card.extra_draws())
             .sum()
     }
 
     /// How many lands does the player have in hand?
     pub fn num_lands_in_hand(&self) -> usize {
-        self.hand
-            .iter()
-            .filter(|c| c.data().card_type == CardType::Land)
-            .count()
+        self.hand.count_where(|c| c.data().card_type == CardType::Land)
+    }
+
+    /// How many nonland cards does the player have in hand?
+    pub fn num_spells_in_hand(&self) -> usize {
+        self.hand.size() - self.num_lands_in_hand()
+    }
+
+    /// How many turns in a row, including this one, has the player not made a land drop?
+    pub fn missed_land_drops_in_a_row(&self) -> u32 {
+        match self.turn_state.land_drops_made {
+            0 => self.missed_land_drops + 1,
+            _ => 0,
+        }
     }
 
     /// How many lands does the player have in play?
     pub fn num_lands_in_play(&self) -> usize {
-        self.permanents
-            .iter()
-            .filter(|c| c.data().card_type == CardType::Land)
-            .count()
+        self.permanents.count_where(|c| c.data().card_type == CardType::Land)
     }
 }
 
@@ -219,6 +491,8 @@ impl State {
 pub struct TurnState {
     pub land_drops_made: u32,
     pub tapped: UnorderedPile,
+    /// The permanents that entered the battlefield this turn
+    pub entered: UnorderedPile,
 }
 
 
@@ -227,20 +501,444 @@ impl TurnState {
         TurnState {
             land_drops_made: 0,
             tapped: UnorderedPile::empty(),
+            entered: UnorderedPile::empty(),
         }
     }
     pub fn reset(&mut self) {
         self.land_drops_made = 0;
         self.tapped.clear();
+        self.entered.clear();
     }
+    /// Tap a copy of this card. Several copies of the same card may be tapped at once.
     pub fn mark_as_tapped(&mut self, card: Card) {
-        if self.is_tapped(card) {
-            log::error!("card is already tapped: {card:?}");
-            return;
-        }
         self.tapped.add(card);
     }
-    pub fn is_tapped(&mut self, card: Card) -> bool {
-        self.tapped.iter().contains(&card)
+    pub fn is_tapped(&self, card: Card) -> bool {
+        self.tapped.contains(card)
+    }
+    /// How many copies of this card are tapped
+    pub fn num_tapped(&self, card: Card) -> usize {
+        self.tapped.count_of(card)
+    }
+    /// How many copies of this card entered the battlefield this turn
+    pub fn num_entered(&self, card: Card) -> usize {
+        self.entered.count_of(card)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::SeedableRng;
+
+    use crate::collection::fixtures;
+
+    use super::*;
+
+    fn sample_state() -> State {
+        let mut deck = UnorderedPile::empty();
+        deck.add_copies(fixtures::card_named("Forest"), 20);
+        deck.add_copies(fixtures::card_named("Hill Giant"), 20);
+        let deck = Deck {
+            command_zone: CommandZone::empty(),
+            deck,
+        };
+        State::new(deck, PlayDraw::Random, &mut Rand::seed_from_u64(0))
+    }
+
+    #[test]
+    fn test_producible_colors_mono_green() {
+        let mut state = sample_state();
+        state.permanents.add(fixtures::card_named("Forest"));
+        state.permanents.add(fixtures::card_named("Forest"));
+        state.permanents.add(fixtures::card_named("Hill Giant"));
+
+        assert_eq!(state.producible_colors(), HashSet::from([ManaType::Green]));
+    }
+
+    #[test]
+    fn test_producible_colors_with_dual() {
+        let mut state = sample_state();
+        state.permanents.add(fixtures::card_named("Forest"));
+        state.permanents.add(fixtures::card_named("Rugged Highlands"));
+
+        assert_eq!(state.producible_colors(), HashSet::from([ManaType::Red, ManaType::Green]));
+
+        state.turn_state.mark_as_tapped(fixtures::card_named("Rugged Highlands"));
+        assert_eq!(state.producible_colors(), HashSet::from([ManaType::Green]));
+        assert_eq!(state.colors_on_battlefield(), HashSet::from([ManaType::Red, ManaType::Green]));
+    }
+
+    #[test]
+    fn test_hand_size_is_seven_after_mulligan() {
+        let mut state = sample_state();
+        let mut rng = Rand::seed_from_u64(1);
+
+        state.draw_hand();
+        assert_eq!(state.hand.size(), 7);
+
+        for _ in 0..3 {
+            state.shuffle_hand_into_library(&mut rng);
+            state.num_mulligans_taken += 1;
+            state.draw_hand();
+            assert_eq!(state.hand.size(), 7);
+        }
+        assert_eq!(state.library.size(), 33);
+    }
+
+    #[test]
+    fn test_bottom_cards() {
+        let mut state = sample_state();
+        state.draw_hand();
+
+        let to_bottom = state.hand.iter().take(2).collect_vec();
+        state.bottom_cards(&to_bottom);
+
+        assert_eq!(state.hand.size(), 5);
+        assert_eq!(state.library.size(), 35);
+        assert_eq!(state.library.iter().take(2).collect_vec(), to_bottom);
+    }
+
+    fn play_land(state: &mut State, name: &str) {
+        let card = fixtures::card_named(name);
+        state.hand.add(card);
+        state.play_card(CardPlay { card, zone: Zone::Hand, payment: ManaPool::empty(), x_value: 0, tax: 0, tapped: vec![] });
+    }
+
+    #[test]
+    fn test_enters_tapped_land_has_no_mana_on_first_turn() {
+        let mut state = sample_state();
+
+        play_land(&mut state, "Rugged Highlands");
+        assert_eq!(state.available_mana(), 0);
+        assert_eq!(state.mana_sources().count(), 0);
+
+        state.end_turn();
+        assert_eq!(state.available_mana(), 1);
+        assert_eq!(state.mana_sources().count(), 1);
+    }
+
+    #[test]
+    fn test_untapped_land_has_mana_immediately() {
+        let mut state = sample_state();
+
+        play_land(&mut state, "Forest");
+        assert_eq!(state.available_mana(), 1);
+    }
+
+    #[test]
+    fn test_only_new_copy_enters_tapped() {
+        let mut state = sample_state();
+
+        play_land(&mut state, "Rugged Highlands");
+        state.end_turn();
+        play_land(&mut state, "Rugged Highlands");
+
+        assert_eq!(state.permanents.size(), 2);
+        assert_eq!(state.available_mana(), 1);
+    }
+
+    #[test]
+    fn test_tapped_sources_are_not_reused() {
+        let mut state = sample_state();
+        play_land(&mut state, "Forest");
+        let sol_ring = fixtures::card_named("Sol Ring");
+        state.hand.add(sol_ring);
+
+        state.play_card(CardPlay { card: sol_ring, zone: Zone::Hand, payment: ManaPool::green(1), x_value: 0, tax: 0, tapped: vec![fixtures::card_named("Forest")] });
+
+        assert_eq!(state.available_mana(), 2); // the sol ring, but not the forest
+        assert!(state.mana_sources().all(|source| source.card == sol_ring));
+    }
+
+    fn play_spell(state: &mut State, name: &str) {
+        let card = fixtures::card_named(name);
+        state.hand.add(card);
+        state.play_card(CardPlay { card, zone: Zone::Hand, payment: ManaPool::empty(), x_value: 0, tax: 0, tapped: vec![] });
+    }
+
+    #[test]
+    fn test_mana_dork_is_summoning_sick() {
+        let mut state = sample_state();
+        play_land(&mut state, "Forest");
+        play_spell(&mut state, "Llanowar Elves");
+
+        assert_eq!(state.available_mana(), 1);
+
+        state.end_turn();
+        assert_eq!(state.available_mana(), 2);
+
+        // a second copy is sick, but the first is not
+        play_spell(&mut state, "Llanowar Elves");
+        assert_eq!(state.available_mana(), 2);
+        state.end_turn();
+        assert_eq!(state.available_mana(), 3);
+    }
+
+    #[test]
+    fn test_mana_sources_before_tapping() {
+        let mut state = sample_state();
+        play_land(&mut state, "Forest");
+        play_land(&mut state, "Forest");
+        let forest = fixtures::card_named("Forest");
+        let elves = fixtures::card_named("Llanowar Elves");
+        state.hand.add(elves);
+        state.play_card(CardPlay { card: elves, zone: Zone::Hand, payment: ManaPool::green(1), x_value: 0, tax: 0, tapped: vec![forest] });
+
+        assert_eq!(state.mana_sources().count(), 1);
+        // the tapped forest still counts, but the elves are summoning sick either way
+        assert_eq!(state.mana_sources_before_tapping().count(), 2);
+    }
+
+    #[test]
+    fn test_artifacts_are_not_summoning_sick() {
+        let mut state = sample_state();
+        play_spell(&mut state, "Sol Ring");
+
+        assert_eq!(state.available_mana(), 2);
+    }
+
+    #[test]
+    fn test_extra_draws_follow_permanents() {
+        let mut state = sample_state();
+        play_spell(&mut state, "Howling Mine");
+        play_spell(&mut state, "Howling Mine");
+
+        assert_eq!(state.extra_draws_per_turn(), 2);
+
+        state.destroy_all(CardType::Artifact);
+        assert_eq!(state.extra_draws_per_turn(), 0);
+    }
+
+    #[test]
+    fn test_extra_draws_count_permanents_however_they_move() {
+        let mut state = sample_state();
+        let howling_mine = fixtures::card_named("Howling Mine");
+
+        state.permanents.add(howling_mine);
+        assert_eq!(state.extra_draws_per_turn(), 1);
+
+        state.remove_from_zone(howling_mine, Zone::Battlefield);
+        assert_eq!(state.extra_draws_per_turn(), 0);
+    }
+
+    #[test]
+    fn test_extra_land_drops_follow_permanents() {
+        let mut state = sample_state();
+        state.hand.add(fixtures::card_named("Forest"));
+        state.hand.add(fixtures::card_named("Mountain"));
+        state.hand.add(fixtures::card_named("Island"));
+
+        play_land(&mut state, "Forest");
+        assert_eq!(state.legal_land_drops().count(), 0);
+
+        play_spell(&mut state, "Exploration");
+        assert_eq!(state.max_land_drops_per_turn(), 2);
+        assert_eq!(state.legal_land_drops().count(), 3);
+
+        state.destroy_all(CardType::Enchantment);
+        assert_eq!(state.max_land_drops_per_turn(), 1);
+        assert_eq!(state.legal_land_drops().count(), 0);
+    }
+
+    #[test]
+    fn test_extra_land_drops_count_permanents_however_they_move() {
+        let mut state = sample_state();
+        let exploration = fixtures::card_named("Exploration");
+
+        state.permanents.add(exploration);
+        assert_eq!(state.max_land_drops_per_turn(), 2);
+
+        state.remove_from_zone(exploration, Zone::Battlefield);
+        assert_eq!(state.max_land_drops_per_turn(), 1);
+    }
+
+    #[test]
+    fn test_play_card_from_library() {
+        let mut state = sample_state();
+        let library_size = state.library.size();
+        let giant = fixtures::card_named("Hill Giant");
+
+        state.play_card(CardPlay { card: giant, zone: Zone::Library, payment: ManaPool::empty(), x_value: 0, tax: 0, tapped: vec![] });
+
+        assert_eq!(state.library.size(), library_size - 1);
+        assert!(state.permanents.contains(giant));
+    }
+
+    #[test]
+    fn test_destroy_all_creatures() {
+        let mut state = sample_state();
+        play_land(&mut state, "Forest");
+        play_spell(&mut state, "Llanowar Elves");
+        play_spell(&mut state, "Llanowar Elves");
+        play_spell(&mut state, "Sol Ring");
+        let lands_before = state.num_lands_in_play();
+
+        let destroyed = state.destroy_all(CardType::Creature);
+
+        assert_eq!(destroyed, 2);
+        assert_eq!(state.num_lands_in_play(), lands_before);
+        assert_eq!(state.permanents.count_where(|c| c.data().card_type == CardType::Creature), 0);
+        assert_eq!(state.graveyard.count_of(fixtures::card_named("Llanowar Elves")), 2);
+        assert_eq!(state.permanents.size(), 2);
+    }
+
+    #[test]
+    fn test_bottom_cards_not_in_hand() {
+        let mut state = sample_state();
+        state.draw_hand();
+
+        state.bottom_cards(&[fixtures::card_named("Lightning Bolt")]);
+
+        assert_eq!(state.hand.size(), 7);
+        assert_eq!(state.library.size(), 33);
+    }
+
+    #[test]
+    fn test_commander_tax_increases_with_each_cast() {
+        let mut state = sample_state();
+        let giant = fixtures::card_named("Hill Giant");
+        state.command_zone.add(giant);
+
+        let first = state.legal_card_plays().find(|play| play.zone == Zone::CommandZone).expect("commander should be castable");
+        assert_eq!(first.tax, 0);
+        assert_eq!(first.cost().map(|cost| cost.mana_value()), Some(4));
+
+        state.play_card(first);
+        assert!(state.permanents.contains(giant));
+        assert_eq!(state.commander_cast_count.get(&giant), Some(&1));
+
+        // the commander dies, and goes back to the command zone
+        state.permanents.remove(giant);
+        state.command_zone.add(giant);
+
+        let second = state.legal_card_plays().find(|play| play.zone == Zone::CommandZone).expect("commander should be castable");
+        assert_eq!(second.tax, 2);
+        assert_eq!(second.cost().map(|cost| cost.mana_value()), Some(6));
+    }
+
+    #[test]
+    fn test_commander_tax_does_not_apply_from_hand() {
+        let mut state = sample_state();
+        let giant = fixtures::card_named("Hill Giant");
+        state.commander_cast_count.insert(giant, 3);
+        state.hand.add(giant);
+
+        let play = state.legal_card_plays().find(|play| play.card == giant).expect("giant should be castable");
+        assert_eq!(play.tax, 0);
+        assert_eq!(state.commander_tax(giant), 6);
+    }
+
+    #[test]
+    fn test_flashback_from_the_graveyard_then_exile() {
+        let mut state = sample_state();
+        let looting = fixtures::card_named("Faithless Looting");
+        state.graveyard.add(looting);
+
+        let flashback = state.legal_card_plays().find(|play| play.card == looting).expect("flashback should be offered");
+        assert_eq!(flashback.zone, Zone::Graveyard);
+
+        state.play_card(flashback);
+        assert_eq!(state.graveyard.count_of(looting), 0);
+        assert_eq!(state.exile.count_of(looting), 1);
+        // it is only tagged to be cast from the graveyard, so it is gone for good
+        assert!(state.legal_card_plays().all(|play| play.card != looting));
+    }
+
+    #[test]
+    fn test_untagged_cards_are_not_cast_from_the_graveyard() {
+        let mut state = sample_state();
+        state.graveyard.add(fixtures::card_named("Hill Giant"));
+        state.graveyard.add(fixtures::card_named("Lightning Bolt"));
+
+        assert!(state.legal_card_plays().chain(state.legal_instant_plays()).all(|play| play.zone != Zone::Graveyard));
+    }
+
+    #[test]
+    fn test_casting_from_hand_still_goes_to_the_graveyard() {
+        let mut state = sample_state();
+        let looting = fixtures::card_named("Faithless Looting");
+        state.hand.add(looting);
+
+        let cast = state.legal_card_plays().find(|play| play.card == looting).expect("looting should be castable from hand");
+        assert_eq!(cast.zone, Zone::Hand);
+        state.play_card(cast);
+
+        assert_eq!(state.graveyard.count_of(looting), 1);
+        assert_eq!(state.legal_card_plays().filter(|play| play.card == looting).count(), 1);
+    }
+
+    fn deck_with_leylines() -> Deck {
+        let mut deck = UnorderedPile::empty();
+        deck.add_copies(fixtures::card_named("Forest"), 20);
+        deck.add_copies(fixtures::card_named("Leyline of Abundance"), 2);
+        Deck { command_zone: CommandZone::empty(), deck }
+    }
+
+    #[test]
+    fn test_start_in_play_cards_begin_on_the_battlefield() {
+        let leyline = fixtures::card_named("Leyline of Abundance");
+        let state = State::new(deck_with_leylines(), PlayDraw::Random, &mut Rand::seed_from_u64(0));
+
+        assert_eq!(state.opening_battlefield, vec![leyline, leyline]);
+        assert_eq!(state.permanents.iter().collect_vec(), vec![leyline, leyline]);
+        assert!(state.library.iter().all(|card| card != leyline));
+        assert_eq!(state.library.size(), 20);
+        // they have been there since before the game, so they are ready on the first turn
+        assert_eq!(state.untapped_permanents().count(), 2);
+        assert_eq!(state.turn_state.num_entered(leyline), 0);
+    }
+
+    #[test]
+    fn test_reset_puts_start_in_play_cards_back_on_the_battlefield() {
+        let leyline = fixtures::card_named("Leyline of Abundance");
+        let deck = deck_with_leylines();
+        let mut state = State::new(deck.clone(), PlayDraw::Random, &mut Rand::seed_from_u64(0));
+        state.destroy_all(CardType::Enchantment);
+
+        state.reset_from(&deck, PlayDraw::Random, &mut Rand::seed_from_u64(3));
+        let fresh = State::new(deck, PlayDraw::Random, &mut Rand::seed_from_u64(3));
+
+        assert_eq!(state.permanents.iter().collect_vec(), vec![leyline, leyline]);
+        assert_eq!(state.library.iter().collect_vec(), fresh.library.iter().collect_vec());
+        assert_eq!(state.graveyard.size(), 0);
+    }
+
+    #[test]
+    fn test_reset_from_matches_new() {
+        let mut deck = UnorderedPile::empty();
+        deck.add_copies(fixtures::card_named("Forest"), 20);
+        deck.add_copies(fixtures::card_named("Hill Giant"), 20);
+        let mut command_zone = CommandZone::empty();
+        command_zone.add(fixtures::card_named("Colossal Dreadmaw"));
+        let deck = Deck { command_zone, deck };
+
+        // play out a few turns, so that every zone has something to clear
+        let mut state = State::new(deck.clone(), PlayDraw::Random, &mut Rand::seed_from_u64(1));
+        state.draw_hand();
+        play_land(&mut state, "Forest");
+        play_spell(&mut state, "Howling Mine");
+        play_spell(&mut state, "Exploration");
+        state.graveyard.add(fixtures::card_named("Hill Giant"));
+        let dreadmaw = state.legal_card_plays().find(|play| play.zone == Zone::CommandZone).expect("commander should be castable");
+        state.play_card(dreadmaw);
+        state.hand_size = 6;
+        state.end_turn();
+
+        state.reset_from(&deck, PlayDraw::Random, &mut Rand::seed_from_u64(2));
+        let fresh = State::new(deck, PlayDraw::Random, &mut Rand::seed_from_u64(2));
+
+        assert_eq!(state.library.iter().collect_vec(), fresh.library.iter().collect_vec());
+        assert_eq!(state.command_zone.iter().collect_vec(), fresh.command_zone.iter().collect_vec());
+        assert_eq!(state.draw_on_first_turn, fresh.draw_on_first_turn);
+        assert_eq!(state.hand.size(), 0);
+        assert_eq!(state.permanents.size(), 0);
+        assert_eq!(state.graveyard.size(), 0);
+        assert_eq!(state.turn, 0);
+        assert_eq!(state.hand_size, DEFAULT_HAND_SIZE);
+        assert_eq!(state.max_land_drops_per_turn(), 1);
+        assert_eq!(state.extra_draws_per_turn(), 0);
+        assert_eq!(state.missed_land_drops, 0);
+        assert!(state.commander_cast_count.is_empty());
+        assert_eq!(state.turn_state.land_drops_made, 0);
     }
 }
@@ -33,6 +33,34 @@ impl UnorderedPile {
     pub fn size(&self) -> usize {
         self.cards.len()
     }
+    /// Counts the cards in this pile matching `predicate`, including every copy.
+    pub fn count_where<F>(&self, predicate: F) -> usize
+    where F: Fn(&Card) -> bool
+    {
+        self.cards
+            .iter()
+            .filter(|card| predicate(card))
+            .count()
+    }
+    /// Counts the copies of `card` in this pile.
+    /// ```
+    /// use deck_optim::game::UnorderedPile;
+    /// use deck_optim::collection::get_sample_cards;
+    ///
+    /// let cards = get_sample_cards(2);
+    /// let mut pile = UnorderedPile::empty();
+    /// pile.add_copies(cards[0], 3);
+    ///
+    /// assert_eq!(pile.count_of(cards[0]), 3);
+    /// assert_eq!(pile.count_of(cards[1]), 0);
+    /// ```
+    pub fn count_of(&self, card: Card) -> usize {
+        self.count_where(|c| *c == card)
+    }
+    /// Is there at least one copy of `card` in this pile?
+    pub fn contains(&self, card: Card) -> bool {
+        self.cards.contains(&card)
+    }
     pub fn clear(&mut self) {
         self.cards.clear()
     }
@@ -66,6 +94,13 @@ impl UnorderedPile {
         self.cards.swap_remove(idx);
         true
     }
+    /// Removes the most recently added card that matches the predicate, keeping the rest in order
+    pub fn take_last<F>(&mut self, predicate: F) -> Option<Card>
+    where F: Fn(&Card) -> bool
+    {
+        let idx = self.cards.iter().rposition(predicate)?;
+        Some(self.cards.remove(idx))
+    }
 }
 
 impl From<Vec<Card>> for UnorderedPile {
@@ -101,4 +136,32 @@ mod tests {
         pile.add_copies(get_sample_cards(10)[0], 12);
         assert_eq!(pile.size(), 12);
     }
+
+    #[test]
+    fn test_counting_copies() {
+        let cards = get_sample_cards(3);
+        let mut pile = UnorderedPile::empty();
+        pile.add_copies(cards[0], 4);
+        pile.add(cards[1]);
+
+        assert_eq!(pile.count_of(cards[0]), 4);
+        assert_eq!(pile.count_of(cards[1]), 1);
+        assert_eq!(pile.count_of(cards[2]), 0);
+        assert_eq!(pile.count_where(|c| *c != cards[1]), 4);
+        assert!(pile.contains(cards[0]));
+        assert!(!pile.contains(cards[2]));
+
+        pile.remove(cards[0]);
+        assert_eq!(pile.count_of(cards[0]), 3);
+    }
+
+    #[test]
+    fn test_counting_empty_pile() {
+        let cards = get_sample_cards(1);
+        let pile = UnorderedPile::empty();
+
+        assert_eq!(pile.count_of(cards[0]), 0);
+        assert_eq!(pile.count_where(|_| true), 0);
+        assert!(!pile.contains(cards[0]));
+    }
 }
@@ -5,6 +5,7 @@ pub mod strategies;
 pub mod metrics;
 pub mod deck;
 pub mod experiment;
+pub mod analytics;
 
 pub mod collection;
 pub use collection::card_cache;
@@ -14,6 +15,9 @@ pub mod scryfall;
 pub mod file_utils;
 pub mod opt_utils;
 
+#[cfg(test)]
+mod test_server;
+
 pub const PROJECT_NAME: &'static str = "deck-optim-0.1.0";
 
 pub use collection::init;
@@ -1,5 +1,8 @@
 use std::{default, fmt};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
+use std::ops::Range;
+
+use itertools::Itertools;
 
 use crate::collection::Card;
 
@@ -9,7 +12,9 @@ pub type Uint = u32;
 pub struct MetricsKey {
     metrics_name: &'static str,
     card: Option<Card>,
-    turn_num: Option<u32>
+    turn_num: Option<u32>,
+    mana_value: Option<u8>,
+    bucket: Option<u32>,
 }
 impl MetricsKey {
     pub fn new(metrics_name: &'static str) -> Self {
@@ -17,6 +22,8 @@ impl MetricsKey {
             metrics_name,
             card: None,
             turn_num: None,
+            mana_value: None,
+            bucket: None,
         }
     }
     pub fn card(mut self, card: Card) -> Self {
@@ -27,6 +34,20 @@ impl MetricsKey {
         self.turn_num = Some(turn_num);
         self
     }
+    /// Buckets this key by a mana value, for looking at the curve
+    pub fn mana_value(mut self, mana_value: u8) -> Self {
+        self.mana_value = Some(mana_value);
+        self
+    }
+    /// Buckets this key by an exact count, for looking at a distribution, such as how many lands are in the opening hand
+    pub fn bucket(mut self, bucket: u32) -> Self {
+        self.bucket = Some(bucket);
+        self
+    }
+    /// The turn this key is for, if it is keyed by turn
+    pub fn turn(&self) -> Option<u32> {
+        self.turn_num
+    }
 }
 
 impl From<&'static str> for MetricsKey {
@@ -44,17 +65,25 @@ impl fmt::Display for MetricsKey {
         if let Some(x) = self.turn_num {
             write!(f, "::{x}")?;
         }
+        if let Some(x) = self.mana_value {
+            write!(f, "::cmc{x}")?;
+        }
+        if let Some(x) = self.bucket {
+            write!(f, "::#{x}")?;
+        }
 
         Ok(())
     }
 }
 
 /// The metrics that are being tracked for a particular key.
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct Metrics {
     total: Uint,
-    min: Uint,
-    max: Uint,
+    /// The smallest contribution of any single trial, `None` until a value is seen
+    min: Option<Uint>,
+    /// The largest contribution of any single trial, `None` until a value is seen
+    max: Option<Uint>,
     trials_seen: Uint,
     count: Uint
 }
@@ -62,24 +91,26 @@ impl default::Default for Metrics {
     fn default() -> Self {
         Self {
             total: 0,
-            min: 0,
-            max: 0,
+            min: None,
+            max: None,
             trials_seen: 1,
             count: 0
         }
     }
 }
 impl Metrics {
+    /// Adds to the running total of this trial.
+    /// Until this is merged with other trials, the trial's contribution is its total.
     pub fn update_add(&mut self, value: Uint) {
         self.total += value;
-        self.min += value;
-        self.max += value;
+        self.min = Some(self.total);
+        self.max = Some(self.total);
         self.count += 1;
     }
     pub fn update_set(&mut self, value: Uint) {
         self.total = value;
-        self.min = value;
-        self.max = value;
+        self.min = Some(value);
+        self.max = Some(value);
         self.count += 1;
     }
 
@@ -90,23 +121,53 @@ impl Metrics {
         let Metrics { total, min, max, trials_seen, count } = other;
 
         self.total += total;
-        self.min = std::cmp::min(self.min, min);
-        self.max = std::cmp::max(self.max, max);
+        self.min = combine(self.min, min, std::cmp::min);
+        self.max = combine(self.max, max, std::cmp::max);
         self.trials_seen += trials_seen;
         self.count += count;
     }
 
+    pub fn total(&self) -> Uint {
+        self.total
+    }
+
+    /// The number of trials that contributed to this metric
+    pub fn trials_seen(&self) -> Uint {
+        self.trials_seen
+    }
+
+    /// The smallest contribution of any single trial, or zero if nothing was recorded
+    pub fn min(&self) -> Uint {
+        self.min.unwrap_or(0)
+    }
+
+    /// The largest contribution of any single trial, or zero if nothing was recorded
+    pub fn max(&self) -> Uint {
+        self.max.unwrap_or(0)
+    }
+
     /// Averages this metrics key accross all of the trials it has seen
     pub fn average(&self) -> f32 {
         self.total as f32 / self.trials_seen as f32
     }
 }
 
-#[derive(Debug)]
+/// Combines two optional values, only using `f` when both are present
+fn combine<F: FnOnce(Uint, Uint) -> Uint>(left: Option<Uint>, right: Option<Uint>, f: F) -> Option<Uint> {
+    match (left, right) {
+        (Some(l), Some(r)) => Some(f(l, r)),
+        (l, r) => l.or(r),
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
 /// Keep track of the metrics data for all keys.
 pub struct MetricsData {
     pub(crate) trials_seen: Uint,
     metrics: HashMap<MetricsKey, Metrics>,
+    /// Every per-trial value of each key.
+    /// Only recorded when created with [`MetricsData::with_distributions`], since it can get large.
+    distributions: Option<HashMap<MetricsKey, Vec<Uint>>>,
 }
 
 impl MetricsData {
@@ -121,6 +182,29 @@ impl MetricsData {
         Self {
             trials_seen: 0,
             metrics: HashMap::new(),
+            distributions: None,
+        }
+    }
+
+    /// Creates an empty metrics data that also records the value of every key in every trial,
+    /// so that percentiles and histograms can be computed.
+    pub fn with_distributions() -> Self {
+        Self {
+            distributions: Some(HashMap::new()),
+            ..Self::empty()
+        }
+    }
+
+    /// Marks the end of a single trial.
+    /// If distributions are being recorded, the value of every key in this trial is saved.
+    pub fn end_trial(&mut self) {
+        self.trials_seen += 1;
+        if let Some(distributions) = self.distributions.as_mut() {
+            for (key, metrics) in self.metrics.iter() {
+                distributions.entry(*key)
+                    .or_default()
+                    .push(metrics.total);
+            }
         }
     }
 
@@ -191,6 +275,20 @@ impl MetricsData {
         }
     }
 
+    /// Removes a key, so it is left out of the report
+    /// ```
+    /// use deck_optim::metrics::MetricsData;
+    ///
+    /// let mut metrics = MetricsData::empty();
+    /// metrics.set("seen a cat this game", 1);
+    ///
+    /// metrics.remove("seen a cat this game");
+    /// assert_eq!(metrics.total("seen a cat this game"), 0);
+    /// ```
+    pub fn remove<K: Into<MetricsKey>>(&mut self, key: K) {
+        self.metrics.remove(&key.into());
+    }
+
     /// Joins two metrics together
     /// ```
     /// use deck_optim::metrics::MetricsData;
@@ -208,27 +306,51 @@ impl MetricsData {
     /// assert_eq!(joined.total("cats"), 7);
     /// assert_eq!(joined.total("dogs"), 1);
     ///
+    /// assert_eq!(joined.min("cats"), 2);
+    /// assert_eq!(joined.max("cats"), 5);
+    /// ```
+    pub fn join(mut left: Self, mut right: Self) -> Self {
+        left.absorb(&mut right);
+        left
+    }
+
+    /// Moves everything recorded in `other` into this, the same as [`MetricsData::join`].
+    /// `other` is left empty but keeps its memory, so that it can be reused for the next trial.
     /// ```
-    pub fn join(mut left: Self, right: Self) -> Self {
+    /// use deck_optim::metrics::MetricsData;
+    ///
+    /// let mut total = MetricsData::empty();
+    /// let mut trial = MetricsData::empty();
+    /// trial.add_count("cats", 2);
+    ///
+    /// total.absorb(&mut trial);
+    /// assert_eq!(total.total("cats"), 2);
+    /// assert_eq!(trial.total("cats"), 0);
+    /// ```
+    pub fn absorb(&mut self, other: &mut Self) {
         use std::collections::hash_map::Entry::Occupied;
         use std::collections::hash_map::Entry::Vacant;
 
-        left.trials_seen += right.trials_seen;
-        left.metrics.reserve(right.metrics.len());
-        for (key, metrics) in right.metrics.into_iter() {
-
-            // We do not want to merge in empty metrics, that messes up `min` as it defaults to zero
-            // (this could also be fixed by using an Optional for min and max)
-            match left.metrics.entry(key) {
-                Occupied(mut left) => {
-                    left.get_mut().merge_in(metrics);
+        self.trials_seen += std::mem::take(&mut other.trials_seen);
+        self.metrics.reserve(other.metrics.len());
+        for (key, metrics) in other.metrics.drain() {
+            match self.metrics.entry(key) {
+                Occupied(mut entry) => {
+                    entry.get_mut().merge_in(metrics);
                 }
-                Vacant(left) => {
-                    left.insert(metrics);
+                Vacant(entry) => {
+                    entry.insert(metrics);
                 }
             }
         }
-        left
+        if let Some(other_distributions) = other.distributions.as_mut() {
+            let distributions = self.distributions.get_or_insert_with(HashMap::new);
+            for (key, samples) in other_distributions.drain() {
+                distributions.entry(key)
+                    .or_default()
+                    .extend(samples);
+            }
+        }
     }
        pub fn keys(&self) -> impl Iterator<Item = MetricsKey> + '_ {
         self.metrics.keys().copied()
@@ -238,6 +360,21 @@ impl MetricsData {
         self.trials_seen 
     }
 
+    /// Finds the key that displays as `name`, such as `"land-drops"` or `"mana_on_turn::3"`.
+    /// Useful when the metric is chosen at runtime, since keys are built from static names.
+    /// ```
+    /// use deck_optim::metrics::MetricsData;
+    ///
+    /// let mut metrics = MetricsData::empty();
+    /// metrics.add_count("cats", 2);
+    /// assert_eq!(metrics.find_key("cats").map(|key| key.to_string()), Some("cats".to_string()));
+    /// assert_eq!(metrics.find_key("dogs"), None);
+    /// ```
+    pub fn find_key(&self, name: &str) -> Option<MetricsKey> {
+        self.keys()
+            .find(|key| key.to_string() == name)
+    }
+
     pub fn get<K: Into<MetricsKey>>(&self, key: K) -> Metrics {
         self.metrics.get(&key.into())
             .copied()
@@ -264,22 +401,313 @@ impl MetricsData {
             .average()
     }
 
-    /// Returns the minimum ever seen for this metric.
+    /// Returns the minimum ever seen for this metric in a single trial.
+    /// ```
+    /// use deck_optim::metrics::MetricsData;
+    ///
+    /// let mut metrics = MetricsData::empty();
+    /// assert_eq!(metrics.min("cats"), 0);
+    ///
+    /// metrics.add_count("cats", 2);
+    /// metrics.add_count("cats", 5);
+    /// assert_eq!(metrics.min("cats"), 7);
+    /// ```
     pub fn min<K: Into<MetricsKey>>(&self, key: K) -> Uint {
         self.get(key)
-            .min
+            .min()
+    }
+    /// Groups the metrics that are keyed by turn, such as `mana_on_turn`, by the rest of their key.
+    /// Each group maps the turn to the average on that turn. Keys without a turn are left out.
+    /// ```
+    /// use deck_optim::metrics::{MetricsData, MetricsKey};
+    ///
+    /// let mut metrics = MetricsData::empty();
+    /// metrics.set(MetricsKey::from("mana_on_turn").turn_num(1), 1);
+    /// metrics.set(MetricsKey::from("mana_on_turn").turn_num(2), 2);
+    /// metrics.add("land-drops");
+    ///
+    /// let by_turn = metrics.by_turn();
+    /// assert_eq!(by_turn.len(), 1);
+    /// assert_eq!(by_turn[&MetricsKey::from("mana_on_turn")][&2], 2.0);
+    /// ```
+    pub fn by_turn(&self) -> BTreeMap<MetricsKey, BTreeMap<u32, f32>> {
+        let mut groups: BTreeMap<MetricsKey, BTreeMap<u32, f32>> = BTreeMap::new();
+        for key in self.keys() {
+            let Some(turn) = key.turn_num else {
+                continue;
+            };
+            let base = MetricsKey { turn_num: None, ..key };
+            groups.entry(base)
+                .or_default()
+                .insert(turn, self.average(key));
+        }
+        groups
+    }
+
+    /// Exports every metric as a json array, with one object per key.
+    /// Keys are sorted so that two runs can be compared.
+    pub fn to_json(&self) -> serde_json::Value {
+        let rows = self.keys()
+            .sorted()
+            .map(|key| {
+                let metrics = self.get(key);
+                serde_json::json!({
+                    "key": key.to_string(),
+                    "name": key.metrics_name,
+                    "card": key.card.map(Card::name),
+                    "turn": key.turn_num,
+                    "mana_value": key.mana_value,
+                    "bucket": key.bucket,
+                    "total": metrics.total(),
+                    "average": metrics.average(),
+                    "min": metrics.min(),
+                    "max": metrics.max(),
+                    "trials_seen": metrics.trials_seen(),
+                })
+            })
+            .collect_vec();
+        serde_json::Value::Array(rows)
+    }
+
+    /// Exports every metric as csv, with a header and one row per key.
+    /// Keys are sorted so that two runs can be compared.
+    pub fn to_csv(&self) -> String {
+        let mut out = String::from("key,total,average,min,max,trials_seen\n");
+        for key in self.keys().sorted() {
+            let metrics = self.get(key);
+            out.push_str(&format!("{},{},{},{},{},{}\n",
+                csv_escape(&key.to_string()),
+                metrics.total(),
+                metrics.average(),
+                metrics.min(),
+                metrics.max(),
+                metrics.trials_seen(),
+            ));
+        }
+        out
+    }
+
+    /// All of the per-trial values of this key, sorted.
+    /// Empty if distributions are not being recorded.
+    fn samples(&self, key: MetricsKey) -> Vec<Uint> {
+        let mut samples = self.distributions
+            .as_ref()
+            .and_then(|distributions| distributions.get(&key))
+            .cloned()
+            .unwrap_or_default();
+        samples.sort();
+        samples
     }
-    /// Returns the maximum ever seen for this metric.
+
+    /// Returns the `p`th percentile (from 0 to 100) of the per-trial values of this key, using
+    /// the nearest rank. Returns `None` if there are no samples, e.g. if distributions are not
+    /// being recorded.
+    /// ```
+    /// use deck_optim::metrics::MetricsData;
+    ///
+    /// let mut metrics = MetricsData::with_distributions();
+    /// metrics.add_count("cats", 3);
+    /// metrics.end_trial();
+    ///
+    /// assert_eq!(metrics.percentile("cats", 50.0), Some(3));
+    /// assert_eq!(MetricsData::empty().percentile("cats", 50.0), None);
+    /// ```
+    pub fn percentile<K: Into<MetricsKey>>(&self, key: K, p: f64) -> Option<Uint> {
+        let samples = self.samples(key.into());
+        if samples.is_empty() {
+            return None;
+        }
+        let rank = (p / 100.0 * samples.len() as f64).ceil() as usize;
+        let idx = rank.clamp(1, samples.len()) - 1;
+        Some(samples[idx])
+    }
+
+    /// Splits the per-trial values of this key into `buckets` equally sized ranges, and counts how
+    /// many trials fall into each. Empty if there are no samples.
+    pub fn histogram<K: Into<MetricsKey>>(&self, key: K, buckets: usize) -> Vec<(Range<Uint>, usize)> {
+        let samples = self.samples(key.into());
+        let (Some(&lo), Some(&hi)) = (samples.first(), samples.last()) else {
+            return Vec::new();
+        };
+        if buckets == 0 {
+            return Vec::new();
+        }
+        let width = (hi - lo + 1).div_ceil(buckets as Uint);
+        let mut histogram = (0..buckets as Uint)
+            .map(|i| (lo + i * width .. lo + (i + 1) * width, 0))
+            .collect_vec();
+        for sample in samples {
+            let idx = ((sample - lo) / width) as usize;
+            histogram[idx].1 += 1;
+        }
+        histogram
+    }
+
+    /// Returns the maximum ever seen for this metric in a single trial.
+    /// ```
+    /// use deck_optim::metrics::MetricsData;
+    ///
+    /// let mut metrics = MetricsData::empty();
+    /// assert_eq!(metrics.max("cats"), 0);
+    ///
+    /// metrics.add_count("cats", 2);
+    /// metrics.add_count("cats", 5);
+    /// assert_eq!(metrics.max("cats"), 7);
+    /// ```
     pub fn max<K: Into<MetricsKey>>(&self, key: K) -> Uint {
         self.get(key)
-            .max
+            .max()
+    }
+}
+
+/// Quotes a csv field if it contains any special characters
+fn csv_escape(field: &str) -> String {
+    if field.contains([',', '"', '\n']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
     }
 }
 
 #[cfg(test)]
 mod tests {
+    use crate::collection::fixtures;
+
     use super::*;
 
+    fn sample_metrics() -> MetricsData {
+        let mut metrics = MetricsData::empty();
+        metrics.trials_seen = 1;
+        metrics.add_count("land-drops", 4);
+        metrics.set(MetricsKey::from("turn-played").card(fixtures::card_named("Hill Giant")), 3);
+        metrics.set(MetricsKey::from("mana_on_turn").turn_num(2), 2);
+        metrics
+    }
+
+    fn sample_distribution() -> MetricsData {
+        (1..=10)
+            .map(|value| {
+                let mut metrics = MetricsData::with_distributions();
+                metrics.add_count("turns", value);
+                metrics.end_trial();
+                metrics
+            })
+            .fold(MetricsData::empty(), MetricsData::join)
+    }
+
+    #[test]
+    fn test_percentiles() {
+        let metrics = sample_distribution();
+
+        assert_eq!(metrics.num_trials(), 10);
+        assert_eq!(metrics.percentile("turns", 50.0), Some(5));
+        assert_eq!(metrics.percentile("turns", 90.0), Some(9));
+        assert_eq!(metrics.percentile("turns", 0.0), Some(1));
+        assert_eq!(metrics.percentile("turns", 100.0), Some(10));
+    }
+
+    #[test]
+    fn test_percentiles_not_recorded_by_default() {
+        let mut metrics = MetricsData::empty();
+        metrics.add_count("turns", 3);
+        metrics.end_trial();
+
+        assert_eq!(metrics.percentile("turns", 50.0), None);
+        assert_eq!(metrics.histogram("turns", 2), vec![]);
+    }
+
+    #[test]
+    fn test_histogram() {
+        let metrics = sample_distribution();
+
+        let histogram = metrics.histogram("turns", 4);
+
+        assert_eq!(histogram, vec![
+            (1..4, 3),
+            (4..7, 3),
+            (7..10, 3),
+            (10..13, 1),
+        ]);
+    }
+
+    #[test]
+    fn test_to_json() {
+        let metrics = sample_metrics();
+
+        let actual = metrics.to_json();
+        let expected = serde_json::json!([
+            {
+                "key": "land-drops", "name": "land-drops", "card": null, "turn": null, "mana_value": null, "bucket": null,
+                "total": 4, "average": 4.0, "min": 4, "max": 4, "trials_seen": 1
+            },
+            {
+                "key": "mana_on_turn::2", "name": "mana_on_turn", "card": null, "turn": 2, "mana_value": null, "bucket": null,
+                "total": 2, "average": 2.0, "min": 2, "max": 2, "trials_seen": 1
+            },
+            {
+                "key": "turn-played::Hill Giant", "name": "turn-played", "card": "Hill Giant", "turn": null, "mana_value": null, "bucket": null,
+                "total": 3, "average": 3.0, "min": 3, "max": 3, "trials_seen": 1
+            },
+        ]);
+
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn test_to_csv() {
+        let metrics = sample_metrics();
+
+        let actual = metrics.to_csv();
+        let expected = "\
+key,total,average,min,max,trials_seen
+land-drops,4,4,4,4,1
+mana_on_turn::2,2,2,2,2,1
+turn-played::Hill Giant,3,3,3,3,1
+";
+
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn test_display_keys() {
+        let hill_giant = fixtures::card_named("Hill Giant");
+
+        assert_eq!(MetricsKey::from("land-drops").to_string(), "land-drops");
+        assert_eq!(MetricsKey::from("turn-played").card(hill_giant).to_string(), "turn-played::Hill Giant");
+        assert_eq!(MetricsKey::from("plays-by-cmc").mana_value(3).to_string(), "plays-by-cmc::cmc3");
+        assert_eq!(MetricsKey::from("plays-by-cmc").turn_num(2).mana_value(0).to_string(), "plays-by-cmc::2::cmc0");
+        assert_eq!(MetricsKey::from("opening-hand-lands").bucket(2).to_string(), "opening-hand-lands::#2");
+    }
+
+    #[test]
+    fn test_by_turn() {
+        let hill_giant = fixtures::card_named("Hill Giant");
+        let trial = |mana: &[u32], drew_giant_on: u32| {
+            let mut metrics = MetricsData::empty();
+            for (turn, mana) in mana.iter().enumerate() {
+                metrics.set(MetricsKey::from("mana_on_turn").turn_num(turn as u32 + 1), *mana);
+            }
+            metrics.add(MetricsKey::from("card-drawn").card(hill_giant).turn_num(drew_giant_on));
+            metrics.add("land-drops");
+            metrics.end_trial();
+            metrics
+        };
+        let metrics = MetricsData::join(trial(&[1, 2, 3], 2), trial(&[1, 1, 2], 2));
+
+        let by_turn = metrics.by_turn();
+
+        assert_eq!(by_turn.keys().map(|key| key.to_string()).collect_vec(), vec!["card-drawn::Hill Giant", "mana_on_turn"]);
+        assert_eq!(by_turn[&MetricsKey::from("mana_on_turn")], BTreeMap::from([(1, 1.0), (2, 1.5), (3, 2.5)]));
+        assert_eq!(by_turn[&MetricsKey::from("card-drawn").card(hill_giant)], BTreeMap::from([(2, 1.0)]));
+    }
+
+    #[test]
+    fn test_csv_escape() {
+        assert_eq!(csv_escape("Ruby, Daring Tracker"), "\"Ruby, Daring Tracker\"");
+        assert_eq!(csv_escape("say \"hi\""), "\"say \"\"hi\"\"\"");
+        assert_eq!(csv_escape("Forest"), "Forest");
+    }
+
     #[test]
     fn join_sums_number_of_trials() {
         let mut left = MetricsData::empty();
@@ -318,4 +746,42 @@ mod tests {
         assert_eq!(both.min("snakes"), 0);
     }
 
+    #[test]
+    fn min_and_max_track_single_trial_contributions() {
+        let mut first = MetricsData::empty();
+        first.add_count("cats", 3);
+
+        let mut second = MetricsData::empty();
+        second.add_count("cats", 5);
+
+        let both = MetricsData::join(first, second);
+
+        assert_eq!(both.min("cats"), 3);
+        assert_eq!(both.max("cats"), 5);
+        assert_eq!(both.total("cats"), 8);
+    }
+
+    #[test]
+    fn min_is_seeded_by_first_trial() {
+        let empty = MetricsData::empty();
+
+        let mut seen = MetricsData::empty();
+        seen.add_count("cats", 4);
+
+        let both = MetricsData::join(empty, seen);
+
+        assert_eq!(both.min("cats"), 4);
+        assert_eq!(both.max("cats"), 4);
+    }
+
+    #[test]
+    fn min_and_max_accumulate_within_a_trial() {
+        let mut metrics = MetricsData::empty();
+        metrics.add_count("cats", 3);
+        metrics.add_count("cats", 5);
+
+        assert_eq!(metrics.min("cats"), 8);
+        assert_eq!(metrics.max("cats"), 8);
+    }
+
 }
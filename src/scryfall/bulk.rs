@@ -0,0 +1,126 @@
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+use crate::collection::{CardSource, CollectionError, SourceId};
+use crate::file_utils::{self, ArgumentReadError};
+use crate::game;
+use crate::scryfall::{convert, types, ScryfallError};
+
+/// Provides cards from one of scryfall's bulk data files, such as "default-cards",
+/// as downloaded from <https://scryfall.com/docs/api/bulk-data>.
+///
+/// The whole file is read up front, so no network calls are needed to look up cards.
+pub struct BulkDataSource {
+    cards: HashMap<String, types::CardData>,
+}
+
+impl BulkDataSource {
+    /// Reads a bulk data file, which is a json array of scryfall cards
+    pub fn from_path(file_path: &PathBuf) -> Result<Self, ArgumentReadError> {
+        log::info!("reading scryfall bulk data from {}", file_path.display());
+        let cards: Vec<types::CardData> = file_utils::read_json_from_path(file_path)?;
+        Ok(Self::from_cards(cards))
+    }
+
+    fn from_cards(cards: Vec<types::CardData>) -> Self {
+        let mut by_name = HashMap::with_capacity(cards.len());
+        for card in cards {
+            // bulk data has one entry per printing. any of them will do
+            by_name.entry(card.name.clone()).or_insert(card);
+        }
+        Self { cards: by_name }
+    }
+
+    pub fn num_cards(&self) -> usize {
+        self.cards.len()
+    }
+}
+
+impl CardSource for BulkDataSource {
+    fn retrieve_cards(&mut self, card_names: &[&str]) -> Result<Vec<game::CardData>, CollectionError> {
+        let output = card_names
+            .iter()
+            .filter_map(|name| self.cards.get(*name))
+            .cloned()
+            .map(convert::convert_card)
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(ScryfallError::from)?;
+
+        Ok(output)
+    }
+
+    fn source_id(&self) -> SourceId {
+        "bulk data".to_string()
+    }
+}
+
+impl std::fmt::Debug for BulkDataSource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "(Scryfall Bulk Data with {} cards)", self.cards.len())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BULK_DATA: &str = r#"[
+        {
+            "object": "card",
+            "name": "Lightning Bolt",
+            "type_line": "Instant",
+            "mana_cost": "{R}",
+            "oracle_text": "Lightning Bolt deals 3 damage to any target.",
+            "set": "lea"
+        },
+        {
+            "object": "card",
+            "name": "Lightning Bolt",
+            "type_line": "Instant",
+            "mana_cost": "{R}",
+            "oracle_text": "Lightning Bolt deals 3 damage to any target.",
+            "set": "m10"
+        },
+        {
+            "object": "card",
+            "name": "Hill Giant",
+            "type_line": "Creature — Giant",
+            "mana_cost": "{3}{R}",
+            "oracle_text": "",
+            "power": "3",
+            "toughness": "3"
+        }
+    ]"#;
+
+    fn bulk_data_source() -> BulkDataSource {
+        let cards: Vec<types::CardData> = serde_json::from_str(BULK_DATA).expect("fixture should parse");
+        BulkDataSource::from_cards(cards)
+    }
+
+    #[test]
+    fn test_one_entry_per_name() {
+        let source = bulk_data_source();
+        assert_eq!(source.num_cards(), 2);
+    }
+
+    #[test]
+    fn test_retrieve_by_name() {
+        let mut source = bulk_data_source();
+
+        let cards = source.retrieve_cards(&["Hill Giant", "Lightning Bolt"]).expect("no errors");
+
+        let names: Vec<_> = cards.iter().map(|card| card.name.as_str()).collect();
+        assert_eq!(names, vec!["Hill Giant", "Lightning Bolt"]);
+        assert_eq!(cards[0].power, Some(3));
+    }
+
+    #[test]
+    fn test_retrieve_not_found() {
+        let mut source = bulk_data_source();
+
+        let cards = source.retrieve_cards(&["Lightning Bolt", "Black Lotus"]).expect("no errors");
+
+        assert_eq!(cards.len(), 1);
+        assert_eq!(cards[0].name, "Lightning Bolt");
+    }
+}
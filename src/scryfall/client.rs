@@ -2,11 +2,11 @@ use std::{num::NonZero, time::Duration};
 
 use itertools::Itertools;
 use ratelimit_meter::{NonConformance, GCRA};
-use reqwest::{blocking::RequestBuilder, header::{ACCEPT, USER_AGENT}};
+use reqwest::{blocking::RequestBuilder, header::{HeaderMap, ACCEPT, RETRY_AFTER, USER_AGENT}, StatusCode};
 use serde::de::DeserializeOwned;
 
 use crate::{
-    collection::CardSource, game, PROJECT_NAME
+    collection::{CardSource, CollectionError, SourceId}, deck::DeckAllocation, game, PROJECT_NAME
 };
 use crate::scryfall::{error::ScryfallError, types, convert};
 
@@ -19,22 +19,45 @@ const DEFAULT_TIMEOUT: Duration = Duration::from_secs(2);
 
 const MAX_CARDS_PER_COLLECTION_REQUEST: usize = 75;
 
-fn build_http_client() -> HttpClient {
+pub(crate) fn build_http_client() -> HttpClient {
+    build_http_client_with_timeout(DEFAULT_TIMEOUT)
+}
+
+fn build_http_client_with_timeout(timeout: Duration) -> HttpClient {
     HttpClient::builder()
-        .timeout(DEFAULT_TIMEOUT)
+        .timeout(timeout)
         .build()
         .expect("to be able to build http client")
 }
 
 type RateLimiter = ratelimit_meter::DirectRateLimiter::<GCRA>;
 
-fn build_rate_limiter() -> RateLimiter {
-    // Rate limit the requests made to 10 per 1 second,
-    // as described here: https://scryfall.com/docs/api.
-    // We will aim for 5 per second to be safe
-    let num_tokens = NonZero::new(5).expect("num tokens must be nonzero");
-    let time = Duration::from_secs(1);
-    RateLimiter::new(num_tokens, time)
+/// How quickly requests may be sent
+#[derive(Debug,Clone,Copy,PartialEq,Eq)]
+pub enum RateLimit {
+    /// At most `requests` in every span of `per`
+    Limited { requests: NonZero<u32>, per: Duration },
+    /// Send requests as fast as we like, which is only polite to local or mock servers
+    Unlimited,
+}
+
+impl Default for RateLimit {
+    fn default() -> Self {
+        // Rate limit the requests made to 10 per 1 second,
+        // as described here: https://scryfall.com/docs/api.
+        // We will aim for 5 per second to be safe
+        Self::Limited {
+            requests: NonZero::new(5).expect("num tokens must be nonzero"),
+            per: Duration::from_secs(1),
+        }
+    }
+}
+
+fn build_rate_limiter(rate_limit: RateLimit) -> Option<RateLimiter> {
+    match rate_limit {
+        RateLimit::Limited { requests, per } => Some(RateLimiter::new(requests, per)),
+        RateLimit::Unlimited => None,
+    }
 }
 
 const RATE_LIMIT_MAX_RETRIES: u32 = 10;
@@ -65,35 +88,196 @@ impl RateLimiterExt for RateLimiter {
     }
 }
 
+const DEFAULT_MAX_RETRIES: u32 = 4;
+const DEFAULT_INITIAL_BACKOFF: Duration = Duration::from_millis(500);
+
+/// How to retry requests that scryfall turned away, either because we sent too many (429)
+/// or because something went wrong on their end (5xx).
+#[derive(Debug,Clone,Copy,PartialEq,Eq)]
+pub struct RetryPolicy {
+    /// How many times to retry before giving up, not counting the first attempt
+    pub max_retries: u32,
+    /// How long to wait before the first retry. This doubles after every retry.
+    pub initial_backoff: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_retries: DEFAULT_MAX_RETRIES,
+            initial_backoff: DEFAULT_INITIAL_BACKOFF,
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// How long to wait before retry number `retry` (counting from zero), when the server does not say
+    fn backoff(&self, retry: u32) -> Duration {
+        self.initial_backoff.saturating_mul(2u32.saturating_pow(retry))
+    }
+}
+
+/// The parts of a response we need in order to decide whether to retry it
+trait RetryableResponse {
+    fn status(&self) -> StatusCode;
+    fn headers(&self) -> &HeaderMap;
+}
+impl RetryableResponse for reqwest::blocking::Response {
+    fn status(&self) -> StatusCode {
+        self.status()
+    }
+    fn headers(&self) -> &HeaderMap {
+        self.headers()
+    }
+}
+
+fn is_retryable(status: StatusCode) -> bool {
+    status == StatusCode::TOO_MANY_REQUESTS || status.is_server_error()
+}
+
+/// Reads a `Retry-After` header given in seconds. Scryfall does not send the http-date form.
+fn retry_after(headers: &HeaderMap) -> Option<Duration> {
+    let seconds = headers.get(RETRY_AFTER)?
+        .to_str().ok()?
+        .trim()
+        .parse::<u64>().ok()?;
+    Some(Duration::from_secs(seconds))
+}
+
+/// Sends a request until it is not turned away with a retryable status, or until we run out of retries.
+/// Waits between attempts using `sleep`, honoring the server's `Retry-After` when there is one.
+/// The last response is returned even if it was an error, so that the caller can report it.
+fn send_with_retries<R, F, S>(policy: &RetryPolicy, mut send: F, mut sleep: S) -> Result<R, ScryfallError>
+where R: RetryableResponse,
+      F: FnMut() -> Result<R, ScryfallError>,
+      S: FnMut(Duration)
+{
+    let mut retry = 0;
+    loop {
+        let response = send()?;
+        let status = response.status();
+        if !is_retryable(status) || retry >= policy.max_retries {
+            if is_retryable(status) {
+                log::error!("scryfall responded with {status} after {retry} retries, giving up");
+            }
+            return Ok(response);
+        }
+
+        let wait = retry_after(response.headers())
+            .unwrap_or_else(|| policy.backoff(retry));
+        log::warn!("scryfall responded with {status}, retrying in {wait:?}");
+        sleep(wait);
+        retry += 1;
+    }
+}
+
 /// Client for hitting scryfall's endpoints
 pub struct ScryfallClient {
     endpoint: String,
     http_client: HttpClient,
-    rate_limiter: RateLimiter
+    /// `None` when requests are not rate limited
+    rate_limiter: Option<RateLimiter>,
+    retry_policy: RetryPolicy,
 }
 
-impl ScryfallClient {
-    pub fn new() -> Self {
+/// Configures a [`ScryfallClient`], for pointing it at a server other than scryfall's, such as a local mock.
+/// ```
+/// use std::time::Duration;
+/// use deck_optim::scryfall::{RateLimit, ScryfallClient};
+///
+/// let client = ScryfallClient::builder()
+///     .endpoint("http://127.0.0.1:8080")
+///     .timeout(Duration::from_millis(500))
+///     .rate_limit(RateLimit::Unlimited)
+///     .build();
+/// ```
+#[derive(Debug,Clone)]
+pub struct ScryfallClientBuilder {
+    endpoint: String,
+    timeout: Duration,
+    rate_limit: RateLimit,
+    retry_policy: RetryPolicy,
+}
+
+impl ScryfallClientBuilder {
+    /// The base url that the api paths are added to, which is scryfall's api by default
+    pub fn endpoint(mut self, endpoint: impl Into<String>) -> Self {
+        self.endpoint = endpoint.into();
+        self
+    }
+    /// How long to wait for each request before giving up on it
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+    /// How quickly requests may be sent. Leave this alone when talking to scryfall itself
+    pub fn rate_limit(mut self, rate_limit: RateLimit) -> Self {
+        self.rate_limit = rate_limit;
+        self
+    }
+    /// How to retry requests that the server turns away
+    pub fn retry_policy(mut self, retry_policy: RetryPolicy) -> Self {
+        self.retry_policy = retry_policy;
+        self
+    }
+    pub fn build(self) -> ScryfallClient {
+        ScryfallClient {
+            endpoint: self.endpoint,
+            http_client: build_http_client_with_timeout(self.timeout),
+            rate_limiter: build_rate_limiter(self.rate_limit),
+            retry_policy: self.retry_policy,
+        }
+    }
+}
+
+impl Default for ScryfallClientBuilder {
+    fn default() -> Self {
         Self {
             endpoint: SCRYFALL_API_ENDPOINT.to_string(),
-            http_client: build_http_client(),
-            rate_limiter: build_rate_limiter(),
+            timeout: DEFAULT_TIMEOUT,
+            rate_limit: RateLimit::default(),
+            retry_policy: RetryPolicy::default(),
         }
     }
+}
+
+impl ScryfallClient {
+    pub fn new() -> Self {
+        Self::builder().build()
+    }
+
+    /// Start configuring a client, with the same defaults as [`ScryfallClient::new`]
+    pub fn builder() -> ScryfallClientBuilder {
+        ScryfallClientBuilder::default()
+    }
+
+    /// Change how requests that scryfall turns away are retried
+    pub fn with_retry_policy(mut self, retry_policy: RetryPolicy) -> Self {
+        self.retry_policy = retry_policy;
+        self
+    }
 
     /// Helper method to wrap http requests to scryfall apis
-    fn make_request<T: DeserializeOwned, F: FnOnce(&mut HttpClient) -> RequestBuilder>(&mut self, builder: F) -> Result<T, ScryfallError> {
-        // must acquire the rate limit
-        self.rate_limiter.acquire()?;
-        // user agent and accept headers are required:
-        //  see: <https://scryfall.com/docs/api>
-        let request = builder(&mut self.http_client)
-            .header(USER_AGENT, PROJECT_NAME)
-            .header(ACCEPT, "application/json");
-
-        log::debug!("about to make request: {request:#?}");
-        let response = request.send()?;
-        log::debug!("received response: {response:#?}");
+    fn make_request<T: DeserializeOwned, F: Fn(&HttpClient) -> RequestBuilder>(&mut self, builder: F) -> Result<T, ScryfallError> {
+        let Self { http_client, rate_limiter, retry_policy, .. } = self;
+
+        let send = || {
+            // must acquire the rate limit, on every attempt
+            if let Some(rate_limiter) = rate_limiter.as_mut() {
+                rate_limiter.acquire()?;
+            }
+            // user agent and accept headers are required:
+            //  see: <https://scryfall.com/docs/api>
+            let request = builder(http_client)
+                .header(USER_AGENT, PROJECT_NAME)
+                .header(ACCEPT, "application/json");
+
+            log::debug!("about to make request: {request:#?}");
+            let response = request.send()?;
+            log::debug!("received response: {response:#?}");
+            Ok(response)
+        };
+        let response = send_with_retries(retry_policy, send, std::thread::sleep)?;
 
         let response = ScryfallError::raise_on_error(response)?;
         let text = response.text()?;
@@ -103,31 +287,34 @@ impl ScryfallClient {
     }
 
     /// Make an API request to <https://scryfall.com/docs/api/cards/named>
-    pub fn get_card_named(&mut self, card_name: &str) -> Result<types::CardCollectionResponse, ScryfallError> {
+    pub fn get_card_named(&mut self, card_name: &str) -> Result<types::CardData, ScryfallError> {
         let url = format!("{}/cards/named", self.endpoint);
 
         log::info!("looking up card name data from scryfall");
-        let data: types::CardCollectionResponse = self.make_request(
-            |http| http.get(url).query(&[("exact", card_name)])
+        let data: types::CardData = self.make_request(
+            |http| http.get(&url).query(&[("exact", card_name)])
         )?;
 
         Ok(data)
     }
 
-    /// Make an API request to <https://scryfall.com/docs/api/cards/collection>
-    pub fn get_card_collection<'a, I: IntoIterator<Item=&'a str>>(&mut self, card_names: I) -> Result<types::CardCollectionResponse, ScryfallError> {
-        use types::CardIdentifier;
+    /// Make an API request to <https://scryfall.com/docs/api/cards/collection>.
+    /// Accepts card names, or deck allocations to request the printings they name.
+    pub fn get_card_collection<'a, T, I>(&mut self, cards: I) -> Result<types::CardCollectionResponse, ScryfallError>
+    where T: Into<types::CardIdentifier<'a>>,
+          I: IntoIterator<Item=T>
+    {
         use types::CardCollectionResponse;
         use types::CardCollectionRequest;
 
         let url = format!("{}/cards/collection", self.endpoint);
 
         let mut data = CardCollectionResponse::empty();
-        let chunks = card_names.into_iter().chunks(MAX_CARDS_PER_COLLECTION_REQUEST);
+        let chunks = cards.into_iter().chunks(MAX_CARDS_PER_COLLECTION_REQUEST);
         for chunk in chunks.into_iter() {
             let identifiers = chunk
                 .into_iter()
-                .map(|name| CardIdentifier::name(name))
+                .map(Into::into)
                 .collect_vec();
                 
             let request_body = CardCollectionRequest {
@@ -151,6 +338,21 @@ impl ScryfallClient {
 
         Ok(data)
     }
+
+    /// Fetches the cards for these identifiers, and converts them for the card collection
+    fn retrieve_identified<'a, T, I>(&mut self, cards: I) -> Result<Vec<game::CardData>, CollectionError>
+    where T: Into<types::CardIdentifier<'a>>,
+          I: IntoIterator<Item=T>
+    {
+        let output = self.get_card_collection(cards)?;
+        let output = output.data
+            .into_iter()
+            .map(convert::convert_card)
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(ScryfallError::from)?;
+
+        Ok(output)
+    }
 }
 
 impl std::fmt::Debug for ScryfallClient {
@@ -159,17 +361,21 @@ impl std::fmt::Debug for ScryfallClient {
     }
 }
 impl CardSource for ScryfallClient {
-    fn retrieve_cards(&mut self, card_names: &[&str]) -> Result<Vec<game::CardData>, Box<dyn std::error::Error>> {
-        let input = card_names
-            .iter()
-            .map(|s| *s);
-        let output = self.get_card_collection(input)?;
-        let output = output.data
+    fn retrieve_cards(&mut self, card_names: &[&str]) -> Result<Vec<game::CardData>, CollectionError> {
+        self.retrieve_identified(card_names.iter().copied())
+    }
+
+    fn retrieve_printings_with_provenance(&mut self, allocations: &[&DeckAllocation]) -> Result<Vec<(game::CardData, SourceId)>, CollectionError> {
+        let source_id = self.source_id();
+        let card_data = self.retrieve_identified(allocations.iter().copied())?
             .into_iter()
-            .map(convert::convert_card)
-            .collect::<Result<Vec<_>, _>>()?;
+            .map(|card| (card, source_id.clone()))
+            .collect();
+        Ok(card_data)
+    }
 
-        Ok(output)
+    fn source_id(&self) -> SourceId {
+        "scryfall".to_string()
     }
 }
 
@@ -177,19 +383,40 @@ impl CardSource for ScryfallClient {
 mod tests {
     use super::*;
 
-    /*
+    use crate::test_server::{self, Response};
+
+    fn mock_client(endpoint: String) -> ScryfallClient {
+        ScryfallClient::builder()
+            .endpoint(endpoint)
+            .rate_limit(RateLimit::Unlimited)
+            .build()
+    }
+
     #[test]
     fn smoke_test_card_named() {
-        let mut client = ScryfallClient::new();
+        let (endpoint, requests) = test_server::serve(vec![
+            Response::json(r#"{ "name": "Lightning Bolt", "type_line": "Instant", "mana_cost": "{R}" }"#),
+        ]);
+        let mut client = mock_client(endpoint);
 
         let resp = client.get_card_named("Lightning Bolt").expect("didn't expect any errors");
         assert_eq!(resp.name.as_str(), "Lightning Bolt");
         assert_eq!(resp.type_line.as_str(), "Instant");
+
+        let request = requests.recv().expect("a request was made");
+        assert!(request.starts_with("GET /cards/named?exact=Lightning+Bolt "), "unexpected request: {request}");
     }
 
     #[test]
     fn smoke_test_list_cards() {
-        let mut client = ScryfallClient::new();
+        let (endpoint, requests) = test_server::serve(vec![Response::json(r#"{
+            "data": [
+                { "name": "Ancient Tomb", "type_line": "Land" },
+                { "name": "Lightning Bolt", "type_line": "Instant", "mana_cost": "{R}" }
+            ],
+            "not_found": []
+        }"#)]);
+        let mut client = mock_client(endpoint);
 
         let resp = client.get_card_collection(["Ancient Tomb", "Lightning Bolt"]).expect("no errors");
 
@@ -199,12 +426,151 @@ mod tests {
         assert!(contains_ancient_tomb);
         assert!(contains_lighhtning_bolt);
         assert_eq!(resp.data.len(), 2);
+
+        let request = requests.recv().expect("a request was made");
+        assert!(request.starts_with("POST /cards/collection "), "unexpected request: {request}");
+        assert!(request.ends_with(r#"{"identifiers":[{"name":"Ancient Tomb"},{"name":"Lightning Bolt"}]}"#), "unexpected request: {request}");
+    }
+
+    #[test]
+    fn test_retrieve_printings_requests_pinned_printing() {
+        let (endpoint, requests) = test_server::serve(vec![Response::json(r#"{
+            "data": [
+                { "name": "Hill Giant", "type_line": "Creature — Giant", "mana_cost": "{3}{R}", "power": "3", "toughness": "3" },
+                { "name": "Lightning Bolt", "type_line": "Instant", "mana_cost": "{R}" }
+            ],
+            "not_found": []
+        }"#)]);
+        let mut client = mock_client(endpoint);
+        let decklist = crate::deck::DeckList::parse_text("1 Hill Giant (M10) 146\n4 Lightning Bolt\n").expect("should parse");
+        let allocations = decklist.allocations().collect_vec();
+
+        let cards = client.retrieve_printings_with_provenance(&allocations).expect("no errors");
+
+        assert_eq!(cards.iter().map(|(card, _)| card.name.as_str()).collect_vec(), vec!["Hill Giant", "Lightning Bolt"]);
+        let request = requests.recv().expect("a request was made");
+        assert!(request.ends_with(r#"{"identifiers":[{"set":"m10","collector_number":"146"},{"name":"Lightning Bolt"}]}"#), "unexpected request: {request}");
+    }
+
+    #[test]
+    fn test_unlimited_client_does_not_wait() {
+        let responses = vec![Response::json(r#"{ "name": "Lightning Bolt", "type_line": "Instant" }"#); 20];
+        let (endpoint, _requests) = test_server::serve(responses);
+        let mut client = mock_client(endpoint);
+
+        let begin = std::time::Instant::now();
+        for _ in 0..20 {
+            client.get_card_named("Lightning Bolt").expect("no errors");
+        }
+
+        // the default limit of 5 per second would take about four seconds
+        assert!(begin.elapsed() < Duration::from_secs(2), "took {:?}", begin.elapsed());
+    }
+
+    #[test]
+    fn test_unreachable_scryfall_is_a_collection_error() {
+        // nothing listens on the discard port, so the connection is refused
+        let mut client = ScryfallClient::builder()
+            .endpoint("http://127.0.0.1:9")
+            .build();
+
+        let err = client.retrieve_cards(&["Lightning Bolt"]).expect_err("the request should fail");
+
+        assert!(matches!(err, CollectionError::Scryfall(ScryfallError::HttpError(_))), "unexpected error: {err:?}");
+    }
+
+    /// A stand in for a http response, with just a status and headers
+    struct MockResponse {
+        status: StatusCode,
+        headers: HeaderMap,
+    }
+    impl RetryableResponse for MockResponse {
+        fn status(&self) -> StatusCode {
+            self.status
+        }
+        fn headers(&self) -> &HeaderMap {
+            &self.headers
+        }
+    }
+
+    /// Responds with each status in turn
+    fn mock_server(statuses: Vec<(StatusCode, Option<&'static str>)>) -> impl FnMut() -> Result<MockResponse, ScryfallError> {
+        let mut statuses = statuses.into_iter();
+        move || {
+            let (status, retry_after) = statuses.next().expect("no more responses were expected");
+            let mut headers = HeaderMap::new();
+            if let Some(retry_after) = retry_after {
+                headers.insert(RETRY_AFTER, retry_after.parse().expect("valid header"));
+            }
+            Ok(MockResponse { status, headers })
+        }
+    }
+
+    #[test]
+    fn test_retries_until_success() {
+        let send = mock_server(vec![
+            (StatusCode::TOO_MANY_REQUESTS, None),
+            (StatusCode::TOO_MANY_REQUESTS, Some("3")),
+            (StatusCode::OK, None),
+        ]);
+        let mut waits = vec![];
+
+        let response = send_with_retries(&RetryPolicy::default(), send, |wait| waits.push(wait))
+            .expect("no errors");
+
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(waits, vec![DEFAULT_INITIAL_BACKOFF, Duration::from_secs(3)]);
+    }
+
+    #[test]
+    fn test_backoff_doubles() {
+        let send = mock_server(vec![
+            (StatusCode::SERVICE_UNAVAILABLE, None),
+            (StatusCode::BAD_GATEWAY, None),
+            (StatusCode::TOO_MANY_REQUESTS, None),
+            (StatusCode::OK, None),
+        ]);
+        let policy = RetryPolicy {
+            max_retries: 5,
+            initial_backoff: Duration::from_millis(100),
+        };
+        let mut waits = vec![];
+
+        let response = send_with_retries(&policy, send, |wait| waits.push(wait)).expect("no errors");
+
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(waits, vec![Duration::from_millis(100), Duration::from_millis(200), Duration::from_millis(400)]);
+    }
+
+    #[test]
+    fn test_gives_up_after_max_retries() {
+        let send = mock_server(vec![(StatusCode::TOO_MANY_REQUESTS, None); 3]);
+        let policy = RetryPolicy {
+            max_retries: 2,
+            initial_backoff: Duration::from_millis(1),
+        };
+        let mut waits = vec![];
+
+        let response = send_with_retries(&policy, send, |wait| waits.push(wait)).expect("no errors");
+
+        assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(waits.len(), 2);
+    }
+
+    #[test]
+    fn test_does_not_retry_client_errors() {
+        let send = mock_server(vec![(StatusCode::NOT_FOUND, None)]);
+        let mut waits = vec![];
+
+        let response = send_with_retries(&RetryPolicy::default(), send, |wait| waits.push(wait)).expect("no errors");
+
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        assert!(waits.is_empty());
     }
-    */
 
     #[test]
     fn test_rate_limiter() {
-        let mut rl = build_rate_limiter();
+        let mut rl = build_rate_limiter(RateLimit::default()).expect("the default is limited");
 
         let begin = std::time::Instant::now();
 
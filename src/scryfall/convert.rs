@@ -13,7 +13,9 @@ pub enum ConversionError {
     #[error("too many separators found in type line")]
     TooManySeparators {},
     #[error("this combination of card types is not supported yet")]
-    UnsupportedCardTypeCombination { card_types: Vec<game::CardType> }
+    UnsupportedCardTypeCombination { card_types: Vec<game::CardType> },
+    #[error("card `{name}` has multiple faces, but none were found")]
+    MissingFaces { name: String },
 }
 
 pub fn convert_mana_cost(mana_cost: String) -> Result<Option<game::ManaCost>, ConversionError> {
@@ -30,10 +32,24 @@ pub fn convert_mana_cost(mana_cost: String) -> Result<Option<game::ManaCost>, Co
     Ok(Some(cost))
 }
 
+/// Converts a power or toughness, which scryfall represents as a string because it may be variable.
+/// Variable portions such as `*` are treated as 0, so `1+*` becomes 1.
+pub fn convert_power_toughness(value: Option<String>) -> Option<i32> {
+    let value = value?;
+    let fixed = value.split('+').next().unwrap_or_default();
+    match fixed.parse::<i32>() {
+        Ok(num) => Some(num),
+        Err(_) => {
+            log::debug!("treating variable power/toughness `{value}` as 0");
+            Some(0)
+        }
+    }
+}
+
 const TYPE_LINE_SEPARATOR: &'static str = "—";
+const FACE_SEPARATOR: &'static str = "//";
 
 pub struct CardTypes {
-    #[allow(dead_code)]
     super_types: Vec<game::SuperType>,
     card_types: Vec<game::CardType>
 }
@@ -69,7 +85,8 @@ pub fn convert_card_types(types: &str) -> Result<CardTypes, ConversionError> {
     Ok(CardTypes { super_types, card_types })
 }
 
-pub fn convert_type_line(type_line: String) -> Result<game::CardType, ConversionError> {
+/// Converts a type line into its super types and its (single) card type
+pub fn convert_type_line(type_line: String) -> Result<(Vec<game::SuperType>, game::CardType), ConversionError> {
     let ct = match type_line.split(TYPE_LINE_SEPARATOR).collect_vec()[..] {
         [] => return Err(ConversionError::TooManySeparators { }),
         [card_types] => {
@@ -82,24 +99,78 @@ pub fn convert_type_line(type_line: String) -> Result<game::CardType, Conversion
     };
 
     match ct.card_types.as_slice() {
-        [card_type] => Ok(card_type.clone()),
+        [card_type] => Ok((ct.super_types, card_type.clone())),
         _ => Err(ConversionError::UnsupportedCardTypeCombination { card_types: ct.card_types })
     }
 }
 
 /// Converts a scryfall card into a game::card::CardData
 pub fn convert_card(card: types::CardData) -> Result<game::CardData, ConversionError> {
-    let card_type = convert_type_line(card.type_line)?;
+    if card.type_line.contains(FACE_SEPARATOR) {
+        return convert_multi_faced_card(card);
+    }
+
+    let (super_types, card_type) = convert_type_line(card.type_line)?;
     let cost = convert_mana_cost(card.mana_cost)?;
     let out = game::card::CardData {
         name: card.name,
         card_type,
+        super_types,
         cost,
+        power: convert_power_toughness(card.power),
+        toughness: convert_power_toughness(card.toughness),
+        back: None,
     };
     Ok(out)
 
 }
 
+/// Converts a split or double-faced card by converting each of its faces.
+/// The front face becomes the card (under the full name of the card), and the remaining faces hang off of it.
+fn convert_multi_faced_card(card: types::CardData) -> Result<game::CardData, ConversionError> {
+    let faces = match card.card_faces {
+        Some(faces) => faces,
+        None => {
+            // without face data, recover what we can by splitting up the combined fields
+            let type_lines = card.type_line.split(FACE_SEPARATOR).map(str::trim);
+            let mana_costs = card.mana_cost.split(FACE_SEPARATOR).map(str::trim).chain(std::iter::repeat(""));
+            card.name.split(FACE_SEPARATOR)
+                .map(str::trim)
+                .zip(type_lines)
+                .zip(mana_costs)
+                .map(|((name, type_line), mana_cost)| types::CardFace {
+                    name: name.to_string(),
+                    type_line: type_line.to_string(),
+                    mana_cost: mana_cost.to_string(),
+                    oracle_text: String::new(),
+                    power: None,
+                    toughness: None,
+                })
+                .collect()
+        }
+    };
+
+    let mut converted = None;
+    for face in faces.into_iter().rev() {
+        let (super_types, card_type) = convert_type_line(face.type_line)?;
+        converted = Some(game::CardData {
+            name: face.name,
+            card_type,
+            super_types,
+            cost: convert_mana_cost(face.mana_cost)?,
+            power: convert_power_toughness(face.power),
+            toughness: convert_power_toughness(face.toughness),
+            back: converted.map(Box::new),
+        });
+    }
+
+    let Some(mut front) = converted else {
+        return Err(ConversionError::MissingFaces { name: card.name });
+    };
+    front.name = card.name;
+    Ok(front)
+}
+
 #[cfg(test)]
 mod tests {
     use game::mana::{ManaCost, ManaPool};
@@ -132,6 +203,7 @@ mod tests {
                 ..Default::default()
             },
             generic: 3,
+            ..Default::default()
         };
         assert_eq!(Some(expected), mana_cost);
     }
@@ -139,6 +211,26 @@ mod tests {
 
 
 
+    #[test]
+    fn test_convert_normal_power_toughness() {
+        let power = convert_power_toughness(Some("3".to_string()));
+        let toughness = convert_power_toughness(Some("3".to_string()));
+        assert_eq!(Some(3), power);
+        assert_eq!(Some(3), toughness);
+    }
+
+    #[test]
+    fn test_convert_star_power_toughness() {
+        assert_eq!(Some(0), convert_power_toughness(Some("*".to_string())));
+        assert_eq!(Some(1), convert_power_toughness(Some("1+*".to_string())));
+        assert_eq!(Some(-1), convert_power_toughness(Some("-1".to_string())));
+    }
+
+    #[test]
+    fn test_convert_missing_power_toughness() {
+        assert_eq!(None, convert_power_toughness(None));
+    }
+
     #[test]
     fn test_convert_instant() {
         let source = "Instant";
@@ -170,7 +262,122 @@ mod tests {
     #[test]
     fn test_convert_mountain_typeline() {
         let source = "Basic Land — Mountain".to_string();
-        let card_type = convert_type_line(source).expect("no errors");
+        let (super_types, card_type) = convert_type_line(source).expect("no errors");
         assert_eq!(game::CardType::Land, card_type);
+        assert_eq!(vec![game::SuperType::Basic], super_types);
+    }
+
+    #[test]
+    fn test_convert_card_keeps_super_types() {
+        let source = types::CardData {
+            name: "Isamaru, Hound of Konda".to_string(),
+            type_line: "Legendary Creature — Dog".to_string(),
+            mana_cost: "{W}".to_string(),
+            oracle_text: "".to_string(),
+            power: Some("2".to_string()),
+            toughness: Some("2".to_string()),
+            card_faces: None,
+        };
+        let card = convert_card(source).expect("no errors");
+        assert_eq!(vec![game::SuperType::Legendary], card.super_types);
+        assert!(card.is_legendary());
+        assert!(!card.is_basic());
+    }
+
+    fn face(name: &str, type_line: &str, mana_cost: &str) -> types::CardFace {
+        types::CardFace {
+            name: name.to_string(),
+            type_line: type_line.to_string(),
+            mana_cost: mana_cost.to_string(),
+            oracle_text: "".to_string(),
+            power: None,
+            toughness: None,
+        }
+    }
+
+    #[test]
+    fn test_convert_split_card() {
+        let source = types::CardData {
+            name: "Commit // Memory".to_string(),
+            type_line: "Instant // Sorcery".to_string(),
+            mana_cost: "{3}{U} // {4}{U}{U}".to_string(),
+            oracle_text: "".to_string(),
+            power: None,
+            toughness: None,
+            card_faces: Some(vec![
+                face("Commit", "Instant", "{3}{U}"),
+                face("Memory", "Aftermath Sorcery", "{4}{U}{U}"),
+            ]),
+        };
+        let card = convert_card(source);
+        // aftermath is not a type we know about
+        assert!(matches!(card, Err(ConversionError::UnknownCardType { .. })));
+
+        let source = types::CardData {
+            name: "Fire // Ice".to_string(),
+            type_line: "Instant // Instant".to_string(),
+            mana_cost: "{1}{R} // {1}{U}".to_string(),
+            oracle_text: "".to_string(),
+            power: None,
+            toughness: None,
+            card_faces: Some(vec![
+                face("Fire", "Instant", "{1}{R}"),
+                face("Ice", "Instant", "{1}{U}"),
+            ]),
+        };
+        let card = convert_card(source).expect("no errors");
+        assert_eq!("Fire // Ice", card.name);
+        assert_eq!(game::CardType::Instant, card.card_type);
+        assert_eq!(ManaCost::try_parse("{1}{R}").ok(), card.cost);
+
+        let back = card.back.expect("has a back face");
+        assert_eq!("Ice", back.name);
+        assert_eq!(ManaCost::try_parse("{1}{U}").ok(), back.cost);
+        assert!(back.back.is_none());
+    }
+
+    #[test]
+    fn test_convert_split_card_without_faces() {
+        let source = types::CardData {
+            name: "Fire // Ice".to_string(),
+            type_line: "Instant // Instant".to_string(),
+            mana_cost: "{1}{R} // {1}{U}".to_string(),
+            oracle_text: "".to_string(),
+            power: None,
+            toughness: None,
+            card_faces: None,
+        };
+        let card = convert_card(source).expect("no errors");
+        assert_eq!(ManaCost::try_parse("{1}{R}").ok(), card.cost);
+        assert_eq!(ManaCost::try_parse("{1}{U}").ok(), card.back.expect("has a back face").cost);
+    }
+
+    #[test]
+    fn test_convert_modal_double_faced_land() {
+        // scryfall leaves the mana cost off of the card, and puts it on each face
+        let source = r#"{
+            "name": "Emeria's Call // Emeria, Shattered Skyclave",
+            "type_line": "Sorcery // Land",
+            "card_faces": [
+                { "name": "Emeria's Call", "type_line": "Sorcery", "mana_cost": "{4}{W}{W}{W}" },
+                { "name": "Emeria, Shattered Skyclave", "type_line": "Land", "mana_cost": "" }
+            ]
+        }"#;
+        let source: types::CardData = serde_json::from_str(source).expect("should deserialize");
+
+        let card = convert_card(source).expect("no errors");
+        assert_eq!(game::CardType::Sorcery, card.card_type);
+        assert_eq!(ManaCost::try_parse("{4}{W}{W}{W}").ok(), card.cost);
+
+        let back = card.back.expect("has a back face");
+        assert_eq!(game::CardType::Land, back.card_type);
+        assert_eq!(None, back.cost);
+    }
+
+    #[test]
+    fn test_cached_card_data_without_super_types() {
+        let source = r#"{ "name": "Mountain", "card_type": "Land", "cost": null, "power": null, "toughness": null }"#;
+        let card: game::CardData = serde_json::from_str(source).expect("should deserialize");
+        assert!(card.super_types.is_empty());
     }
 }
@@ -1,7 +1,11 @@
 mod client;
 pub use client::*;
 
+mod bulk;
+pub use bulk::*;
+
 mod types;
+pub use types::CardIdentifier;
 mod convert;
 
 mod error;
@@ -1,12 +1,34 @@
 use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Deserialize)]
+use crate::deck::DeckAllocation;
+
+#[derive(Debug, Clone, Deserialize)]
 #[allow(unused)]
 pub struct CardData {
     pub name: String,
     pub type_line: String,
+    /// Cards with multiple faces may leave this out, and put the mana cost on each face instead
+    #[serde(default)]
     pub mana_cost: String,
+    #[serde(default)]
     pub oracle_text: String,
+    pub power: Option<String>,
+    pub toughness: Option<String>,
+    /// Present for split cards, double-faced cards, and the like
+    pub card_faces: Option<Vec<CardFace>>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[allow(unused)]
+pub struct CardFace {
+    pub name: String,
+    pub type_line: String,
+    #[serde(default)]
+    pub mana_cost: String,
+    #[serde(default)]
+    pub oracle_text: String,
+    pub power: Option<String>,
+    pub toughness: Option<String>,
 }
 
 
@@ -35,16 +57,47 @@ pub struct CardCollectionRequest<'a> {
     pub identifiers: Vec<CardIdentifier<'a>>
 }
 
+/// One of the ways to identify a card in a request to <https://scryfall.com/docs/api/cards/collection>
 #[derive(Debug, Serialize)]
 #[allow(unused, non_camel_case_types)]
 pub enum CardIdentifier<'a> {
     name(&'a str),
-    id(&'a str)
+    id(&'a str),
+    /// A specific printing, by its set code and collector number
+    #[serde(untagged)]
+    printing {
+        set: &'a str,
+        collector_number: &'a str,
+    },
+    /// The most recent printing of a card in a set
+    #[serde(untagged)]
+    name_in_set {
+        name: &'a str,
+        set: &'a str,
+    },
+}
+
+impl<'a> From<&'a str> for CardIdentifier<'a> {
+    fn from(name: &'a str) -> Self {
+        CardIdentifier::name(name)
+    }
+}
+
+impl<'a> From<&'a DeckAllocation> for CardIdentifier<'a> {
+    /// Asks for the printing the decklist requested, if any, and otherwise for the card by name
+    fn from(da: &'a DeckAllocation) -> Self {
+        match (da.set(), da.collector_number()) {
+            (Some(set), Some(collector_number)) => CardIdentifier::printing { set, collector_number },
+            (Some(set), None) => CardIdentifier::name_in_set { name: da.name(), set },
+            (None, _) => CardIdentifier::name(da.name()),
+        }
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::deck::DeckList;
 
     #[test]
     fn card_identifier_deser() {
@@ -54,4 +107,23 @@ mod tests {
 
         assert_eq!(json, "{\"name\":\"Ancient Tomb\"}");
     }
+
+    #[test]
+    fn card_identifier_from_deck_allocation() {
+        let decklist = DeckList::parse_text("1 Hill Giant (M10) 146
+1 Island (DMU)
+4 Lightning Bolt
+").expect("should parse");
+
+        let json = decklist.allocations()
+            .map(CardIdentifier::from)
+            .map(|identifier| serde_json::to_string(&identifier).expect("success"))
+            .collect::<Vec<_>>();
+
+        assert_eq!(json, vec![
+            "{\"set\":\"m10\",\"collector_number\":\"146\"}",
+            "{\"name\":\"Island\",\"set\":\"dmu\"}",
+            "{\"name\":\"Lightning Bolt\"}",
+        ]);
+    }
 }
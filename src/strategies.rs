@@ -1,3 +1,7 @@
+use std::str::FromStr;
+
+use crate::collection::Card;
+use crate::game::annotations::AnnotationValue;
 use crate::game::card_play::CardPlay;
 use crate::game::state::State;
 use crate::trial::Rand;
@@ -8,30 +12,174 @@ pub mod payment_solver;
 pub trait Strategy {
     fn mulligan_hand(&mut self, state: &State) -> bool { false }
     fn card_plays(&mut self, state: &State) -> Vec<CardPlay> { vec![] }
+    /// Cards to play at instant speed, at the end of the turn.
+    fn instant_plays(&mut self, state: &State) -> Vec<CardPlay> { vec![] }
+    /// After keeping a hand, choose `n` cards to put on the bottom of the library.
+    fn cards_to_bottom(&self, state: &State, n: usize) -> Vec<Card> {
+        mulligan_strategies::bottom_highest_mana_value(state, n)
+    }
+    /// At the end of the turn, choose `n` cards to discard down to the maximum hand size.
+    fn cards_to_discard(&self, state: &State, n: usize) -> Vec<Card> {
+        discard_strategies::excess_lands_then_highest_mana_value(state, n)
+    }
+    /// When a card with `core:Modes` is played, choose which of its `options` to apply, by index.
+    fn choose_mode(&mut self, _state: &State, _options: &[AnnotationValue]) -> usize { 0 }
 }
 
 #[derive(Clone)]
 pub struct DefaultStrategy;
 impl Strategy for DefaultStrategy { }
 
+/// Decides whether to mulligan a hand, returning true to take a mulligan
+pub type MulliganFn = fn(&State) -> bool;
+
+/// Scores how much we want to play a card
+pub type UtilityFn = fn(Card) -> utility_functions::Utility;
+
+/// How to decide whether to mulligan a hand
+#[derive(Clone,Copy,Debug,Default,PartialEq,Eq)]
+pub enum MulliganStrategy {
+    /// Keep hands with a reasonable number of lands
+    #[default]
+    LandCount,
+    /// Keep hands with a reasonable number of lands and an early play
+    Curve,
+    /// Keep hands that can cast a spell by turn 3, accounting for lands that enter tapped
+    Functional,
+    /// Keep every hand
+    Never,
+}
+
+/// Every mulligan strategy, by the name it is selected with on the command line
+pub const MULLIGAN_STRATEGIES: &[(&str, MulliganStrategy)] = &[
+    ("land-count", MulliganStrategy::LandCount),
+    ("curve", MulliganStrategy::Curve),
+    ("functional", MulliganStrategy::Functional),
+    ("never", MulliganStrategy::Never),
+];
+
+impl MulliganStrategy {
+    pub fn resolve(self) -> MulliganFn {
+        match self {
+            MulliganStrategy::LandCount => mulligan_strategies::between_3_and_4_lands,
+            MulliganStrategy::Curve => |state| !mulligan_strategies::keep_by_curve(state),
+            MulliganStrategy::Functional => |state| !mulligan_strategies::functional_first_three_turns(state),
+            MulliganStrategy::Never => |_| false,
+        }
+    }
+}
+
+impl FromStr for MulliganStrategy {
+    type Err = UnknownStrategy;
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        lookup("mulligan strategy", MULLIGAN_STRATEGIES, name)
+    }
+}
+
+/// How to score the cards we could play, when choosing what to play each turn
+#[derive(Clone,Copy,Debug,Default,PartialEq,Eq)]
+pub enum UtilityFunction {
+    /// Play the most expensive cards
+    ManaValue,
+    /// Play the most expensive cards, and always make a land drop
+    ManaValueOrFixedLand,
+    /// Play by `core:Priority`, falling back to mana value, and always make a land drop
+    #[default]
+    Priority,
+}
+
+/// Every utility function, by the name it is selected with on the command line
+pub const UTILITY_FUNCTIONS: &[(&str, UtilityFunction)] = &[
+    ("mana-value", UtilityFunction::ManaValue),
+    ("mana-value-or-fixed-land", UtilityFunction::ManaValueOrFixedLand),
+    ("priority", UtilityFunction::Priority),
+];
+
+impl UtilityFunction {
+    pub fn resolve(self) -> UtilityFn {
+        match self {
+            UtilityFunction::ManaValue => utility_functions::mana_value,
+            UtilityFunction::ManaValueOrFixedLand => utility_functions::mana_value_or_fixed_land,
+            UtilityFunction::Priority => utility_functions::priority_or_fixed_land,
+        }
+    }
+}
+
+impl FromStr for UtilityFunction {
+    type Err = UnknownStrategy;
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        lookup("utility function", UTILITY_FUNCTIONS, name)
+    }
+}
+
+/// How to choose between land drops that are equally good this turn
+#[derive(Clone,Copy,Debug,Default,PartialEq,Eq)]
+pub enum LandDropTieBreak {
+    /// Prefer the land drop that leaves the most spells castable next turn
+    #[default]
+    CastableNextTurn,
+    /// Keep the first of them, without looking ahead
+    None,
+}
+
+/// Every land drop tie-break, by the name it is selected with on the command line
+pub const LAND_DROP_TIE_BREAKS: &[(&str, LandDropTieBreak)] = &[
+    ("castable-next-turn", LandDropTieBreak::CastableNextTurn),
+    ("none", LandDropTieBreak::None),
+];
+
+impl FromStr for LandDropTieBreak {
+    type Err = UnknownStrategy;
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        lookup("land drop tie-break", LAND_DROP_TIE_BREAKS, name)
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+#[error("unknown {kind} `{name}`, expected one of: {}", expected.join(", "))]
+pub struct UnknownStrategy {
+    kind: &'static str,
+    name: String,
+    expected: Vec<&'static str>,
+}
+
+fn lookup<T: Copy>(kind: &'static str, registry: &[(&'static str, T)], name: &str) -> Result<T, UnknownStrategy> {
+    registry
+        .iter()
+        .find(|(registered, _)| registered.eq_ignore_ascii_case(name))
+        .map(|(_, value)| *value)
+        .ok_or_else(|| UnknownStrategy {
+            kind,
+            name: name.to_string(),
+            expected: registry.iter().map(|(registered, _)| *registered).collect(),
+        })
+}
+
 #[derive(Clone)]
 pub struct StrategyImpl {
-    pub rng: Rand
+    pub rng: Rand,
+    pub mulligan: MulliganStrategy,
+    pub utility: UtilityFunction,
+    pub land_tie_break: LandDropTieBreak,
 }
 impl Strategy for StrategyImpl {
     fn mulligan_hand(&mut self, state: &State) -> bool { 
-        mulligan_strategies::between_3_and_4_lands(state)
+        (self.mulligan.resolve())(state)
     }
     fn card_plays(&mut self, state: &State) -> Vec<CardPlay> { 
         let plays = card_play_strategies::play_a_land_and_a_card(
             state, 
-            &utility_functions::mana_value_or_fixed_land,
+            &self.utility.resolve(),
+            self.land_tie_break,
         );
         plays
     }
+    fn instant_plays(&mut self, state: &State) -> Vec<CardPlay> {
+        card_play_strategies::play_instants(state, &self.utility.resolve())
+    }
 }
 
-mod utility_functions {
+pub mod utility_functions {
     use crate::{collection::Card, game::CardType};
 
     pub type Utility = u32;
@@ -51,14 +199,41 @@ mod utility_functions {
         } 
         mana_value(card)
     }
+
+    /// The card's `core:Priority`, so that play order can be tuned from the annotations file.
+    /// Negative priorities count as zero. Falls back to the mana value.
+    pub fn utility_from_priority(card: Card) -> Utility {
+        match card.priority() {
+            Some(priority) => priority.clamp(0, Utility::MAX as i64) as Utility,
+            None => mana_value(card),
+        }
+    }
+
+    /// Like [`utility_from_priority`], but lands without a priority are always worth playing.
+    pub fn priority_or_fixed_land(card: Card) -> Utility {
+        match card.priority() {
+            Some(_) => utility_from_priority(card),
+            None => mana_value_or_fixed_land(card),
+        }
+    }
 }
 
 #[allow(dead_code)]
 mod mulligan_strategies {
+    use itertools::Itertools;
+
+    use crate::game::CardType;
+    use crate::game::mana::ManaSource;
+
     use super::*;
 
+    const MAX_MULLIGANS: u32 = 3;
+
+    /// The turn by which a hand should be able to cast something
+    const CURVE_TURN: usize = 3;
+
     pub fn between_3_and_4_lands(state: &State) -> bool {
-        if state.num_mulligans_taken >= 3 {
+        if state.num_mulligans_taken >= MAX_MULLIGANS {
             log::debug!("refusing to take a mulligan #{}", state.num_mulligans_taken);
             return false;
         }
@@ -68,7 +243,342 @@ mod mulligan_strategies {
 
         !good
     }
+
+    /// Returns true if the hand has 2 to 5 lands, and those lands can cast at least one spell in
+    /// the hand by turn 3.
+    pub fn keep_by_curve(state: &State) -> bool {
+        if state.num_mulligans_taken >= MAX_MULLIGANS {
+            log::debug!("refusing to take a mulligan #{}", state.num_mulligans_taken);
+            return true;
+        }
+        let land_count = state.num_lands_in_hand();
+        if !(2..=5).contains(&land_count) {
+            log::debug!("saw hand with {land_count} lands, on mulligan #{}, not keeping", state.num_mulligans_taken);
+            return false;
+        }
+
+        let lands = state.hand
+            .iter()
+            .filter(|c| c.data().card_type == CardType::Land)
+            .filter_map(ManaSource::try_from)
+            .collect_vec();
+        let spells = state.hand
+            .iter()
+            .filter(|c| c.data().card_type != CardType::Land)
+            .filter_map(|c| c.data().cost)
+            .collect_vec();
+
+        // by the curve turn, we could have played any of these combinations of lands
+        let num_land_drops = std::cmp::min(CURVE_TURN, lands.len());
+        let castable = lands
+            .into_iter()
+            .combinations(num_land_drops)
+            .any(|land_drops| {
+                spells.iter().any(|cost| payment_solver::autotap_pay_for(land_drops.clone(), cost).is_some())
+            });
+        log::debug!("saw hand with {} cards and {land_count} lands, on mulligan #{}, castable={castable}", state.hand.size(), state.num_mulligans_taken);
+
+        castable
+    }
+
+    /// Returns true if there is some order to play the lands in the hand, one per turn, that casts
+    /// at least one spell in the hand by turn 3. Unlike [`keep_by_curve`], lands that enter tapped
+    /// can not be used on the turn they are played.
+    pub fn functional_first_three_turns(state: &State) -> bool {
+        if state.num_mulligans_taken >= MAX_MULLIGANS {
+            log::debug!("refusing to take a mulligan #{}", state.num_mulligans_taken);
+            return true;
+        }
+
+        let lands = state.hand
+            .iter()
+            .filter(|c| c.data().card_type == CardType::Land)
+            .collect_vec();
+        let spells = state.hand
+            .iter()
+            .filter(|c| c.data().card_type != CardType::Land)
+            .filter_map(|c| c.data().cost)
+            .collect_vec();
+
+        let num_land_drops = std::cmp::min(CURVE_TURN, lands.len());
+        let functional = lands
+            .into_iter()
+            .permutations(num_land_drops)
+            .any(|land_drops| {
+                (1..=CURVE_TURN).any(|turn| {
+                    let mana_sources = untapped_lands_on_turn(&land_drops, turn)
+                        .filter_map(ManaSource::try_from)
+                        .collect_vec();
+                    spells.iter().any(|cost| payment_solver::autotap_pay_for(mana_sources.clone(), cost).is_some())
+                })
+            });
+        log::debug!("saw hand with {} cards, on mulligan #{}, functional={functional}", state.hand.size(), state.num_mulligans_taken);
+
+        functional
+    }
+
+    /// Plays `land_drops` one per turn, and returns the lands we could tap on `turn` (counting from 1).
+    /// The land played that turn can not be tapped if it enters tapped.
+    fn untapped_lands_on_turn(land_drops: &[Card], turn: usize) -> impl Iterator<Item = Card> + '_ {
+        let num_played = std::cmp::min(turn, land_drops.len());
+        land_drops[..num_played]
+            .iter()
+            .enumerate()
+            .filter(move |(idx, land)| *idx + 1 < turn || !land.enters_tapped())
+            .map(|(_, land)| *land)
+    }
+
+    /// Bottom the `n` most expensive cards in hand.
+    pub fn bottom_highest_mana_value(state: &State, n: usize) -> Vec<Card> {
+        state.hand
+            .iter()
+            .sorted_by_key(|card| std::cmp::Reverse(utility_functions::mana_value(*card)))
+            .take(n)
+            .collect()
+    }
+}
+
+mod discard_strategies {
+    use itertools::Itertools;
+
+    use crate::game::CardType;
+
+    use super::*;
+
+    /// How many lands to hold on to, so that we still have land drops coming
+    const LANDS_TO_KEEP: usize = 2;
+
+    /// Discard lands beyond the first few, then the most expensive spells.
+    pub fn excess_lands_then_highest_mana_value(state: &State, n: usize) -> Vec<Card> {
+        let (lands, spells): (Vec<Card>, Vec<Card>) = state.hand
+            .iter()
+            .partition(|card| card.data().card_type == CardType::Land);
+
+        let excess_lands = lands.iter().copied().skip(LANDS_TO_KEEP);
+        let expensive_spells = spells
+            .iter()
+            .copied()
+            .sorted_by_key(|card| std::cmp::Reverse(utility_functions::mana_value(*card)));
+        let kept_lands = lands.iter().copied().take(LANDS_TO_KEEP);
+
+        excess_lands
+            .chain(expensive_spells)
+            .chain(kept_lands)
+            .take(n)
+            .collect()
+    }
 }
 
 mod card_play_strategies;
 
+
+#[cfg(test)]
+mod tests {
+    use rand::SeedableRng;
+
+    use crate::collection::fixtures;
+    use crate::game::{CommandZone, Deck, PlayDraw, UnorderedPile};
+
+    use super::*;
+
+    fn state_with_hand(hand: &[&str]) -> State {
+        let deck = Deck {
+            command_zone: CommandZone::empty(),
+            deck: UnorderedPile::empty(),
+        };
+        let mut state = State::new(deck, PlayDraw::Random, &mut Rand::seed_from_u64(0));
+        state.hand = hand.iter()
+            .map(|name| fixtures::card_named(name))
+            .collect::<Vec<_>>()
+            .into();
+        state
+    }
+
+    #[test]
+    fn test_priority_outranks_mana_value() {
+        let mut state = state_with_hand(&["Grizzly Bears", "Hill Giant"]);
+        for name in ["Mountain", "Mountain", "Mountain", "Forest"] {
+            state.permanents.add(fixtures::card_named(name));
+        }
+
+        let by_mana_value = card_play_strategies::play_a_card(&state, &utility_functions::mana_value);
+        let by_priority = card_play_strategies::play_a_card(&state, &utility_functions::utility_from_priority);
+
+        assert_eq!(by_mana_value.iter().map(|play| play.card).collect::<Vec<_>>(), vec![fixtures::card_named("Hill Giant")]);
+        assert_eq!(by_priority.iter().map(|play| play.card).collect::<Vec<_>>(), vec![fixtures::card_named("Grizzly Bears")]);
+    }
+
+    #[test]
+    fn test_utility_from_priority_falls_back_to_mana_value() {
+        assert_eq!(utility_functions::utility_from_priority(fixtures::card_named("Hill Giant")), 4);
+        assert_eq!(utility_functions::utility_from_priority(fixtures::card_named("Grizzly Bears")), 10);
+    }
+
+    #[test]
+    fn test_discard_excess_lands_first() {
+        let state = state_with_hand(&["Forest", "Forest", "Mountain", "Mountain", "Grizzly Bears", "Colossal Dreadmaw", "Hill Giant"]);
+
+        let discarded = discard_strategies::excess_lands_then_highest_mana_value(&state, 3);
+
+        let lands = discarded.iter().filter(|card| card.data().card_type == crate::game::CardType::Land).count();
+        assert_eq!(lands, 2);
+        assert!(discarded.contains(&fixtures::card_named("Colossal Dreadmaw")));
+    }
+
+    #[test]
+    fn test_functional_with_basics() {
+        let state = state_with_hand(&["Forest", "Forest", "Mountain", "Centaur Courser", "Hill Giant", "Hill Giant", "Colossal Dreadmaw"]);
+
+        assert!(mulligan_strategies::functional_first_three_turns(&state));
+    }
+
+    #[test]
+    fn test_functional_with_tapped_lands() {
+        // the third tapped land comes down on turn 3, leaving only two mana for the three drop
+        let state = state_with_hand(&["Rugged Highlands", "Rugged Highlands", "Rugged Highlands", "Centaur Courser", "Hill Giant", "Hill Giant", "Colossal Dreadmaw"]);
+
+        assert!(!mulligan_strategies::functional_first_three_turns(&state));
+        assert!(mulligan_strategies::keep_by_curve(&state));
+    }
+
+    #[test]
+    fn test_functional_plays_tapped_land_early() {
+        // playing the tapped land first leaves everything untapped by turn 3
+        let state = state_with_hand(&["Rugged Highlands", "Forest", "Forest", "Centaur Courser", "Hill Giant", "Hill Giant", "Colossal Dreadmaw"]);
+
+        assert!(mulligan_strategies::functional_first_three_turns(&state));
+    }
+
+    #[test]
+    fn test_keep_by_curve_all_lands() {
+        let state = state_with_hand(&["Forest", "Forest", "Forest", "Mountain", "Mountain", "Mountain", "Island"]);
+
+        assert!(!mulligan_strategies::keep_by_curve(&state));
+    }
+
+    #[test]
+    fn test_keep_by_curve_turn_two_play() {
+        let state = state_with_hand(&["Forest", "Mountain", "Island", "Grizzly Bears", "Colossal Dreadmaw", "Colossal Dreadmaw", "Hill Giant"]);
+
+        assert!(mulligan_strategies::keep_by_curve(&state));
+    }
+
+    #[test]
+    fn test_keep_by_curve_only_bombs() {
+        let state = state_with_hand(&["Forest", "Forest", "Mountain", "Colossal Dreadmaw", "Colossal Dreadmaw", "Colossal Dreadmaw", "Hill Giant"]);
+
+        assert!(!mulligan_strategies::keep_by_curve(&state));
+    }
+
+    #[test]
+    fn test_keep_by_curve_wrong_colors() {
+        let state = state_with_hand(&["Island", "Island", "Island", "Grizzly Bears", "Colossal Dreadmaw", "Colossal Dreadmaw", "Hill Giant"]);
+
+        assert!(!mulligan_strategies::keep_by_curve(&state));
+    }
+
+    #[test]
+    fn test_exploration_plays_two_lands() {
+        let mut state = state_with_hand(&["Forest", "Mountain", "Hill Giant"]);
+        for name in ["Mountain", "Mountain", "Exploration"] {
+            state.permanents.add(fixtures::card_named(name));
+        }
+        let mut strategies = StrategyImpl {
+            rng: Rand::seed_from_u64(0),
+            mulligan: MulliganStrategy::default(),
+            utility: UtilityFunction::default(),
+            land_tie_break: LandDropTieBreak::default(),
+        };
+
+        let mut played = strategies.card_plays(&state).iter().map(|play| play.card).collect::<Vec<_>>();
+        played.sort();
+
+        let mut expected = vec![fixtures::card_named("Forest"), fixtures::card_named("Mountain"), fixtures::card_named("Hill Giant")];
+        expected.sort();
+        assert_eq!(played, expected);
+    }
+
+    #[test]
+    fn test_one_land_drop_without_exploration() {
+        let state = state_with_hand(&["Forest", "Mountain", "Hill Giant"]);
+        let mut strategies = StrategyImpl {
+            rng: Rand::seed_from_u64(0),
+            mulligan: MulliganStrategy::default(),
+            utility: UtilityFunction::default(),
+            land_tie_break: LandDropTieBreak::default(),
+        };
+
+        let plays = strategies.card_plays(&state);
+
+        assert_eq!(plays.len(), 1);
+        assert_eq!(plays[0].card.data().card_type, crate::game::CardType::Land);
+    }
+
+    #[test]
+    fn test_instants_wait_for_end_of_turn() {
+        let mut state = state_with_hand(&["Lightning Bolt", "Hill Giant"]);
+        state.permanents.add(fixtures::card_named("Mountain"));
+        let mut strategies = StrategyImpl {
+            rng: Rand::seed_from_u64(0),
+            mulligan: MulliganStrategy::default(),
+            utility: UtilityFunction::default(),
+            land_tie_break: LandDropTieBreak::default(),
+        };
+
+        let main_phase = strategies.card_plays(&state);
+        assert!(main_phase.is_empty());
+
+        let end_of_turn = strategies.instant_plays(&state);
+        assert_eq!(end_of_turn.len(), 1);
+        assert_eq!(end_of_turn[0].card, fixtures::card_named("Lightning Bolt"));
+    }
+
+    #[test]
+    fn test_instants_use_leftover_mana() {
+        let mut state = state_with_hand(&["Lightning Bolt"]);
+        let mountain = fixtures::card_named("Mountain");
+        state.permanents.add(mountain);
+        state.turn_state.mark_as_tapped(mountain);
+        let mut strategies = StrategyImpl {
+            rng: Rand::seed_from_u64(0),
+            mulligan: MulliganStrategy::default(),
+            utility: UtilityFunction::default(),
+            land_tie_break: LandDropTieBreak::default(),
+        };
+
+        assert!(strategies.instant_plays(&state).is_empty());
+    }
+
+    #[test]
+    fn test_strategies_by_name() {
+        assert_eq!("never".parse::<MulliganStrategy>().unwrap(), MulliganStrategy::Never);
+        assert_eq!("Land-Count".parse::<MulliganStrategy>().unwrap(), MulliganStrategy::LandCount);
+        assert_eq!("mana-value".parse::<UtilityFunction>().unwrap(), UtilityFunction::ManaValue);
+        assert_eq!("none".parse::<LandDropTieBreak>().unwrap(), LandDropTieBreak::None);
+
+        let err = "sometimes".parse::<MulliganStrategy>().unwrap_err();
+        assert_eq!(err.to_string(), "unknown mulligan strategy `sometimes`, expected one of: land-count, curve, functional, never");
+    }
+
+    #[test]
+    fn test_default_bottoms_highest_mana_value() {
+        let mut deck = UnorderedPile::empty();
+        deck.add_copies(fixtures::card_named("Forest"), 4);
+        deck.add(fixtures::card_named("Lightning Bolt"));
+        deck.add(fixtures::card_named("Hill Giant"));
+        deck.add(fixtures::card_named("Colossal Dreadmaw"));
+        let deck = Deck {
+            command_zone: CommandZone::empty(),
+            deck,
+        };
+        let mut state = State::new(deck, PlayDraw::Random, &mut Rand::seed_from_u64(0));
+        state.draw_hand();
+
+        let mut bottomed = DefaultStrategy.cards_to_bottom(&state, 2);
+        bottomed.sort();
+
+        let mut expected = vec![fixtures::card_named("Hill Giant"), fixtures::card_named("Colossal Dreadmaw")];
+        expected.sort();
+        assert_eq!(bottomed, expected);
+    }
+}
@@ -1,15 +1,20 @@
+use std::collections::HashSet;
+
 use itertools::Itertools;
 use rand::Rng;
 
 use crate::game::UnorderedPile;
 use crate::opt_utils::OptExt;
 
+use crate::strategies::LandDropTieBreak;
 use crate::strategies::payment_solver;
 use crate::strategies::utility_functions::Utility;
 use crate::collection::Card;
 use crate::game::card::CardType;
 use crate::game::card_play::CardPlay;
 use crate::game::mana::ManaSource;
+use crate::game::mana::ManaType;
+use crate::game::mana::PHYREXIAN_LIFE_COST;
 use crate::game::state::State;
 use crate::trial::Rand;
 
@@ -17,11 +22,13 @@ use crate::trial::Rand;
 #[derive(Debug,Clone)]
 struct Soln {
     pub card_plays: Vec<CardPlay>,
-    pub utility: Utility
+    pub utility: Utility,
+    /// Breaks ties in utility, see [`castable_next_turn`]
+    pub castable_next_turn: usize,
 }
 impl Soln {
     fn replace_if_better(&mut self, other: Self) {
-        if other.utility > self.utility {
+        if (other.utility, other.castable_next_turn) > (self.utility, self.castable_next_turn) {
             *self = other;
         }
 
@@ -31,22 +38,33 @@ impl Soln {
 // The solution is a list of cards to play
 pub type CardPlaySolution = Vec<CardPlay>;
 
-pub fn play_a_land_and_a_card<F>(state: &State, utility_fn: &F) -> CardPlaySolution 
+/// Makes as many land drops as we are allowed, then plays cards with the mana available.
+/// When several land drops are as good this turn, `tie_break` decides between them. With
+/// [`LandDropTieBreak::CastableNextTurn`], it prefers the one leaving the most cards castable next turn,
+/// so that a tapped land or a second color comes down before it is needed.
+pub fn play_a_land_and_a_card<F>(state: &State, utility_fn: &F, tie_break: LandDropTieBreak) -> CardPlaySolution 
     where F: Fn(Card) -> Utility 
 {
     let mut soln = Soln {
         card_plays: Vec::new(),
-        utility: 0
+        utility: 0,
+        castable_next_turn: 0,
     };
 
-    state.legal_land_drops()
+    let mut land_drops = state.legal_land_drops().peekable();
+    if land_drops.peek().is_none() {
+        return play_a_card(state, utility_fn);
+    }
+
+    land_drops
         .for_each(|land_drop| {
                 
             let mut card_plays = vec![land_drop.clone()];
             let next = state.with_having_played(land_drop.clone());
 
             log::debug!("forecasting land drop - what if we played {:?}", land_drop.card);
-            card_plays.extend(play_a_card(&next, utility_fn));
+            // only the first land drop looks ahead, the rest of the turn is the same either way
+            card_plays.extend(play_a_land_and_a_card(&next, utility_fn, LandDropTieBreak::None));
 
             let utility = card_plays
                 .iter()
@@ -54,9 +72,20 @@ pub fn play_a_land_and_a_card<F>(state: &State, utility_fn: &F) -> CardPlaySolut
                 .map(utility_fn)
                 .sum();
 
+            let castable_next_turn = match tie_break {
+                LandDropTieBreak::CastableNextTurn => {
+                    let after = card_plays[1..]
+                        .iter()
+                        .fold(next, |state, card_play| state.with_having_played(card_play.clone()));
+                    castable_next_turn(&after)
+                }
+                LandDropTieBreak::None => 0,
+            };
+
             soln.replace_if_better(Soln {
                 card_plays,
-                utility
+                utility,
+                castable_next_turn,
             });
     });
 
@@ -68,13 +97,72 @@ pub fn play_a_card<F>(state: &State, utility_fn: &F) -> CardPlaySolution
 {
     let mut plays = Vec::new();
     let available_mana = state.mana_sources().collect_vec();
-    let legal_plays = state.legal_card_plays().collect_vec();
+    let producible = state.producible_colors();
+    let legal_plays = state.legal_card_plays()
+        .filter(|card_play| colors_are_producible(card_play.card, &producible))
+        .collect_vec();
 
     naive_greedy(&mut plays, available_mana, legal_plays, utility_fn);
 
     plays
 }
 
+/// Play whatever we can at instant speed, with the mana left over from the main phase.
+pub fn play_instants<F>(state: &State, utility_fn: &F) -> CardPlaySolution 
+    where F: Fn(Card) -> Utility
+{
+    let mut plays = Vec::new();
+    let available_mana = state.mana_sources().collect_vec();
+    let producible = state.producible_colors();
+    let legal_plays = state.legal_instant_plays()
+        .filter(|card_play| colors_are_producible(card_play.card, &producible))
+        .collect_vec();
+
+    naive_greedy(&mut plays, available_mana, legal_plays, utility_fn);
+
+    plays
+}
+
+/// How many of the spells left in hand we could pay for next turn, with every permanent untapped
+/// and the best of the land drops we would have then. Only the colors and the amount of mana are checked.
+fn castable_next_turn(state: &State) -> usize {
+    let mut upkeep = state.clone();
+    upkeep.end_turn();
+
+    let count_castable = |state: &State| {
+        let producible = state.producible_colors();
+        let available = state.available_mana();
+        state.hand
+            .iter()
+            .filter(|card| card.data().card_type != CardType::Land)
+            .filter_map(|card| card.data().cost.as_ref())
+            .filter(|cost| cost.mana_value() <= available)
+            .filter(|cost| cost.colors.mana_types().all(|mana_type| producible.contains(&mana_type)))
+            .count()
+    };
+
+    upkeep.legal_land_drops()
+        .map(|land_drop| count_castable(&upkeep.with_having_played(land_drop)))
+        .chain(std::iter::once(count_castable(&upkeep)))
+        .max()
+        .unwrap_or(0)
+}
+
+/// Whether every colored pip of the card's cost is a color we could make, so that it is worth asking the payment solver.
+/// Phyrexian pips can be paid with life, so they do not count.
+fn colors_are_producible(card: Card, producible: &HashSet<ManaType>) -> bool {
+    let Some(cost) = &card.data().cost else {
+        return true;
+    };
+    let castable = cost.colors
+        .mana_types()
+        .all(|mana_type| producible.contains(&mana_type));
+    if !castable {
+        log::debug!("   no sources for the colors of {card:?}, skipping");
+    }
+    castable
+}
+
 pub fn naive_greedy<F: Fn(Card) -> Utility>(plays: &mut Vec<CardPlay>, mut mana_sources: Vec<ManaSource>, mut legal_plays: Vec<CardPlay>, utility_fn: &F) {
     log::debug!("begin naive greedy algorithm, available mana: {mana_sources:?} and {} potential plays", legal_plays.len());
     loop {
@@ -82,28 +170,49 @@ pub fn naive_greedy<F: Fn(Card) -> Utility>(plays: &mut Vec<CardPlay>, mut mana_
         // filter pick the best thing to play first
         legal_plays.sort_by_key(|card_play| utility_fn(card_play.card));
         // pick a card to play
-        let Some(CardPlay { card: candidate, zone, payment: _ }) = legal_plays.pop() else {
+        let Some(candidate_play) = legal_plays.pop() else {
             log::debug!("       can't pick a card to play, returning");
             break;
         };
-        log::debug!("   evaluating candidate: {candidate:?} with cost {}", candidate.data().cost.display());
-        let Some(mana_cost) = candidate.data().cost.clone() else {
+        let CardPlay { card: candidate, zone, tax, .. } = candidate_play;
+        log::debug!("   evaluating candidate: {candidate:?} with cost {}", candidate_play.cost().display());
+        let Some(mana_cost) = candidate_play.cost() else {
             log::debug!("       candidate doesn't have a cost, can't play");
             continue;
         };
 
         // TODO: can we avoid this clone?
-        let Some((payment, unused_mana)) = payment_solver::autotap_pay_for(mana_sources.clone(), &mana_cost) else {
+        let Some((mut payment, unused_mana)) = payment_solver::autotap_pay_for(mana_sources.clone(), &mana_cost) else {
             log::debug!("       no ways to pay for {mana_cost} with {mana_sources:?}, skipping");
             continue;
         };
         mana_sources = unused_mana;
 
+        if mana_cost.x_count > 0 {
+            // pour all the remaining mana into X
+            for source in mana_sources.drain(..) {
+                if let Some(mana) = source.free_outputs().max_by_key(|mana| mana.mana_value()) {
+                    payment.add(source.card, mana);
+                }
+            }
+        }
+        let fixed_mana = mana_cost.mana_value() - payment.life_paid / PHYREXIAN_LIFE_COST;
+        let x_value = payment.mana_used.mana_value()
+            .saturating_sub(fixed_mana)
+            .checked_div(mana_cost.x_count)
+            .unwrap_or(0);
+        if mana_cost.x_count > 0 {
+            log::debug!("       choosing X={x_value} for {candidate:?}");
+        }
+
         log::debug!("       playing {candidate:?} with {payment:?}");
         plays.push(CardPlay {
             card: candidate, 
             zone,
-            payment: payment.mana_used
+            payment: payment.mana_used,
+            x_value,
+            tax,
+            tapped: payment.cards_to_tap.iter().map(|(card, _)| *card).collect(),
         });
     }
 
@@ -118,7 +227,7 @@ pub fn random_nonland(rng: &mut Rand, state: &State) -> Option<Card> {
 fn pick_random_filtered<F>(rng: &mut Rand, cards: &UnorderedPile, filter: F) -> Option<Card>
 where F: Fn(&Card) -> bool,
 {
-    let count = cards.iter().filter(&filter).count();
+    let count = cards.count_where(&filter);
     if count == 0 {
         return None;
     }
@@ -128,3 +237,168 @@ where F: Fn(&Card) -> bool,
         .filter(&filter)
         .nth(idx)
 }
+
+#[cfg(test)]
+mod tests {
+    use rand::SeedableRng;
+
+    use crate::collection::fixtures;
+    use crate::game::{CommandZone, Deck, PlayDraw, Zone};
+    use crate::game::mana::ManaPool;
+    use crate::strategies::utility_functions;
+
+    use super::*;
+
+    fn mana_source(name: &str, produces: ManaPool) -> ManaSource {
+        ManaSource {
+            card: fixtures::card_named(name),
+            produces: vec![produces.into()],
+            produces_snow: false,
+        }
+    }
+
+    fn card_play(name: &str) -> CardPlay {
+        CardPlay {
+            card: fixtures::card_named(name),
+            zone: Zone::Hand,
+            payment: ManaPool::empty(),
+            x_value: 0,
+            tax: 0,
+            tapped: vec![],
+        }
+    }
+
+    #[test]
+    fn test_naive_greedy_chooses_x_from_leftover_mana() {
+        let mana_sources = vec![
+            mana_source("Mountain", ManaPool::red(1)),
+            mana_source("Forest", ManaPool::green(1)),
+            mana_source("Sol Ring", ManaPool::colorless(2)),
+        ];
+        let legal_plays = vec![card_play("Fireball")];
+
+        let mut plays = Vec::new();
+        naive_greedy(&mut plays, mana_sources, legal_plays, &utility_functions::mana_value);
+
+        assert_eq!(plays.len(), 1);
+        assert_eq!(plays[0].card, fixtures::card_named("Fireball"));
+        assert_eq!(plays[0].x_value, 3);
+        assert_eq!(plays[0].payment.mana_value(), 4);
+        assert_eq!(plays[0].tapped.len(), 3);
+    }
+
+    #[test]
+    fn test_naive_greedy_pays_the_commander_tax() {
+        let mana_sources = || (0..5).map(|_| mana_source("Mountain", ManaPool::red(1))).collect_vec();
+        let first_cast = CardPlay { zone: Zone::CommandZone, ..card_play("Hill Giant") };
+        let second_cast = CardPlay { tax: 2, ..first_cast.clone() };
+
+        let mut plays = Vec::new();
+        naive_greedy(&mut plays, mana_sources(), vec![first_cast], &utility_functions::mana_value);
+        assert_eq!(plays.len(), 1);
+        assert_eq!(plays[0].payment.mana_value(), 4);
+        assert_eq!(plays[0].zone, Zone::CommandZone);
+
+        let mut plays = Vec::new();
+        naive_greedy(&mut plays, mana_sources(), vec![second_cast], &utility_functions::mana_value);
+        assert!(plays.is_empty(), "six mana with the tax, but only five mountains");
+    }
+
+    #[test]
+    fn test_lands_are_not_reused_within_a_turn() {
+        let mut deck = UnorderedPile::empty();
+        deck.add_copies(fixtures::card_named("Forest"), 10);
+        let deck = Deck {
+            command_zone: CommandZone::empty(),
+            deck,
+        };
+        let mut state = State::new(deck, PlayDraw::Random, &mut Rand::seed_from_u64(0));
+        let forest = fixtures::card_named("Forest");
+        let elves = fixtures::card_named("Llanowar Elves");
+        state.permanents.add(forest);
+        state.hand.add_copies(elves, 2);
+
+        let plays = play_a_card(&state, &utility_functions::mana_value);
+        assert_eq!(plays.len(), 1);
+        assert_eq!(plays[0].tapped, vec![forest]);
+
+        // the forest stays tapped, so nothing else can be paid for this turn
+        state.play_card(plays[0].clone());
+        assert!(play_a_card(&state, &utility_functions::mana_value).is_empty());
+
+        state.end_turn();
+        assert_eq!(play_a_card(&state, &utility_functions::mana_value).len(), 1);
+    }
+
+    #[test]
+    fn test_two_spells_use_up_every_land_for_the_turn() {
+        let deck = Deck {
+            command_zone: CommandZone::empty(),
+            deck: UnorderedPile::empty(),
+        };
+        let mut state = State::new(deck, PlayDraw::Random, &mut Rand::seed_from_u64(0));
+        let forest = fixtures::card_named("Forest");
+        let bears = fixtures::card_named("Grizzly Bears");
+        state.permanents.add_copies(forest, 4);
+        state.hand.add_copies(bears, 3);
+
+        // four forests pay for two bears, each tapping its own pair
+        let plays = play_a_card(&state, &utility_functions::mana_value);
+        assert_eq!(plays.len(), 2);
+        assert_eq!(plays.iter().map(|play| play.tapped.len()).sum::<usize>(), 4);
+        for play in plays {
+            state.play_card(play);
+        }
+
+        assert_eq!(state.turn_state.num_tapped(forest), 4);
+        assert_eq!(state.mana_sources().count(), 0);
+        // the third bears can not be paid for with mana that was already spent
+        assert!(play_a_card(&state, &utility_functions::mana_value).is_empty());
+        assert_eq!(state.hand.count_of(bears), 1);
+    }
+
+    #[test]
+    fn test_tapped_dual_is_played_before_it_is_needed() {
+        let deck = Deck {
+            command_zone: CommandZone::empty(),
+            deck: UnorderedPile::empty(),
+        };
+        let mut state = State::new(deck, PlayDraw::Random, &mut Rand::seed_from_u64(0));
+        let forest = fixtures::card_named("Forest");
+        let guildgate = fixtures::card_named("Selesnya Guildgate");
+        let watchwolf = fixtures::card_named("Watchwolf");
+        state.hand.add(forest);
+        state.hand.add(guildgate);
+        state.hand.add(watchwolf);
+
+        // nothing is castable on turn one either way, but only the guildgate leaves both colors untapped on turn two
+        let plays = play_a_land_and_a_card(&state, &utility_functions::mana_value_or_fixed_land, LandDropTieBreak::CastableNextTurn);
+        assert_eq!(plays.iter().map(|play| play.card).collect_vec(), vec![guildgate]);
+        for play in plays {
+            state.play_card(play);
+        }
+        state.end_turn();
+
+        let plays = play_a_land_and_a_card(&state, &utility_functions::mana_value_or_fixed_land, LandDropTieBreak::CastableNextTurn);
+        assert_eq!(plays.iter().map(|play| play.card).collect_vec(), vec![forest, watchwolf]);
+    }
+
+    #[test]
+    fn test_land_drop_tie_break_can_be_turned_off() {
+        let deck = Deck {
+            command_zone: CommandZone::empty(),
+            deck: UnorderedPile::empty(),
+        };
+        let mut state = State::new(deck, PlayDraw::Random, &mut Rand::seed_from_u64(0));
+        let forest = fixtures::card_named("Forest");
+        state.hand.add(forest);
+        state.hand.add(fixtures::card_named("Selesnya Guildgate"));
+        state.hand.add(fixtures::card_named("Watchwolf"));
+
+        let plays = play_a_land_and_a_card(&state, &utility_functions::mana_value_or_fixed_land, LandDropTieBreak::None);
+
+        // without looking ahead, the first of the equally good land drops is kept, even though it is not the guildgate
+        assert_eq!(state.legal_land_drops().next().map(|play| play.card), Some(forest));
+        assert_eq!(plays.iter().map(|play| play.card).collect_vec(), vec![forest]);
+    }
+}
@@ -1,9 +1,12 @@
-use itertools::Itertools;
+use std::collections::HashSet;
 
 use crate::collection::Card;
 use crate::game::mana::ManaPool;
+use crate::game::mana::ManaAbility;
 use crate::game::mana::ManaCost;
 use crate::game::mana::ManaSource;
+use crate::game::mana::ManaType;
+use crate::game::mana::PHYREXIAN_LIFE_COST;
 
 /// Iterate over all the ways we can pay for a certain amount of generic mana with this mana pool.
 /// # Example
@@ -56,37 +59,123 @@ pub fn payment_methods_for_generic(available: &ManaPool, generic: u8) -> impl It
 /// assert_eq!(ways_to_pay.next(), None);
 ///
 /// ```
+///
+/// Phyrexian pips may also be paid with life, in which case they are left out of the payment.
+/// Payments that use more mana (and less life) come first.
+/// ```
+/// use deck_optim::game::mana::ManaPool;
+/// use deck_optim::game::mana::ManaCost;
+/// use deck_optim::strategies::payment_solver;
+///
+/// let available = ManaPool::try_parse("{R}").expect("should parse");
+/// let cost = ManaCost::try_parse("{R/P}").expect("should parse");
+///
+/// let mut ways_to_pay = payment_solver::payment_methods_for(&available, &cost);
+///
+/// assert_eq!(ways_to_pay.next(), Some(ManaPool::red(1)));
+/// assert_eq!(ways_to_pay.next(), Some(ManaPool::empty())); // pay 2 life instead
+/// assert_eq!(ways_to_pay.next(), None);
+/// ```
 pub fn payment_methods_for(available: &ManaPool, cost: &ManaCost) -> impl Iterator<Item = ManaPool> {
-    // first, pay off the colored portion
-    let Some(remaining) = *available - cost.colors else {
-        // unable to pay because of the colored mana requirements
-        return vec![].into_iter();
-    };
+    payment_methods_with_snow(available, &ManaPool::empty(), cost)
+}
+
+/// Iterate over all possible ways we can pay this cost given the available mana,
+/// where `snow` is the part of `available` that was produced by snow sources.
+/// Each {S} pip must be paid with snow mana.
+/// ```
+/// use deck_optim::game::mana::ManaPool;
+/// use deck_optim::game::mana::ManaCost;
+/// use deck_optim::strategies::payment_solver;
+///
+/// let available = ManaPool::try_parse("{G}{G}").expect("should parse");
+/// let cost = ManaCost::try_parse("{S}{G}").expect("should parse");
+///
+/// assert_eq!(payment_solver::payment_methods_with_snow(&available, &ManaPool::green(1), &cost).next(), Some(ManaPool::green(2)));
+/// assert_eq!(payment_solver::payment_methods_with_snow(&available, &ManaPool::empty(), &cost).next(), None);
+/// ```
+pub fn payment_methods_with_snow(available: &ManaPool, snow: &ManaPool, cost: &ManaCost) -> impl Iterator<Item = ManaPool> {
+    let mut solutions = vec![];
 
-    // now, the question is: how many ways can the generic portion be payed off?
-    let solutions = payment_methods_for_generic(&remaining, cost.generic)
-        .map(|payment| payment + cost.colors)
-        .collect_vec();
+    // pay off the snow pips first, since only some of our mana can go towards them
+    for snow_payment in payment_methods_for_generic(snow, cost.s_count) {
+        let Some(remaining) = *available - snow_payment else {
+            log::error!("snow mana {snow} is not part of the available mana {available}");
+            continue;
+        };
+        let next_solutions = payment_methods_without_snow(&remaining, cost)
+            .into_iter()
+            .map(|payment| payment + snow_payment);
+        solutions.extend(next_solutions);
+    }
 
-    // coerce the types so it's the same as above
     solutions.into_iter()
 }
 
+/// All the ways to pay for everything in `cost` except for its {S} pips.
+fn payment_methods_without_snow(available: &ManaPool, cost: &ManaCost) -> Vec<ManaPool> {
+    let mut solutions = vec![];
+
+    for paid_with_mana in phyrexian_splits(&cost.phyrexian) {
+        let colors = cost.colors + paid_with_mana;
+
+        // first, pay off the colored portion
+        let Some(remaining) = *available - colors else {
+            // unable to pay because of the colored mana requirements
+            continue;
+        };
+
+        // now, the question is: how many ways can the generic portion be payed off?
+        let next_solutions = payment_methods_for_generic(&remaining, cost.generic)
+            .map(|payment| payment + colors);
+        solutions.extend(next_solutions);
+    }
+
+    solutions
+}
+
+/// All the ways to choose which phyrexian pips get paid for with mana (the rest are paid with life).
+/// Sorted so that paying entirely with mana comes first.
+fn phyrexian_splits(phyrexian: &ManaPool) -> Vec<ManaPool> {
+    let mut splits = vec![ManaPool::empty()];
+    for &mt in ManaType::all() {
+        splits = splits
+            .into_iter()
+            .flat_map(|split| (0..=phyrexian[mt]).map(move |amount| {
+                let mut next = split;
+                next[mt] = amount;
+                next
+            }))
+            .collect();
+    }
+    splits.sort_by_key(|split| std::cmp::Reverse(split.mana_value()));
+    splits
+}
+
+/// How much life is paid when `payment` is used to pay for `cost`.
+/// Any pips of the cost not covered by the payment must be phyrexian pips paid with life.
+pub fn life_paid_for(cost: &ManaCost, payment: &ManaPool) -> u8 {
+    cost.mana_value().saturating_sub(payment.mana_value()) * PHYREXIAN_LIFE_COST
+}
+
 
 /// A payment solution is a list of 
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct PaymentSolution {
     pub cards_to_tap: Vec<(Card, ManaPool)>,
     pub mana_used: ManaPool,
+    /// Life paid in place of phyrexian mana
+    pub life_paid: u8,
 }
 impl PaymentSolution {
     fn new() -> Self {
         Self {
             cards_to_tap: Vec::with_capacity(3),
-            mana_used: ManaPool::empty()
+            mana_used: ManaPool::empty(),
+            life_paid: 0,
         }
     }
-    fn add(&mut self, card: Card, mana: ManaPool) {
+    pub fn add(&mut self, card: Card, mana: ManaPool) {
         self.mana_used = self.mana_used + mana;
         self.cards_to_tap.push((card, mana));
     }
@@ -95,6 +184,13 @@ impl PaymentSolution {
         next.add(card, mana);
         next
     }
+    /// Feed `input` from the mana we already have into the card's ability, which adds `output`.
+    fn with_activation(&self, card: Card, input: ManaPool, output: ManaPool) -> Option<Self> {
+        let mut next = self.clone();
+        next.mana_used = (next.mana_used - input)?;
+        next.add(card, output);
+        Some(next)
+    }
 }
 
 /// Construct a possible way to pay for the given mana cost, using a list of mana sources.
@@ -115,11 +211,13 @@ impl PaymentSolution {
 /// let mut mana_sources = vec![
 ///     ManaSource {
 ///         card: mock_forest,
-///         produces: vec![ManaPool::green(1)]
+///         produces: vec![ManaPool::green(1).into()],
+///         produces_snow: false,
 ///     },
 ///     ManaSource {
 ///         card: mock_taiga,
-///         produces: vec![ManaPool::red(1), ManaPool::green(1)]
+///         produces: vec![ManaPool::red(1).into(), ManaPool::green(1).into()],
+///         produces_snow: false,
 ///     }
 /// ];
 ///
@@ -133,55 +231,229 @@ impl PaymentSolution {
 ///         (mock_forest, ManaPool::green(1)),
 ///         (mock_taiga, ManaPool::red(1))
 ///     ],
-///     mana_used: ManaPool::try_parse("{R}{G}").expect("should parse")
+///     mana_used: ManaPool::try_parse("{R}{G}").expect("should parse"),
+///     life_paid: 0,
 /// };
 ///
 /// assert_eq!(solution, expected_solution);
 /// assert_eq!(0, unused_sources.len());
 /// ```
 pub fn autotap_pay_for(mut available_mana: Vec<ManaSource>, cost: &ManaCost) -> Option<(PaymentSolution, Vec<ManaSource>)> {
-    let mut partial_soln = PaymentSolution::new();
-    // first, we tap everything that only can tap for one thing.
-    // this reduces the size of the list and prevents unnecessary recursion
-    
-    // TODO: only tap what is needed
-    available_mana.retain(|mana_source| {
-        match &mana_source.produces[..] {
-            &[] => false,
-            &[mana] => {
-                partial_soln.add(mana_source.card, mana);
-                false
+    let max_taps = prepare_sources(&mut available_mana, cost);
+
+    /// Lower is better: pay as little life as possible, then leave the most flexible sources
+    /// untapped, then tap as few sources and as little mana as possible.
+    type SolutionRank = (u8, std::cmp::Reverse<usize>, usize, u8);
+
+    struct Search<'a> {
+        available_mana: &'a [ManaSource],
+        cost: &'a ManaCost,
+        max_taps: usize,
+        best: Option<(SolutionRank, PaymentSolution, Vec<bool>)>,
+    }
+
+    impl Search<'_> {
+        fn visit(&mut self, idx: usize, partial_soln: &PaymentSolution, snow: ManaPool, tapped: &mut Vec<bool>) {
+            if let Some(payment) = payment_methods_with_snow(&partial_soln.mana_used, &snow, self.cost).next() {
+                let mut solution = partial_soln.clone();
+                solution.life_paid = life_paid_for(self.cost, &payment);
+                let rank = (
+                    solution.life_paid,
+                    std::cmp::Reverse(self.colors_left(tapped)),
+                    solution.cards_to_tap.len(),
+                    solution.mana_used.mana_value(),
+                );
+                let life_paid = solution.life_paid;
+                if self.best.as_ref().is_none_or(|(best_rank, _, _)| rank < *best_rank) {
+                    self.best = Some((rank, solution, tapped.clone()));
+                }
+                // tapping anything else would only be wasteful, unless it saves us some life
+                if life_paid == 0 {
+                    return;
+                }
+            }
+
+            if idx >= self.available_mana.len() || partial_soln.cards_to_tap.len() >= self.max_taps {
+                return;
+            }
+
+            let source = &self.available_mana[idx];
+            // try tapping this source for each thing it can produce
+            tapped[idx] = true;
+            for ability in source.produces.iter() {
+                for (next, next_snow) in activations(source, ability, partial_soln, snow) {
+                    self.visit(idx + 1, &next, next_snow, tapped);
+                }
             }
-            _ => true,
+            // or leave it untapped
+            tapped[idx] = false;
+            self.visit(idx + 1, partial_soln, snow, tapped);
         }
-    });
-
-    fn _autotap_recursive(partial_soln: PaymentSolution, mut available_mana: Vec<ManaSource>, cost: &ManaCost) -> Option<(PaymentSolution, Vec<ManaSource>)> {
-        // if we can pay for the cost already, do so and we are done.
-        // this should be done before going into each source, because we might have enough floating
-        // mana already to pay for the cost
-        if payment_methods_for(&partial_soln.mana_used, cost).next().is_some() {
-            return Some((partial_soln, available_mana));
+
+        /// The number of distinct types of mana the untapped sources could still produce
+        fn colors_left(&self, tapped: &[bool]) -> usize {
+            ManaType::all()
+                .iter()
+                .filter(|&&mt| {
+                    self.available_mana
+                        .iter()
+                        .zip(tapped)
+                        .filter(|(_, &is_tapped)| !is_tapped)
+                        .any(|(source, _)| source.produces.iter().any(|ability| ability.output[mt] > 0))
+                })
+                .count()
         }
+    }
 
-        let Some(new_source) = available_mana.pop() else {
-            return None; // no more mana to use
-        };
-        // try to tap the first one, then tap the second
-        for payment in new_source.produces.iter() {
+    let mut search = Search {
+        available_mana: &available_mana,
+        cost,
+        max_taps,
+        best: None,
+    };
+    let mut tapped = vec![false; available_mana.len()];
+    search.visit(0, &PaymentSolution::new(), ManaPool::empty(), &mut tapped);
+
+    let (_, solution, tapped) = search.best?;
+    let unused_sources = available_mana
+        .into_iter()
+        .zip(tapped)
+        .filter(|(_, is_tapped)| !is_tapped)
+        .map(|(source, _)| source)
+        .collect();
+
+    Some((solution, unused_sources))
+}
 
-            let next = partial_soln.with_payment(new_source.card, *payment);
+/// Lazily find every distinct way to tap the mana sources to pay for the cost.
+///
+/// Solutions which tap the same cards, even for different mana, are only yielded once. As with
+/// [`autotap_pay_for`], a solution never taps more sources than it needs to, unless that saves some life.
+///
+/// # Example
+/// ```
+/// use deck_optim::strategies::payment_solver;
+/// use deck_optim::game::mana::ManaPool;
+/// use deck_optim::game::mana::ManaCost;
+/// use deck_optim::game::mana::ManaSource;
+///
+/// let [mock_forest, mock_taiga] = deck_optim::collection::get_sample_cards_static::<2>();
+///
+/// let mana_sources = vec![
+///     ManaSource {
+///         card: mock_forest,
+///         produces: vec![ManaPool::green(1).into()],
+///         produces_snow: false,
+///     },
+///     ManaSource {
+///         card: mock_taiga,
+///         produces: vec![ManaPool::red(1).into(), ManaPool::green(1).into()],
+///         produces_snow: false,
+///     }
+/// ];
+///
+/// let cost_to_pay = ManaCost::try_parse("{G}").expect("should parse");
+///
+/// let solutions: Vec<_> = payment_solver::all_autotap_solutions(mana_sources, &cost_to_pay).collect();
+///
+/// assert_eq!(solutions.len(), 2);
+/// assert_eq!(solutions[0].cards_to_tap, vec![(mock_forest, ManaPool::green(1))]);
+/// assert_eq!(solutions[1].cards_to_tap, vec![(mock_taiga, ManaPool::green(1))]);
+/// ```
+pub fn all_autotap_solutions(mut available_mana: Vec<ManaSource>, cost: &ManaCost) -> impl Iterator<Item = PaymentSolution> + use<'_> {
+    let max_taps = prepare_sources(&mut available_mana, cost);
+
+    // the same search as `autotap_pay_for`, but depth first with an explicit stack so that it can be paused
+    let mut stack = vec![(0, PaymentSolution::new(), ManaPool::empty())];
+    let mut seen: HashSet<Vec<Card>> = HashSet::new();
+
+    std::iter::from_fn(move || {
+        while let Some((idx, partial_soln, snow)) = stack.pop() {
+            let mut found = None;
+            if let Some(payment) = payment_methods_with_snow(&partial_soln.mana_used, &snow, cost).next() {
+                let mut solution = partial_soln.clone();
+                solution.life_paid = life_paid_for(cost, &payment);
+                let life_paid = solution.life_paid;
+
+                let mut cards_tapped: Vec<Card> = solution.cards_to_tap.iter().map(|(card, _)| *card).collect();
+                cards_tapped.sort();
+                if seen.insert(cards_tapped) {
+                    found = Some(solution);
+                }
+                // tapping anything else would only be wasteful, unless it saves us some life
+                if life_paid == 0 {
+                    if found.is_some() {
+                        return found;
+                    }
+                    continue;
+                }
+            }
 
-            // TODO: is this too inefficient?
-            if let Some(solution) = _autotap_recursive(next, available_mana.clone(), cost) {
-                return Some(solution);
+            if idx < available_mana.len() && partial_soln.cards_to_tap.len() < max_taps {
+                let source = &available_mana[idx];
+                // pushed first so that it is popped last: try tapping the source before leaving it untapped
+                stack.push((idx + 1, partial_soln.clone(), snow));
+                let tapped: Vec<_> = source.produces
+                    .iter()
+                    .flat_map(|ability| activations(source, ability, &partial_soln, snow))
+                    .collect();
+                for (next, next_snow) in tapped.into_iter().rev() {
+                    stack.push((idx + 1, next, next_snow));
+                }
             }
-        }
 
+            if found.is_some() {
+                return found;
+            }
+        }
         None
+    })
+}
+
+/// Drops the sources that can never help pay, and puts the ones that convert mana last.
+/// Returns the most sources that a payment for `cost` could need to tap.
+fn prepare_sources(available_mana: &mut Vec<ManaSource>, cost: &ManaCost) -> usize {
+    // sources that produce nothing can never help pay
+    available_mana.retain(|mana_source| !mana_source.produces.is_empty());
+
+    // abilities with an input are paid for with mana from the sources tapped before them,
+    // so visit those last (the sort is stable, so the rest keep their order)
+    available_mana.sort_by_key(|mana_source| !mana_source.produces.iter().all(ManaAbility::is_free));
+    let num_costed_sources = available_mana
+        .iter()
+        .filter(|mana_source| !mana_source.produces.iter().all(ManaAbility::is_free))
+        .count();
+
+    // every free source produces at least one pip, so a solution never needs to tap more sources than
+    // the cost has pips, plus however many sources only convert mana. Anything more would leave a
+    // source tapped for nothing.
+    cost.mana_value() as usize + num_costed_sources
+}
+
+/// Every way to use `ability` of `source`, along with the snow mana we are left with.
+/// Abilities with an input can only be fed mana that the partial solution already has.
+fn activations(source: &ManaSource, ability: &ManaAbility, partial_soln: &PaymentSolution, snow: ManaPool) -> Vec<(PaymentSolution, ManaPool)> {
+    let with_output = |snow: ManaPool| if source.produces_snow { snow + ability.output } else { snow };
+
+    if ability.is_free() {
+        let next = partial_soln.with_payment(source.card, ability.output);
+        return vec![(next, with_output(snow))];
     }
 
-    _autotap_recursive(partial_soln, available_mana, cost)
+    payment_methods_without_snow(&partial_soln.mana_used, &ability.input)
+        .into_iter()
+        .filter(|input| input.mana_value() == ability.input.mana_value())
+        .filter_map(|input| {
+            let next = partial_soln.with_activation(source.card, input, ability.output)?;
+            let remaining = (partial_soln.mana_used - input)?;
+            // we choose which mana feeds the ability, so spend the non-snow mana first
+            let mut next_snow = snow;
+            for &mt in ManaType::all() {
+                next_snow[mt] = next_snow[mt].min(remaining[mt]);
+            }
+            Some((next, with_output(next_snow)))
+        })
+        .collect()
 }
 
 #[cfg(test)]
@@ -295,6 +567,28 @@ mod tests {
         assert_eq!(payment_methods.next(), None);
     }
 
+    #[test]
+    fn test_payment_method_colorless_pip_needs_colorless() {
+        let cost = ManaCost::try_parse("{1}{C}").expect("should parse");
+
+        let colored = ManaPool::try_parse("{G}{G}{R}").expect("should parse");
+        assert_eq!(payment_methods_for(&colored, &cost).next(), None);
+
+        let with_colorless = ManaPool::try_parse("{C}{G}").expect("should parse");
+        let payments: Vec<_> = payment_methods_for(&with_colorless, &cost).collect();
+        assert_eq!(payments, vec![with_colorless]);
+    }
+
+    #[test]
+    fn test_payment_method_colorless_pays_generic() {
+        let available = ManaPool::try_parse("{C}{C}{G}").expect("should parse");
+        let cost = ManaCost::try_parse("{2}{G}").expect("should parse");
+
+        let payments: Vec<_> = payment_methods_for(&available, &cost).collect();
+
+        assert_eq!(payments, vec![available]);
+    }
+
     #[test]
     fn test_payment_method_multiple_answers() {
         let available = ManaPool::try_parse("{W}{U}{B}{G}{G}").expect("should parse");
@@ -336,6 +630,64 @@ mod tests {
         assert_eq!(actual_answers, expected_answers);
     } 
 
+    #[test]
+    fn test_payment_method_colorless_not_paid_by_colors() {
+        let available = ManaPool::try_parse("{R}{R}").expect("should parse");
+        let cost = ManaCost::try_parse("{C}").expect("should parse");
+
+        let mut payment_methods = payment_methods_for(&available, &cost);
+
+        assert_eq!(payment_methods.next(), None);
+    }
+
+    #[test]
+    fn test_payment_method_colorless_paid_by_colorless() {
+        let available = ManaPool::try_parse("{C}{R}").expect("should parse");
+        let cost = ManaCost::try_parse("{C}").expect("should parse");
+
+        let payment_methods = payment_methods_for(&available, &cost).collect::<Vec<_>>();
+
+        assert_eq!(payment_methods, vec![ManaPool::colorless(1)]);
+    }
+
+    #[test]
+    fn test_payment_method_colorless_and_generic() {
+        let available = ManaPool::try_parse("{C}{R}{R}").expect("should parse");
+        let cost = ManaCost::try_parse("{1}{C}").expect("should parse");
+
+        let payment_methods = payment_methods_for(&available, &cost).collect::<Vec<_>>();
+
+        assert_eq!(payment_methods, vec![ManaPool::try_parse("{C}{R}").expect("should parse")]);
+    }
+
+    #[test]
+    fn test_autotap_colorless_taps_colorless_source() {
+        let [mock_mountain, mock_wastes] = collection::get_sample_cards_static::<2>();
+        let mana_sources = vec![
+            ManaSource {
+                card: mock_mountain,
+                produces: vec![ManaPool::red(1).into()],
+                produces_snow: false,
+            },
+            ManaSource {
+                card: mock_wastes,
+                produces: vec![ManaPool::colorless(1).into()],
+                produces_snow: false,
+            },
+        ];
+
+        let cost_to_pay = ManaCost::try_parse("{C}").expect("should parse");
+        let (solution, unused_sources) = autotap_pay_for(mana_sources.clone(), &cost_to_pay)
+            .expect("found a solution");
+
+        assert_eq!(solution.cards_to_tap, vec![(mock_wastes, ManaPool::colorless(1))]);
+        assert_eq!(unused_sources.len(), 1);
+        assert_eq!(unused_sources[0].card, mock_mountain);
+
+        let only_mountain = mana_sources.into_iter().take(1).collect();
+        assert!(autotap_pay_for(only_mountain, &cost_to_pay).is_none());
+    }
+
     #[test]
     fn test_autotap_simple_match() {
         let [mock_mountain] = collection::get_sample_cards_static::<1>();
@@ -343,7 +695,8 @@ mod tests {
         let mana_sources = vec![
             ManaSource {
                 card: mock_mountain,
-                produces: vec![ManaPool::red(1)]
+                produces: vec![ManaPool::red(1).into()],
+                produces_snow: false,
             }
         ];
 
@@ -356,7 +709,8 @@ mod tests {
             cards_to_tap: vec![
                 (mock_mountain, ManaPool::red(1))
             ],
-            mana_used: ManaPool::red(1)
+            mana_used: ManaPool::red(1),
+            life_paid: 0,
         };
 
         assert_eq!(solution, expected_solution);
@@ -370,7 +724,8 @@ mod tests {
         let mana_sources = vec![
             ManaSource {
                 card: mock_ancient_tomb,
-                produces: vec![ManaPool::colorless(2)]
+                produces: vec![ManaPool::colorless(2).into()],
+                produces_snow: false,
             }
         ];
 
@@ -383,7 +738,8 @@ mod tests {
             cards_to_tap: vec![
                 (mock_ancient_tomb, ManaPool::colorless(2))  // Overpaying, but necessary
             ],
-            mana_used: ManaPool::colorless(2)
+            mana_used: ManaPool::colorless(2),
+            life_paid: 0,
         };
 
         assert_eq!(solution, expected_solution);
@@ -398,7 +754,8 @@ mod tests {
         let mana_sources = vec![
             ManaSource {
                 card: mock_forest,
-                produces: vec![ManaPool::green(1)]
+                produces: vec![ManaPool::green(1).into()],
+                produces_snow: false,
             }
         ];
 
@@ -416,11 +773,13 @@ mod tests {
         let mana_sources = vec![
             ManaSource {
                 card: mock_forest,
-                produces: vec![ManaPool::green(1)]
+                produces: vec![ManaPool::green(1).into()],
+                produces_snow: false,
             },
             ManaSource {
                 card: mock_taiga,
-                produces: vec![ManaPool::red(1), ManaPool::green(1)]
+                produces: vec![ManaPool::red(1).into(), ManaPool::green(1).into()],
+                produces_snow: false,
             }
         ];
 
@@ -434,7 +793,8 @@ mod tests {
                 (mock_forest, ManaPool::green(1)),
                 (mock_taiga, ManaPool::red(1))
             ],
-            mana_used: ManaPool::try_parse("{R}{G}").expect("should parse")
+            mana_used: ManaPool::try_parse("{R}{G}").expect("should parse"),
+            life_paid: 0,
         };
 
         assert_eq!(solution, expected_solution);
@@ -448,11 +808,13 @@ mod tests {
         let mana_sources = vec![
             ManaSource {
                 card: mock_forest,
-                produces: vec![ManaPool::green(1)]
+                produces: vec![ManaPool::green(1).into()],
+                produces_snow: false,
             },
             ManaSource {
                 card: mock_taiga,
-                produces: vec![ManaPool::red(1), ManaPool::green(1)]
+                produces: vec![ManaPool::red(1).into(), ManaPool::green(1).into()],
+                produces_snow: false,
             }
         ];
 
@@ -470,15 +832,18 @@ mod tests {
         let mana_sources = vec![
             ManaSource {
                 card: mock_forest,
-                produces: vec![ManaPool::green(1)]
+                produces: vec![ManaPool::green(1).into()],
+                produces_snow: false,
             },
             ManaSource {
                 card: mock_taiga,
-                produces: vec![ManaPool::red(1), ManaPool::green(1)]
+                produces: vec![ManaPool::red(1).into(), ManaPool::green(1).into()],
+                produces_snow: false,
             },
             ManaSource {
                 card: mock_other,
-                produces: vec![]
+                produces: vec![],
+                produces_snow: false,
             }
         ];
 
@@ -492,12 +857,364 @@ mod tests {
                 (mock_forest, ManaPool::green(1)),
                 (mock_taiga, ManaPool::red(1))
             ],
-            mana_used: ManaPool::try_parse("{R}{G}").expect("should parse")
+            mana_used: ManaPool::try_parse("{R}{G}").expect("should parse"),
+            life_paid: 0,
         };
 
         assert_eq!(solution, expected_solution);
         assert_eq!(0, unused_sources.len());
     }
 
-}
+    #[test]
+    fn test_payment_method_phyrexian_from_empty_pool() {
+        let available = ManaPool::empty();
+        let cost = ManaCost::try_parse("{R/P}").expect("should parse");
+
+        let mut payment_methods = payment_methods_for(&available, &cost);
+
+        assert_eq!(payment_methods.next(), Some(ManaPool::empty()));
+        assert_eq!(payment_methods.next(), None);
+    }
+
+    #[test]
+    fn test_payment_method_phyrexian_and_generic() {
+        let available = ManaPool::try_parse("{R}{G}").expect("should parse");
+        let cost = ManaCost::try_parse("{1}{R/P}").expect("should parse");
 
+        let payment_methods = payment_methods_for(&available, &cost);
+        let actual_answers: Vec<_> = payment_methods.collect();
+
+        let expected_answers = vec![
+            ManaPool::try_parse("{R}{G}").expect("should parse"),
+            ManaPool::try_parse("{G}").expect("should parse"),
+            ManaPool::try_parse("{R}").expect("should parse"),
+        ];
+
+        assert_eq!(actual_answers, expected_answers);
+    }
+
+    #[test]
+    fn test_autotap_phyrexian_with_life() {
+        let cost_to_pay = ManaCost::try_parse("{R/P}").expect("should parse");
+
+        let (solution, unused_sources) = autotap_pay_for(vec![], &cost_to_pay)
+            .expect("found a solution");
+
+        let expected_solution = PaymentSolution {
+            cards_to_tap: vec![],
+            mana_used: ManaPool::empty(),
+            life_paid: 2,
+        };
+
+        assert_eq!(solution, expected_solution);
+        assert_eq!(0, unused_sources.len());
+    }
+
+    #[test]
+    fn test_autotap_phyrexian_prefers_mana() {
+        let [mock_mountain] = collection::get_sample_cards_static::<1>();
+
+        let mana_sources = vec![
+            ManaSource {
+                card: mock_mountain,
+                produces: vec![ManaPool::red(1).into()],
+                produces_snow: false,
+            }
+        ];
+
+        let cost_to_pay = ManaCost::try_parse("{R/P}").expect("should parse");
+
+        let (solution, _) = autotap_pay_for(mana_sources, &cost_to_pay)
+            .expect("found a solution");
+
+        assert_eq!(solution.mana_used, ManaPool::red(1));
+        assert_eq!(solution.life_paid, 0);
+    }
+
+    fn mountain_and_taiga() -> (Card, Card, Vec<ManaSource>) {
+        let [mock_mountain, mock_taiga] = collection::get_sample_cards_static::<2>();
+
+        let mana_sources = vec![
+            ManaSource {
+                card: mock_mountain,
+                produces: vec![ManaPool::red(1).into()],
+                produces_snow: false,
+            },
+            ManaSource {
+                card: mock_taiga,
+                produces: vec![ManaPool::red(1).into(), ManaPool::green(1).into()],
+                produces_snow: false,
+            }
+        ];
+
+        (mock_mountain, mock_taiga, mana_sources)
+    }
+
+    #[test]
+    fn test_autotap_keeps_dual_land_untapped() {
+        let (mock_mountain, mock_taiga, mana_sources) = mountain_and_taiga();
+
+        let cost_to_pay = ManaCost::try_parse("{R}").expect("should parse");
+
+        let (solution, unused_sources) = autotap_pay_for(mana_sources, &cost_to_pay)
+            .expect("found a solution");
+
+        assert_eq!(solution.cards_to_tap, vec![(mock_mountain, ManaPool::red(1))]);
+        assert_eq!(unused_sources.len(), 1);
+        assert_eq!(unused_sources[0].card, mock_taiga);
+    }
+
+    #[test]
+    fn test_autotap_keeps_dual_land_untapped_regardless_of_order() {
+        let (mock_mountain, mock_taiga, mut mana_sources) = mountain_and_taiga();
+        mana_sources.reverse();
+
+        let cost_to_pay = ManaCost::try_parse("{R}").expect("should parse");
+
+        let (solution, unused_sources) = autotap_pay_for(mana_sources, &cost_to_pay)
+            .expect("found a solution");
+
+        assert_eq!(solution.cards_to_tap, vec![(mock_mountain, ManaPool::red(1))]);
+        assert_eq!(unused_sources.len(), 1);
+        assert_eq!(unused_sources[0].card, mock_taiga);
+    }
+
+    #[test]
+    fn test_autotap_only_taps_what_is_needed() {
+        let (mock_mountain, _, mut mana_sources) = mountain_and_taiga();
+        mana_sources.push(ManaSource {
+            card: mock_mountain,
+            produces: vec![ManaPool::red(1).into()],
+            produces_snow: false,
+        });
+
+        let cost_to_pay = ManaCost::try_parse("{G}").expect("should parse");
+
+        let (solution, unused_sources) = autotap_pay_for(mana_sources, &cost_to_pay)
+            .expect("found a solution");
+
+        assert_eq!(solution.mana_used, ManaPool::green(1));
+        assert_eq!(unused_sources.len(), 2);
+    }
+
+    fn snow_and_plain_forest() -> (Card, Card, Vec<ManaSource>) {
+        let [mock_forest, mock_snow_forest] = collection::get_sample_cards_static::<2>();
+
+        let mana_sources = vec![
+            ManaSource {
+                card: mock_forest,
+                produces: vec![ManaPool::green(1).into()],
+                produces_snow: false,
+            },
+            ManaSource {
+                card: mock_snow_forest,
+                produces: vec![ManaPool::green(1).into()],
+                produces_snow: true,
+            }
+        ];
+
+        (mock_forest, mock_snow_forest, mana_sources)
+    }
+
+    #[test]
+    fn test_autotap_pays_snow_with_snow_source() {
+        let (mock_forest, mock_snow_forest, mana_sources) = snow_and_plain_forest();
+
+        let cost_to_pay = ManaCost::try_parse("{S}{G}").expect("should parse");
+
+        let (solution, unused_sources) = autotap_pay_for(mana_sources, &cost_to_pay)
+            .expect("found a solution");
+
+        assert_eq!(solution.mana_used, ManaPool::green(2));
+        assert_eq!(solution.cards_to_tap, vec![
+            (mock_forest, ManaPool::green(1)),
+            (mock_snow_forest, ManaPool::green(1)),
+        ]);
+        assert_eq!(unused_sources.len(), 0);
+    }
+
+    #[test]
+    fn test_autotap_snow_without_snow_sources() {
+        let (_, _, mut mana_sources) = snow_and_plain_forest();
+        mana_sources.pop();
+        mana_sources.push(mana_sources[0].clone());
+
+        let cost_to_pay = ManaCost::try_parse("{S}{G}").expect("should parse");
+
+        assert_eq!(autotap_pay_for(mana_sources, &cost_to_pay), None);
+    }
+
+    fn filter_land_and_plains() -> (Card, Card, Vec<ManaSource>) {
+        let [mock_filter_land, mock_plains] = collection::get_sample_cards_static::<2>();
+
+        // the filter land comes first, even though it needs the plains to pay for it
+        let mana_sources = vec![
+            ManaSource {
+                card: mock_filter_land,
+                produces: vec![ManaAbility::try_parse("{W}: {U}{U}").expect("should parse")],
+                produces_snow: false,
+            },
+            ManaSource {
+                card: mock_plains,
+                produces: vec![ManaPool::white(1).into()],
+                produces_snow: false,
+            }
+        ];
+
+        (mock_filter_land, mock_plains, mana_sources)
+    }
+
+    #[test]
+    fn test_autotap_filter_land_with_colored_input() {
+        let (mock_filter_land, mock_plains, mana_sources) = filter_land_and_plains();
+
+        let cost_to_pay = ManaCost::try_parse("{U}{U}").expect("should parse");
+
+        let (solution, unused_sources) = autotap_pay_for(mana_sources, &cost_to_pay)
+            .expect("found a solution");
+
+        assert_eq!(solution.mana_used, ManaPool::blue(2));
+        assert_eq!(solution.cards_to_tap, vec![
+            (mock_plains, ManaPool::white(1)),
+            (mock_filter_land, ManaPool::blue(2)),
+        ]);
+        assert_eq!(unused_sources.len(), 0);
+    }
+
+    #[test]
+    fn test_autotap_filter_land_without_input() {
+        let (_, _, mut mana_sources) = filter_land_and_plains();
+        mana_sources.pop();
+
+        let cost_to_pay = ManaCost::try_parse("{U}").expect("should parse");
+
+        assert_eq!(autotap_pay_for(mana_sources, &cost_to_pay), None);
+    }
+
+    #[test]
+    fn test_autotap_filter_land_input_is_spent() {
+        let (_, _, mana_sources) = filter_land_and_plains();
+
+        // the plains can pay for the white pip, or feed the filter land, but not both
+        let cost_to_pay = ManaCost::try_parse("{W}{U}").expect("should parse");
+
+        assert_eq!(autotap_pay_for(mana_sources, &cost_to_pay), None);
+    }
+
+    #[test]
+    fn test_autotap_zero_cost_ability() {
+        let [mock_forest] = collection::get_sample_cards_static::<1>();
+        let ability = ManaAbility::try_parse("{0}: {G}").expect("should parse");
+        assert!(ability.is_free());
+
+        let mana_sources = vec![
+            ManaSource {
+                card: mock_forest,
+                produces: vec![ability],
+                produces_snow: false,
+            }
+        ];
+
+        let cost_to_pay = ManaCost::try_parse("{G}").expect("should parse");
+
+        let (solution, _) = autotap_pay_for(mana_sources, &cost_to_pay)
+            .expect("found a solution");
+
+        assert_eq!(solution.cards_to_tap, vec![(mock_forest, ManaPool::green(1))]);
+    }
+
+    #[test]
+    fn test_payment_methods_for_snow_needs_snow_mana() {
+        let available = ManaPool::try_parse("{R}{G}").expect("should parse");
+        let cost = ManaCost::try_parse("{S}").expect("should parse");
+
+        assert_eq!(payment_methods_for(&available, &cost).next(), None);
+
+        let payments: Vec<_> = payment_methods_with_snow(&available, &ManaPool::red(1), &cost).collect();
+        assert_eq!(payments, vec![ManaPool::red(1)]);
+    }
+
+    /// Two forests, a taiga, and a mountain, where the forests are interchangeable
+    fn redundant_board() -> ([Card; 3], Vec<ManaSource>) {
+        let [mock_forest, mock_taiga, mock_mountain] = collection::get_sample_cards_static::<3>();
+        let forest = ManaSource {
+            card: mock_forest,
+            produces: vec![ManaPool::green(1).into()],
+            produces_snow: false,
+        };
+        let mana_sources = vec![
+            forest.clone(),
+            forest,
+            ManaSource {
+                card: mock_taiga,
+                produces: vec![ManaPool::red(1).into(), ManaPool::green(1).into()],
+                produces_snow: false,
+            },
+            ManaSource {
+                card: mock_mountain,
+                produces: vec![ManaPool::red(1).into()],
+                produces_snow: false,
+            },
+        ];
+        ([mock_forest, mock_taiga, mock_mountain], mana_sources)
+    }
+
+    fn cards_tapped(solution: &PaymentSolution) -> Vec<Card> {
+        let mut cards: Vec<Card> = solution.cards_to_tap.iter().map(|(card, _)| *card).collect();
+        cards.sort();
+        cards
+    }
+
+    #[test]
+    fn test_all_autotap_solutions_with_redundant_sources() {
+        let ([forest, taiga, mountain], mana_sources) = redundant_board();
+        let cost_to_pay = ManaCost::try_parse("{R}{G}").expect("should parse");
+
+        let solutions: Vec<_> = all_autotap_solutions(mana_sources, &cost_to_pay).collect();
+        let actual: HashSet<Vec<Card>> = solutions.iter().map(cards_tapped).collect();
+
+        let mut expected: HashSet<Vec<Card>> = HashSet::new();
+        for mut pair in [vec![forest, taiga], vec![forest, mountain], vec![taiga, mountain]] {
+            pair.sort();
+            expected.insert(pair);
+        }
+
+        assert_eq!(solutions.len(), 3, "solutions tapping the same cards should only be found once");
+        assert_eq!(actual, expected);
+        for solution in solutions.iter() {
+            assert_eq!(solution.mana_used, ManaPool::try_parse("{R}{G}").expect("should parse"));
+            assert_eq!(solution.life_paid, 0);
+        }
+    }
+
+    #[test]
+    fn test_all_autotap_solutions_single_pip() {
+        let ([forest, taiga, _], mana_sources) = redundant_board();
+        let cost_to_pay = ManaCost::try_parse("{G}").expect("should parse");
+
+        let solutions: Vec<_> = all_autotap_solutions(mana_sources, &cost_to_pay)
+            .map(|solution| cards_tapped(&solution))
+            .collect();
+
+        assert_eq!(solutions, vec![vec![forest], vec![taiga]]);
+    }
+
+    #[test]
+    fn test_all_autotap_solutions_includes_the_autotap_choice() {
+        let (_, mana_sources) = redundant_board();
+        let cost_to_pay = ManaCost::try_parse("{1}{R}").expect("should parse");
+
+        let (best, _) = autotap_pay_for(mana_sources.clone(), &cost_to_pay).expect("found a solution");
+        let solutions: Vec<_> = all_autotap_solutions(mana_sources, &cost_to_pay).collect();
+
+        assert!(solutions.iter().any(|solution| cards_tapped(solution) == cards_tapped(&best)));
+    }
+
+    #[test]
+    fn test_all_autotap_solutions_none_possible() {
+        let (_, mana_sources) = redundant_board();
+        let cost_to_pay = ManaCost::try_parse("{U}").expect("should parse");
+
+        assert_eq!(all_autotap_solutions(mana_sources, &cost_to_pay).count(), 0);
+    }
+}
@@ -0,0 +1,60 @@
+//! A local http server that stands in for scryfall and the deck builders in tests, so that they do not need the network.
+
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::TcpListener;
+use std::sync::mpsc::{self, Receiver};
+
+/// A canned http response
+#[derive(Clone,Copy,Debug)]
+pub(crate) struct Response {
+    status: &'static str,
+    content_type: &'static str,
+    body: &'static str,
+}
+
+impl Response {
+    /// A successful response with a json body
+    pub(crate) fn json(body: &'static str) -> Self {
+        Self { status: "200 OK", content_type: "application/json", body }
+    }
+
+    /// A plain text response with this status, like `404 Not Found`
+    pub(crate) fn text(status: &'static str, body: &'static str) -> Self {
+        Self { status, content_type: "text/plain", body }
+    }
+}
+
+/// Serve each of these responses in turn, one per connection, returning the endpoint to request them from.
+/// The requests are sent back over the channel, so that tests can check what was asked for.
+pub(crate) fn serve(responses: Vec<Response>) -> (String, Receiver<String>) {
+    let listener = TcpListener::bind("127.0.0.1:0").expect("to bind a local port");
+    let addr = listener.local_addr().expect("to have a local address");
+    let (requests, received) = mpsc::channel();
+    std::thread::spawn(move || {
+        for Response { status, content_type, body } in responses {
+            let (stream, _) = listener.accept().expect("to accept a connection");
+            let mut reader = BufReader::new(stream);
+            let mut request = String::new();
+            let mut content_length = 0;
+            let mut line = String::new();
+            // read the headers up to the blank line that ends them, then the body
+            while reader.read_line(&mut line).is_ok_and(|n| n > 0) && line != "\r\n" {
+                if let Some(length) = line.to_lowercase().strip_prefix("content-length:") {
+                    content_length = length.trim().parse().expect("a valid content length");
+                }
+                request.push_str(&line);
+                line.clear();
+            }
+            let mut request_body = vec![0; content_length];
+            reader.read_exact(&mut request_body).expect("to read the request body");
+            request.push_str(&String::from_utf8_lossy(&request_body));
+            // tests that do not check the requests may have hung up already
+            requests.send(request).ok();
+
+            let mut stream = reader.into_inner();
+            write!(stream, "HTTP/1.1 {status}\r\nContent-Type: {content_type}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}", body.len())
+                .expect("to write the response");
+        }
+    });
+    (format!("http://{addr}"), received)
+}
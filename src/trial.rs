@@ -1,10 +1,16 @@
-use rand::SeedableRng;
+use std::fmt;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
 
+use rand::{Rng, SeedableRng};
+
+use crate::collection::Card;
 use crate::game::annotations::AnnotationValue;
-use crate::game::Deck;
+use crate::game::{CardType, Deck, OrderedPile};
+use crate::game::card_play::CardPlay;
 use crate::game::Library;
 use crate::game::Hand;
-use crate::game::state::State;
+use crate::game::state::{PlayDraw, State};
 use crate::strategies::Strategy;
 use crate::watcher::Watcher;
 use crate::metrics::MetricsData;
@@ -15,22 +21,122 @@ pub type Rand = rand::rngs::StdRng;
 pub struct Props {
     pub max_turn: u32,
     pub num_trials: u32,
+    /// When supplied, every trial is seeded deterministically from this, so runs are reproducible
+    pub seed: Option<u64>,
+    /// Record every per-trial value, so that percentiles can be computed
+    pub record_distributions: bool,
+    /// How many cards are drawn for the opening hand
+    pub hand_size: u32,
+    /// Cards drawn on top of the opening hand once it is kept, for formats or effects that start with extra cards.
+    /// Unlike a bigger `hand_size`, these are not bottomed by mulligans
+    pub opening_extra_cards: u32,
+    /// The most mulligans a strategy may take before it has to keep
+    pub max_mulligans: u32,
+    /// Whether we go first or second
+    pub play_draw: PlayDraw,
+    /// The chance that the opponent wipes our creatures off the board on each of their turns
+    pub wipe_chance: f64,
+    /// Cards beyond this are discarded at the end of each turn
+    pub max_hand_size: u32,
+    /// Run the trials on a pool of this many threads, instead of rayon's global pool
+    pub num_threads: Option<usize>,
+    /// Plays beyond this many in a single turn are dropped, so that a strategy stuck in a loop can not hang the run
+    pub max_plays_per_turn: u32,
+    /// Keep a [`TrialLog`] of everything that happens, for debugging strategies
+    pub record_replay: bool,
+    /// Stop starting new trials once this much time has passed, and report the ones that finished.
+    /// Check [`MetricsData::num_trials`] for how many that was
+    pub time_budget: Option<Duration>,
 }
 impl Default for Props {
     fn default() -> Self {
         Self {
             max_turn: 12,
             num_trials: 1000,
+            seed: None,
+            record_distributions: false,
+            hand_size: crate::game::DEFAULT_HAND_SIZE,
+            opening_extra_cards: 0,
+            max_mulligans: 7,
+            play_draw: PlayDraw::Random,
+            wipe_chance: 0.0,
+            max_hand_size: 7,
+            num_threads: None,
+            max_plays_per_turn: 100,
+            record_replay: false,
+            time_budget: None,
+        }
+    }
+}
+
+/// Something that happened during a trial. Cards drawn into the opening hand happen on turn 0.
+#[derive(Debug,Clone,Copy,PartialEq,Eq)]
+pub enum TurnEvent {
+    Draw { turn: u32, card: Card },
+    LandDrop { turn: u32, card: Card },
+    Cast { turn: u32, card: Card },
+}
+
+impl TurnEvent {
+    pub fn turn(&self) -> u32 {
+        match *self {
+            TurnEvent::Draw { turn, .. } | TurnEvent::LandDrop { turn, .. } | TurnEvent::Cast { turn, .. } => turn,
+        }
+    }
+}
+
+impl fmt::Display for TurnEvent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TurnEvent::Draw { card, .. } => write!(f, "drew {}", card.name()),
+            TurnEvent::LandDrop { card, .. } => write!(f, "played {}", card.name()),
+            TurnEvent::Cast { card, .. } => write!(f, "cast {}", card.name()),
         }
     }
 }
 
+/// The events of a single trial, in the order they happened
+#[derive(Debug,Clone,Default,PartialEq,Eq)]
+pub struct TrialLog {
+    pub events: Vec<TurnEvent>,
+}
+
+impl fmt::Display for TrialLog {
+    /// One line per turn, starting with the opening hand
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut current_turn = None;
+        for event in self.events.iter() {
+            let turn = event.turn();
+            if current_turn == Some(turn) {
+                write!(f, ", {event}")?;
+                continue;
+            }
+            if current_turn.is_some() {
+                writeln!(f)?;
+            }
+            match turn {
+                0 => write!(f, "Opening hand: {event}")?,
+                _ => write!(f, "Turn {turn}: {event}")?,
+            }
+            current_turn = Some(turn);
+        }
+        if current_turn.is_some() {
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
+
 /// Work needed for a particular run
 pub struct Trial {
     pub rng: Rand,
     pub state: State,
     pub metrics: MetricsData,
-    pub props: Props
+    pub props: Props,
+    /// Whether to shuffle the library before drawing the opening hand
+    shuffle_library: bool,
+    /// Only kept when the props ask for a replay
+    replay: Option<TrialLog>,
 }
 
 impl Trial {
@@ -38,16 +144,43 @@ impl Trial {
         Self::from_props(deck, rng, Props::default())
     }
     pub fn from_props(deck: Deck, mut rng: Rand, props: Props) -> Self {
-        let state = State::new(
+        let mut state = State::new(
             deck,
+            props.play_draw,
             &mut rng
         );
+        state.hand_size = props.hand_size;
+        let metrics = empty_metrics(&props);
         Trial {
             rng,
             state,
-            metrics: MetricsData::empty(),
+            metrics,
             props,
+            shuffle_library: true,
+            replay: props.record_replay.then(TrialLog::default),
+        }
+    }
+    /// A trial that does not shuffle before drawing the opening hand, so that tests can control exactly
+    /// which cards are drawn. `order` is the library from the top card down, and should be made of the same
+    /// cards as `deck`. Mulligans still shuffle.
+    pub fn with_fixed_library(deck: Deck, order: Vec<Card>, props: Props) -> Self {
+        let mut expected = deck.deck.iter().collect::<Vec<_>>();
+        let mut actual = order.clone();
+        expected.sort();
+        actual.sort();
+        if expected != actual {
+            log::warn!("the fixed library order does not have the same cards as the deck, using the order anyway");
+        }
+
+        let rng = Rand::seed_from_u64(props.seed.unwrap_or(0));
+        let mut trial = Self::from_props(deck, rng, props);
+        // the top of an ordered pile is its last card
+        trial.state.library = OrderedPile::from(order.into_iter().rev().collect());
+        for &card in trial.state.opening_battlefield.iter() {
+            trial.state.library.remove(card);
         }
+        trial.shuffle_library = false;
+        trial
     }
     pub fn library(&self) -> &Library {
         &self.state.library
@@ -61,35 +194,87 @@ impl Trial {
         self.state.turn
     }
 
-    pub fn run<S, W>(mut self, strategies: &mut S, watcher: &W) -> MetricsData
+    pub fn run<S, W>(self, strategies: &mut S, watcher: &W) -> MetricsData
+    where S: Strategy,
+          W: Watcher
+    {
+        self.run_with_replay(strategies, watcher).0
+    }
+
+    /// Runs the trial, also returning its replay if the props asked for one
+    pub fn run_with_replay<S, W>(mut self, strategies: &mut S, watcher: &W) -> (MetricsData, Option<TrialLog>)
     where S: Strategy,
           W: Watcher
     {
-        self.state.library.shuffle(&mut self.rng);
+        self.play(strategies, watcher);
+        (self.metrics, self.replay)
+    }
+
+    /// Start this trial over from the deck with a new rng, as [`Trial::from_props`] would, but reusing the state.
+    fn reset(&mut self, deck: &Deck, rng: Rand) {
+        self.rng = rng;
+        self.state.reset_from(deck, self.props.play_draw, &mut self.rng);
+        self.state.hand_size = self.props.hand_size;
+        self.shuffle_library = true;
+        self.replay = self.props.record_replay.then(TrialLog::default);
+    }
+
+    /// Plays out the game, recording into the trial's metrics
+    fn play<S, W>(&mut self, strategies: &mut S, watcher: &W)
+    where S: Strategy,
+          W: Watcher
+    {
+        if self.shuffle_library {
+            self.state.library.shuffle(&mut self.rng);
+        }
 
         self.state.draw_hand();
 
         while strategies.mulligan_hand(&self.state) {
+            if self.state.num_mulligans_taken >= self.props.max_mulligans {
+                log::warn!("strategy used up all mulligans");
+                break;
+            }
+
             self.state.shuffle_hand_into_library(&mut self.rng);
 
             self.state.num_mulligans_taken += 1;
             self.state.draw_hand();
+        };
 
-            if self.state.num_mulligans_taken >= 6 {
-                log::warn!("strategy used up all mulligans");
+        // london mulligan: we always draw seven, then bottom one card per mulligan taken
+        let num_to_bottom = self.state.num_mulligans_taken as usize;
+        if num_to_bottom > 0 {
+            let to_bottom = strategies.cards_to_bottom(&self.state, num_to_bottom);
+            if to_bottom.len() != num_to_bottom {
+                log::warn!("strategy chose {} cards to bottom, expected {num_to_bottom}", to_bottom.len());
+            }
+            self.state.bottom_cards(&to_bottom);
+        }
+        for _ in 0..self.props.opening_extra_cards {
+            if self.state.draw_to_hand().is_none() {
                 break;
             }
-            
-            // TODO: mulligan with london mulligan
-        };
-        
+        }
+
+        let opening_hand = self.state.hand.iter().collect::<Vec<_>>();
+        for card in opening_hand {
+            watcher.card_drawn(card, &self.state, &mut self.metrics);
+            self.record(TurnEvent::Draw { turn: 0, card });
+        }
         watcher.opening_hand(&self.state, &mut self.metrics);
 
         self.state.turn = 1;
         while self.state.turn() <= self.props.max_turn && !self.state.game_loss {
+            watcher.turn_begin(&self.state, &mut self.metrics);
+
             let draw = self.turn() > 1 || self.state.draw_on_first_turn;
             if draw {
-                self.state.draw_to_hand();
+                for _ in 0..1 + self.state.extra_draws_per_turn() {
+                    let Some(card) = self.state.draw_to_hand() else { break };
+                    watcher.card_drawn(card, &self.state, &mut self.metrics);
+                    self.record(TurnEvent::Draw { turn: self.state.turn, card });
+                }
             }
 
             log::debug!("on turn {}, {} cards in hand, {} cards in play, {} cards in graveyard, {} cards in deck",
@@ -101,42 +286,182 @@ impl Trial {
             );
 
 
-            for card_play in strategies.card_plays(&self.state) {
-                log::debug!("playing card: {card_play:?}");
-                watcher.card_play(card_play.card, &self.state, &mut self.metrics);
+            let mut plays_left = self.props.max_plays_per_turn as usize;
 
-                card_play.card
-                    .effects()
-                    .iter()
-                    .for_each(|effect| self.apply_card_effect(effect));
+            plays_left -= self.make_plays_until_done(S::card_plays, plays_left, strategies, watcher);
 
-                self.state.play_card(card_play);
+            watcher.combat_damage(&self.state, &mut self.metrics);
+
+            self.make_plays_until_done(S::instant_plays, plays_left, strategies, watcher);
+
+            // cleanup step: discard down to the maximum hand size
+            let num_to_discard = self.state.hand.size().saturating_sub(self.props.max_hand_size as usize);
+            if num_to_discard > 0 {
+                let to_discard = strategies.cards_to_discard(&self.state, num_to_discard);
+                if to_discard.len() != num_to_discard {
+                    log::warn!("strategy chose {} cards to discard, expected {num_to_discard}", to_discard.len());
+                }
+                self.state.discard(&to_discard);
             }
 
             watcher.turn_end(&self.state, &mut self.metrics);
 
+            // the opponent's turn
+            if self.props.wipe_chance > 0.0 && self.rng.gen_bool(self.props.wipe_chance.min(1.0)) {
+                let destroyed = self.state.destroy_all(CardType::Creature);
+                log::debug!("opponent wiped the board, destroying {destroyed} creatures");
+            }
+
             self.state.end_turn();
         }
 
         watcher.game_end(&self.state, &mut self.metrics);
 
-        self.metrics.trials_seen += 1;
-        self.metrics
+        self.metrics.end_trial();
+    }
+
+    fn record(&mut self, event: TurnEvent) {
+        if let Some(replay) = self.replay.as_mut() {
+            replay.events.push(event);
+        }
+    }
+
+    /// Keeps asking `choose` for plays and making them until it has none left, since a play may open up more.
+    /// Makes at most `limit` plays in all, so that a strategy which never runs out can not hang the trial,
+    /// and returns how many were made
+    fn make_plays_until_done<S, W>(&mut self, choose: fn(&mut S, &State) -> Vec<CardPlay>, limit: usize, strategies: &mut S, watcher: &W) -> usize
+    where S: Strategy,
+          W: Watcher
+    {
+        let mut num_plays = 0;
+        while num_plays < limit {
+            let card_plays = choose(strategies, &self.state);
+            if card_plays.is_empty() {
+                return num_plays;
+            }
+            num_plays += self.make_plays(card_plays, limit - num_plays, strategies, watcher);
+        }
+        log::warn!("strategy reached the limit of {limit} plays on turn {}, skipping the rest of the turn's plays", self.state.turn);
+        num_plays
+    }
+
+    /// Makes at most `limit` of the plays, and returns how many were made
+    fn make_plays<S, W>(&mut self, card_plays: Vec<CardPlay>, limit: usize, strategies: &mut S, watcher: &W) -> usize
+    where S: Strategy,
+          W: Watcher
+    {
+        if card_plays.len() > limit {
+            log::warn!("strategy chose {} plays on turn {}, but only {limit} more are allowed, dropping the rest", card_plays.len(), self.state.turn);
+        }
+        let num_plays = card_plays.len().min(limit);
+        for card_play in card_plays.into_iter().take(limit) {
+            log::debug!("playing card: {card_play:?}");
+            watcher.card_play(card_play.card, &self.state, &mut self.metrics);
+            let (turn, card) = (self.state.turn, card_play.card);
+            self.record(match card.data().card_type {
+                CardType::Land => TurnEvent::LandDrop { turn, card },
+                _ => TurnEvent::Cast { turn, card },
+            });
+
+            card_play.card
+                .effects()
+                .iter()
+                .for_each(|effect| self.apply_card_effect(card_play.card, effect));
+            self.apply_chosen_mode(card_play.card, strategies);
+
+            self.state.play_card(card_play);
+        }
+        num_plays
+    }
+
+    /// Lets the strategy pick one of the card's modes, if it has any, and applies it
+    fn apply_chosen_mode<S: Strategy>(&mut self, card: Card, strategies: &mut S) {
+        let modes = card.modes();
+        if modes.is_empty() {
+            return;
+        }
+        let choice = strategies.choose_mode(&self.state, modes);
+        match modes.get(choice) {
+            Some(mode) => {
+                log::debug!("{card:?} chose mode {mode:?}");
+                self.apply_card_effect(card, mode);
+            }
+            None => log::warn!("strategy chose mode {choice} of {card:?}, which only has {}", modes.len()),
+        }
     }
 
-    fn apply_card_effect(&mut self, effect: &AnnotationValue) {
+    fn apply_card_effect(&mut self, card: Card, effect: &AnnotationValue) {
         use AnnotationValue::*;
         match effect {
             String(s) if s == "fetches" => {
-                todo!("apply game action: {s}")
+                match self.state.fetch_card(|c| card.can_fetch(*c)) {
+                    Some(fetched) => {
+                        log::debug!("{card:?} fetched {fetched:?}");
+                        self.state.add_permanent(fetched);
+                    }
+                    None => {
+                        log::debug!("{card:?} could not find any of {:?} in the library", card.fetch_targets());
+                    }
+                }
+                self.state.library.shuffle(&mut self.rng);
+            }
+            String(s) if s == "regrowth" => {
+                let targets = card.recur_targets();
+                match self.state.return_to_hand(|c| targets.is_empty() || targets.contains(&c.name())) {
+                    Some(returned) => log::debug!("{card:?} returned {returned:?} to hand"),
+                    None => log::debug!("{card:?} could not find anything to return from the graveyard"),
+                }
+            }
+            String(s) if s == "tutor" => {
+                let targets = card.tutor_targets();
+                match self.state.tutor_to_hand(|c| targets.contains(&c.name())) {
+                    Some(tutored) => log::debug!("{card:?} put {tutored:?} into hand"),
+                    None => log::debug!("{card:?} could not find any of {targets:?} in the library"),
+                }
+                self.state.library.shuffle(&mut self.rng);
+            }
+            String(s) if s == "self-sacrifice" => {
+                let destroyed = self.state.destroy_all(CardType::Creature);
+                log::debug!("{card:?} sacrificed {destroyed} creatures");
             }
             _ => { /* nothing to do */}
         }
+    }
 
+}
 
+/// Runs trials of the same deck one after another, resetting a single [`Trial`] in place between them
+/// instead of cloning the deck and building a new state every time.
+pub struct TrialRunner<'a> {
+    deck: &'a Deck,
+    trial: Trial,
+}
 
+impl<'a> TrialRunner<'a> {
+    pub fn new(deck: &'a Deck, props: Props) -> Self {
+        // the rng is replaced at the start of every trial
+        let trial = Trial::from_props(deck.clone(), Rand::seed_from_u64(0), props);
+        TrialRunner { deck, trial }
     }
 
+    /// Runs a single trial seeded with `rng`, adding its metrics to `total`.
+    /// The result is the same as running `Trial::from_props` with the same rng and joining its metrics into `total`.
+    pub fn run_into<S, W>(&mut self, rng: Rand, strategies: &mut S, watcher: &W, total: &mut MetricsData)
+    where S: Strategy,
+          W: Watcher
+    {
+        self.trial.reset(self.deck, rng);
+        self.trial.play(strategies, watcher);
+        total.absorb(&mut self.trial.metrics);
+    }
+}
+
+/// Metrics to record a trial into, which keep distributions if the props ask for them
+fn empty_metrics(props: &Props) -> MetricsData {
+    match props.record_distributions {
+        true => MetricsData::with_distributions(),
+        false => MetricsData::empty(),
+    }
 }
 
 pub fn run_trials<S, W>(deck: Deck, strategies: S, watcher: W, props: Props) -> MetricsData
@@ -148,17 +473,988 @@ where S: Strategy + Clone + Sync,
 
     log::info!("beginning trial with props: {props:?}");
 
-    (0..props.num_trials)
+    let deadline = props.time_budget.map(|budget| Instant::now() + budget);
+    // set by the first worker to notice the deadline, so the others can skip the clock
+    let out_of_time = AtomicBool::new(false);
+    let is_out_of_time = || {
+        if out_of_time.load(Ordering::Relaxed) {
+            return true;
+        }
+        let expired = deadline.is_some_and(|deadline| Instant::now() >= deadline);
+        if expired {
+            out_of_time.store(true, Ordering::Relaxed);
+        }
+        expired
+    };
+
+    // each worker folds its trials into one running total, reusing the same trial state and scratch
+    // metrics for every trial, instead of cloning the deck and allocating fresh ones per trial
+    let run = || (0..props.num_trials)
         .into_iter()
         .into_par_iter()
-        .map(|_| {
-            let rng = rand::rngs::StdRng::from_entropy();
-            let t = Trial::from_props(
-                deck.clone(),
-                rng,
-                props
-            );
-            t.run(&mut strategies.clone(), &watcher)
-        })
-        .reduce(|| MetricsData::empty(), MetricsData::join)
+        .fold(
+            || (empty_metrics(&props), TrialRunner::new(&deck, props)),
+            |(mut total, mut runner), trial_idx| {
+                // trials already in flight finish, but no new ones start once the budget is spent
+                if is_out_of_time() {
+                    return (total, runner);
+                }
+                let rng = match props.seed {
+                    Some(seed) => Rand::seed_from_u64(seed.wrapping_add(trial_idx as u64)),
+                    None => Rand::from_entropy(),
+                };
+                runner.run_into(rng, &mut strategies.clone(), &watcher, &mut total);
+                (total, runner)
+            }
+        )
+        .map(|(total, _)| total)
+        .reduce(MetricsData::empty, MetricsData::join);
+
+    let metrics = match props.num_threads {
+        None => run(),
+        Some(num_threads) => match rayon::ThreadPoolBuilder::new().num_threads(num_threads).build() {
+            Ok(pool) => pool.install(run),
+            Err(e) => {
+                log::error!("could not start a pool of {num_threads} threads, using the global pool instead: {e}");
+                run()
+            }
+        },
+    };
+    if out_of_time.load(Ordering::Relaxed) {
+        log::warn!("ran out of time after {} of {} trials", metrics.num_trials(), props.num_trials);
+    }
+    metrics
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::collection::fixtures;
+    use crate::game::{CommandZone, UnorderedPile};
+    use crate::metrics::{MetricsKey, Uint};
+    use crate::strategies::{DefaultStrategy, LandDropTieBreak, MulliganStrategy, StrategyImpl, UtilityFunction};
+    use crate::watcher::{CastabilityWatcher, WatcherImpl};
+    use itertools::Itertools;
+
+    use super::*;
+
+    /// Mulligans a fixed number of times, then keeps.
+    #[derive(Clone)]
+    struct MulliganTimes(u32);
+    impl Strategy for MulliganTimes {
+        fn mulligan_hand(&mut self, state: &State) -> bool {
+            state.num_mulligans_taken < self.0
+        }
+    }
+
+    /// Records the size of the opening hand.
+    #[derive(Clone)]
+    struct OpeningHandSize;
+    impl Watcher for OpeningHandSize {
+        fn opening_hand(&self, state: &State, metrics: &mut MetricsData) {
+            metrics.add_count("opening-hand::size", state.hand.size() as u32);
+        }
+    }
+
+    #[test]
+    fn test_seeded_trials_are_reproducible() {
+        let mut deck = UnorderedPile::empty();
+        deck.add_copies(fixtures::card_named("Forest"), 17);
+        deck.add_copies(fixtures::card_named("Mountain"), 17);
+        deck.add_copies(fixtures::card_named("Hill Giant"), 13);
+        deck.add_copies(fixtures::card_named("Llanowar Elves"), 13);
+        let deck = Deck {
+            command_zone: CommandZone::empty(),
+            deck,
+        };
+        let props = Props {
+            max_turn: 8,
+            num_trials: 50,
+            seed: Some(42),
+            ..Props::default()
+        };
+        let strategies = StrategyImpl {
+            rng: Rand::seed_from_u64(42),
+            mulligan: MulliganStrategy::Curve,
+            utility: UtilityFunction::default(),
+            land_tie_break: LandDropTieBreak::default(),
+        };
+
+        let first = run_trials(deck.clone(), strategies.clone(), WatcherImpl, props);
+        let second = run_trials(deck, strategies, WatcherImpl, props);
+
+        assert_eq!(first.num_trials(), 50);
+        assert_eq!(first, second);
+    }
+
+    #[test]
+    fn test_folded_trials_match_joining_each_trial() {
+        let props = Props {
+            max_turn: 6,
+            num_trials: 30,
+            seed: Some(11),
+            record_distributions: true,
+            ..Props::default()
+        };
+        let strategies = StrategyImpl {
+            rng: Rand::seed_from_u64(11),
+            mulligan: MulliganStrategy::default(),
+            utility: UtilityFunction::default(),
+            land_tie_break: LandDropTieBreak::default(),
+        };
+
+        let joined = (0..props.num_trials)
+            .map(|trial_idx| {
+                let rng = Rand::seed_from_u64(11 + trial_idx as u64);
+                Trial::from_props(forests_and_giants(), rng, props).run(&mut strategies.clone(), &WatcherImpl)
+            })
+            .fold(MetricsData::empty(), MetricsData::join);
+        let folded = run_trials(forests_and_giants(), strategies, WatcherImpl, props);
+
+        assert_eq!(folded.num_trials(), 30);
+        assert_eq!(folded, joined);
+    }
+
+    #[test]
+    fn test_trial_runner_matches_fresh_trials() {
+        let mut deck = UnorderedPile::empty();
+        deck.add_copies(fixtures::card_named("Forest"), 20);
+        deck.add_copies(fixtures::card_named("Llanowar Elves"), 10);
+        deck.add_copies(fixtures::card_named("Howling Mine"), 5);
+        deck.add_copies(fixtures::card_named("Exploration"), 5);
+        deck.add_copies(fixtures::card_named("Hill Giant"), 20);
+        let mut command_zone = CommandZone::empty();
+        command_zone.add(fixtures::card_named("Colossal Dreadmaw"));
+        let deck = Deck { command_zone, deck };
+
+        let props = Props {
+            max_turn: 8,
+            num_trials: 25,
+            seed: Some(5),
+            record_distributions: true,
+            wipe_chance: 0.2,
+            ..Props::default()
+        };
+        let strategies = StrategyImpl {
+            rng: Rand::seed_from_u64(5),
+            mulligan: MulliganStrategy::default(),
+            utility: UtilityFunction::default(),
+            land_tie_break: LandDropTieBreak::default(),
+        };
+
+        let fresh = (0..props.num_trials)
+            .map(|trial_idx| {
+                let rng = Rand::seed_from_u64(5 + trial_idx as u64);
+                Trial::from_props(deck.clone(), rng, props).run(&mut strategies.clone(), &WatcherImpl)
+            })
+            .fold(MetricsData::empty(), MetricsData::join);
+
+        let mut runner = TrialRunner::new(&deck, props);
+        let mut reused = MetricsData::with_distributions();
+        for trial_idx in 0..props.num_trials {
+            let rng = Rand::seed_from_u64(5 + trial_idx as u64);
+            runner.run_into(rng, &mut strategies.clone(), &WatcherImpl, &mut reused);
+        }
+
+        assert_eq!(reused.num_trials(), 25);
+        assert_eq!(reused, fresh);
+    }
+
+    #[test]
+    fn test_single_thread_matches_global_pool() {
+        let props = Props {
+            max_turn: 6,
+            num_trials: 20,
+            seed: Some(7),
+            ..Props::default()
+        };
+        let strategies = StrategyImpl {
+            rng: Rand::seed_from_u64(7),
+            mulligan: MulliganStrategy::default(),
+            utility: UtilityFunction::default(),
+            land_tie_break: LandDropTieBreak::default(),
+        };
+
+        let global = run_trials(forests_and_giants(), strategies.clone(), WatcherImpl, props);
+        let single = run_trials(forests_and_giants(), strategies, WatcherImpl, Props { num_threads: Some(1), ..props });
+
+        assert_eq!(single.num_trials(), 20);
+        assert_eq!(single, global);
+    }
+
+    #[test]
+    fn test_opening_extra_cards_are_drawn_after_keeping() {
+        let props = Props {
+            max_turn: 1,
+            opening_extra_cards: 2,
+            ..Props::default()
+        };
+        let mut trial = Trial::from_props(forests_and_giants(), Rand::seed_from_u64(0), props);
+        trial.play(&mut MulliganTimes(1), &OpeningHandSize);
+
+        // seven drawn, one bottomed for the mulligan, then two more
+        assert_eq!(trial.metrics.total("opening-hand::size"), 8);
+    }
+
+    #[test]
+    fn test_opening_hand_lands_match_the_hypergeometric_distribution() {
+        let deck = forests_and_giants();
+        let props = Props {
+            max_turn: 1,
+            num_trials: 4000,
+            seed: Some(17),
+            ..Props::default()
+        };
+        let metrics = run_trials(deck.clone(), MulliganTimes(0), WatcherImpl, props);
+
+        let expected = crate::analytics::opening_hand_land_distribution(&deck, props.hand_size as usize);
+        for (lands, probability) in expected.into_iter().enumerate() {
+            let simulated = metrics.average(MetricsKey::from("opening-hand-lands").bucket(lands as u32)) as f64;
+            assert!((simulated - probability).abs() < 0.03, "{lands} lands: simulated {simulated}, expected {probability}");
+        }
+    }
+
+    /// Takes its time deciding whether to mulligan, then keeps.
+    #[derive(Clone)]
+    struct SlowToKeep(Duration);
+    impl Strategy for SlowToKeep {
+        fn mulligan_hand(&mut self, _state: &State) -> bool {
+            std::thread::sleep(self.0);
+            false
+        }
+    }
+
+    #[test]
+    fn test_time_budget_reports_partial_results() {
+        let props = Props {
+            max_turn: 2,
+            num_trials: 1000,
+            num_threads: Some(2),
+            time_budget: Some(Duration::from_millis(100)),
+            ..Props::default()
+        };
+
+        let begin = Instant::now();
+        let metrics = run_trials(forests_and_giants(), SlowToKeep(Duration::from_millis(10)), OpeningHandSize, props);
+
+        // all 1000 trials would take at least five seconds over two threads
+        assert!(begin.elapsed() < Duration::from_secs(2), "took {:?}", begin.elapsed());
+        assert!(metrics.num_trials() > 0);
+        assert!(metrics.num_trials() < 1000);
+        // only the trials that ran are aggregated
+        assert_eq!(metrics.total("opening-hand::size"), 7 * metrics.num_trials());
+    }
+
+    #[test]
+    fn test_generous_time_budget_runs_every_trial() {
+        let props = Props {
+            max_turn: 4,
+            num_trials: 20,
+            seed: Some(3),
+            ..Props::default()
+        };
+        let unbounded = run_trials(forests_and_giants(), MulliganTimes(0), OpeningHandSize, props);
+        let budgeted = run_trials(forests_and_giants(), MulliganTimes(0), OpeningHandSize, Props { time_budget: Some(Duration::from_secs(60)), ..props });
+
+        assert_eq!(budgeted.num_trials(), 20);
+        assert_eq!(budgeted, unbounded);
+    }
+
+    fn fetch_trial(library: &[(&str, usize)]) -> Trial {
+        let mut deck = UnorderedPile::empty();
+        for (name, count) in library {
+            deck.add_copies(fixtures::card_named(name), *count);
+        }
+        let deck = Deck {
+            command_zone: CommandZone::empty(),
+            deck,
+        };
+        Trial::new(deck, Rand::seed_from_u64(0))
+    }
+
+    fn play_fetchland(trial: &mut Trial) {
+        let fetchland = fixtures::card_named("Evolving Wilds");
+        for effect in fetchland.effects() {
+            trial.apply_card_effect(fetchland, effect);
+        }
+    }
+
+    #[test]
+    fn test_fetches_basic_land() {
+        let mut trial = fetch_trial(&[("Forest", 10), ("Hill Giant", 10)]);
+
+        play_fetchland(&mut trial);
+
+        assert_eq!(trial.state.permanents.iter().collect_vec(), vec![fixtures::card_named("Forest")]);
+        assert_eq!(trial.library().size(), 19);
+    }
+
+    #[test]
+    fn test_fetches_nothing_when_no_target() {
+        let mut trial = fetch_trial(&[("Hill Giant", 10)]);
+
+        play_fetchland(&mut trial);
+
+        assert_eq!(trial.state.permanents.size(), 0);
+        assert_eq!(trial.library().size(), 10);
+    }
+
+    #[test]
+    fn test_fetches_snow_covered_basic_by_land_type() {
+        let mut trial = fetch_trial(&[("Snow-Covered Forest", 1), ("Hill Giant", 10)]);
+
+        play_fetchland(&mut trial);
+
+        assert_eq!(trial.state.permanents.iter().collect_vec(), vec![fixtures::card_named("Snow-Covered Forest")]);
+        assert_eq!(trial.library().size(), 10);
+    }
+
+    #[test]
+    fn test_fetched_land_enters_like_a_played_permanent() {
+        let mut trial = fetch_trial(&[("Rugged Highlands", 1), ("Hill Giant", 10)]);
+        let tempt = fixtures::card_named("Tempt with Discovery");
+        let highlands = fixtures::card_named("Rugged Highlands");
+
+        for effect in tempt.effects() {
+            trial.apply_card_effect(tempt, effect);
+        }
+
+        assert!(trial.state.permanents.contains(highlands));
+        assert!(trial.state.turn_state.is_tapped(highlands), "the highlands enters tapped, even when fetched");
+    }
+
+    fn play_regrowth(trial: &mut Trial, regrowth: &str) {
+        let regrowth = fixtures::card_named(regrowth);
+        for effect in regrowth.effects() {
+            trial.apply_card_effect(regrowth, effect);
+        }
+    }
+
+    #[test]
+    fn test_regrowth_returns_last_card_in_graveyard() {
+        let mut trial = fetch_trial(&[("Forest", 10)]);
+        trial.state.graveyard.add(fixtures::card_named("Lightning Bolt"));
+        trial.state.graveyard.add(fixtures::card_named("Hill Giant"));
+
+        play_regrowth(&mut trial, "Regrowth");
+
+        assert_eq!(trial.hand().size(), 1);
+        assert!(trial.hand().contains(fixtures::card_named("Hill Giant")));
+        assert_eq!(trial.state.graveyard.size(), 1);
+    }
+
+    #[test]
+    fn test_regrowth_returns_recur_target() {
+        let mut trial = fetch_trial(&[("Forest", 10)]);
+        trial.state.graveyard.add(fixtures::card_named("Grizzly Bears"));
+        trial.state.graveyard.add(fixtures::card_named("Lightning Bolt"));
+
+        play_regrowth(&mut trial, "Gravedigger");
+
+        assert!(trial.hand().contains(fixtures::card_named("Grizzly Bears")));
+        assert_eq!(trial.state.graveyard.size(), 1);
+    }
+
+    #[test]
+    fn test_regrowth_with_empty_graveyard() {
+        let mut trial = fetch_trial(&[("Forest", 10)]);
+
+        play_regrowth(&mut trial, "Regrowth");
+
+        assert_eq!(trial.hand().size(), 0);
+    }
+
+    #[test]
+    fn test_regrowth_does_not_return_itself() {
+        let mut trial = fetch_trial(&[("Forest", 10)]);
+        let regrowth = fixtures::card_named("Regrowth");
+        trial.state.graveyard.add(fixtures::card_named("Hill Giant"));
+        trial.state.hand.add(regrowth);
+
+        let play = CardPlay {
+            card: regrowth,
+            zone: crate::game::Zone::Hand,
+            payment: crate::game::ManaPool::empty(),
+            x_value: 0,
+            tax: 0,
+            tapped: vec![],
+        };
+        trial.make_plays(vec![play], 1, &mut DefaultStrategy, &WatcherImpl);
+
+        assert!(trial.hand().contains(fixtures::card_named("Hill Giant")));
+        assert!(trial.state.graveyard.contains(regrowth));
+        assert_eq!(trial.state.graveyard.size(), 1);
+    }
+
+    fn play_tutor(trial: &mut Trial) {
+        let tutor = fixtures::card_named("Demonic Tutor");
+        for effect in tutor.effects() {
+            trial.apply_card_effect(tutor, effect);
+        }
+    }
+
+    #[test]
+    fn test_tutor_puts_target_into_hand() {
+        let mut trial = fetch_trial(&[("Forest", 10), ("Hill Giant", 10), ("Colossal Dreadmaw", 1)]);
+        let before = trial.library().iter().collect_vec();
+
+        play_tutor(&mut trial);
+
+        let after = trial.library().iter().collect_vec();
+        let untouched = before.iter().copied().filter(|c| c.name() != "Colossal Dreadmaw").collect_vec();
+        assert_eq!(trial.hand().iter().collect_vec(), vec![fixtures::card_named("Colossal Dreadmaw")]);
+        assert_eq!(after.len(), 20);
+        assert_ne!(after, untouched, "the library should be reshuffled");
+    }
+
+    #[test]
+    fn test_tutor_finds_nothing_when_no_target() {
+        let mut trial = fetch_trial(&[("Forest", 10), ("Hill Giant", 10)]);
+
+        play_tutor(&mut trial);
+
+        assert_eq!(trial.hand().size(), 0);
+        assert_eq!(trial.library().size(), 20);
+    }
+
+    struct PicksMode(usize);
+    impl Strategy for PicksMode {
+        fn choose_mode(&mut self, _state: &State, _options: &[AnnotationValue]) -> usize {
+            self.0
+        }
+    }
+
+    fn play_command<S: Strategy>(strategy: &mut S) -> Trial {
+        let mut trial = fetch_trial(&[("Forest", 10), ("Colossal Dreadmaw", 1)]);
+        let command = fixtures::card_named("Kolaghan's Command");
+        trial.state.graveyard.add(fixtures::card_named("Hill Giant"));
+        trial.state.hand.add(command);
+
+        let play = CardPlay {
+            card: command,
+            zone: crate::game::Zone::Hand,
+            payment: crate::game::ManaPool::empty(),
+            x_value: 0,
+            tax: 0,
+            tapped: vec![],
+        };
+        trial.make_plays(vec![play], 1, strategy, &WatcherImpl);
+        trial
+    }
+
+    #[test]
+    fn test_strategy_chooses_second_mode() {
+        let trial = play_command(&mut PicksMode(1));
+
+        assert_eq!(trial.hand().iter().collect_vec(), vec![fixtures::card_named("Hill Giant")]);
+        assert!(!trial.state.graveyard.contains(fixtures::card_named("Hill Giant")));
+        assert_eq!(trial.library().size(), 11, "the tutor mode should not have run");
+    }
+
+    #[test]
+    fn test_default_strategy_chooses_first_mode() {
+        let trial = play_command(&mut DefaultStrategy);
+
+        assert_eq!(trial.hand().iter().collect_vec(), vec![fixtures::card_named("Colossal Dreadmaw")]);
+        assert_eq!(trial.library().size(), 10);
+        assert!(trial.state.graveyard.contains(fixtures::card_named("Hill Giant")), "the regrowth mode should not have run");
+    }
+
+    #[test]
+    fn test_out_of_range_mode_applies_nothing() {
+        let trial = play_command(&mut PicksMode(5));
+
+        assert_eq!(trial.hand().size(), 0);
+        assert_eq!(trial.library().size(), 11);
+        assert!(trial.state.graveyard.contains(fixtures::card_named("Kolaghan's Command")));
+    }
+
+    #[test]
+    fn test_london_mulligan_bottoms_cards() {
+        let mut deck = UnorderedPile::empty();
+        deck.add_copies(fixtures::card_named("Forest"), 30);
+        deck.add_copies(fixtures::card_named("Hill Giant"), 30);
+        let deck = Deck {
+            command_zone: CommandZone::empty(),
+            deck,
+        };
+        let props = Props {
+            max_turn: 0,
+            num_trials: 1,
+            ..Props::default()
+        };
+
+        let trial = Trial::from_props(deck, Rand::seed_from_u64(0), props);
+        let metrics = trial.run(&mut MulliganTimes(2), &OpeningHandSize);
+
+        assert_eq!(metrics.total("opening-hand::size"), 5);
+    }
+
+    fn forests_and_giants() -> Deck {
+        let mut deck = UnorderedPile::empty();
+        deck.add_copies(fixtures::card_named("Forest"), 30);
+        deck.add_copies(fixtures::card_named("Hill Giant"), 30);
+        Deck {
+            command_zone: CommandZone::empty(),
+            deck,
+        }
+    }
+
+    /// Records the size of the library at the end of each turn.
+    struct LibrarySize;
+    impl Watcher for LibrarySize {
+        fn turn_end(&self, state: &State, metrics: &mut MetricsData) {
+            metrics.set(MetricsKey::from("library-size").turn_num(state.turn), state.library.size() as u32);
+        }
+    }
+
+    fn library_size_after_first_turn(play_draw: PlayDraw) -> Uint {
+        let props = Props {
+            max_turn: 1,
+            num_trials: 1,
+            play_draw,
+            ..Props::default()
+        };
+
+        let trial = Trial::from_props(forests_and_giants(), Rand::seed_from_u64(0), props);
+        let metrics = trial.run(&mut MulliganTimes(0), &LibrarySize);
+
+        metrics.total(MetricsKey::from("library-size").turn_num(1))
+    }
+
+    #[test]
+    fn test_always_play_skips_first_draw() {
+        assert_eq!(library_size_after_first_turn(PlayDraw::AlwaysPlay), 53);
+    }
+
+    #[test]
+    fn test_always_draw_draws_on_first_turn() {
+        assert_eq!(library_size_after_first_turn(PlayDraw::AlwaysDraw), 52);
+    }
+
+    fn cards_drawn(play_draw: PlayDraw) -> MetricsData {
+        let props = Props {
+            max_turn: 3,
+            num_trials: 1,
+            play_draw,
+            ..Props::default()
+        };
+
+        let trial = Trial::from_props(forests_and_giants(), Rand::seed_from_u64(0), props);
+        trial.run(&mut MulliganTimes(1), &WatcherImpl)
+    }
+
+    #[test]
+    fn test_cards_drawn_per_turn_on_the_play() {
+        let metrics = cards_drawn(PlayDraw::AlwaysPlay);
+
+        // seven cards, with one bottomed for the mulligan
+        assert_eq!(metrics.total(MetricsKey::from("cards-drawn").turn_num(0)), 6);
+        assert_eq!(metrics.total(MetricsKey::from("cards-drawn").turn_num(1)), 0);
+        assert_eq!(metrics.total(MetricsKey::from("cards-drawn").turn_num(2)), 1);
+        assert_eq!(metrics.total(MetricsKey::from("cards-drawn").turn_num(3)), 1);
+    }
+
+    #[test]
+    fn test_cards_drawn_per_turn_on_the_draw() {
+        let metrics = cards_drawn(PlayDraw::AlwaysDraw);
+
+        assert_eq!(metrics.total(MetricsKey::from("cards-drawn").turn_num(0)), 6);
+        assert_eq!(metrics.total(MetricsKey::from("cards-drawn").turn_num(1)), 1);
+        assert_eq!(metrics.total(MetricsKey::from("cards-drawn").turn_num(2)), 1);
+    }
+
+    #[test]
+    fn test_fixed_library_draws_the_top_seven() {
+        let names = ["Forest", "Hill Giant", "Mountain", "Llanowar Elves", "Forest", "Lightning Bolt", "Island", "Colossal Dreadmaw", "Sol Ring"];
+        let order = names.iter().map(|name| fixtures::card_named(name)).collect_vec();
+        let mut deck = UnorderedPile::empty();
+        for &card in order.iter() {
+            deck.add(card);
+        }
+        let deck = Deck {
+            command_zone: CommandZone::empty(),
+            deck,
+        };
+        let props = Props {
+            max_turn: 0,
+            num_trials: 1,
+            ..Props::default()
+        };
+
+        /// Records each card in the opening hand.
+        struct OpeningHand;
+        impl Watcher for OpeningHand {
+            fn card_drawn(&self, card: Card, _state: &State, metrics: &mut MetricsData) {
+                metrics.add(MetricsKey::from("drawn").card(card));
+            }
+        }
+
+        let trial = Trial::with_fixed_library(deck, order.clone(), props);
+        assert_eq!(trial.library().peek_top(7).iter().rev().copied().collect_vec(), order[..7]);
+
+        let metrics = trial.run(&mut MulliganTimes(0), &OpeningHand);
+
+        for card in order.iter().copied().unique() {
+            let expected = order[..7].iter().filter(|c| **c == card).count() as Uint;
+            assert_eq!(metrics.total(MetricsKey::from("drawn").card(card)), expected, "{card:?}");
+        }
+    }
+
+    /// Plays like the default strategy, but always keeps
+    struct KeepAndPlay(StrategyImpl);
+    impl Strategy for KeepAndPlay {
+        fn card_plays(&mut self, state: &State) -> Vec<CardPlay> {
+            self.0.card_plays(state)
+        }
+        fn instant_plays(&mut self, state: &State) -> Vec<CardPlay> {
+            self.0.instant_plays(state)
+        }
+    }
+
+    #[test]
+    fn test_replay_records_plays() {
+        let names = ["Forest", "Llanowar Elves", "Forest", "Grizzly Bears", "Hill Giant", "Hill Giant"];
+        let order = names.iter().map(|name| fixtures::card_named(name)).collect_vec();
+        let deck = Deck {
+            command_zone: CommandZone::empty(),
+            deck: UnorderedPile::from(order.clone()),
+        };
+        let props = Props {
+            max_turn: 2,
+            num_trials: 1,
+            hand_size: 3,
+            play_draw: PlayDraw::AlwaysPlay,
+            record_replay: true,
+            ..Props::default()
+        };
+        let mut strategies = KeepAndPlay(StrategyImpl {
+            rng: Rand::seed_from_u64(0),
+            mulligan: MulliganStrategy::default(),
+            utility: UtilityFunction::default(),
+            land_tie_break: LandDropTieBreak::default(),
+        });
+
+        let trial = Trial::with_fixed_library(deck, order, props);
+        let (_, replay) = trial.run_with_replay(&mut strategies, &WatcherImpl);
+        let replay = replay.expect("a replay was requested");
+
+        let card = fixtures::card_named;
+        assert_eq!(replay.events, vec![
+            TurnEvent::Draw { turn: 0, card: card("Forest") },
+            TurnEvent::Draw { turn: 0, card: card("Llanowar Elves") },
+            TurnEvent::Draw { turn: 0, card: card("Forest") },
+            TurnEvent::LandDrop { turn: 1, card: card("Forest") },
+            TurnEvent::Cast { turn: 1, card: card("Llanowar Elves") },
+            TurnEvent::Draw { turn: 2, card: card("Grizzly Bears") },
+            TurnEvent::LandDrop { turn: 2, card: card("Forest") },
+            TurnEvent::Cast { turn: 2, card: card("Grizzly Bears") },
+        ]);
+        assert_eq!(replay.to_string(), "\
+Opening hand: drew Forest, drew Llanowar Elves, drew Forest
+Turn 1: played Forest, cast Llanowar Elves
+Turn 2: drew Grizzly Bears, played Forest, cast Grizzly Bears
+");
+    }
+
+    #[test]
+    fn test_no_replay_by_default() {
+        let props = Props {
+            max_turn: 2,
+            num_trials: 1,
+            ..Props::default()
+        };
+
+        let trial = Trial::from_props(forests_and_giants(), Rand::seed_from_u64(0), props);
+        let (_, replay) = trial.run_with_replay(&mut MulliganTimes(0), &WatcherImpl);
+
+        assert_eq!(replay, None);
+    }
+
+    #[test]
+    fn test_every_turn_is_idle_without_plays() {
+        let props = Props {
+            max_turn: 4,
+            num_trials: 1,
+            ..Props::default()
+        };
+
+        let trial = Trial::from_props(forests_and_giants(), Rand::seed_from_u64(0), props);
+        let metrics = trial.run(&mut MulliganTimes(0), &WatcherImpl);
+
+        assert_eq!(metrics.total("idle-turns"), 4);
+    }
+
+    /// Plays a single Howling Mine as soon as it can, ignoring its cost.
+    struct PlayHowlingMine;
+    impl Strategy for PlayHowlingMine {
+        fn card_plays(&mut self, state: &State) -> Vec<CardPlay> {
+            let mine = fixtures::card_named("Howling Mine");
+            if state.permanents.contains(mine) || !state.hand.contains(mine) {
+                return vec![];
+            }
+            vec![CardPlay {
+                card: mine,
+                zone: crate::game::Zone::Hand,
+                payment: crate::game::ManaPool::empty(),
+                x_value: 0,
+                tax: 0,
+                tapped: vec![],
+            }]
+        }
+    }
+
+    /// How many nonland spells were cast over a few seeded games of this deck
+    fn spells_cast(land: &str, spell: &str) -> u32 {
+        let mut deck = UnorderedPile::empty();
+        deck.add_copies(fixtures::card_named(land), 20);
+        deck.add_copies(fixtures::card_named(spell), 20);
+        let deck = Deck { command_zone: CommandZone::empty(), deck };
+        let props = Props {
+            max_turn: 8,
+            num_trials: 10,
+            seed: Some(9),
+            ..Props::default()
+        };
+        let strategies = StrategyImpl {
+            rng: Rand::seed_from_u64(9),
+            mulligan: MulliganStrategy::Never,
+            utility: UtilityFunction::default(),
+            land_tie_break: LandDropTieBreak::default(),
+        };
+        run_trials(deck, strategies, WatcherImpl, props).total("card-plays")
+    }
+
+    #[test]
+    fn test_colorless_spells_need_colorless_sources() {
+        // Wastes has no annotation, it makes colorless because it is a basic land
+        assert!(spells_cast("Wastes", "Thought-Knot Seer") > 0);
+        assert_eq!(spells_cast("Forest", "Thought-Knot Seer"), 0);
+        assert!(spells_cast("Wastes", "Howling Mine") > 0);
+    }
+
+    #[test]
+    fn test_commander_castable_on_the_turn_it_has_enough_lands() {
+        let dreadmaw = fixtures::card_named("Colossal Dreadmaw");
+        let mut deck = UnorderedPile::empty();
+        deck.add_copies(fixtures::card_named("Forest"), 40);
+        let mut command_zone = CommandZone::empty();
+        command_zone.add(dreadmaw);
+        let deck = Deck { command_zone, deck };
+        let props = Props {
+            max_turn: 8,
+            num_trials: 5,
+            seed: Some(1),
+            play_draw: PlayDraw::AlwaysPlay,
+            ..Props::default()
+        };
+        let strategies = StrategyImpl {
+            rng: Rand::seed_from_u64(1),
+            mulligan: MulliganStrategy::Never,
+            utility: UtilityFunction::default(),
+            land_tie_break: LandDropTieBreak::default(),
+        };
+        let castability = |target_turn| {
+            let watcher = CastabilityWatcher { target: dreadmaw, target_turn };
+            run_trials(deck.clone(), strategies.clone(), watcher, props)
+        };
+
+        // a land drop every turn makes six forests, enough for the dreadmaw, on turn 6
+        let metrics = castability(6);
+        let castable = MetricsKey::from(CastabilityWatcher::KEY).card(dreadmaw);
+        for turn in 1..=8 {
+            let expected = if turn >= 6 { 1.0 } else { 0.0 };
+            assert_eq!(metrics.average(castable.turn_num(turn)), expected, "turn {turn}");
+        }
+        assert_eq!(metrics.average(CastabilityWatcher::ON_TIME_KEY), 1.0);
+        assert_eq!(castability(5).average(CastabilityWatcher::ON_TIME_KEY), 0.0);
+    }
+
+    #[test]
+    fn test_howling_mine_draws_an_extra_card() {
+        let mut deck = UnorderedPile::empty();
+        deck.add_copies(fixtures::card_named("Howling Mine"), 40);
+        let props = Props {
+            max_turn: 3,
+            num_trials: 1,
+            play_draw: PlayDraw::AlwaysDraw,
+            ..Props::default()
+        };
+
+        let trial = Trial::from_props(Deck { command_zone: CommandZone::empty(), deck }, Rand::seed_from_u64(0), props);
+        let metrics = trial.run(&mut PlayHowlingMine, &WatcherImpl);
+
+        // the mine comes down on turn 1, after that turn's draw
+        assert_eq!(metrics.total(MetricsKey::from("cards-drawn").turn_num(1)), 1);
+        assert_eq!(metrics.total(MetricsKey::from("cards-drawn").turn_num(2)), 2);
+        assert_eq!(metrics.total(MetricsKey::from("cards-drawn").turn_num(3)), 2);
+    }
+
+    /// The cap on plays per turn in [`test_plays_per_turn_are_capped`]
+    const MAX_PLAYS_PER_TURN: u32 = 20;
+
+    /// Casts the same bolt out of the graveyard every time it is asked, without ever running out,
+    /// as a misconfigured effect might
+    struct EndlessFlashback;
+    impl Strategy for EndlessFlashback {
+        fn card_plays(&mut self, _state: &State) -> Vec<CardPlay> {
+            let bolt = CardPlay {
+                card: fixtures::card_named("Lightning Bolt"),
+                zone: crate::game::Zone::Graveyard,
+                payment: crate::game::ManaPool::empty(),
+                x_value: 0,
+                tax: 0,
+                tapped: vec![],
+            };
+            vec![bolt]
+        }
+    }
+
+    #[test]
+    fn test_plays_per_turn_are_capped() {
+        let props = Props {
+            max_turn: 3,
+            num_trials: 1,
+            max_plays_per_turn: MAX_PLAYS_PER_TURN,
+            ..Props::default()
+        };
+
+        let trial = Trial::from_props(forests_and_giants(), Rand::seed_from_u64(0), props);
+        let metrics = trial.run(&mut EndlessFlashback, &WatcherImpl);
+
+        assert_eq!(metrics.num_trials(), 1);
+        assert_eq!(metrics.total("card-plays"), 3 * MAX_PLAYS_PER_TURN);
+        assert_eq!(metrics.total(MetricsKey::from("plays-on-turn").turn_num(2)), MAX_PLAYS_PER_TURN);
+    }
+
+    /// Records the number of creatures left on the battlefield.
+    struct CreaturesAtEnd;
+    impl Watcher for CreaturesAtEnd {
+        fn game_end(&self, state: &State, metrics: &mut MetricsData) {
+            let creatures = state.permanents.count_where(|c| c.data().card_type == CardType::Creature);
+            metrics.add_count("creatures", creatures as u32);
+            metrics.add_count("lands", state.num_lands_in_play() as u32);
+        }
+    }
+
+    fn creatures_at_end(wipe_chance: f64) -> MetricsData {
+        let mut deck = UnorderedPile::empty();
+        deck.add_copies(fixtures::card_named("Forest"), 20);
+        deck.add_copies(fixtures::card_named("Llanowar Elves"), 20);
+        let props = Props {
+            max_turn: 5,
+            num_trials: 1,
+            wipe_chance,
+            ..Props::default()
+        };
+        let mut strategies = StrategyImpl {
+            rng: Rand::seed_from_u64(0),
+            mulligan: MulliganStrategy::default(),
+            utility: UtilityFunction::default(),
+            land_tie_break: LandDropTieBreak::default(),
+        };
+
+        let trial = Trial::from_props(Deck { command_zone: CommandZone::empty(), deck }, Rand::seed_from_u64(0), props);
+        trial.run(&mut strategies, &CreaturesAtEnd)
+    }
+
+    #[test]
+    fn test_opponent_wipes_creatures() {
+        let without_wipes = creatures_at_end(0.0);
+        let with_wipes = creatures_at_end(1.0);
+
+        assert!(without_wipes.total("creatures") > 0);
+        assert_eq!(with_wipes.total("creatures"), 0);
+        assert!(with_wipes.total("lands") > 0);
+    }
+
+    /// Records the size of the hand at the end of each turn.
+    struct HandSize;
+    impl Watcher for HandSize {
+        fn turn_end(&self, state: &State, metrics: &mut MetricsData) {
+            metrics.set(MetricsKey::from("hand-size").turn_num(state.turn), state.hand.size() as u32);
+            metrics.set(MetricsKey::from("graveyard-size").turn_num(state.turn), state.graveyard.size() as u32);
+        }
+    }
+
+    #[test]
+    fn test_discard_to_max_hand_size() {
+        let props = Props {
+            max_turn: 3,
+            num_trials: 1,
+            hand_size: 10,
+            play_draw: PlayDraw::AlwaysDraw,
+            ..Props::default()
+        };
+
+        // this strategy never plays anything, so the hand only grows
+        let trial = Trial::from_props(forests_and_giants(), Rand::seed_from_u64(0), props);
+        let metrics = trial.run(&mut MulliganTimes(0), &HandSize);
+
+        for turn in 1..=3 {
+            assert_eq!(metrics.total(MetricsKey::from("hand-size").turn_num(turn)), 7);
+        }
+        assert_eq!(metrics.total(MetricsKey::from("graveyard-size").turn_num(1)), 4);
+        assert_eq!(metrics.total(MetricsKey::from("graveyard-size").turn_num(3)), 6);
+    }
+
+    #[test]
+    fn test_custom_hand_size() {
+        let props = Props {
+            max_turn: 0,
+            num_trials: 1,
+            hand_size: 5,
+            ..Props::default()
+        };
+
+        let trial = Trial::from_props(forests_and_giants(), Rand::seed_from_u64(0), props);
+        let metrics = trial.run(&mut MulliganTimes(0), &OpeningHandSize);
+
+        assert_eq!(metrics.total("opening-hand::size"), 5);
+    }
+
+    #[test]
+    fn test_mulligan_cap() {
+        let props = Props {
+            max_turn: 0,
+            num_trials: 1,
+            max_mulligans: 2,
+            ..Props::default()
+        };
+
+        let trial = Trial::from_props(forests_and_giants(), Rand::seed_from_u64(0), props);
+        let metrics = trial.run(&mut MulliganTimes(10), &OpeningHandSize);
+
+        // two mulligans, then forced to keep and bottom two
+        assert_eq!(metrics.total("opening-hand::size"), 5);
+    }
+
+    #[test]
+    fn test_never_mulligan_strategy() {
+        // without any lands, every hand is a mulligan by land count
+        let mut deck = UnorderedPile::empty();
+        deck.add_copies(fixtures::card_named("Hill Giant"), 60);
+        let deck = Deck {
+            command_zone: CommandZone::empty(),
+            deck,
+        };
+        let props = Props {
+            max_turn: 2,
+            num_trials: 20,
+            seed: Some(3),
+            ..Props::default()
+        };
+        let strategies = |name: &str| StrategyImpl {
+            rng: Rand::seed_from_u64(3),
+            mulligan: name.parse().unwrap(),
+            utility: UtilityFunction::default(),
+            land_tie_break: LandDropTieBreak::default(),
+        };
+
+        let land_count = run_trials(deck.clone(), strategies("land-count"), WatcherImpl, props);
+        let never = run_trials(deck, strategies("never"), WatcherImpl, props);
+
+        assert!(land_count.total("num-mulligans") > 0);
+        assert_eq!(never.num_trials(), 20);
+        assert_eq!(never.total("num-mulligans"), 0);
+    }
 }
@@ -1,25 +1,317 @@
-use crate::game::CardType;
+use crate::game::{CardType, ManaType};
 use crate::metrics::{MetricsData, MetricsKey};
 use crate::collection::Card;
 use crate::game::state::State;
+use crate::strategies::payment_solver;
 
 
 #[allow(unused)]
 pub trait Watcher {
     fn opening_hand(&self, state: &State, metrics: &mut MetricsData) { }
 
+    /// Called at the start of each turn, before the draw
+    fn turn_begin(&self, state: &State, metrics: &mut MetricsData) { }
+
     fn turn_end(&self, state: &State, metrics: &mut MetricsData) { }
 
+    fn combat_damage(&self, state: &State, metrics: &mut MetricsData) { }
+
     fn game_end(&self, state: &State, metrics: &mut MetricsData) { }
 
     fn card_play(&self, card_play: Card, state: &State, metrics: &mut MetricsData) { }
+
+    /// Called for each card in the kept opening hand (on turn 0), and for each card drawn after that
+    fn card_drawn(&self, card: Card, state: &State, metrics: &mut MetricsData) { }
+}
+
+/// Forwards every hook to both watchers, first `A` and then `B`. Made with [`combine`].
+#[derive(Clone)]
+pub struct CombinedWatcher<A, B> {
+    pub first: A,
+    pub second: B,
+}
+
+/// Layers two watchers, so that both see every event without either having to know about the other.
+/// Combine more than two by nesting.
+/// ```
+/// use deck_optim::watcher::{self, WatcherImpl};
+///
+/// let watcher = watcher::combine(WatcherImpl, WatcherImpl);
+/// ```
+pub fn combine<A: Watcher, B: Watcher>(first: A, second: B) -> CombinedWatcher<A, B> {
+    CombinedWatcher { first, second }
+}
+
+impl<A: Watcher, B: Watcher> Watcher for CombinedWatcher<A, B> {
+    fn opening_hand(&self, state: &State, metrics: &mut MetricsData) {
+        self.first.opening_hand(state, metrics);
+        self.second.opening_hand(state, metrics);
+    }
+
+    fn turn_begin(&self, state: &State, metrics: &mut MetricsData) {
+        self.first.turn_begin(state, metrics);
+        self.second.turn_begin(state, metrics);
+    }
+
+    fn turn_end(&self, state: &State, metrics: &mut MetricsData) {
+        self.first.turn_end(state, metrics);
+        self.second.turn_end(state, metrics);
+    }
+
+    fn combat_damage(&self, state: &State, metrics: &mut MetricsData) {
+        self.first.combat_damage(state, metrics);
+        self.second.combat_damage(state, metrics);
+    }
+
+    fn game_end(&self, state: &State, metrics: &mut MetricsData) {
+        self.first.game_end(state, metrics);
+        self.second.game_end(state, metrics);
+    }
+
+    fn card_play(&self, card_play: Card, state: &State, metrics: &mut MetricsData) {
+        self.first.card_play(card_play, state, metrics);
+        self.second.card_play(card_play, state, metrics);
+    }
+
+    fn card_drawn(&self, card: Card, state: &State, metrics: &mut MetricsData) {
+        self.first.card_drawn(card, state, metrics);
+        self.second.card_drawn(card, state, metrics);
+    }
+}
+
+/// A watcher that can be switched off, for ones that only make sense when configured
+impl<W: Watcher> Watcher for Option<W> {
+    fn opening_hand(&self, state: &State, metrics: &mut MetricsData) {
+        if let Some(watcher) = self {
+            watcher.opening_hand(state, metrics);
+        }
+    }
+
+    fn turn_begin(&self, state: &State, metrics: &mut MetricsData) {
+        if let Some(watcher) = self {
+            watcher.turn_begin(state, metrics);
+        }
+    }
+
+    fn turn_end(&self, state: &State, metrics: &mut MetricsData) {
+        if let Some(watcher) = self {
+            watcher.turn_end(state, metrics);
+        }
+    }
+
+    fn combat_damage(&self, state: &State, metrics: &mut MetricsData) {
+        if let Some(watcher) = self {
+            watcher.combat_damage(state, metrics);
+        }
+    }
+
+    fn game_end(&self, state: &State, metrics: &mut MetricsData) {
+        if let Some(watcher) = self {
+            watcher.game_end(state, metrics);
+        }
+    }
+
+    fn card_play(&self, card_play: Card, state: &State, metrics: &mut MetricsData) {
+        if let Some(watcher) = self {
+            watcher.card_play(card_play, state, metrics);
+        }
+    }
+
+    fn card_drawn(&self, card: Card, state: &State, metrics: &mut MetricsData) {
+        if let Some(watcher) = self {
+            watcher.card_drawn(card, state, metrics);
+        }
+    }
+}
+
+/// After this many turns in a row without a land drop while holding spells, we are mana screwed
+const MANA_SCREW_TURNS: u32 = 2;
+/// Having this many more lands than the turn number means we are mana flooded
+const MANA_FLOOD_EXCESS_LANDS: usize = 3;
+
+/// The key for the first turn a card of this type was played, such as `first-played::creature`.
+/// Games that never play one record nothing, so its average is over the games that did.
+pub fn first_played_key(card_type: &CardType) -> MetricsKey {
+    let name = match card_type {
+        CardType::Land => "first-played::land",
+        CardType::Instant => "first-played::instant",
+        CardType::Creature => "first-played::creature",
+        CardType::Sorcery => "first-played::sorcery",
+        CardType::Artifact => "first-played::artifact",
+        CardType::Enchantment => "first-played::enchantment",
+        CardType::Planeswalker => "first-played::planeswalker",
+    };
+    MetricsKey::from(name)
+}
+
+/// Spells of at least this mana value count as threats, unless configured otherwise
+pub const DEFAULT_THREAT_MANA_VALUE: u8 = 4;
+
+/// Records the first turn we cast a threat, a nonland spell of at least `min_mana_value`, under `first-threat-turn`.
+/// Trials that never cast one record nothing.
+#[derive(Clone)]
+pub struct ThreatWatcher {
+    pub min_mana_value: u8,
+}
+impl Default for ThreatWatcher {
+    fn default() -> Self {
+        Self {
+            min_mana_value: DEFAULT_THREAT_MANA_VALUE,
+        }
+    }
+}
+impl Watcher for ThreatWatcher {
+    fn card_play(&self, card_play: Card, state: &State, metrics: &mut MetricsData) {
+        let data = card_play.data();
+        if data.card_type == CardType::Land {
+            return;
+        }
+        let mana_value = data.cost.map(|cost| cost.mana_value()).unwrap_or(0);
+        if mana_value >= self.min_mana_value && metrics.total("first-threat-turn") == 0 {
+            metrics.add_count("first-threat-turn", state.turn);
+        }
+    }
+}
+
+/// Games count as consistent if they make a land drop on every turn through this one...
+pub const DEFAULT_CONSISTENT_LAND_DROP_TURNS: u32 = 4;
+/// ...and cast a nonland spell by this turn
+pub const DEFAULT_CONSISTENT_SPELL_TURN: u32 = 3;
+
+/// Records whether each game "did its thing" under `consistent-game`, so that its average is the success rate.
+/// A game is a success if it makes a land drop on every turn through `land_drop_turns`, and casts a nonland spell by `spell_turn`.
+#[derive(Clone)]
+pub struct ConsistencyWatcher {
+    pub land_drop_turns: u32,
+    pub spell_turn: u32,
+}
+impl ConsistencyWatcher {
+    pub const KEY: &'static str = "consistent-game";
+}
+impl Default for ConsistencyWatcher {
+    fn default() -> Self {
+        Self {
+            land_drop_turns: DEFAULT_CONSISTENT_LAND_DROP_TURNS,
+            spell_turn: DEFAULT_CONSISTENT_SPELL_TURN,
+        }
+    }
+}
+impl Watcher for ConsistencyWatcher {
+    fn card_play(&self, card_play: Card, state: &State, metrics: &mut MetricsData) {
+        if card_play.data().card_type == CardType::Land {
+            if state.turn <= self.land_drop_turns {
+                metrics.set(MetricsKey::from("consistency::land-drop").turn_num(state.turn), 1);
+            }
+        } else if state.turn <= self.spell_turn {
+            metrics.set("consistency::early-spell", 1);
+        }
+    }
+
+    fn game_end(&self, _state: &State, metrics: &mut MetricsData) {
+        let made_land_drops = (1..=self.land_drop_turns)
+            .all(|turn| metrics.total(MetricsKey::from("consistency::land-drop").turn_num(turn)) > 0);
+        let cast_early_spell = metrics.total("consistency::early-spell") > 0;
+        metrics.add_if(Self::KEY, made_land_drops && cast_early_spell);
+
+        // the flags only matter within a game, so keep them out of the report
+        for turn in 1..=self.land_drop_turns {
+            metrics.remove(MetricsKey::from("consistency::land-drop").turn_num(turn));
+        }
+        metrics.remove("consistency::early-spell");
+    }
+}
+
+/// Records how many spells are left in hand at the end of each turn that the untapped mana could still have cast,
+/// by turn under `unused-castable`. Each spell is checked on its own, so two spells that each fit the mana left count
+/// as two even if only one of them could have been cast. Compare with `plays-on-turn` to see how often a strategy
+/// leaves plays on the table.
+#[derive(Clone, Default)]
+pub struct EfficiencyWatcher;
+impl EfficiencyWatcher {
+    pub const KEY: &'static str = "unused-castable";
+}
+impl Watcher for EfficiencyWatcher {
+    fn turn_end(&self, state: &State, metrics: &mut MetricsData) {
+        let mana_sources: Vec<_> = state.mana_sources().collect();
+        let producible = state.producible_colors();
+        let castable = state.hand
+            .iter()
+            .filter(|card| card.data().card_type != CardType::Land)
+            .filter_map(|card| card.data().cost)
+            // skip asking the payment solver about spells whose colors we can not make at all
+            .filter(|cost| cost.colors.mana_types().all(|mana_type| producible.contains(&mana_type)))
+            .filter(|cost| payment_solver::autotap_pay_for(mana_sources.clone(), cost).is_some())
+            .count();
+        metrics.add_count(MetricsKey::from(Self::KEY).turn_num(state.turn), castable as u32);
+    }
+}
+
+/// Answers "what are the odds I can cast this by turn N?" for a single `target` card.
+/// Records, by turn under `castable::<card>`, whether the target could have been cast by the end of that turn, so that
+/// its average on each turn is the cumulative probability. It counts as castable on a turn if it was cast, or if it was
+/// in hand or in the command zone and the mana on the battlefield could pay for it, commander tax and all, had nothing
+/// else been tapped. Also records whether it was castable by `target_turn` under `castable-on-time`.
+#[derive(Clone)]
+pub struct CastabilityWatcher {
+    pub target: Card,
+    pub target_turn: u32,
+}
+impl CastabilityWatcher {
+    pub const KEY: &'static str = "castable";
+    pub const ON_TIME_KEY: &'static str = "castable-on-time";
+
+    fn key(&self) -> MetricsKey {
+        MetricsKey::from(Self::KEY).card(self.target)
+    }
+
+    /// Whether the target is somewhere it can be cast from, and the mana could pay for it this turn
+    fn castable_now(&self, state: &State) -> bool {
+        let mana_sources: Vec<_> = state.mana_sources_before_tapping().collect();
+        state.legal_card_plays()
+            .chain(state.legal_instant_plays())
+            .filter(|card_play| card_play.card == self.target)
+            .filter_map(|card_play| card_play.cost())
+            .any(|cost| payment_solver::autotap_pay_for(mana_sources.clone(), &cost).is_some())
+    }
+}
+impl Watcher for CastabilityWatcher {
+    fn card_play(&self, card_play: Card, state: &State, metrics: &mut MetricsData) {
+        if card_play == self.target {
+            metrics.set(self.key().turn_num(state.turn), 1);
+        }
+    }
+
+    fn turn_end(&self, state: &State, metrics: &mut MetricsData) {
+        let castable_before = state.turn
+            .checked_sub(1)
+            .is_some_and(|last_turn| metrics.total(self.key().turn_num(last_turn)) > 0);
+        let castable = castable_before || self.castable_now(state);
+        // does nothing if the target was already cast this turn
+        metrics.set(self.key().turn_num(state.turn), castable as u32);
+    }
+
+    fn game_end(&self, state: &State, metrics: &mut MetricsData) {
+        // a game that ends early counts as it stood on its last turn
+        let turn = self.target_turn.min(state.turn);
+        metrics.add_if(Self::ON_TIME_KEY, metrics.total(self.key().turn_num(turn)) > 0);
+    }
 }
 
 #[derive(Clone)]
 pub struct WatcherImpl;
 impl Watcher for WatcherImpl {
     fn opening_hand<'a>(&self, state: &State, metrics: &mut MetricsData) { 
-        metrics.add_count("opening-hand::lands", state.num_lands_in_hand() as u32);
+        let num_lands = state.num_lands_in_hand() as u32;
+        metrics.add_count("opening-hand::lands", num_lands);
+        // record every bucket, so that each one's average is how often the hand has exactly that many lands
+        for lands in 0..=state.hand.size() as u32 {
+            metrics.add_if(MetricsKey::from("opening-hand-lands").bucket(lands), lands == num_lands);
+        }
+    }
+
+    fn turn_begin(&self, state: &State, metrics: &mut MetricsData) {
+        // record zero plays up front, so that turns without any still count towards the average
+        metrics.add_count(MetricsKey::from("plays-on-turn").turn_num(state.turn), 0);
     }
 
     fn card_play(&self, card_play: Card, state: &State, metrics: &mut MetricsData) {
@@ -27,12 +319,17 @@ impl Watcher for WatcherImpl {
             metrics.add("land-drops");
         } else {
              metrics.add("card-plays");
+             let mana_value = card_play.data().cost.map(|cost| cost.mana_value()).unwrap_or(0);
+             metrics.add(MetricsKey::from("plays-by-cmc").mana_value(mana_value));
         }
 
         metrics.set(
             MetricsKey::from("turn-played").card(card_play),
             state.turn
         );
+        // only the first play of each type is kept
+        metrics.set(first_played_key(&card_play.data().card_type), state.turn);
+        metrics.add(MetricsKey::from("plays-on-turn").turn_num(state.turn));
 
         if metrics.total("card-plays") == 7 {
             metrics.add_count("turn-to-reach-7-plays", state.turn);
@@ -40,16 +337,593 @@ impl Watcher for WatcherImpl {
 
     }
 
+    fn card_drawn(&self, card: Card, state: &State, metrics: &mut MetricsData) {
+        metrics.add(MetricsKey::from("cards-drawn").turn_num(state.turn));
+        // summing over the turns up to N gives how often the card was drawn by turn N
+        metrics.add(MetricsKey::from("card-drawn").card(card).turn_num(state.turn));
+    }
+
+    fn combat_damage(&self, state: &State, metrics: &mut MetricsData) {
+        // tapped copies are left out one by one, so the other copies of a tapped creature still attack
+        let damage = state.untapped_permanents()
+            .filter(|card| card.data().card_type == CardType::Creature)
+            .filter_map(|card| card.data().power)
+            .sum::<i32>();
+        metrics.add_count("damage-dealt", damage.max(0) as u32);
+    }
+
     fn turn_end(&self, state: &State, metrics: &mut MetricsData) {
         let available_mana = state.available_mana() as u32;
         metrics.set(
             MetricsKey::from("mana_on_turn").turn_num(state.turn),
             available_mana
         );
+
+        // only count the turn we become screwed, not every turn after
+        let screwed = state.missed_land_drops_in_a_row() == MANA_SCREW_TURNS
+            && state.num_lands_in_hand() == 0
+            && state.num_spells_in_hand() > 0;
+        metrics.add_if("mana-screwed", screwed);
+
+        // the lands tapped this turn still count, since this measures fixing rather than leftover mana
+        let colors_available = state.colors_on_battlefield()
+            .into_iter()
+            .filter(|mana_type| *mana_type != ManaType::Colorless)
+            .count();
+        metrics.set(
+            MetricsKey::from("colors-available").turn_num(state.turn),
+            colors_available as u32
+        );
+
+        let idle = metrics.total(MetricsKey::from("plays-on-turn").turn_num(state.turn)) == 0;
+        metrics.add_if("idle-turns", idle);
+
+        let lands_seen = state.num_lands_in_play() + state.num_lands_in_hand();
+        let flooded = lands_seen > state.turn as usize + MANA_FLOOD_EXCESS_LANDS;
+        if flooded && metrics.total("mana-flooded") == 0 {
+            metrics.add("mana-flooded");
+        }
     }
 
     fn game_end(&self, state: &State, metrics: &mut MetricsData) {
         metrics.add_count("num-turns", state.turn);
         metrics.add_count("num-mulligans", state.num_mulligans_taken);
+
+        for card in state.hand.iter() {
+            if card.data().card_type != CardType::Land {
+                metrics.add(MetricsKey::from("stuck-in-hand").card(card));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::SeedableRng;
+
+    use crate::collection::fixtures;
+    use crate::game::card_play::CardPlay;
+    use crate::game::{CommandZone, Deck, ManaPool, PlayDraw, UnorderedPile, Zone};
+    use crate::trial::Rand;
+
+    use super::*;
+
+    fn empty_state() -> State {
+        let deck = Deck {
+            command_zone: CommandZone::empty(),
+            deck: UnorderedPile::empty(),
+        };
+        State::new(deck, PlayDraw::Random, &mut Rand::seed_from_u64(0))
+    }
+
+    fn state_with_library(card_name: &str, count: usize) -> State {
+        let mut deck = UnorderedPile::empty();
+        deck.add_copies(fixtures::card_named(card_name), count);
+        let deck = Deck {
+            command_zone: CommandZone::empty(),
+            deck,
+        };
+        let mut state = State::new(deck, PlayDraw::Random, &mut Rand::seed_from_u64(0));
+        state.draw_hand();
+        state
+    }
+
+    fn play_turns(state: &mut State, turns: u32, metrics: &mut MetricsData) {
+        for _ in 0..turns {
+            state.turn += 1;
+            state.draw_to_hand();
+            WatcherImpl.turn_end(state, metrics);
+            state.end_turn();
+        }
+    }
+
+    /// Plays a game with the consistency watcher, playing the named cards on each turn
+    fn consistency_of(turns: &[&[&str]]) -> MetricsData {
+        let mut state = empty_state();
+        let mut metrics = MetricsData::empty();
+        let watcher = ConsistencyWatcher::default();
+        state.turn = 1;
+        for plays in turns {
+            for name in plays.iter() {
+                watcher.card_play(fixtures::card_named(name), &state, &mut metrics);
+            }
+            state.end_turn();
+        }
+        watcher.game_end(&state, &mut metrics);
+        metrics.end_trial();
+        metrics
+    }
+
+    #[test]
+    fn test_consistent_game() {
+        let metrics = consistency_of(&[
+            &["Forest"],
+            &["Forest", "Grizzly Bears"],
+            &["Forest"],
+            &["Mountain", "Hill Giant"],
+            &[],
+        ]);
+
+        assert_eq!(metrics.total(ConsistencyWatcher::KEY), 1);
+        assert_eq!(metrics.total("consistency::early-spell"), 0);
+        assert_eq!(metrics.total(MetricsKey::from("consistency::land-drop").turn_num(1)), 0);
+    }
+
+    #[test]
+    fn test_inconsistent_games() {
+        let missed_land_drop = consistency_of(&[
+            &["Forest"],
+            &["Forest", "Grizzly Bears"],
+            &[],
+            &["Mountain", "Hill Giant"],
+        ]);
+        let late_spell = consistency_of(&[
+            &["Forest"],
+            &["Forest"],
+            &["Forest"],
+            &["Mountain", "Hill Giant"],
+        ]);
+        let too_short = consistency_of(&[
+            &["Forest", "Llanowar Elves"],
+            &["Forest"],
+        ]);
+
+        for metrics in [missed_land_drop, late_spell, too_short] {
+            assert_eq!(metrics.total(ConsistencyWatcher::KEY), 0);
+            assert_eq!(metrics.get(ConsistencyWatcher::KEY).trials_seen(), 1);
+        }
+    }
+
+    #[test]
+    fn test_consistency_rate() {
+        let success = || consistency_of(&[&["Forest", "Llanowar Elves"], &["Forest"], &["Forest"], &["Forest"]]);
+        let failure = || consistency_of(&[&["Forest"], &[], &[], &[]]);
+
+        let metrics = [success(), failure(), failure()]
+            .into_iter()
+            .fold(MetricsData::empty(), MetricsData::join);
+
+        assert_eq!(metrics.num_trials(), 3);
+        assert!((metrics.average(ConsistencyWatcher::KEY) - 1.0 / 3.0).abs() < 1e-6);
+    }
+
+    /// Puts the named permanents onto the battlefield, untapped and not summoning sick
+    fn state_with_board(permanents: &[&str], hand: &[&str]) -> State {
+        let mut state = empty_state();
+        state.turn = 1;
+        for name in permanents {
+            state.permanents.add(fixtures::card_named(name));
+        }
+        for name in hand {
+            state.hand.add(fixtures::card_named(name));
+        }
+        state
+    }
+
+    fn unused_castable(state: &State) -> u32 {
+        let mut metrics = MetricsData::empty();
+        EfficiencyWatcher.turn_end(state, &mut metrics);
+        metrics.total(MetricsKey::from(EfficiencyWatcher::KEY).turn_num(state.turn))
+    }
+
+    #[test]
+    fn test_unused_castable_after_playing_one_of_two() {
+        let bears = fixtures::card_named("Grizzly Bears");
+        let forest = fixtures::card_named("Forest");
+        let play_bears = CardPlay {
+            card: bears,
+            zone: Zone::Hand,
+            payment: ManaPool::green(2),
+            x_value: 0,
+            tax: 0,
+            tapped: vec![forest, forest],
+        };
+
+        let mut enough_forests = state_with_board(&["Forest", "Forest", "Forest", "Forest"], &["Grizzly Bears", "Grizzly Bears"]);
+        assert_eq!(unused_castable(&enough_forests), 2);
+        enough_forests.play_card(play_bears.clone());
+        // two forests are tapped, and the other two could still cast the second bears
+        assert_eq!(unused_castable(&enough_forests), 1);
+
+        let mut one_forest_short = state_with_board(&["Forest", "Forest", "Forest"], &["Grizzly Bears", "Grizzly Bears"]);
+        one_forest_short.play_card(play_bears);
+        // only one forest is left, which is not enough for the second bears
+        assert_eq!(unused_castable(&one_forest_short), 0);
+    }
+
+    #[test]
+    fn test_unused_castable_skips_lands_and_uncastable_spells() {
+        let mut state = state_with_board(&["Forest", "Forest"], &["Forest", "Lightning Bolt", "Hill Giant", "Grizzly Bears"]);
+        state.turn_state.mark_as_tapped(fixtures::card_named("Forest"));
+
+        // bolt needs red, the giant needs four mana, and the bears need two with only one forest untapped
+        assert_eq!(unused_castable(&state), 0);
+    }
+
+    fn castable_on_turn(watcher: &CastabilityWatcher, metrics: &MetricsData, turn: u32) -> u32 {
+        metrics.total(MetricsKey::from(CastabilityWatcher::KEY).card(watcher.target).turn_num(turn))
+    }
+
+    #[test]
+    fn test_castable_counts_mana_tapped_for_other_spells() {
+        let giant = fixtures::card_named("Hill Giant");
+        let watcher = CastabilityWatcher { target: giant, target_turn: 1 };
+        let mut state = state_with_board(&["Mountain", "Mountain", "Mountain", "Mountain"], &["Hill Giant"]);
+        state.turn_state.mark_as_tapped(fixtures::card_named("Mountain"));
+        let mut metrics = MetricsData::empty();
+
+        watcher.turn_end(&state, &mut metrics);
+        watcher.game_end(&state, &mut metrics);
+
+        // a mountain was tapped for something else, but the giant could have been cast instead
+        assert_eq!(castable_on_turn(&watcher, &metrics, 1), 1);
+        assert_eq!(metrics.total(CastabilityWatcher::ON_TIME_KEY), 1);
+    }
+
+    #[test]
+    fn test_castable_stays_castable_once_cast() {
+        let giant = fixtures::card_named("Hill Giant");
+        let watcher = CastabilityWatcher { target: giant, target_turn: 3 };
+        let mut state = state_with_board(&["Mountain", "Mountain", "Mountain"], &["Hill Giant"]);
+        let mut metrics = MetricsData::empty();
+
+        // three mountains are not enough
+        watcher.turn_end(&state, &mut metrics);
+        assert_eq!(castable_on_turn(&watcher, &metrics, 1), 0);
+
+        state.turn = 2;
+        state.permanents.add(fixtures::card_named("Mountain"));
+        state.hand.remove(giant);
+        state.permanents.add(giant);
+        watcher.card_play(giant, &state, &mut metrics);
+        watcher.turn_end(&state, &mut metrics);
+        assert_eq!(castable_on_turn(&watcher, &metrics, 2), 1);
+
+        // the giant is on the battlefield now, and still counts
+        state.turn = 3;
+        watcher.turn_end(&state, &mut metrics);
+        watcher.game_end(&state, &mut metrics);
+        assert_eq!(castable_on_turn(&watcher, &metrics, 3), 1);
+        assert_eq!(metrics.total(CastabilityWatcher::ON_TIME_KEY), 1);
+    }
+
+    #[test]
+    fn test_castable_pays_commander_tax() {
+        let dreadmaw = fixtures::card_named("Colossal Dreadmaw");
+        let watcher = CastabilityWatcher { target: dreadmaw, target_turn: 1 };
+        let mut state = state_with_board(&["Forest"; 6], &[]);
+        state.command_zone.add(dreadmaw);
+        state.commander_cast_count.insert(dreadmaw, 1);
+        let mut metrics = MetricsData::empty();
+
+        watcher.turn_end(&state, &mut metrics);
+        watcher.game_end(&state, &mut metrics);
+
+        // six forests would cast it the first time, but not with two more to pay
+        assert_eq!(castable_on_turn(&watcher, &metrics, 1), 0);
+        assert_eq!(metrics.total(CastabilityWatcher::ON_TIME_KEY), 0);
+    }
+
+    #[test]
+    fn test_opening_hand_land_distribution() {
+        let hands: [&[&str]; 4] = [
+            &["Forest", "Forest", "Hill Giant", "Hill Giant", "Hill Giant", "Hill Giant", "Hill Giant"],
+            &["Forest", "Forest", "Forest", "Forest", "Hill Giant", "Hill Giant", "Hill Giant"],
+            &["Forest", "Mountain", "Hill Giant", "Hill Giant", "Hill Giant", "Hill Giant", "Hill Giant"],
+            &["Hill Giant", "Hill Giant", "Hill Giant", "Hill Giant", "Hill Giant", "Hill Giant", "Hill Giant"],
+        ];
+        let metrics = hands
+            .iter()
+            .map(|hand| {
+                let state = state_with_board(&[], hand);
+                let mut metrics = MetricsData::empty();
+                WatcherImpl.opening_hand(&state, &mut metrics);
+                metrics.end_trial();
+                metrics
+            })
+            .fold(MetricsData::empty(), MetricsData::join);
+
+        let bucket = |lands| MetricsKey::from("opening-hand-lands").bucket(lands);
+        assert_eq!(metrics.average(bucket(0)), 0.25);
+        assert_eq!(metrics.average(bucket(2)), 0.5);
+        assert_eq!(metrics.average(bucket(4)), 0.25);
+        // every hand counts towards every bucket, even the ones it does not land in
+        for lands in [1, 3, 5, 6, 7] {
+            assert_eq!(metrics.average(bucket(lands)), 0.0, "{lands} lands");
+            assert_eq!(metrics.get(bucket(lands)).trials_seen(), 4);
+        }
+        assert_eq!(metrics.find_key("opening-hand-lands::#8"), None);
+    }
+
+    #[test]
+    fn test_opening_hand_land_buckets_cover_extra_cards() {
+        // a hand of nine, as with opening extra cards
+        let state = state_with_board(&[], &["Forest"; 9]);
+        let mut metrics = MetricsData::empty();
+        WatcherImpl.opening_hand(&state, &mut metrics);
+        metrics.end_trial();
+
+        assert_eq!(metrics.total(MetricsKey::from("opening-hand-lands").bucket(9)), 1);
+        assert_eq!(metrics.find_key("opening-hand-lands::#10"), None);
+    }
+
+    /// Counts the hooks it sees under its own names, so that two of them can share the metrics
+    #[derive(Clone)]
+    struct CountingWatcher {
+        card_plays: &'static str,
+        turn_ends: &'static str,
+    }
+    impl Watcher for CountingWatcher {
+        fn card_play(&self, _card_play: Card, _state: &State, metrics: &mut MetricsData) {
+            metrics.add(self.card_plays);
+        }
+        fn turn_end(&self, _state: &State, metrics: &mut MetricsData) {
+            metrics.add(self.turn_ends);
+        }
+    }
+
+    #[test]
+    fn test_combined_watcher_forwards_to_both() {
+        let watcher = combine(
+            CountingWatcher { card_plays: "first::card-plays", turn_ends: "first::turn-ends" },
+            CountingWatcher { card_plays: "second::card-plays", turn_ends: "second::turn-ends" },
+        );
+        let state = empty_state();
+        let mut metrics = MetricsData::empty();
+
+        watcher.card_play(fixtures::card_named("Forest"), &state, &mut metrics);
+        watcher.card_play(fixtures::card_named("Hill Giant"), &state, &mut metrics);
+        watcher.turn_end(&state, &mut metrics);
+
+        assert_eq!(metrics.total("first::card-plays"), 2);
+        assert_eq!(metrics.total("second::card-plays"), 2);
+        assert_eq!(metrics.total("first::turn-ends"), 1);
+        assert_eq!(metrics.total("second::turn-ends"), 1);
+    }
+
+    #[test]
+    fn test_first_threat_turn() {
+        let watcher = ThreatWatcher::default();
+        let mut state = empty_state();
+        let mut metrics = MetricsData::empty();
+
+        for (turn, name) in [(1, "Forest"), (2, "Grizzly Bears"), (4, "Hill Giant"), (5, "Colossal Dreadmaw")] {
+            state.turn = turn;
+            watcher.card_play(fixtures::card_named(name), &state, &mut metrics);
+        }
+
+        assert_eq!(metrics.total("first-threat-turn"), 4);
+    }
+
+    #[test]
+    fn test_first_threat_turn_threshold() {
+        let watcher = ThreatWatcher { min_mana_value: 2 };
+        let mut state = empty_state();
+        let mut metrics = MetricsData::empty();
+
+        for (turn, name) in [(2, "Grizzly Bears"), (4, "Hill Giant")] {
+            state.turn = turn;
+            watcher.card_play(fixtures::card_named(name), &state, &mut metrics);
+        }
+
+        assert_eq!(metrics.total("first-threat-turn"), 2);
+    }
+
+    #[test]
+    fn test_idle_turns() {
+        let mut state = state_with_library("Hill Giant", 20);
+        let mut metrics = MetricsData::empty();
+
+        for turn in 1..=4 {
+            state.turn = turn;
+            WatcherImpl.turn_begin(&state, &mut metrics);
+            if turn == 2 {
+                WatcherImpl.card_play(fixtures::card_named("Forest"), &state, &mut metrics);
+            }
+            WatcherImpl.turn_end(&state, &mut metrics);
+        }
+
+        assert_eq!(metrics.total("idle-turns"), 3);
+        assert_eq!(metrics.total(MetricsKey::from("plays-on-turn").turn_num(2)), 1);
+    }
+
+    #[test]
+    fn test_colors_available_by_turn() {
+        let mut state = empty_state();
+        let mut metrics = MetricsData::empty();
+
+        for (turn, land) in [(1, "Forest"), (2, "Forest"), (3, "Mountain"), (4, "Rugged Highlands")] {
+            state.turn = turn;
+            state.permanents.add(fixtures::card_named(land));
+            state.turn_state.mark_as_tapped(fixtures::card_named(land));
+            WatcherImpl.turn_end(&state, &mut metrics);
+            state.end_turn();
+        }
+        state.turn = 5;
+        state.permanents.add(fixtures::card_named("Sol Ring"));
+        WatcherImpl.turn_end(&state, &mut metrics);
+
+        let colors_available = |turn| metrics.total(MetricsKey::from("colors-available").turn_num(turn));
+        assert_eq!(colors_available(1), 1);
+        assert_eq!(colors_available(2), 1);
+        assert_eq!(colors_available(3), 2);
+        assert_eq!(colors_available(4), 2);
+        assert_eq!(colors_available(5), 2);
+    }
+
+    #[test]
+    fn test_plays_by_cmc() {
+        let state = empty_state();
+        let mut metrics = MetricsData::empty();
+
+        for name in ["Llanowar Elves", "Centaur Courser", "Forest", "Llanowar Elves"] {
+            WatcherImpl.card_play(fixtures::card_named(name), &state, &mut metrics);
+        }
+
+        assert_eq!(metrics.total(MetricsKey::from("plays-by-cmc").mana_value(1)), 2);
+        assert_eq!(metrics.total(MetricsKey::from("plays-by-cmc").mana_value(3)), 1);
+        assert_eq!(metrics.total(MetricsKey::from("plays-by-cmc").mana_value(0)), 0);
+    }
+
+    #[test]
+    fn test_first_played_by_card_type() {
+        let play_game = |plays: &[(u32, &str)]| {
+            let mut state = empty_state();
+            let mut metrics = MetricsData::empty();
+            for (turn, name) in plays {
+                state.turn = *turn;
+                WatcherImpl.card_play(fixtures::card_named(name), &state, &mut metrics);
+            }
+            metrics.end_trial();
+            metrics
+        };
+        let first = play_game(&[(1, "Forest"), (2, "Grizzly Bears"), (3, "Hill Giant"), (3, "Lightning Bolt")]);
+        let second = play_game(&[(1, "Forest"), (2, "Forest"), (4, "Hill Giant")]);
+
+        // only the first creature of each game counts
+        assert_eq!(first.total(first_played_key(&CardType::Creature)), 2);
+        assert_eq!(first.total(first_played_key(&CardType::Instant)), 3);
+
+        let both = MetricsData::join(first, second);
+        assert_eq!(both.average(first_played_key(&CardType::Creature)), 3.0);
+        assert_eq!(both.average(first_played_key(&CardType::Land)), 1.0);
+        // only the first game cast an instant, so the second does not drag its average down
+        assert_eq!(both.average(first_played_key(&CardType::Instant)), 3.0);
+        assert_eq!(both.get(first_played_key(&CardType::Instant)).trials_seen(), 1);
+        assert_eq!(both.find_key("first-played::sorcery"), None);
+    }
+
+    #[test]
+    fn test_card_drawn_by_turn() {
+        let state = empty_state();
+        let mut metrics = MetricsData::empty();
+        let hill_giant = fixtures::card_named("Hill Giant");
+
+        WatcherImpl.card_drawn(hill_giant, &state, &mut metrics);
+        WatcherImpl.card_drawn(hill_giant, &state, &mut metrics);
+        WatcherImpl.card_drawn(fixtures::card_named("Forest"), &state, &mut metrics);
+
+        assert_eq!(metrics.total(MetricsKey::from("card-drawn").card(hill_giant).turn_num(0)), 2);
+        assert_eq!(metrics.total(MetricsKey::from("card-drawn").card(hill_giant).turn_num(1)), 0);
+        assert_eq!(metrics.total(MetricsKey::from("cards-drawn").turn_num(0)), 3);
+    }
+
+    #[test]
+    fn test_uncast_card_is_stuck_in_hand() {
+        let state = state_with_library("Colossal Dreadmaw", 20);
+        let mut metrics = MetricsData::empty();
+
+        WatcherImpl.game_end(&state, &mut metrics);
+
+        let dreadmaw = fixtures::card_named("Colossal Dreadmaw");
+        assert_eq!(metrics.total(MetricsKey::from("stuck-in-hand").card(dreadmaw)), 7);
+    }
+
+    #[test]
+    fn test_lands_are_not_stuck_in_hand() {
+        let state = state_with_library("Forest", 20);
+        let mut metrics = MetricsData::empty();
+
+        WatcherImpl.game_end(&state, &mut metrics);
+
+        let forest = fixtures::card_named("Forest");
+        assert_eq!(metrics.total(MetricsKey::from("stuck-in-hand").card(forest)), 0);
+    }
+
+    #[test]
+    fn test_mana_screwed_once() {
+        let mut state = state_with_library("Hill Giant", 20);
+        let mut metrics = MetricsData::empty();
+
+        play_turns(&mut state, 5, &mut metrics);
+
+        assert_eq!(metrics.total("mana-screwed"), 1);
+        assert_eq!(metrics.total("mana-flooded"), 0);
+    }
+
+    #[test]
+    fn test_not_mana_screwed_when_making_land_drops() {
+        let mut state = state_with_library("Forest", 20);
+        let mut metrics = MetricsData::empty();
+
+        for _ in 0..5 {
+            state.turn += 1;
+            let land_drop = state.legal_land_drops().next().expect("a land to play");
+            state.play_card(land_drop);
+            WatcherImpl.turn_end(&state, &mut metrics);
+            state.end_turn();
+        }
+
+        assert_eq!(metrics.total("mana-screwed"), 0);
+    }
+
+    #[test]
+    fn test_mana_flooded_once() {
+        let mut state = state_with_library("Forest", 20);
+        let mut metrics = MetricsData::empty();
+
+        play_turns(&mut state, 5, &mut metrics);
+
+        assert_eq!(metrics.total("mana-flooded"), 1);
+        assert_eq!(metrics.total("mana-screwed"), 0);
+    }
+
+    #[test]
+    fn test_combat_damage_sums_untapped_creatures() {
+        let mut state = empty_state();
+        state.permanents.add(fixtures::card_named("Hill Giant"));
+        state.permanents.add(fixtures::card_named("Llanowar Elves"));
+        state.permanents.add(fixtures::card_named("Forest"));
+
+        let mut metrics = MetricsData::empty();
+        WatcherImpl.combat_damage(&state, &mut metrics);
+
+        assert_eq!(metrics.total("damage-dealt"), 4);
+    }
+
+    #[test]
+    fn test_combat_damage_skips_tapped_creatures() {
+        let mut state = empty_state();
+        state.permanents.add(fixtures::card_named("Hill Giant"));
+        state.permanents.add(fixtures::card_named("Llanowar Elves"));
+        state.turn_state.mark_as_tapped(fixtures::card_named("Llanowar Elves"));
+
+        let mut metrics = MetricsData::empty();
+        WatcherImpl.combat_damage(&state, &mut metrics);
+
+        assert_eq!(metrics.total("damage-dealt"), 3);
+    }
+
+    #[test]
+    fn test_combat_damage_counts_untapped_copies() {
+        let mut state = empty_state();
+        let bears = fixtures::card_named("Grizzly Bears");
+        state.permanents.add_copies(bears, 2);
+        state.turn_state.mark_as_tapped(bears);
+
+        let mut metrics = MetricsData::empty();
+        WatcherImpl.combat_damage(&state, &mut metrics);
+
+        assert_eq!(metrics.total("damage-dealt"), 2, "the untapped bears should still deal damage");
     }
 }
@@ -0,0 +1,58 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// Every card the integration tests look up, in scryfall's bulk data format
+pub const BULK_DATA: &str = r#"[
+    {
+        "object": "card",
+        "name": "Forest",
+        "type_line": "Basic Land — Forest",
+        "oracle_text": "({T}: Add {G}.)"
+    },
+    {
+        "object": "card",
+        "name": "Rugged Highlands",
+        "type_line": "Land",
+        "oracle_text": ""
+    },
+    {
+        "object": "card",
+        "name": "Hill Giant",
+        "type_line": "Creature — Giant",
+        "mana_cost": "{3}{R}",
+        "oracle_text": "",
+        "power": "3",
+        "toughness": "3"
+    },
+    {
+        "object": "card",
+        "name": "Grizzly Bears",
+        "type_line": "Creature — Bear",
+        "mana_cost": "{1}{G}",
+        "oracle_text": "",
+        "power": "2",
+        "toughness": "2"
+    }
+]"#;
+
+/// A scratch directory, used as the home directory so that the card cache starts out empty
+pub fn scratch_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("deck-optim-{name}-{}", std::process::id()));
+    fs::create_dir_all(&dir).expect("can create scratch directory");
+    dir
+}
+
+/// Runs the binary with `dir` as its home directory, loading cards from [`BULK_DATA`] instead of scryfall
+pub fn main_command(dir: &Path) -> Command {
+    let bulk_data = dir.join("bulk-data.json");
+    fs::write(&bulk_data, BULK_DATA).expect("can write the bulk data");
+
+    let mut command = Command::new(env!("CARGO_BIN_EXE_main"));
+    command
+        .env("HOME", dir)
+        .env("XDG_CACHE_HOME", dir.join("cache"))
+        .env("XDG_DATA_HOME", dir.join("data"))
+        .arg("--bulk-data").arg(bulk_data);
+    command
+}
@@ -0,0 +1,94 @@
+mod common;
+
+use std::fs;
+
+use common::{main_command, scratch_dir};
+
+const DECK: &str = r#"{
+    "decklist": [
+        { "name": "Forest", "quantity": 24 },
+        { "name": "Hill Giant", "quantity": 36 }
+    ]
+}"#;
+
+/// The red giants are outside the colors of the green commander
+const OFF_COLOR_DECK: &str = r#"{
+    "command_zone": [
+        { "name": "Grizzly Bears", "quantity": 1 }
+    ],
+    "decklist": [
+        { "name": "Forest", "quantity": 60 },
+        { "name": "Hill Giant", "quantity": 39 }
+    ]
+}"#;
+
+#[test]
+fn test_dry_run_prints_summary_without_simulating() {
+    let dir = scratch_dir("dry-run");
+    let deck = dir.join("deck.json");
+    let report = dir.join("report.json");
+    fs::write(&deck, DECK).unwrap();
+
+    let output = main_command(&dir)
+        .arg("--dry-run")
+        .arg("--deck-list").arg(&deck)
+        .arg("--output").arg(&report)
+        .arg("--output-format").arg("json")
+        .output()
+        .expect("can run the binary");
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let report_written = report.exists();
+    fs::remove_dir_all(&dir).ok();
+
+    assert!(output.status.success(), "dry run failed: {stdout}");
+    assert!(stdout.contains("Average Mana Value"), "no deck summary in: {stdout}");
+    assert!(stdout.contains("skipping the simulation"));
+    assert!(!report_written, "a dry run should not record any metrics");
+}
+
+#[test]
+fn test_dry_run_checks_color_identity() {
+    let dir = scratch_dir("dry-run-color-identity");
+    let deck = dir.join("deck.json");
+    fs::write(&deck, OFF_COLOR_DECK).unwrap();
+
+    let output = main_command(&dir)
+        .arg("--dry-run")
+        .arg("--deck-list").arg(&deck)
+        .output()
+        .expect("can run the binary");
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    fs::remove_dir_all(&dir).ok();
+
+    assert!(stdout.contains("outside the commander's color identity"), "no color identity check in: {stdout}");
+    assert!(stdout.contains("Hill Giant"));
+    assert!(stdout.contains("deck failed validation"));
+    assert!(!stdout.contains("skipping the simulation"), "the dry run should stop at the failed check: {stdout}");
+}
+
+#[test]
+fn test_dry_run_checks_the_chosen_format() {
+    let dir = scratch_dir("dry-run-format");
+    let deck = dir.join("deck.json");
+    let banlist = dir.join("banlist.json");
+    fs::write(&deck, DECK).unwrap();
+    fs::write(&banlist, r#"{ "commander": { "banned": ["Hill Giant"] } }"#).unwrap();
+
+    // the deck has no commander, but is still checked as a commander deck
+    let output = main_command(&dir)
+        .arg("--dry-run")
+        .arg("--deck-list").arg(&deck)
+        .arg("--banlist").arg(&banlist)
+        .arg("--game-format").arg("commander")
+        .output()
+        .expect("can run the binary");
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    fs::remove_dir_all(&dir).ok();
+
+    assert!(stdout.contains("deck is not legal in Commander"), "not checked as a commander deck: {stdout}");
+    assert!(stdout.contains("`Hill Giant` is banned"));
+    assert!(stdout.contains("deck failed validation"));
+}
@@ -0,0 +1,56 @@
+use rand::SeedableRng;
+
+use deck_optim::collection::CardCollection;
+use deck_optim::deck::DeckList;
+use deck_optim::game::CardData;
+use deck_optim::game::annotations::CardAnnotations;
+use deck_optim::strategies::{LandDropTieBreak, MulliganStrategy, StrategyImpl, UtilityFunction};
+use deck_optim::trial::{self, Props, Rand};
+use deck_optim::watcher::WatcherImpl;
+
+const DECK: &str = r#"{
+    "decklist": [
+        { "name": "Forest", "quantity": 30 },
+        { "name": "Spoiled Squirrel", "quantity": 30, "definition": { "card_type": "Creature", "cost": "{G}", "power": 1, "toughness": 1 } }
+    ]
+}"#;
+
+const ANNOTATIONS: &str = r#"
+{ "annotations": [
+    { "targets": ["Forest"], "key": "core:Produces", "values": [{ "Mana": "{G}" }] }
+] }
+"#;
+
+#[test]
+fn test_inline_card_is_cast() {
+    let forest: CardData = serde_json::from_value(serde_json::json!({
+        "name": "Forest", "card_type": "Land", "super_types": ["Basic"], "cost": null, "power": null, "toughness": null
+    })).expect("card data should parse");
+    let decklist: DeckList = serde_json::from_str(DECK).expect("deck list should parse");
+
+    let mut collection = CardCollection::from_card_data(vec![forest]);
+    collection.add_missing_cards(decklist.inline_cards());
+    let annotations: CardAnnotations = serde_json::from_str(ANNOTATIONS).expect("annotations should parse");
+    collection.apply_annotations(annotations);
+    let deck = decklist.into_deck(&collection).expect("the inline card should be found");
+    let squirrel = collection.card_named("Spoiled Squirrel").expect("the inline card was added");
+    deck_optim::init(collection);
+
+    let props = Props {
+        max_turn: 6,
+        num_trials: 10,
+        seed: Some(5),
+        ..Props::default()
+    };
+    let strategies = StrategyImpl {
+        rng: Rand::seed_from_u64(5),
+        mulligan: MulliganStrategy::Never,
+        utility: UtilityFunction::default(),
+        land_tie_break: LandDropTieBreak::default(),
+    };
+    let metrics = trial::run_trials(deck, strategies, WatcherImpl, props);
+
+    assert_eq!(metrics.num_trials(), 10);
+    assert!(metrics.total("card-plays") > 0, "the inline card should have been cast");
+    assert!(metrics.get(deck_optim::metrics::MetricsKey::from("turn-played").card(squirrel)).trials_seen() > 0);
+}
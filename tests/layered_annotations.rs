@@ -0,0 +1,77 @@
+mod common;
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Output;
+
+use common::{main_command, scratch_dir};
+
+const DECK: &str = r#"{
+    "decklist": [
+        { "name": "Rugged Highlands", "quantity": 60 }
+    ]
+}"#;
+
+const BASE: &str = r#"
+{ "annotations": [
+    { "targets": ["Rugged Highlands"], "key": "core:Produces", "values": [{ "Mana": "{R}" }] }
+] }
+"#;
+
+const OVERLAY: &str = r#"
+{ "annotations": [
+    { "targets": ["Rugged Highlands"], "key": "core:Produces", "values": [{ "Mana": "{G}" }] },
+    { "targets": ["Rugged Highlands"], "key": "core:EntersTapped" }
+] }
+"#;
+
+/// Dumps the collection for the deck, with each of `annotations` passed as an `--annotations` file
+fn dump_collection(dir: &Path, annotations: &[PathBuf]) -> Output {
+    let deck = dir.join("deck.json");
+    fs::write(&deck, DECK).unwrap();
+
+    let mut command = main_command(dir);
+    command.arg("--deck-list").arg(&deck);
+    for path in annotations {
+        command.arg("--annotations").arg(path);
+    }
+    command
+        .arg("dump-collection").arg(dir.join("cards.json"))
+        .output()
+        .expect("can run the binary")
+}
+
+#[test]
+fn test_annotation_files_are_layered_in_order() {
+    let dir = scratch_dir("layered-annotations");
+    let base = dir.join("base.json");
+    let overlay = dir.join("overlay.json");
+    fs::write(&base, BASE).unwrap();
+    fs::write(&overlay, OVERLAY).unwrap();
+
+    let output = dump_collection(&dir, &[base, overlay]);
+    let stdout = String::from_utf8_lossy(&output.stdout).to_string();
+    let dumped = fs::read_to_string(dir.join("cards.json")).unwrap_or_default();
+    fs::remove_dir_all(&dir).ok();
+
+    assert!(output.status.success(), "dump failed: {stdout}");
+    let produces = dumped.find("core:Produces").expect("the base file should be applied");
+    let enters_tapped = dumped.find("core:EntersTapped").expect("the overlay should be applied");
+    assert!(produces < enters_tapped, "the overlay should come after the base file: {dumped}");
+    assert!(dumped.contains("{R}") && dumped.contains("{G}"), "the overlay should add to the base file: {dumped}");
+}
+
+#[test]
+fn test_missing_annotation_file_is_an_error() {
+    let dir = scratch_dir("missing-annotations");
+    let base = dir.join("base.json");
+    fs::write(&base, BASE).unwrap();
+
+    let output = dump_collection(&dir, &[base, dir.join("typo.json")]);
+    let stdout = String::from_utf8_lossy(&output.stdout).to_string();
+    let dumped = dir.join("cards.json").exists();
+    fs::remove_dir_all(&dir).ok();
+
+    assert!(stdout.contains("typo.json"), "the missing file should be reported: {stdout}");
+    assert!(!dumped, "nothing should be dumped without every annotations file");
+}